@@ -0,0 +1,162 @@
+//! Correction of a word with a confidence score, to decide whether to apply it automatically.
+
+use crate::{
+    layer_stack::LayerStack,
+    search_approx::{search_approx, Distance, FoundWord, IterationStack, SearchOptions, WordPool},
+};
+use vague_search_core::CompiledTrie;
+
+/// The factor by which the weight of a candidate is divided for each edit
+/// it needs more than the best candidate.
+const DISTANCE_WEIGHT_DIVISOR: f64 = 10.0;
+
+/// Correct a word with the best word of the trie, along with a confidence score.
+pub trait Correct {
+    /// Return the best correction of the word at a distance of at most `max_dist`
+    /// (following the [FoundWord](crate::search_approx::FoundWord) order),
+    /// along with its confidence in `[0, 1]`, or None if there is no candidate.
+    ///
+    /// With `d` the distance of the correction and `n` the number of characters of the word,
+    /// the confidence is computed as:
+    ///
+    /// `confidence = (1 - d / (n + 1)) * w_best / Σ w_i`
+    ///
+    /// where the candidates `i` are all the words at a distance of at most `max_dist`,
+    /// weighted by `w_i = freq_i / 10^(d_i - d)`.
+    /// It thus decreases with the distance of the correction and increases with its share
+    /// of the candidates frequencies: a correction without any other candidate has
+    /// a confidence of `1 - d / (n + 1)`, while two equally frequent candidates at
+    /// the same distance halve it.
+    fn correct_with_confidence(&self, word: &str, max_dist: Distance) -> Option<(String, f64)>;
+
+    /// Return the best correction of the word at a distance of at most `max_dist`
+    /// among the words of frequency at least `min_freq`, or None if there is none.
+    ///
+    /// The closer but rarer words are ignored, so that only a common enough word
+    /// is applied automatically.
+    fn safe_correction(&self, word: &str, max_dist: Distance, min_freq: u32) -> Option<FoundWord>;
+}
+
+/// Return the words of the trie at a distance of at most `max_dist` from the word.
+fn candidates(trie: &CompiledTrie, word: &str, max_dist: Distance) -> Vec<FoundWord> {
+    let mut layer_stack = LayerStack::with_capacity(0, 0);
+    let mut iter_stack = IterationStack::new();
+    search_approx(
+        trie,
+        word,
+        &SearchOptions {
+            dist_max: max_dist,
+            ..SearchOptions::default()
+        },
+        &mut layer_stack,
+        &mut iter_stack,
+        &mut WordPool::new(),
+        Vec::new(),
+    )
+}
+
+impl Correct for CompiledTrie<'_> {
+    fn correct_with_confidence(&self, word: &str, max_dist: Distance) -> Option<(String, f64)> {
+        let candidates = candidates(self, word, max_dist);
+
+        let best = candidates.iter().min()?;
+        let weight = |dist: Distance, freq: f64| {
+            freq / DISTANCE_WEIGHT_DIVISOR.powi(i32::from(dist - best.dist))
+        };
+        let total_weight: f64 = candidates
+            .iter()
+            .map(|c| weight(c.dist, f64::from(c.freq.get())))
+            .sum();
+        let share = weight(best.dist, f64::from(best.freq.get())) / total_weight;
+
+        let nb_chars = word.chars().count() as f64;
+        let distance_factor = 1.0 - f64::from(best.dist) / (nb_chars + 1.0);
+
+        Some((best.word.clone(), distance_factor * share))
+    }
+
+    fn safe_correction(&self, word: &str, max_dist: Distance, min_freq: u32) -> Option<FoundWord> {
+        candidates(self, word, max_dist)
+            .into_iter()
+            .filter(|c| c.freq.get() >= min_freq)
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::create_trie;
+
+    fn assert_confidence(result: Option<(String, f64)>, word: &str, confidence: f64) {
+        let (found, found_confidence) = result.unwrap();
+        assert_eq!(found, word);
+        assert!(
+            (found_confidence - confidence).abs() < 1e-9,
+            "{} != {}",
+            found_confidence,
+            confidence
+        );
+    }
+
+    #[test]
+    fn test_correct_obvious() {
+        let trie = create_trie(&[("apple", 10), ("banana", 5), ("cherry", 3)]);
+
+        // A single candidate, only lowered by its distance
+        assert_confidence(trie.correct_with_confidence("aple", 2), "apple", 0.8);
+        assert_confidence(trie.correct_with_confidence("apple", 2), "apple", 1.0);
+        assert_eq!(trie.correct_with_confidence("xyz", 1), None);
+    }
+
+    #[test]
+    fn test_correct_ties() {
+        let trie = create_trie(&[("cap", 5), ("car", 5), ("cat", 5)]);
+
+        // Three equally good candidates
+        let (word, confidence) = trie.correct_with_confidence("ca", 1).unwrap();
+        assert_eq!(word, "cap");
+        assert!(confidence < 0.25);
+
+        // A more frequent candidate is more confident
+        let trie = create_trie(&[("cap", 5), ("car", 90), ("cat", 5)]);
+        assert_confidence(
+            trie.correct_with_confidence("ca", 1),
+            "car",
+            (1.0 - 1.0 / 3.0) * 0.9,
+        );
+    }
+
+    #[test]
+    fn test_correct_farther_candidates() {
+        let trie = create_trie(&[("test", 1), ("tests", 10)]);
+
+        // The candidates at a greater distance weigh less
+        assert_confidence(
+            trie.correct_with_confidence("test", 1),
+            "test",
+            1.0 / (1.0 + 10.0 / 10.0),
+        );
+        assert_confidence(trie.correct_with_confidence("test", 0), "test", 1.0);
+    }
+
+    #[test]
+    fn test_safe_correction() {
+        let trie = create_trie(&[("apple", 3), ("apples", 200), ("maple", 80)]);
+
+        // The closest word is too rare, a farther common one is applied
+        let found = trie.safe_correction("appel", 2, 50).unwrap();
+        assert_eq!((found.word.as_str(), found.dist), ("apples", 2));
+        let found = trie.safe_correction("appel", 2, 1).unwrap();
+        assert_eq!((found.word.as_str(), found.dist), ("apple", 1));
+
+        // The best word of the same distance is kept among the common ones
+        let found = trie.safe_correction("aple", 1, 50).unwrap();
+        assert_eq!((found.word.as_str(), found.freq.get()), ("maple", 80));
+
+        // No word is common enough, or close enough
+        assert!(trie.safe_correction("appel", 2, 500).is_none());
+        assert!(trie.safe_correction("appel", 0, 1).is_none());
+        assert!(trie.safe_correction("xyz", 2, 1).is_none());
+    }
+}
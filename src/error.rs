@@ -6,10 +6,20 @@ use std::{
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
+/// The usage of the options only available with some features.
+#[cfg(feature = "ngram-model")]
+const NGRAM_RANK_USAGE: &str = " [--ngram-rank]";
+#[cfg(not(feature = "ngram-model"))]
+const NGRAM_RANK_USAGE: &str = "";
+
 #[derive(Snafu)]
-#[snafu(visibility(pub(crate)))] // Make creatable enum variants crate-visible
+#[snafu(visibility(pub))] // Make creatable enum variants visible to the binary
 pub enum Error {
-    #[snafu(display("Usage: {} /path/to/compiled/dict.bin", bin_name))]
+    #[snafu(display(
+        "Usage: {} [--ignore-case] [--pin-first] [--pin-last] [--freq-cap <N> | --freq-log] [--boost <FACTOR> /path/to/words.txt] [--ndjson | --csv | --csv-no-header] [--highlight] [--phrase-separator <CHAR>] [--no-checksum]{} /path/to/compiled/dict.bin [/path/to/queries.txt]",
+        bin_name,
+        NGRAM_RANK_USAGE
+    ))]
     CliArgs { bin_name: String },
     #[snafu(display("Error while reading dictionary file {}: {}", path.display(), source))]
     DictionaryRead {
@@ -18,6 +28,18 @@ pub enum Error {
     },
     #[snafu(display("Error while reading the standard input stream: {}", source))]
     Stdin { source: std::io::Error },
+    #[snafu(display("Error while reading the queries file {}: {}", path.display(), source))]
+    QueriesRead {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[snafu(display("Error while reading the boosted words file {}: {}", path.display(), source))]
+    BoostRead {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[snafu(display("Could not parse the substitution cost at line {}: {}", number, line))]
+    SubstitutionParse { number: usize, line: String },
     #[snafu(display("Error while parsing the command '{}': {}", line, cause))]
     CommandParse { line: String, cause: String },
 }
@@ -0,0 +1,244 @@
+//! A case-folded index over the words of a [CompiledTrie](CompiledTrie), to search them
+//! regardless of their case while the trie keeps them as they were compiled.
+//!
+//! Compiling a lowercased dictionary makes the case-insensitive searches as fast as
+//! the normal ones, but loses the original case of the words. Instead, this index is
+//! a second trie of the folded words built at runtime, each of them giving back the different
+//! words of the trie it comes from (e.g. `"hello"` for `"Hello"` and `"HELLO"`). The searches
+//! then run on the folded trie without ignoring the case, which avoids comparing the lowercase
+//! mappings of the characters of each cell like [search_approx](search_approx) does.
+//! This costs the memory of the folded trie and of a copy of each original word,
+//! and the time to build them when the dictionary is loaded.
+
+use crate::{
+    layer_stack::LayerStack,
+    search_approx::{
+        keep_best, search_approx, FoundWord, IterationStack, Ranking, SearchOptions, WordPool,
+    },
+    search_exact::search_exact,
+};
+use std::{convert::TryFrom, num::NonZeroU32};
+use vague_search_core::{CompileOptions, CompiledTrie, TrieBuilder};
+
+/// Fold the case of a character like the case-insensitive comparison of
+/// [search_approx](search_approx): a character with a single-character lowercase mapping
+/// is replaced by it, while the others (e.g. `'İ'` to `"i̇"`) are kept as they are,
+/// since they only match the characters of the same mapping.
+fn fold_char(c: char) -> char {
+    let mut lowercase = c.to_lowercase();
+    match (lowercase.next(), lowercase.next()) {
+        (Some(folded), None) => folded,
+        _ => c,
+    }
+}
+
+/// Fold the case of each character of the word (see [fold_char](fold_char)).
+pub fn fold_case(word: &str) -> String {
+    word.chars().map(fold_char).collect()
+}
+
+/// The case-folded index of the words of a trie.
+pub struct FoldedIndex {
+    /// The folded words, whose frequency is the 1-based index of their original words.
+    folded: CompiledTrie<'static>,
+
+    /// The original words of each folded word, in lexicographic order.
+    originals: Vec<Box<[String]>>,
+}
+
+impl FoldedIndex {
+    /// Build the folded index of all the words of the trie.
+    pub fn new(trie: &CompiledTrie) -> Self {
+        let mut builder = TrieBuilder::new(CompileOptions::default());
+        let mut originals: Vec<Vec<String>> = Vec::new();
+        for (word, _) in trie.iter_words() {
+            let originals_id = builder.frequency_mut(&fold_case(&word));
+            match *originals_id {
+                Some(id) => originals[id.get() as usize - 1].push(word),
+                None => {
+                    originals.push(vec![word]);
+                    *originals_id = u32::try_from(originals.len())
+                        .ok()
+                        .and_then(NonZeroU32::new);
+                }
+            }
+        }
+
+        Self {
+            folded: builder.compile(),
+            originals: originals.into_iter().map(Vec::into_boxed_slice).collect(),
+        }
+    }
+
+    /// Return the original words of a word of the folded trie, given its frequency.
+    fn originals(&self, originals_id: NonZeroU32) -> &[String] {
+        &self.originals[originals_id.get() as usize - 1]
+    }
+}
+
+/// Search for the words of the trie equal to the word regardless of their case,
+/// with the folded index of the trie.
+///
+/// Return the words as they are in the trie with their frequency, in lexicographic order.
+pub fn search_exact_ci(
+    trie: &CompiledTrie,
+    index: &FoldedIndex,
+    word: &str,
+) -> Vec<(String, NonZeroU32)> {
+    if word.is_empty() {
+        return Vec::new();
+    }
+    let originals_id = match search_exact(&index.folded, &fold_case(word), None) {
+        Some(id) => id,
+        None => return Vec::new(),
+    };
+
+    index
+        .originals(originals_id)
+        .iter()
+        .filter_map(|original| Some((original.clone(), search_exact(trie, original, None)?)))
+        .collect()
+}
+
+/// Search for the words of the trie at a distance of at most `dist_max` from the word
+/// regardless of their case, with the folded index of the trie.
+///
+/// Return the same words as [search_approx](search_approx) with the same options
+/// and `ignore_case`, as they are in the trie. The characters given to the costs
+/// (e.g. to a substitution table) are the folded ones, and the `pinned` characters
+/// are compared regardless of their case.
+/// There is no word pool nor stacks to reuse since the results are new words.
+pub fn search_approx_ci(
+    trie: &CompiledTrie,
+    index: &FoldedIndex,
+    word: &str,
+    options: &SearchOptions,
+) -> Vec<FoundWord> {
+    let mut word_pool = WordPool::new();
+    let folded_words = search_approx(
+        &index.folded,
+        &fold_case(word),
+        &SearchOptions {
+            limit: None,
+            ignore_case: false,
+            ranking: Ranking::default(),
+            ..*options
+        },
+        &mut LayerStack::with_capacity(0, 0),
+        &mut IterationStack::new(),
+        &mut word_pool,
+        Vec::new(),
+    );
+
+    let found_words = folded_words
+        .iter()
+        .flat_map(|folded| {
+            index
+                .originals(folded.freq)
+                .iter()
+                .filter_map(move |original| {
+                    Some(FoundWord {
+                        word: original.clone(),
+                        freq: search_exact(trie, original, None)?,
+                        dist: folded.dist,
+                        edits: None,
+                    })
+                })
+        })
+        .collect();
+    keep_best(found_words, options.limit, &options.ranking, &mut word_pool)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{search_approx::Distance, test_utils::create_trie};
+
+    fn found(words: Vec<FoundWord>) -> Vec<(String, u32, Distance)> {
+        let mut words: Vec<_> = words
+            .into_iter()
+            .map(|w| (w.word, w.freq.get(), w.dist))
+            .collect();
+        words.sort_unstable();
+        words
+    }
+
+    #[test]
+    fn test_fold_case() {
+        assert_eq!(fold_case("HeLLo"), "hello");
+        assert_eq!(fold_case("ÉTÉ 日本"), "été 日本");
+        assert_eq!(fold_case("İ"), "İ");
+    }
+
+    #[test]
+    fn test_search_exact_ci() {
+        let trie = create_trie(&[("Hello", 3), ("HELLO", 1), ("help", 2), ("world", 4)]);
+        let index = FoldedIndex::new(&trie);
+
+        let nz = |freq| NonZeroU32::new(freq).unwrap();
+        let hellos = vec![("HELLO".to_string(), nz(1)), ("Hello".to_string(), nz(3))];
+        assert_eq!(search_exact_ci(&trie, &index, "HELLO"), hellos);
+        assert_eq!(search_exact_ci(&trie, &index, "hello"), hellos);
+        assert_eq!(
+            search_exact_ci(&trie, &index, "WoRlD"),
+            vec![("world".to_string(), nz(4))]
+        );
+        assert!(search_exact_ci(&trie, &index, "hell").is_empty());
+        assert!(search_exact_ci(&trie, &index, "").is_empty());
+
+        // The folded words are compiled with patricia nodes, like any other trie
+        let folded = create_trie(&[("hello", 1), ("help", 2), ("world", 3)]);
+        assert_eq!(index.folded.get_root_siblings(), folded.get_root_siblings());
+    }
+
+    #[test]
+    fn test_search_approx_ci() {
+        let trie = create_trie(&[
+            ("Hello", 3),
+            ("HELLO", 1),
+            ("help", 2),
+            ("Yellow", 5),
+            ("日本", 6),
+        ]);
+        let index = FoldedIndex::new(&trie);
+
+        for &(query, dist_max) in &[("HELLO", 0), ("HELLO", 2), ("yELLOW", 1), ("日本語", 1)] {
+            let options = SearchOptions {
+                dist_max,
+                ..SearchOptions::default()
+            };
+            let expected = search_approx(
+                &trie,
+                query,
+                &SearchOptions {
+                    ignore_case: true,
+                    ..options
+                },
+                &mut LayerStack::with_capacity(0, 0),
+                &mut IterationStack::new(),
+                &mut WordPool::new(),
+                Vec::new(),
+            );
+            let found_words = search_approx_ci(&trie, &index, query, &options);
+            assert_eq!(found(found_words), found(expected), "{}", query);
+        }
+
+        let found_words = search_approx_ci(&trie, &index, "HELLO", &SearchOptions::default());
+        assert_eq!(
+            found(found_words),
+            vec![("HELLO".to_string(), 1, 0), ("Hello".to_string(), 3, 0)]
+        );
+
+        // The limit keeps the best original words
+        let options = SearchOptions {
+            dist_max: 1,
+            limit: Some(2),
+            ..SearchOptions::default()
+        };
+        let found_words = search_approx_ci(&trie, &index, "hellp", &options);
+        assert_eq!(
+            found(found_words),
+            vec![("Hello".to_string(), 3, 1), ("help".to_string(), 2, 1)]
+        );
+    }
+}
@@ -4,10 +4,16 @@
 /// A layer is a sized contiguous collection of data, also synonymous to a [slice](std::slice).
 /// Each layer is also represented by a character.
 /// The string resulted of the concatenation of all layers character can then be queried.
+///
+/// The consecutive layers of a group (e.g. of the characters of a single node)
+/// can also be popped all at once, see [push_grouped_layer](Self::push_grouped_layer).
 pub struct LayerStack<E, S: Copy + Into<usize>> {
     elements: Vec<E>,
     layers: Vec<S>,
     word: String,
+
+    /// The number of layers of each group, the last group having the last layers.
+    groups: Vec<usize>,
 }
 
 impl<E, S: Copy + Into<usize>> LayerStack<E, S> {
@@ -21,6 +27,7 @@ impl<E, S: Copy + Into<usize>> LayerStack<E, S> {
             elements: Vec::with_capacity(cap_elements),
             layers: Vec::with_capacity(cap_layers),
             word: String::with_capacity(cap_layers),
+            groups: Vec::with_capacity(cap_layers),
         }
     }
 
@@ -28,6 +35,15 @@ impl<E, S: Copy + Into<usize>> LayerStack<E, S> {
     pub fn clear(&mut self) {
         self.elements.clear();
         self.layers.clear();
+        self.word.clear();
+        self.groups.clear();
+    }
+
+    /// Reserve the capacity of at least `nb_layers` more layers of the wanted size,
+    /// to push them without reallocating the inner storage.
+    pub fn reserve_layers(&mut self, nb_layers: usize, size: S) {
+        self.elements.reserve(nb_layers * size.into());
+        self.layers.reserve(nb_layers);
     }
 
     /// Retrieve the string resulted of the concatenation of all layers character.
@@ -48,6 +64,30 @@ impl<E, S: Copy + Into<usize>> LayerStack<E, S> {
     /// The last pushed is also accessible by calling the
     /// [fetch_layer](Self::fetch_layer) method.
     pub fn push_layer(&mut self, layer_char: Option<char>, size: S) -> &mut [E]
+    where
+        E: Default,
+    {
+        self.groups.push(1);
+        self.push_layer_elements(layer_char, size)
+    }
+
+    /// Create a new layer of the wanted size in the stack and return it, like
+    /// [push_layer](Self::push_layer) but in the group of the last layer, so that
+    /// [pop_group](Self::pop_group) pops them together. It begins a new group
+    /// if the stack is empty.
+    pub fn push_grouped_layer(&mut self, layer_char: char, size: S) -> &mut [E]
+    where
+        E: Default,
+    {
+        match self.groups.last_mut() {
+            Some(nb_layers) => *nb_layers += 1,
+            None => self.groups.push(1),
+        }
+        self.push_layer_elements(Some(layer_char), size)
+    }
+
+    /// Push the elements, the size and the character of a new layer, without its group.
+    fn push_layer_elements(&mut self, layer_char: Option<char>, size: S) -> &mut [E]
     where
         E: Default,
     {
@@ -76,6 +116,14 @@ impl<E, S: Copy + Into<usize>> LayerStack<E, S> {
     ///
     /// Return whether a layer was popped.
     pub fn pop_layer(&mut self) -> bool {
+        // Remove the layer from its group, and the group with its last layer
+        if let Some(nb_layers) = self.groups.last_mut() {
+            *nb_layers -= 1;
+            if *nb_layers == 0 {
+                self.groups.pop();
+            }
+        }
+
         // Get and remove the last layer size
         if let Some(size) = self.layers.pop() {
             // Remove the last `size` elements corresponding to the popped layer
@@ -89,6 +137,28 @@ impl<E, S: Copy + Into<usize>> LayerStack<E, S> {
         }
     }
 
+    /// Delete all the layers of the last group if the stack is not empty.
+    ///
+    /// Return whether a group was popped.
+    pub fn pop_group(&mut self) -> bool {
+        let nb_layers = match self.groups.pop() {
+            Some(nb_layers) => nb_layers,
+            None => return false,
+        };
+
+        let first_layer = self.layers.len() - nb_layers;
+        let nb_elements: usize = self.layers[first_layer..]
+            .iter()
+            .map(|&size| size.into())
+            .sum();
+        self.elements.truncate(self.elements.len() - nb_elements);
+        self.layers.truncate(first_layer);
+        for _ in 0..nb_layers {
+            self.word.pop();
+        }
+        true
+    }
+
     /// Return the last pushed layer as a mutable slice.
     pub fn fetch_layer(&self) -> Option<&[E]> {
         if let Some(&size) = self.layers.last() {
@@ -99,9 +169,20 @@ impl<E, S: Copy + Into<usize>> LayerStack<E, S> {
         }
     }
 
+    /// Return the concatenated string of the layers characters, all the elements
+    /// of the layers before the last one, and the last layer as a mutable slice.
+    ///
+    /// The last layer is empty if the stack is.
+    pub fn split_last_layer(&mut self) -> (&str, &[E], &mut [E]) {
+        let size = self.layers.last().map_or(0, |&size| size.into());
+        let nb_previous = self.elements.len() - size;
+        let (previous, last_layer) = self.elements.split_at_mut(nb_previous);
+        (&self.word, previous, last_layer)
+    }
+
     /// Same as fetch_last_3_layers but the caller must make sure there is at least 3
     /// layers in the stack.
-    pub unsafe fn fetch_last_3_layers_unsafe(&mut self) -> [&mut [E]; 3] {
+    pub(crate) unsafe fn fetch_last_3_layers_unsafe(&mut self) -> [&mut [E]; 3] {
         use std::hint::unreachable_unchecked;
 
         let mut sizes = self.layers.iter();
@@ -191,42 +272,42 @@ mod test {
     #[test]
     pub fn test_one_layer() {
         let mut stack = LayerStack::<u16, u8>::with_capacity(0, 0);
-        assert_eq!(stack.pop_layer(), false);
+        assert!(!stack.pop_layer());
         assert!(stack.fetch_layer().is_none());
         assert_eq!(stack.get_layers_word(), "");
 
         let layer = stack.push_layer(None, u8::MAX);
         assert_eq!(layer.len(), u8::MAX as usize);
         assert_eq!(stack.get_layers_word(), "");
-        assert_eq!(stack.pop_layer(), true);
+        assert!(stack.pop_layer());
 
         let layer = stack.push_layer(Some('a'), 0);
         assert_eq!(layer.len(), 0);
         assert_eq!(stack.get_layers_word(), "a");
-        assert_eq!(stack.pop_layer(), true);
+        assert!(stack.pop_layer());
 
         stack.push_layer(Some('b'), 14);
         let layer = stack.fetch_layer();
         assert!(layer.is_some());
         assert_eq!(layer.unwrap().len(), 14);
         assert_eq!(stack.get_layers_word(), "b");
-        assert_eq!(stack.pop_layer(), true);
+        assert!(stack.pop_layer());
 
         assert_eq!(stack.get_layers_word(), "");
-        assert_eq!(stack.pop_layer(), false);
+        assert!(!stack.pop_layer());
         assert!(stack.fetch_layer().is_none());
     }
 
     #[test]
     pub fn test_many_layers() {
-        let mut stack = LayerStack::with_capacity(1000, 100);
-        assert_eq!(stack.pop_layer(), false);
+        let mut stack = LayerStack::<usize, usize>::with_capacity(1000, 100);
+        assert!(!stack.pop_layer());
         assert!(stack.fetch_layer().is_none());
 
         for len in 0..=1000 {
             let layer = stack.push_layer(Some('a'), len);
-            for i in 0..len {
-                layer[i as usize] = i;
+            for (i, e) in layer.iter_mut().enumerate() {
+                *e = i;
             }
         }
 
@@ -242,11 +323,11 @@ mod test {
             }
 
             assert_eq!(stack.get_layers_word().chars().count(), len + 1);
-            assert_eq!(stack.pop_layer(), true);
+            assert!(stack.pop_layer());
         }
 
         assert!(stack.fetch_layer().is_none());
-        assert_eq!(stack.pop_layer(), false);
+        assert!(!stack.pop_layer());
     }
 
     #[test]
@@ -308,4 +389,63 @@ mod test {
         assert_eq!(parent.len(), 0);
         assert_eq!(stack.get_layers_word(), "");
     }
+
+    #[test]
+    pub fn test_pop_group() {
+        let mut stack = LayerStack::<u8, u8>::with_capacity(0, 0);
+        assert!(!stack.pop_group());
+
+        stack.push_layer(None, 3);
+        stack.push_layer(Some('a'), 3);
+        stack.reserve_layers(3, 3);
+        for &c in &['b', 'c', 'd'] {
+            stack.push_grouped_layer(c, 3);
+        }
+        stack.push_layer(Some('e'), 3);
+        assert_eq!(stack.get_layers_word(), "abcde");
+        assert_eq!(stack.nb_layers(), 6);
+
+        // The grouped layers are popped with the layer they follow
+        assert!(stack.pop_group());
+        assert_eq!(stack.get_layers_word(), "abcd");
+        assert!(stack.pop_group());
+        assert_eq!(stack.get_layers_word(), "");
+        assert_eq!(stack.nb_layers(), 1);
+        assert_eq!(stack.fetch_layer().map(<[_]>::len), Some(3));
+
+        // Popping a layer of a group keeps the others in it
+        stack.push_layer(Some('f'), 2);
+        stack.push_grouped_layer('g', 2);
+        assert!(stack.pop_layer());
+        assert_eq!(stack.get_layers_word(), "f");
+        stack.push_grouped_layer('h', 2);
+        assert!(stack.pop_group());
+        assert_eq!(stack.get_layers_word(), "");
+
+        assert!(stack.pop_group());
+        assert!(!stack.pop_group());
+        assert!(stack.fetch_layer().is_none());
+
+        // A grouped layer begins a group in an empty stack
+        stack.push_grouped_layer('i', 1);
+        assert!(stack.pop_group());
+        assert_eq!(stack.nb_layers(), 0);
+    }
+
+    #[test]
+    pub fn test_split_last_layer() {
+        let mut stack = LayerStack::<usize, usize>::with_capacity(0, 0);
+        let (word, previous, last) = stack.split_last_layer();
+        assert_eq!((word, previous.len(), last.len()), ("", 0, 0));
+
+        stack.push_layer(None, 2).copy_from_slice(&[1, 2]);
+        stack.push_layer(Some('a'), 2).copy_from_slice(&[3, 4]);
+        stack.push_layer(Some('b'), 2).copy_from_slice(&[5, 6]);
+
+        let (word, previous, last) = stack.split_last_layer();
+        assert_eq!(word, "ab");
+        assert_eq!(previous, &[1, 2, 3, 4]);
+        last[0] = 7;
+        assert_eq!(stack.fetch_layer(), Some(&[7, 6][..]));
+    }
 }
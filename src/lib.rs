@@ -0,0 +1,29 @@
+//! The search engine of the vague-search project, used by the application binary
+//! and reusable as a library.
+//!
+//! The words of a [CompiledTrie](vague_search_core::CompiledTrie), e.g. read from a dictionary
+//! file with [DictionaryFile](vague_search_core::DictionaryFile), are found by a [Searcher](Searcher)
+//! either exactly or at a
+//! [distance](https://en.wikipedia.org/wiki/Damerau%E2%80%93Levenshtein_distance)
+//! of at most N from the query. The modules give the finer-grained searches and their options,
+//! and the [query](query) module the query protocol of the binary.
+
+pub mod correction;
+pub mod error;
+pub mod folded_index;
+pub mod layer_stack;
+pub mod query;
+pub mod search_approx;
+pub mod search_automaton;
+pub mod search_exact;
+pub mod search_hamming;
+pub mod search_pattern;
+pub mod search_prefix;
+mod searcher;
+pub mod substitution;
+#[cfg(test)]
+mod test_utils;
+pub mod text_score;
+
+pub use search_approx::{Distance, FoundWord, SearchOptions};
+pub use searcher::{Searcher, SearcherBuilder};
@@ -4,6 +4,40 @@
 //! of the syntax `approx <N> <WORD>` to search for words in a
 //! [distance](https://en.wikipedia.org/wiki/Damerau%E2%80%93Levenshtein_distance)
 //! of at most N inside a compiled dictionary.
+//! The `exact <WORD>` and `prefix <WORD>` actions respectively search for
+//! the word itself and for all words beginning with it, and `complete <WORD> <N>`
+//! for the N most frequent words beginning with it. The `nearest <WORD>` action searches
+//! for the closest word, whatever its distance (e.g. to always suggest a correction),
+//! and the `pattern <PATTERN>` action for all words matching the pattern, where `?`
+//! stands for any one character and `*` for any number of characters.
+//! The `count` action returns
+//! the number of words of the dictionary, and the `stats` action describes
+//! how it has been compiled (size of its arrays and number of nodes of each type).
+//! The `help` action writes the syntax of the actions, and `quit` (or `exit`)
+//! stops reading them before the end of the input.
+//! The actions can also be read from a file given after the dictionary path.
+//! The `--ignore-case` option makes the approximate search ignore the case of the letters,
+//! and the `--pin-first` and `--pin-last` options make it only find the words beginning
+//! (respectively ending) with the same character as the query.
+//! The `--freq-cap <N>` and `--freq-log` options dampen the frequencies used to rank
+//! the words found at the same distance, so that extremely frequent words do not always come first.
+//! The `--boost <FACTOR> <WORDS>` option multiplies this frequency by the factor for the words
+//! of the given file (one per line), e.g. to rank first the words already used by the user.
+//! The `--ndjson` option writes each result as a JSON object on its own line,
+//! instead of a JSON array of the results per query, and the `--csv` option writes them
+//! as `word,freq,distance` CSV rows after a header line per query (omitted with `--csv-no-header`).
+//! With the `--highlight` option, each JSON result also has the spans of its characters
+//! matching the query, e.g. `"match":[[0,1],[2,5]]` (see [match_spans](vague_search::search_approx::alignment::match_spans)).
+//! The `--phrase-separator <CHAR>` option makes the character stand for a space in the words
+//! of the queries, to search the multi-word entries of a dictionary compiled with the same separator
+//! (see [unescape_phrase](vague_search_core::unescape_phrase)).
+//! The `--no-checksum` option skips the verification of the checksum of the dictionary
+//! when it is loaded, for speed on trusted files.
+//! With the `ngram-model` feature, the `--ngram-rank` option ranks the words found at the same
+//! distance and with the same ranking frequency by how much they look like the words of the
+//! dictionary (see [ngram_logprob](vague_search_core::CompiledTrie::ngram_logprob)).
+//!
+//! The search engine itself is the [vague-search](vague_search) library of the same package.
 //!
 //! See the [vague-search-index](../vague_search_index/index.html) crate for
 //! documentation about the dictionary compiler binary.
@@ -11,53 +45,205 @@
 //! See the [vague-search-core](../vague_search_core/index.html) crate for
 //! documentation about types and functions shared by the binaries.
 
-use error::*;
 use snafu::*;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use vague_search::{
+    error::*,
+    query::{self, OutputFormat},
+    search_approx::{FreqDamping, PinnedChars, Ranking, WordBoost},
+};
 use vague_search_core::DictionaryFile;
 
-mod error;
-mod layer_stack;
-mod query;
-mod search_approx;
-mod search_exact;
-
 /// Represents the expected parsed program arguments.
 #[derive(Debug)]
 struct Args {
     dict_path: PathBuf,
+    queries_path: Option<PathBuf>,
+    ignore_case: bool,
+    pinned: PinnedChars,
+    damping: FreqDamping,
+    boost: Option<(u32, PathBuf)>,
+    format: OutputFormat,
+    phrase_separator: Option<char>,
+    verify_checksum: bool,
+    #[cfg(feature = "ngram-model")]
+    ngram_rank: bool,
 }
 
 /// Parse the arguments and return an error if the wrong number is given or a parsing error happens.
 fn parse_args() -> Result<Args> {
     const BIN_NAME_DEFAULT: &str = "vague-search";
-    let mut args = std::env::args();
+    const IGNORE_CASE_FLAG: &str = "--ignore-case";
+    const PIN_FIRST_FLAG: &str = "--pin-first";
+    const PIN_LAST_FLAG: &str = "--pin-last";
+    const FREQ_CAP_FLAG: &str = "--freq-cap";
+    const FREQ_LOG_FLAG: &str = "--freq-log";
+    const BOOST_FLAG: &str = "--boost";
+    const NDJSON_FLAG: &str = "--ndjson";
+    const CSV_FLAG: &str = "--csv";
+    const CSV_NO_HEADER_FLAG: &str = "--csv-no-header";
+    const HIGHLIGHT_FLAG: &str = "--highlight";
+    const PHRASE_SEPARATOR_FLAG: &str = "--phrase-separator";
+    const NO_CHECKSUM_FLAG: &str = "--no-checksum";
+    #[cfg(feature = "ngram-model")]
+    const NGRAM_RANK_FLAG: &str = "--ngram-rank";
+    let mut args = std::env::args().peekable();
 
     let bin_name = args.next().unwrap_or_else(|| BIN_NAME_DEFAULT.to_string());
     let cliargs_ctx = CliArgs {
         bin_name: &bin_name,
     };
 
+    // Parse the options given before the dictionary path
+    let mut ignore_case = false;
+    let mut pinned = PinnedChars::default();
+    let mut damping = FreqDamping::None;
+    let mut boost = None;
+    let mut format = OutputFormat::Json { highlight: false };
+    let mut highlight = false;
+    let mut phrase_separator = None;
+    let mut verify_checksum = true;
+    #[cfg(feature = "ngram-model")]
+    let mut ngram_rank = false;
+    while let Some(option) = args.peek().filter(|arg| arg.starts_with("--")).cloned() {
+        args.next();
+        match option.as_str() {
+            IGNORE_CASE_FLAG => ignore_case = true,
+            PIN_FIRST_FLAG => pinned.first = true,
+            PIN_LAST_FLAG => pinned.last = true,
+            FREQ_CAP_FLAG if damping == FreqDamping::None => {
+                let cap = args.next().and_then(|cap| cap.parse().ok());
+                damping = FreqDamping::Cap(cap.context(cliargs_ctx)?);
+            }
+            FREQ_LOG_FLAG if damping == FreqDamping::None => damping = FreqDamping::Log,
+            NDJSON_FLAG => format = OutputFormat::Ndjson { highlight: false },
+            CSV_FLAG => format = OutputFormat::Csv { header: true },
+            CSV_NO_HEADER_FLAG => format = OutputFormat::Csv { header: false },
+            HIGHLIGHT_FLAG => highlight = true,
+            BOOST_FLAG if boost.is_none() => {
+                let factor = args.next().and_then(|factor| factor.parse().ok());
+                let words_path = args.next().map(PathBuf::from);
+                boost = Some((
+                    factor.context(cliargs_ctx)?,
+                    words_path.context(cliargs_ctx)?,
+                ));
+            }
+            PHRASE_SEPARATOR_FLAG if phrase_separator.is_none() => {
+                let separator = args.next().and_then(|arg| single_char(&arg));
+                phrase_separator = Some(separator.context(cliargs_ctx)?);
+            }
+            NO_CHECKSUM_FLAG => verify_checksum = false,
+            #[cfg(feature = "ngram-model")]
+            NGRAM_RANK_FLAG => ngram_rank = true,
+            _ => None.context(cliargs_ctx)?,
+        }
+    }
+
+    if highlight {
+        format = match format {
+            OutputFormat::Json { .. } => OutputFormat::Json { highlight },
+            OutputFormat::Ndjson { .. } => OutputFormat::Ndjson { highlight },
+            // The CSV rows do not have a field for the spans
+            OutputFormat::Csv { .. } => None.context(cliargs_ctx)?,
+        };
+    }
+
     let dict_path = args.next().context(cliargs_ctx)?.into();
+    let queries_path = args.next().map(PathBuf::from);
 
     // Make sure no more argument has been given
     if args.next().is_some() {
         None.context(cliargs_ctx)?;
     }
 
-    Ok(Args { dict_path })
+    Ok(Args {
+        dict_path,
+        queries_path,
+        ignore_case,
+        pinned,
+        damping,
+        boost,
+        format,
+        phrase_separator,
+        verify_checksum,
+        #[cfg(feature = "ngram-model")]
+        ngram_rank,
+    })
+}
+
+/// Return the character of the argument if it is made of a single one.
+fn single_char(arg: &str) -> Option<char> {
+    let mut chars = arg.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+/// Read the boosted words of a file, one per line.
+fn read_boost(factor: u32, path: &Path) -> Result<WordBoost> {
+    let words = std::fs::read_to_string(path).context(BoostRead { path })?;
+    let words = words
+        .lines()
+        .map(str::trim)
+        .filter(|word| !word.is_empty())
+        .map(String::from);
+    Ok(WordBoost::new(words, factor))
 }
 
 fn main() -> Result<()> {
     let args = parse_args()?;
 
     eprintln!("Reading compressed dictionary...");
-    let dict_file = DictionaryFile::read_file(&args.dict_path).context(DictionaryRead {
+    let dict_file = if args.verify_checksum {
+        DictionaryFile::read_file(&args.dict_path)
+    } else {
+        DictionaryFile::read_file_unverified(&args.dict_path, None)
+    };
+    let dict_file = dict_file.context(DictionaryRead {
         path: args.dict_path,
     })?;
 
-    eprintln!("Listening for queries in stdin...");
-    query::process_stdin_queries(&dict_file.trie)?;
+    let boost = match &args.boost {
+        Some((factor, path)) => Some(read_boost(*factor, path)?),
+        None => None,
+    };
+    let ranking = Ranking {
+        damping: args.damping,
+        boost: boost.as_ref(),
+        #[cfg(feature = "ngram-model")]
+        ngram_model: if args.ngram_rank {
+            Some(&dict_file.trie)
+        } else {
+            None
+        },
+    };
+
+    match &args.queries_path {
+        Some(queries_path) => {
+            eprintln!("Processing the queries of {}...", queries_path.display());
+            query::process_file_queries(
+                &dict_file.trie,
+                queries_path,
+                args.ignore_case,
+                args.pinned,
+                &ranking,
+                args.format,
+                args.phrase_separator,
+            )?;
+        }
+        None => {
+            eprintln!("Listening for queries in stdin...");
+            query::process_stdin_queries(
+                &dict_file.trie,
+                args.ignore_case,
+                args.pinned,
+                &ranking,
+                args.format,
+                args.phrase_separator,
+            )?;
+        }
+    }
 
     Ok(())
 }
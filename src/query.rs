@@ -1,64 +1,337 @@
 use crate::{
     error::*,
-    layer_stack::LayerStack,
-    search_approx::{search_approx, Distance, FoundWord, IterationStack, WordCharCount},
+    search_approx::{
+        alignment::{edit_operations, match_spans},
+        search_approx, search_nearest, sort_ranked, Distance, EditCosts, FoundWord, PinnedChars,
+        Ranking, SearchOptions,
+    },
     search_exact::search_exact,
+    search_pattern::search_pattern,
+    search_prefix::{search_completions, search_prefix},
+    searcher::{Searcher, SearcherBuilder},
 };
 use snafu::*;
-use std::{io::Write, num::NonZeroU32};
-use vague_search_core::CompiledTrie;
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    mem::size_of,
+    num::NonZeroU32,
+    path::Path,
+};
+use vague_search_core::{unescape_phrase, CompiledTrie, CompiledTrieNode, Header, RangeElement};
+
+/// The format in which the results of the queries are written.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum OutputFormat {
+    /// A JSON array of the results per query, on a single line ending with a newline
+    /// (`[]` for a query without any result, even an exact one),
+    /// with the spans of each word matching the query if `highlight` is set.
+    Json { highlight: bool },
+
+    /// Each result as a JSON object on its own line (newline-delimited JSON),
+    /// without any surrounding array, with the same spans as [Json](OutputFormat::Json).
+    Ndjson { highlight: bool },
+
+    /// Each result as a `word,freq,distance` CSV row (`word,freq` for the completions),
+    /// preceded by a header line per query if `header` is set.
+    Csv { header: bool },
+}
+
+/// The action of a query line, given by its first word.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Action {
+    /// `approx <N> <WORD> [LIMIT]`: search the words at a distance of at most N.
+    Approx,
+
+    /// `exact <WORD>`: search the word itself.
+    Exact,
+
+    /// `nearest <WORD>`: search the closest word, whatever its distance.
+    Nearest,
+
+    /// `prefix <WORD>`: search all words beginning with the word.
+    Prefix,
+
+    /// `complete <WORD> <N>`: search the N most frequent words beginning with the word.
+    Complete,
+
+    /// `pattern <PATTERN>`: search all words matching the pattern, where `?` is any character
+    /// and `*` any number of characters.
+    Pattern,
+
+    /// `count`: count the words of the dictionary.
+    Count,
+
+    /// `stats`: describe how the dictionary has been compiled.
+    Stats,
+
+    /// `help`: describe the syntax of the queries.
+    Help,
+
+    /// `quit` or `exit`: stop processing the queries.
+    Quit,
+}
+
+impl Action {
+    /// Return the action of the name, if any.
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "approx" => Some(Action::Approx),
+            "exact" => Some(Action::Exact),
+            "nearest" => Some(Action::Nearest),
+            "prefix" => Some(Action::Prefix),
+            "complete" => Some(Action::Complete),
+            "pattern" => Some(Action::Pattern),
+            "count" => Some(Action::Count),
+            "stats" => Some(Action::Stats),
+            "help" => Some(Action::Help),
+            "quit" | "exit" => Some(Action::Quit),
+            _ => None,
+        }
+    }
+
+    /// Return the parse error cause of a line with more arguments than the action takes.
+    fn too_many_arguments(self) -> &'static str {
+        match self {
+            Action::Approx => "Too many arguments for an approximate search",
+            Action::Exact => "Too many arguments for an exact search",
+            Action::Nearest => "Too many arguments for a nearest search",
+            Action::Prefix => "Too many arguments for a prefix search",
+            Action::Complete => "Too many arguments for a completion",
+            Action::Pattern => "Too many arguments for a pattern search",
+            Action::Count => "Too many arguments for a count",
+            Action::Stats => "Too many arguments for the statistics",
+            Action::Help => "Too many arguments for the help",
+            Action::Quit => "Too many arguments to quit",
+        }
+    }
+}
+
+/// The syntax of the queries, written by the `help` action.
+const HELP: &str = "\
+approx <N> <WORD> [LIMIT]  search the words at a distance of at most N, at most LIMIT of them
+exact <WORD>               search the word itself
+nearest <WORD>             search the closest word, whatever its distance
+prefix <WORD>              search all words beginning with the word
+complete <WORD> <N>        search the N most frequent words beginning with the word
+pattern <PATTERN>          search all words matching the pattern, with the ? and * wildcards
+count                      count the words of the dictionary
+stats                      describe how the dictionary has been compiled
+help                       describe the syntax of the queries
+quit | exit                stop processing the queries
+";
 
-/// Parse a command line and extract the query word and the searching distance.
-fn parse_command_line(line: &str) -> Result<(&str, Distance)> {
+/// A parsed query line: its action and the arguments the action takes,
+/// the others being left to their default value.
+#[derive(Debug, Eq, PartialEq)]
+struct ParsedQuery<'a> {
+    action: Action,
+
+    /// The searched word, prefix for the prefix searches and completions,
+    /// or pattern for the pattern searches.
+    /// Empty for the actions without a word.
+    word: &'a str,
+
+    /// The maximum distance of an approximate search, else 0.
+    dist: Distance,
+
+    /// The maximum number of results, always given for a completion.
+    limit: Option<usize>,
+}
+
+impl<'a> ParsedQuery<'a> {
+    /// Create the query of the action, with the default value of all its arguments.
+    fn new(action: Action) -> Self {
+        Self {
+            action,
+            word: "",
+            dist: 0,
+            limit: None,
+        }
+    }
+}
+
+/// Return the next argument of a query line, or an error with the cause if there is none.
+fn next_argument<'a>(
+    line: &str,
+    split: &mut impl Iterator<Item = &'a str>,
+    cause: &'static str,
+) -> Result<&'a str> {
+    split.next().context(CommandParse { line, cause })
+}
+
+/// Parse a numeric argument of a query line, or return an error with the cause.
+fn parse_number<T: std::str::FromStr>(line: &str, arg: &str, cause: &'static str) -> Result<T> {
+    arg.parse().ok().context(CommandParse { line, cause })
+}
+
+/// Parse a query line into its action and arguments.
+fn parse_command_line(line: &str) -> Result<ParsedQuery<'_>> {
     let mut split = line.split_whitespace();
-    let action = split.next().context(CommandParse {
+    let action = next_argument(line, &mut split, "No action found")?;
+    let action = Action::from_name(action).context(CommandParse {
         line,
-        cause: "No action found",
+        cause: "Action not recognized",
     })?;
 
-    if action != "approx" {
-        None.context(CommandParse {
-            line,
-            cause: "Action not recognized",
-        })?
+    let mut query = ParsedQuery::new(action);
+    match action {
+        Action::Approx => {
+            let dist = next_argument(line, &mut split, "No distance found")?;
+            query.dist = parse_number(line, dist, "Could not parse the distance into an integer")?;
+            query.word = next_argument(line, &mut split, "No word found")?;
+            query.limit = split
+                .next()
+                .map(|limit| parse_number(line, limit, "Could not parse the limit into an integer"))
+                .transpose()?;
+        }
+        Action::Exact | Action::Nearest | Action::Prefix | Action::Pattern => {
+            query.word = next_argument(line, &mut split, "No word found")?;
+        }
+        Action::Complete => {
+            query.word = next_argument(line, &mut split, "No word found")?;
+            let limit = next_argument(line, &mut split, "No number of completions found")?;
+            query.limit = Some(parse_number(
+                line,
+                limit,
+                "Could not parse the number of completions into an integer",
+            )?);
+        }
+        Action::Count | Action::Stats | Action::Help | Action::Quit => {}
     }
 
-    let dist = split
-        .next()
-        .context(CommandParse {
+    ensure!(
+        split.next().is_none(),
+        CommandParse {
             line,
-            cause: "No distance found",
-        })?
-        .parse()
-        .ok()
-        .context(CommandParse {
-            line,
-            cause: "Could not parse the distance into an integer",
-        })?;
+            cause: action.too_many_arguments(),
+        }
+    );
+    Ok(query)
+}
 
-    let word = split.next().context(CommandParse {
-        line,
-        cause: "No word found",
-    })?;
+/// Unescape the word of the query as a phrase, where the separator stands for a space,
+/// using the buffer to store it if it changes.
+fn unescape_query<'a>(query: &mut ParsedQuery<'a>, separator: char, buffer: &'a mut String) {
+    if let Cow::Owned(phrase) = unescape_phrase(query.word, separator) {
+        *buffer = phrase;
+        query.word = buffer;
+    }
+}
+
+/// Normalize the whole word of the query to the Unicode NFC form,
+/// using the buffer to store it if it is not already normalized.
+#[cfg(feature = "nfc")]
+fn normalize_query<'a>(query: &mut ParsedQuery<'a>, buffer: &'a mut String) {
+    use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+
+    if is_nfc_quick(query.word.chars()) == IsNormalized::Yes {
+        return;
+    }
 
-    Ok((word, dist))
+    buffer.clear();
+    buffer.extend(query.word.nfc());
+    query.word = buffer;
 }
 
-/// Format the result (word, freq) to JSON and append it to the given buffer.
+/// Write the word as the content of a JSON string, escaping the characters which need to be.
+fn write_json_escaped(word: &str, json_writer: &mut impl Write) {
+    let needs_escape = |b: u8| b == b'"' || b == b'\\' || b < 0x20;
+
+    // Fast path: most words do not contain any character to escape
+    let bytes = word.as_bytes();
+    if !bytes.iter().any(|&b| needs_escape(b)) {
+        let r = json_writer.write_all(bytes);
+        debug_assert!(r.is_ok());
+        return;
+    }
+
+    // Multibyte UTF-8 characters never contain bytes to escape, so the word
+    // can be processed byte per byte
+    let mut start = 0;
+    for (i, &b) in bytes.iter().enumerate() {
+        if !needs_escape(b) {
+            continue;
+        }
+
+        // Write the unescaped part before the current character
+        let r = json_writer.write_all(&bytes[start..i]);
+        debug_assert!(r.is_ok());
+
+        let r = match b {
+            b'"' => json_writer.write_all(b"\\\""),
+            b'\\' => json_writer.write_all(b"\\\\"),
+            _ => {
+                // Control character, written as \u00XX
+                const HEX: &[u8; 16] = b"0123456789abcdef";
+                let escaped = [
+                    b'\\',
+                    b'u',
+                    b'0',
+                    b'0',
+                    HEX[(b >> 4) as usize],
+                    HEX[(b & 0xF) as usize],
+                ];
+                json_writer.write_all(&escaped)
+            }
+        };
+        debug_assert!(r.is_ok());
+
+        start = i + 1;
+    }
+
+    let r = json_writer.write_all(&bytes[start..]);
+    debug_assert!(r.is_ok());
+}
+
+/// The characters of a word matching the query, as `(start, end)` character positions
+/// with an exclusive end.
+type Span = (usize, usize);
+
+/// Write the spans of a result as a `"match"` JSON field preceded by a comma,
+/// e.g. `,"match":[[0,1],[2,5]]`.
+fn write_json_spans(spans: &[Span], mut json_writer: &mut impl Write) {
+    let r = json_writer.write_all(b",\"match\":[");
+    debug_assert!(r.is_ok());
+
+    for (i, &(start, end)) in spans.iter().enumerate() {
+        let r = json_writer.write_all(if i == 0 { b"[" } else { b",[" });
+        debug_assert!(r.is_ok());
+
+        let r = itoa::write(&mut json_writer, start);
+        debug_assert!(r.is_ok());
+
+        let r = json_writer.write_all(b",");
+        debug_assert!(r.is_ok());
+
+        let r = itoa::write(&mut json_writer, end);
+        debug_assert!(r.is_ok());
+
+        let r = json_writer.write_all(b"]");
+        debug_assert!(r.is_ok());
+    }
+
+    let r = json_writer.write_all(b"]");
+    debug_assert!(r.is_ok());
+}
+
+/// Format the result (word, freq) to JSON, with its spans if any,
+/// and append it to the given buffer.
 fn write_json_result(
     word: &str,
     freq: NonZeroU32,
     dist: Distance,
+    spans: Option<&[Span]>,
     mut json_writer: &mut impl Write,
 ) {
-    // Write: {"word":"<word>","freq":<freq>,"distance":<dist>}
+    // Write: {"word":"<word>","freq":<freq>,"distance":<dist>[,"match":<spans>]}
     // Do not use format!() and such to avoid its overhead
 
     let r = json_writer.write_all(b"{\"word\":\"");
     debug_assert!(r.is_ok());
 
-    let r = json_writer.write_all(word.as_bytes());
-    debug_assert!(r.is_ok());
+    write_json_escaped(word, json_writer);
 
     let r = json_writer.write_all(b"\",\"freq\":");
     debug_assert!(r.is_ok());
@@ -72,6 +345,10 @@ fn write_json_result(
     let r = itoa::write(&mut json_writer, dist);
     debug_assert!(r.is_ok());
 
+    if let Some(spans) = spans {
+        write_json_spans(spans, json_writer);
+    }
+
     let r = json_writer.write_all(b"}");
     debug_assert!(r.is_ok());
 }
@@ -84,8 +361,7 @@ fn write_json_result_dist_0(word: &str, freq: NonZeroU32, mut json_writer: &mut
     let r = json_writer.write_all(b"[{\"word\":\"");
     debug_assert!(r.is_ok());
 
-    let r = json_writer.write_all(word.as_bytes());
-    debug_assert!(r.is_ok());
+    write_json_escaped(word, json_writer);
 
     let r = json_writer.write_all(b"\",\"freq\":");
     debug_assert!(r.is_ok());
@@ -97,93 +373,477 @@ fn write_json_result_dist_0(word: &str, freq: NonZeroU32, mut json_writer: &mut
     debug_assert!(r.is_ok());
 }
 
+/// Format the completion (word, freq) to JSON, with its spans if any,
+/// and append it to the given buffer.
+fn write_json_completion(
+    word: &str,
+    freq: NonZeroU32,
+    spans: Option<&[Span]>,
+    mut json_writer: &mut impl Write,
+) {
+    // Write: {"word":"<word>","freq":<freq>[,"match":<spans>]}
+    // Do not use format!() and such to avoid its overhead
+
+    let r = json_writer.write_all(b"{\"word\":\"");
+    debug_assert!(r.is_ok());
+
+    write_json_escaped(word, json_writer);
+
+    let r = json_writer.write_all(b"\",\"freq\":");
+    debug_assert!(r.is_ok());
+
+    let r = itoa::write(&mut json_writer, freq.get());
+    debug_assert!(r.is_ok());
+
+    if let Some(spans) = spans {
+        write_json_spans(spans, json_writer);
+    }
+
+    let r = json_writer.write_all(b"}");
+    debug_assert!(r.is_ok());
+}
+
+/// A result to write: its word, its frequency and its distance if it has been
+/// found by a distance search.
+type ResultRow<'a> = (&'a str, NonZeroU32, Option<Distance>);
+
+/// Write the word as a CSV field, quoting it if it contains a special character.
+fn write_csv_field(word: &str, csv_writer: &mut impl Write) {
+    let needs_quotes = |c: char| c == ',' || c == '"' || c == '\n' || c == '\r';
+    if !word.contains(needs_quotes) {
+        let r = csv_writer.write_all(word.as_bytes());
+        debug_assert!(r.is_ok());
+        return;
+    }
+
+    // Quoted field, with its quotes doubled
+    let r = csv_writer.write_all(b"\"");
+    debug_assert!(r.is_ok());
+
+    for (i, part) in word.split('"').enumerate() {
+        if i != 0 {
+            let r = csv_writer.write_all(b"\"\"");
+            debug_assert!(r.is_ok());
+        }
+
+        let r = csv_writer.write_all(part.as_bytes());
+        debug_assert!(r.is_ok());
+    }
+
+    let r = csv_writer.write_all(b"\"");
+    debug_assert!(r.is_ok());
+}
+
+/// Format the result to a CSV row and append it to the given buffer.
+fn write_csv_result(
+    word: &str,
+    freq: NonZeroU32,
+    dist: Option<Distance>,
+    mut csv_writer: &mut impl Write,
+) {
+    // Write: <word>,<freq>[,<dist>]\n
+    write_csv_field(word, csv_writer);
+
+    let r = csv_writer.write_all(b",");
+    debug_assert!(r.is_ok());
+
+    let r = itoa::write(&mut csv_writer, freq.get());
+    debug_assert!(r.is_ok());
+
+    if let Some(dist) = dist {
+        let r = csv_writer.write_all(b",");
+        debug_assert!(r.is_ok());
+
+        let r = itoa::write(&mut csv_writer, dist);
+        debug_assert!(r.is_ok());
+    }
+
+    let r = csv_writer.write_all(b"\n");
+    debug_assert!(r.is_ok());
+}
+
+/// Write the results of a query in the output format.
+/// `with_distance` tells whether the results come from a distance search,
+/// and `match_spans` gives the spans of a word when the format highlights them.
+fn write_results<'a>(
+    results: impl IntoIterator<Item = ResultRow<'a>>,
+    with_distance: bool,
+    match_spans: impl Fn(&str) -> Vec<Span>,
+    format: OutputFormat,
+    writer: &mut impl Write,
+) {
+    let highlight = matches!(
+        format,
+        OutputFormat::Json { highlight: true } | OutputFormat::Ndjson { highlight: true }
+    );
+    let write_json = |(word, freq, dist): ResultRow, writer: &mut _| {
+        let spans = if highlight {
+            Some(match_spans(word))
+        } else {
+            None
+        };
+        match dist {
+            Some(dist) => write_json_result(word, freq, dist, spans.as_deref(), writer),
+            None => write_json_completion(word, freq, spans.as_deref(), writer),
+        }
+    };
+
+    match format {
+        OutputFormat::Json { .. } => {
+            let r = writer.write_all(b"[");
+            debug_assert!(r.is_ok());
+
+            for (i, result) in results.into_iter().enumerate() {
+                // Add comma between elements in the JSON array
+                // But there must not be a trailing comma
+                if i != 0 {
+                    let r = writer.write_all(b",");
+                    debug_assert!(r.is_ok());
+                }
+
+                // Append the formatted result to the JSON buffer
+                write_json(result, writer);
+            }
+
+            let r = writer.write_all(b"]\n");
+            debug_assert!(r.is_ok());
+        }
+        OutputFormat::Ndjson { .. } => {
+            for result in results {
+                write_json(result, writer);
+
+                let r = writer.write_all(b"\n");
+                debug_assert!(r.is_ok());
+            }
+        }
+        OutputFormat::Csv { header } => {
+            if header {
+                let header: &[u8] = if with_distance {
+                    b"word,freq,distance\n"
+                } else {
+                    b"word,freq\n"
+                };
+                let r = writer.write_all(header);
+                debug_assert!(r.is_ok());
+            }
+
+            for (word, freq, dist) in results {
+                write_csv_result(word, freq, dist, writer);
+            }
+        }
+    }
+}
+
+/// Write the number of words of the dictionary on its own line.
+/// It is a valid JSON, NDJSON and CSV output.
+fn write_count(count: usize, mut writer: &mut impl Write) {
+    let r = itoa::write(&mut writer, count);
+    debug_assert!(r.is_ok());
+
+    let r = writer.write_all(b"\n");
+    debug_assert!(r.is_ok());
+}
+
+/// Write the statistics of the dictionary as a JSON object on its own line:
+/// the size of its arrays, in number of elements and in bytes,
+/// the number of nodes of each type and the number of characters of the longest patricia node.
+fn write_stats(trie: &CompiledTrie, mut json_writer: &mut impl Write) {
+    let header = Header::from(trie);
+    let histogram = trie.node_type_histogram();
+    let fields = [
+        ("nb_nodes", header.nb_nodes),
+        ("nb_chars", header.nb_chars_bytes),
+        ("nb_ranges", header.nb_ranges),
+        (
+            "nodes_bytes",
+            header.nb_nodes * size_of::<CompiledTrieNode>(),
+        ),
+        ("chars_bytes", header.nb_chars_bytes),
+        ("ranges_bytes", header.nb_ranges * size_of::<RangeElement>()),
+        ("naive_nodes", histogram.naive),
+        ("patricia_nodes", histogram.patricia),
+        ("range_nodes", histogram.range),
+        ("longest_patricia", trie.longest_patricia_len()),
+    ];
+
+    for (i, (name, value)) in fields.iter().enumerate() {
+        let r = json_writer.write_all(if i == 0 { b"{\"" } else { b",\"" });
+        debug_assert!(r.is_ok());
+
+        let r = json_writer.write_all(name.as_bytes());
+        debug_assert!(r.is_ok());
+
+        let r = json_writer.write_all(b"\":");
+        debug_assert!(r.is_ok());
+
+        let r = itoa::write(&mut json_writer, *value);
+        debug_assert!(r.is_ok());
+    }
+
+    let r = json_writer.write_all(b"}\n");
+    debug_assert!(r.is_ok());
+}
+
 /// Search for a word in the trie and return the result in a JSON representation.
-fn process_search_exact(trie: &CompiledTrie, word: &str, json_writer: &mut impl Write) {
+fn process_search_exact(
+    trie: &CompiledTrie,
+    word: &str,
+    format: OutputFormat,
+    json_writer: &mut impl Write,
+) {
     // Search at a distance 0 and append the formatted result to the JSON buffer
-    if let Some(freq) = search_exact(trie, word, None) {
-        write_json_result_dist_0(word, freq, json_writer)
-    } else {
-        let r = json_writer.write_all(b"[]");
-        debug_assert!(r.is_ok());
+    let freq = search_exact(trie, word, None);
+    match (format, freq) {
+        (OutputFormat::Json { highlight: false }, Some(freq)) => {
+            write_json_result_dist_0(word, freq, json_writer)
+        }
+        _ => write_results(
+            freq.map(|freq| (word, freq, Some(0))),
+            true,
+            |word| vec![(0, word.chars().count())],
+            format,
+            json_writer,
+        ),
     }
 }
 
-/// Search for all words in the trie at a given distance (or less) of the query
+/// Write the completions of the prefix in the output format.
+fn write_completions(
+    completions: &[(String, NonZeroU32)],
+    prefix: &str,
+    format: OutputFormat,
+    json_writer: &mut impl Write,
+) {
+    // The completions only match their prefix
+    let prefix_len = prefix.chars().count();
+    write_results(
+        completions
+            .iter()
+            .map(|(word, freq)| (word.as_str(), *freq, None)),
+        false,
+        |_| {
+            if prefix_len == 0 {
+                Vec::new()
+            } else {
+                vec![(0, prefix_len)]
+            }
+        },
+        format,
+        json_writer,
+    );
+}
+
+/// Search for all words in the trie beginning with the query
+/// and return the result in a JSON representation.
+fn process_search_prefix(
+    trie: &CompiledTrie,
+    prefix: &str,
+    format: OutputFormat,
+    json_writer: &mut impl Write,
+) {
+    write_completions(&search_prefix(trie, prefix), prefix, format, json_writer);
+}
+
+/// Search for the most frequent words in the trie beginning with the query
+/// and return the result in a JSON representation.
+fn process_search_complete(
+    trie: &CompiledTrie,
+    prefix: &str,
+    limit: usize,
+    format: OutputFormat,
+    json_writer: &mut impl Write,
+) {
+    write_completions(
+        &search_completions(trie, prefix, limit),
+        prefix,
+        format,
+        json_writer,
+    );
+}
+
+/// Search for all words in the trie matching the pattern
 /// and return the result in a JSON representation.
-fn process_search_approx<'a>(
-    trie: &'a CompiledTrie,
+fn process_search_pattern(
+    trie: &CompiledTrie,
+    pattern: &str,
+    format: OutputFormat,
+    json_writer: &mut impl Write,
+) {
+    // The whole words match the pattern
+    write_results(
+        search_pattern(trie, pattern)
+            .iter()
+            .map(|(word, freq)| (word.as_str(), *freq, None)),
+        false,
+        |word| vec![(0, word.chars().count())],
+        format,
+        json_writer,
+    );
+}
+
+/// Search for all words in the trie of the searcher at a given distance (or less)
+/// of the query with the search options, and return the result in a JSON representation.
+fn process_search_approx(
+    searcher: &mut Searcher,
     word: &str,
-    distance: Distance,
-    layer_stack: &mut LayerStack<Distance, WordCharCount>,
-    iter_stack: &mut IterationStack<'a>,
-    result_buffer: &mut Vec<FoundWord>,
+    options: &SearchOptions,
+    format: OutputFormat,
     json_writer: &mut impl Write,
 ) {
-    // Clear the buffers of their old data
+    let trie = searcher.trie();
+    let Searcher {
+        layer_stack,
+        iter_stack,
+        word_pool,
+        result_buffer,
+        ..
+    } = searcher;
+
+    // Clear the buffers of their old data, keeping the old words for reuse
     layer_stack.clear();
     iter_stack.clear();
-    result_buffer.clear();
+    word_pool.recycle(result_buffer);
 
     // Search at the query distance
     *result_buffer = search_approx(
         trie,
         word,
-        distance,
+        options,
         layer_stack,
         iter_stack,
+        word_pool,
         std::mem::take(result_buffer),
     );
 
-    // Sort the results based on the order defined by FoundWord
-    result_buffer.sort_unstable();
+    // Sort the results based on the order defined by FoundWord, with the ranking frequencies,
+    // unless the search already kept the best ones sorted
+    if options.limit.is_none() {
+        sort_ranked(result_buffer, &options.ranking);
+    }
 
-    let r = json_writer.write_all(b"[");
-    debug_assert!(r.is_ok());
+    write_found_words(
+        result_buffer,
+        word,
+        options.ignore_case,
+        &options.edit_costs(),
+        format,
+        json_writer,
+    );
+}
 
-    let mut first = true;
-    for found_word in result_buffer.iter_mut() {
-        // Add comma between elements in the JSON array
-        // But there must not be a trailing comma
-        if !first {
-            let r = json_writer.write_all(b",");
-            debug_assert!(r.is_ok());
-        }
-        first = false;
+/// Search for the closest word in the trie of the searcher, whatever its distance
+/// to the query, and return the result in a JSON representation.
+fn process_search_nearest(
+    searcher: &mut Searcher,
+    word: &str,
+    ignore_case: bool,
+    ranking: &Ranking,
+    format: OutputFormat,
+    json_writer: &mut impl Write,
+) {
+    let trie = searcher.trie();
+    let Searcher {
+        layer_stack,
+        iter_stack,
+        word_pool,
+        result_buffer,
+        ..
+    } = searcher;
 
-        // Extract inner string to reduce memory usage
-        let inner_word = std::mem::take(&mut found_word.word);
+    // The stacks are cleared before each of the searches
+    word_pool.recycle(result_buffer);
+    *result_buffer = search_nearest(
+        trie,
+        word,
+        ignore_case,
+        ranking,
+        layer_stack,
+        iter_stack,
+        word_pool,
+        std::mem::take(result_buffer),
+    );
 
-        // Append the formatted result to the JSON buffer
-        write_json_result(&inner_word, found_word.freq, found_word.dist, json_writer);
-    }
+    write_found_words(
+        result_buffer,
+        word,
+        ignore_case,
+        &EditCosts::default(),
+        format,
+        json_writer,
+    );
+}
 
-    let r = json_writer.write_all(b"]\n");
-    debug_assert!(r.is_ok());
+/// Write the words found by an approximate search of the query in the output format,
+/// with the spans matching the query given by the alignments of the search.
+fn write_found_words(
+    found_words: &[FoundWord],
+    word: &str,
+    ignore_case: bool,
+    costs: &EditCosts,
+    format: OutputFormat,
+    json_writer: &mut impl Write,
+) {
+    write_results(
+        found_words.iter().map(|found_word| {
+            (
+                found_word.word.as_str(),
+                found_word.freq,
+                Some(found_word.dist),
+            )
+        }),
+        true,
+        |found| {
+            let (edits, _) = edit_operations(word, found, ignore_case, costs);
+            match_spans(&edits, found.chars().count())
+        },
+        format,
+        json_writer,
+    );
 }
 
-/// Process queries received in the [standard input stream](std::io::stdin)
-pub fn process_stdin_queries(trie: &CompiledTrie) -> Result<()> {
+/// Process the query lines of the input and write their results in the output.
+///
+/// If `ignore_case` is set, the approximate searches ignore the case of the characters,
+/// and they only find the words with the `pinned` characters of the query
+/// (except for the nearest word, which is always found).
+/// The results of the approximate searches found at the same distance are ranked by `ranking`,
+/// and all results are written in the given format.
+/// With a `phrase_separator`, the words of the queries are unescaped as phrases
+/// (see [unescape_phrase](vague_search_core::unescape_phrase)).
+///
+/// The queries stop at the end of the input or at a `quit` (or `exit`) query.
+/// The output is flushed after the results of each query, so that it can be buffered
+/// while the results of a query are still written as soon as they are found.
+#[allow(clippy::too_many_arguments)]
+fn process_queries(
+    trie: &CompiledTrie,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    ignore_case: bool,
+    pinned: PinnedChars,
+    ranking: &Ranking,
+    format: OutputFormat,
+    phrase_separator: Option<char>,
+) -> std::io::Result<()> {
     const LINE_CAP: usize = 100;
-    const LAYER_STACK_ELEMENTS_CAP: usize = 2000;
-    const LAYER_STACK_LAYERS_CAP: usize = 50;
-    const ITERATION_STACK_CAP: usize = 500;
-    const RESULT_BUFFER_CAP: usize = 1000;
 
     // Initialize all buffers used to reduce allocation overhead
     let mut line = String::with_capacity(LINE_CAP);
-    let mut layer_stack =
-        LayerStack::with_capacity(LAYER_STACK_ELEMENTS_CAP, LAYER_STACK_LAYERS_CAP);
-    let mut iter_stack = IterationStack::with_capacity(ITERATION_STACK_CAP);
-    let mut result_buffer = Vec::with_capacity(RESULT_BUFFER_CAP);
+    let mut searcher = SearcherBuilder::new().build(trie);
+    let mut phrase_buffer = String::new();
+    #[cfg(feature = "nfc")]
+    let mut nfc_buffer = String::with_capacity(LINE_CAP);
+
+    // Counting the words traverses the whole trie, so only do it once
+    let mut word_count = None;
 
-    let input_stream = std::io::stdin();
     loop {
         line.clear();
-        match input_stream.read_line(&mut line) {
+        match input.read_line(&mut line) {
             Ok(0) => return Ok(()), // EOF reached
             Ok(_) => {
-                // Parse the command
-                let (word, dist) = match parse_command_line(&line.trim()) {
+                // Parse the query
+                let mut query = match parse_command_line(line.trim()) {
                     Ok(e) => e,
                     Err(e) => {
                         eprintln!("> {}", e);
@@ -191,25 +851,779 @@ pub fn process_stdin_queries(trie: &CompiledTrie) -> Result<()> {
                     }
                 };
 
-                let stdout = std::io::stdout();
-                let mut lock = stdout.lock();
+                if let Some(separator) = phrase_separator {
+                    unescape_query(&mut query, separator, &mut phrase_buffer);
+                }
+
+                // The dictionary words are expected to be in NFC, so must be the query
+                #[cfg(feature = "nfc")]
+                normalize_query(&mut query, &mut nfc_buffer);
 
                 // Search and return the result in a JSON representation
-                if dist == 0 {
-                    process_search_exact(trie, word, &mut lock)
-                } else {
-                    process_search_approx(
-                        trie,
+                // The approximate searches at a distance 0 are also handled by search_approx
+                let ParsedQuery {
+                    action,
+                    word,
+                    dist,
+                    limit,
+                } = query;
+                match action {
+                    Action::Exact => process_search_exact(trie, word, format, output),
+                    Action::Nearest => process_search_nearest(
+                        &mut searcher,
                         word,
-                        dist,
-                        &mut layer_stack,
-                        &mut iter_stack,
-                        &mut result_buffer,
-                        &mut lock,
-                    )
+                        ignore_case,
+                        ranking,
+                        format,
+                        output,
+                    ),
+                    Action::Prefix => process_search_prefix(trie, word, format, output),
+                    Action::Complete => {
+                        // The parser always gives the number of completions
+                        let limit = limit.unwrap_or(0);
+                        process_search_complete(trie, word, limit, format, output)
+                    }
+                    Action::Pattern => process_search_pattern(trie, word, format, output),
+                    Action::Count => {
+                        let count = *word_count.get_or_insert_with(|| trie.word_count());
+                        write_count(count, output)
+                    }
+                    Action::Stats => write_stats(trie, output),
+                    Action::Help => {
+                        let r = output.write_all(HELP.as_bytes());
+                        debug_assert!(r.is_ok());
+                    }
+                    Action::Quit => return Ok(()),
+                    Action::Approx => {
+                        // The options of the query, with the ones of the command line
+                        let options = SearchOptions {
+                            dist_max: dist,
+                            limit,
+                            ignore_case,
+                            pinned,
+                            ranking: *ranking,
+                            ..SearchOptions::default()
+                        };
+                        process_search_approx(&mut searcher, word, &options, format, output)
+                    }
+                }
+                let r = output.flush();
+                debug_assert!(r.is_ok());
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Process queries received in the [standard input stream](std::io::stdin)
+/// and write their results in the standard output.
+///
+/// See [process_queries](process_queries) for the options.
+pub fn process_stdin_queries(
+    trie: &CompiledTrie,
+    ignore_case: bool,
+    pinned: PinnedChars,
+    ranking: &Ranking,
+    format: OutputFormat,
+    phrase_separator: Option<char>,
+) -> Result<()> {
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+    process_queries(
+        trie,
+        &mut stdin.lock(),
+        &mut BufWriter::new(stdout.lock()),
+        ignore_case,
+        pinned,
+        ranking,
+        format,
+        phrase_separator,
+    )
+    .context(Stdin)
+}
+
+/// Process the queries of a file, one per line, and write their results in the standard output.
+///
+/// See [process_queries](process_queries) for the options.
+pub fn process_file_queries(
+    trie: &CompiledTrie,
+    path: &Path,
+    ignore_case: bool,
+    pinned: PinnedChars,
+    ranking: &Ranking,
+    format: OutputFormat,
+    phrase_separator: Option<char>,
+) -> Result<()> {
+    let file = File::open(path).context(QueriesRead { path })?;
+    let stdout = std::io::stdout();
+    process_queries(
+        trie,
+        &mut BufReader::new(file),
+        &mut BufWriter::new(stdout.lock()),
+        ignore_case,
+        pinned,
+        ranking,
+        format,
+        phrase_separator,
+    )
+    .context(QueriesRead { path })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Decode the first JSON string of the input, following the JSON specification.
+    fn decode_json_string(json: &str) -> String {
+        let start = json.find("\"word\":\"").unwrap() + "\"word\":\"".len();
+        let mut chars = json[start..].chars();
+        let mut decoded = String::new();
+        loop {
+            match chars.next().unwrap() {
+                '"' => return decoded,
+                '\\' => match chars.next().unwrap() {
+                    '"' => decoded.push('"'),
+                    '\\' => decoded.push('\\'),
+                    '/' => decoded.push('/'),
+                    'n' => decoded.push('\n'),
+                    't' => decoded.push('\t'),
+                    'r' => decoded.push('\r'),
+                    'u' => {
+                        let hex: String = chars.by_ref().take(4).collect();
+                        let code = u32::from_str_radix(&hex, 16).unwrap();
+                        decoded.push(std::char::from_u32(code).unwrap());
+                    }
+                    c => panic!("Invalid escape character: {}", c),
+                },
+                c => {
+                    assert!(c as u32 >= 0x20, "Unescaped control character");
+                    decoded.push(c);
                 }
             }
-            Err(e) => Err(e).context(Stdin)?,
         }
     }
+
+    fn json_result(word: &str) -> String {
+        let mut buf = Vec::new();
+        write_json_result(word, NonZeroU32::new(3).unwrap(), 1, None, &mut buf);
+        String::from_utf8(buf).unwrap()
+    }
+
+    /// Create the parsed query with the arguments.
+    fn parsed_query(
+        action: Action,
+        word: &str,
+        dist: Distance,
+        limit: Option<usize>,
+    ) -> ParsedQuery<'_> {
+        ParsedQuery {
+            action,
+            word,
+            dist,
+            limit,
+        }
+    }
+
+    /// Return the cause of the parse error of the line.
+    fn parse_error(line: &str) -> String {
+        match parse_command_line(line) {
+            Err(crate::error::Error::CommandParse { cause, .. }) => cause,
+            result => panic!("{:?} parsed into {:?}", line, result),
+        }
+    }
+
+    #[test]
+    fn test_parse_action() {
+        assert_eq!(parse_error(""), "No action found");
+        assert_eq!(parse_error("   "), "No action found");
+        assert_eq!(parse_error("search 1 test"), "Action not recognized");
+        assert_eq!(parse_error("APPROX 1 test"), "Action not recognized");
+        for &(name, action) in &[
+            ("approx", Action::Approx),
+            ("exact", Action::Exact),
+            ("prefix", Action::Prefix),
+            ("complete", Action::Complete),
+            ("count", Action::Count),
+            ("stats", Action::Stats),
+            ("help", Action::Help),
+            ("quit", Action::Quit),
+            ("exit", Action::Quit),
+        ] {
+            assert_eq!(Action::from_name(name), Some(action));
+        }
+    }
+
+    #[test]
+    fn test_parse_approx() {
+        let parsed = parse_command_line("approx 2 test").unwrap();
+        assert_eq!(parsed, parsed_query(Action::Approx, "test", 2, None));
+
+        let parsed = parse_command_line("approx 1 test 10").unwrap();
+        assert_eq!(parsed, parsed_query(Action::Approx, "test", 1, Some(10)));
+
+        assert_eq!(
+            parse_error("approx 1 test ten"),
+            "Could not parse the limit into an integer"
+        );
+        assert_eq!(
+            parse_error("approx 1 test -1"),
+            "Could not parse the limit into an integer"
+        );
+        assert_eq!(
+            parse_error("approx one test"),
+            "Could not parse the distance into an integer"
+        );
+        assert_eq!(parse_error("approx 1"), "No word found");
+        assert_eq!(parse_error("approx"), "No distance found");
+        assert_eq!(
+            parse_error("approx 1 test 10 20"),
+            "Too many arguments for an approximate search"
+        );
+    }
+
+    #[test]
+    fn test_parse_exact() {
+        let parsed = parse_command_line("exact test").unwrap();
+        assert_eq!(parsed, parsed_query(Action::Exact, "test", 0, None));
+
+        let parsed = parse_command_line("exact   日本 ").unwrap();
+        assert_eq!(parsed, parsed_query(Action::Exact, "日本", 0, None));
+
+        assert_eq!(parse_error("exact"), "No word found");
+        assert_eq!(
+            parse_error("exact test 2"),
+            "Too many arguments for an exact search"
+        );
+        assert!(parse_command_line("exact 0 test").is_err());
+    }
+
+    #[test]
+    fn test_parse_nearest() {
+        let parsed = parse_command_line("nearest test").unwrap();
+        assert_eq!(parsed, parsed_query(Action::Nearest, "test", 0, None));
+
+        assert_eq!(parse_error("nearest"), "No word found");
+        assert_eq!(
+            parse_error("nearest test 2"),
+            "Too many arguments for a nearest search"
+        );
+    }
+
+    #[test]
+    fn test_parse_pattern() {
+        let parsed = parse_command_line("pattern t??t").unwrap();
+        assert_eq!(parsed, parsed_query(Action::Pattern, "t??t", 0, None));
+
+        assert_eq!(parse_error("pattern"), "No word found");
+        assert_eq!(
+            parse_error("pattern t??t 2"),
+            "Too many arguments for a pattern search"
+        );
+    }
+
+    #[test]
+    fn test_process_queries_pattern() {
+        let trie = crate::test_utils::create_trie(&[("test", 1), ("text", 2), ("toast", 3)]);
+        let input = "pattern te?t\npattern t???t\npattern ???\npattern *st\n";
+
+        let mut output = Vec::new();
+        process_queries(
+            &trie,
+            &mut input.as_bytes(),
+            &mut output,
+            false,
+            PinnedChars::default(),
+            &Ranking::default(),
+            OutputFormat::Json { highlight: true },
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            concat!(
+                "[{\"word\":\"test\",\"freq\":1,\"match\":[[0,4]]},",
+                "{\"word\":\"text\",\"freq\":2,\"match\":[[0,4]]}]\n",
+                "[{\"word\":\"toast\",\"freq\":3,\"match\":[[0,5]]}]\n",
+                "[]\n",
+                "[{\"word\":\"test\",\"freq\":1,\"match\":[[0,4]]},",
+                "{\"word\":\"toast\",\"freq\":3,\"match\":[[0,5]]}]\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_process_queries_nearest() {
+        let trie = crate::test_utils::create_trie(&[("abcd", 1), ("abdc", 2), ("xyz", 3)]);
+        let input = "approx 1 ab
+nearest ab
+nearest xyzxyz
+nearest abcd
+";
+
+        // The closest word is found even past the distance of the approximate searches
+        let mut output = Vec::new();
+        process_queries(
+            &trie,
+            &mut input.as_bytes(),
+            &mut output,
+            false,
+            PinnedChars::default(),
+            &Ranking::default(),
+            OutputFormat::Json { highlight: false },
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            concat!(
+                "[]\n",
+                "[{\"word\":\"abdc\",\"freq\":2,\"distance\":2}]\n",
+                "[{\"word\":\"xyz\",\"freq\":3,\"distance\":3}]\n",
+                "[{\"word\":\"abcd\",\"freq\":1,\"distance\":0}]\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_prefix() {
+        let parsed = parse_command_line("prefix te").unwrap();
+        assert_eq!(parsed, parsed_query(Action::Prefix, "te", 0, None));
+
+        assert_eq!(parse_error("prefix"), "No word found");
+        assert_eq!(
+            parse_error("prefix te st"),
+            "Too many arguments for a prefix search"
+        );
+    }
+
+    #[test]
+    fn test_parse_complete() {
+        let parsed = parse_command_line("complete te 5").unwrap();
+        assert_eq!(parsed, parsed_query(Action::Complete, "te", 0, Some(5)));
+
+        assert_eq!(parse_error("complete"), "No word found");
+        assert_eq!(parse_error("complete te"), "No number of completions found");
+        assert_eq!(
+            parse_error("complete te five"),
+            "Could not parse the number of completions into an integer"
+        );
+        assert_eq!(
+            parse_error("complete te 5 6"),
+            "Too many arguments for a completion"
+        );
+    }
+
+    #[test]
+    fn test_parse_count() {
+        let count = parsed_query(Action::Count, "", 0, None);
+        assert_eq!(parse_command_line("count").unwrap(), count);
+        assert_eq!(parse_command_line("  count ").unwrap(), count);
+        assert_eq!(parse_error("count words"), "Too many arguments for a count");
+    }
+
+    #[test]
+    fn test_parse_help_quit() {
+        let help = parsed_query(Action::Help, "", 0, None);
+        assert_eq!(parse_command_line("help").unwrap(), help);
+        assert_eq!(
+            parse_error("help approx"),
+            "Too many arguments for the help"
+        );
+
+        let quit = parsed_query(Action::Quit, "", 0, None);
+        assert_eq!(parse_command_line("quit").unwrap(), quit);
+        assert_eq!(parse_command_line(" exit").unwrap(), quit);
+        assert_eq!(parse_error("quit now"), "Too many arguments to quit");
+    }
+
+    #[test]
+    fn test_process_queries_help_quit() {
+        let trie = crate::test_utils::create_trie(&[("test", 3)]);
+        let input = "help\nexact test\nquit\nexact test\n";
+
+        // The queries after quitting are not processed
+        let mut output = Vec::new();
+        process_queries(
+            &trie,
+            &mut input.as_bytes(),
+            &mut output,
+            false,
+            PinnedChars::default(),
+            &Ranking::default(),
+            OutputFormat::Json { highlight: false },
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!(
+                "{}[{{\"word\":\"test\",\"freq\":3,\"distance\":0}}]\n",
+                HELP
+            )
+        );
+
+        // Every action is described by the help
+        for line in HELP.lines() {
+            let name = line.split_whitespace().next().unwrap();
+            assert!(Action::from_name(name).is_some(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_stats() {
+        let stats = parsed_query(Action::Stats, "", 0, None);
+        assert_eq!(parse_command_line("stats").unwrap(), stats);
+        assert_eq!(
+            parse_error("stats 1"),
+            "Too many arguments for the statistics"
+        );
+
+        let trie = crate::test_utils::create_trie(&[("car", 3), ("cart", 1), ("a", 2), ("b", 2)]);
+        let mut output = Vec::new();
+        write_stats(&trie, &mut output);
+        let stats = String::from_utf8(output).unwrap();
+
+        let histogram = trie.node_type_histogram();
+        let nb_nodes = histogram.naive + histogram.patricia + histogram.range;
+        assert!(stats.starts_with(&format!("{{\"nb_nodes\":{},", nb_nodes)));
+        assert!(stats.contains(&format!(
+            ",\"nodes_bytes\":{},",
+            nb_nodes * size_of::<CompiledTrieNode>()
+        )));
+        assert!(stats.contains(&format!(",\"range_nodes\":{},", histogram.range)));
+        assert!(stats.ends_with(&format!(
+            ",\"longest_patricia\":{}}}\n",
+            trie.longest_patricia_len()
+        )));
+    }
+
+    #[cfg(feature = "nfc")]
+    #[test]
+    fn test_normalize_query_nfd() {
+        let trie = crate::test_utils::create_trie(&[("caf\u{e9}", 2)]);
+
+        // "café" with a decomposed accent
+        let line = "exact cafe\u{301}";
+        let mut buffer = String::new();
+        let mut query = parse_command_line(line).unwrap();
+        assert_eq!(search_exact(&trie, "cafe\u{301}", None), None);
+
+        normalize_query(&mut query, &mut buffer);
+        assert_eq!(query, parsed_query(Action::Exact, "caf\u{e9}", 0, None));
+        assert_eq!(search_exact(&trie, query.word, None), NonZeroU32::new(2));
+
+        // Already normalized words are kept as is
+        let mut buffer = String::new();
+        let mut query = parse_command_line("approx 1 caf\u{e9}").unwrap();
+        normalize_query(&mut query, &mut buffer);
+        assert_eq!(query, parsed_query(Action::Approx, "caf\u{e9}", 1, None));
+    }
+
+    #[test]
+    fn test_process_queries() {
+        let trie = crate::test_utils::create_trie(&[("test", 3), ("tests", 1), ("toast", 2)]);
+        let input =
+            "exact test\nunknown\nexact tes\napprox 1 tesst\n\ncomplete t 2\napprox 0 tests\ncount\ncount";
+
+        let mut output = Vec::new();
+        process_queries(
+            &trie,
+            &mut input.as_bytes(),
+            &mut output,
+            false,
+            PinnedChars::default(),
+            &Ranking::default(),
+            OutputFormat::Json { highlight: false },
+            None,
+        )
+        .unwrap();
+
+        // The invalid lines are skipped, and the words not found give an empty line of results
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "[{\"word\":\"test\",\"freq\":3,\"distance\":0}]\n\
+             []\n\
+             [{\"word\":\"test\",\"freq\":3,\"distance\":1},{\"word\":\"tests\",\"freq\":1,\"distance\":1}]\n\
+             [{\"word\":\"test\",\"freq\":3},{\"word\":\"toast\",\"freq\":2}]\n\
+             [{\"word\":\"tests\",\"freq\":1,\"distance\":0}]\n\
+             3\n3\n"
+        );
+    }
+
+    /// A writer keeping the bytes written before each flush.
+    #[derive(Default)]
+    struct FlushRecorder {
+        buffer: Vec<u8>,
+        flushed: Vec<String>,
+    }
+
+    impl Write for FlushRecorder {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.buffer.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            let bytes = std::mem::take(&mut self.buffer);
+            self.flushed.push(String::from_utf8(bytes).unwrap());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_process_queries_flush() {
+        let trie = crate::test_utils::create_trie(&[("test", 3), ("tests", 1)]);
+        let input = "exact test\nunknown\napprox 1 tests\ncount";
+
+        // The results of each query are flushed once they are all written
+        let mut output = FlushRecorder::default();
+        process_queries(
+            &trie,
+            &mut input.as_bytes(),
+            &mut output,
+            false,
+            PinnedChars::default(),
+            &Ranking::default(),
+            OutputFormat::Ndjson { highlight: false },
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            output.flushed,
+            [
+                "{\"word\":\"test\",\"freq\":3,\"distance\":0}\n",
+                "{\"word\":\"tests\",\"freq\":1,\"distance\":0}\n\
+                 {\"word\":\"test\",\"freq\":3,\"distance\":1}\n",
+                "2\n",
+            ]
+        );
+        assert!(output.buffer.is_empty());
+    }
+
+    #[test]
+    fn test_process_queries_empty_dictionary() {
+        let trie = crate::test_utils::create_trie(&[]);
+        let input =
+            "exact test\napprox 0 test\napprox 2 test\nnearest test\nprefix t\ncomplete t 2\npattern t??t\ncount";
+
+        let mut output = Vec::new();
+        process_queries(
+            &trie,
+            &mut input.as_bytes(),
+            &mut output,
+            true,
+            PinnedChars::default(),
+            &Ranking::default(),
+            OutputFormat::Json { highlight: false },
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "[]\n[]\n[]\n[]\n[]\n[]\n[]\n0\n"
+        );
+    }
+
+    #[test]
+    fn test_process_queries_ndjson() {
+        let trie = crate::test_utils::create_trie(&[("test", 3), ("tests", 1), ("toast", 2)]);
+        let input = "exact test\napprox 1 tesst\nexact tost\ncomplete t 2\napprox 0 tests";
+
+        let mut output = Vec::new();
+        process_queries(
+            &trie,
+            &mut input.as_bytes(),
+            &mut output,
+            false,
+            PinnedChars::default(),
+            &Ranking::default(),
+            OutputFormat::Ndjson { highlight: false },
+            None,
+        )
+        .unwrap();
+
+        // One line per result, and none for a query without any
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"word\":\"test\",\"freq\":3,\"distance\":0}\n\
+             {\"word\":\"test\",\"freq\":3,\"distance\":1}\n\
+             {\"word\":\"tests\",\"freq\":1,\"distance\":1}\n\
+             {\"word\":\"test\",\"freq\":3}\n\
+             {\"word\":\"toast\",\"freq\":2}\n\
+             {\"word\":\"tests\",\"freq\":1,\"distance\":0}\n"
+        );
+    }
+
+    #[test]
+    fn test_process_queries_highlight() {
+        let trie = crate::test_utils::create_trie(&[("test", 3), ("tests", 1), ("toast", 2)]);
+        let input = "exact test\napprox 1 tesst\ncomplete t 2\napprox 1 tset\nexact tost";
+
+        let run = |format| {
+            let mut output = Vec::new();
+            process_queries(
+                &trie,
+                &mut input.as_bytes(),
+                &mut output,
+                false,
+                PinnedChars::default(),
+                &Ranking::default(),
+                format,
+                None,
+            )
+            .unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        // The spans around the edits are the common prefix and suffix of the query and word
+        assert_eq!(
+            run(OutputFormat::Json { highlight: true }),
+            "[{\"word\":\"test\",\"freq\":3,\"distance\":0,\"match\":[[0,4]]}]\n\
+             [{\"word\":\"test\",\"freq\":3,\"distance\":1,\"match\":[[0,4]]},\
+             {\"word\":\"tests\",\"freq\":1,\"distance\":1,\"match\":[[0,3]]}]\n\
+             [{\"word\":\"test\",\"freq\":3,\"match\":[[0,1]]},\
+             {\"word\":\"toast\",\"freq\":2,\"match\":[[0,1]]}]\n\
+             [{\"word\":\"test\",\"freq\":3,\"distance\":1,\"match\":[[0,1],[3,4]]}]\n\
+             []\n"
+        );
+        assert_eq!(
+            run(OutputFormat::Ndjson { highlight: true }).lines().nth(2),
+            Some("{\"word\":\"tests\",\"freq\":1,\"distance\":1,\"match\":[[0,3]]}")
+        );
+        assert!(!run(OutputFormat::Json { highlight: false }).contains("match"));
+    }
+
+    #[test]
+    fn test_process_queries_csv() {
+        let trie = crate::test_utils::create_trie(&[("test", 3), ("tests", 1), ("toast", 2)]);
+        let input = "exact test\nexact tost\ncomplete t 2";
+
+        let run = |header| {
+            let mut output = Vec::new();
+            process_queries(
+                &trie,
+                &mut input.as_bytes(),
+                &mut output,
+                false,
+                PinnedChars::default(),
+                &Ranking::default(),
+                OutputFormat::Csv { header },
+                None,
+            )
+            .unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        assert_eq!(
+            run(true),
+            "word,freq,distance\ntest,3,0\nword,freq,distance\nword,freq\ntest,3\ntoast,2\n"
+        );
+        assert_eq!(run(false), "test,3,0\ntest,3\ntoast,2\n");
+    }
+
+    #[test]
+    fn test_process_queries_phrases() {
+        let trie =
+            crate::test_utils::create_trie(&[("new york", 10), ("new", 5), ("snake_case", 2)]);
+        let input = "exact new_york\nexact snake\\_case\nprefix new_\ncount";
+
+        let run = |phrase_separator| {
+            let mut output = Vec::new();
+            process_queries(
+                &trie,
+                &mut input.as_bytes(),
+                &mut output,
+                false,
+                PinnedChars::default(),
+                &Ranking::default(),
+                OutputFormat::Csv { header: false },
+                phrase_separator,
+            )
+            .unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        assert_eq!(
+            run(Some('_')),
+            "new york,10,0\nsnake_case,2,0\nnew york,10\n3\n"
+        );
+        assert_eq!(run(None), "3\n");
+    }
+
+    #[test]
+    fn test_csv_quoting() {
+        let csv = |word| {
+            let mut buf = Vec::new();
+            write_csv_result(word, NonZeroU32::new(3).unwrap(), Some(1), &mut buf);
+            String::from_utf8(buf).unwrap()
+        };
+
+        assert_eq!(csv("test"), "test,3,1\n");
+        assert_eq!(csv("日本"), "日本,3,1\n");
+        assert_eq!(csv("a,b"), "\"a,b\",3,1\n");
+        assert_eq!(csv("say \"hi\""), "\"say \"\"hi\"\"\",3,1\n");
+        assert_eq!(csv("a\nb"), "\"a\nb\",3,1\n");
+    }
+
+    #[test]
+    fn test_json_prefix() {
+        let trie = crate::test_utils::create_trie(&[("a\"b", 2), ("a", 1), ("ab", 4), ("b", 3)]);
+
+        let mut buf = Vec::new();
+        process_search_prefix(
+            &trie,
+            "a",
+            OutputFormat::Json { highlight: false },
+            &mut buf,
+        );
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "[{\"word\":\"a\",\"freq\":1},{\"word\":\"a\\\"b\",\"freq\":2},{\"word\":\"ab\",\"freq\":4}]\n"
+        );
+
+        let mut buf = Vec::new();
+        process_search_prefix(
+            &trie,
+            "c",
+            OutputFormat::Json { highlight: false },
+            &mut buf,
+        );
+        assert_eq!(String::from_utf8(buf).unwrap(), "[]\n");
+    }
+
+    #[test]
+    fn test_json_no_escape() {
+        let json = json_result("abé日🦀");
+        assert_eq!(json, r#"{"word":"abé日🦀","freq":3,"distance":1}"#);
+        assert_eq!(decode_json_string(&json), "abé日🦀");
+    }
+
+    #[test]
+    fn test_json_escape_quote_backslash() {
+        let json = json_result("a\"b");
+        assert_eq!(json, r#"{"word":"a\"b","freq":3,"distance":1}"#);
+        assert_eq!(decode_json_string(&json), "a\"b");
+
+        let json = json_result("c\\d");
+        assert_eq!(json, r#"{"word":"c\\d","freq":3,"distance":1}"#);
+        assert_eq!(decode_json_string(&json), "c\\d");
+    }
+
+    #[test]
+    fn test_json_escape_control_characters() {
+        let json = json_result("line\nbreak\u{1f}");
+        assert_eq!(
+            json,
+            r#"{"word":"line\u000abreak\u001f","freq":3,"distance":1}"#
+        );
+        assert_eq!(decode_json_string(&json), "line\nbreak\u{1f}");
+
+        let mut buf = Vec::new();
+        write_json_result_dist_0("\"\t\"", NonZeroU32::new(1).unwrap(), &mut buf);
+        let json = String::from_utf8(buf).unwrap();
+        assert_eq!(
+            json,
+            "[{\"word\":\"\\\"\\u0009\\\"\",\"freq\":1,\"distance\":0}]\n"
+        );
+        assert_eq!(decode_json_string(&json), "\"\t\"");
+    }
 }
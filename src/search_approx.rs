@@ -1,15 +1,24 @@
 use crate::{
     layer_stack::LayerStack,
-    search_exact::{compare_keys, search_exact_children},
+    search_exact::{compare_keys, descend_prefix, search_exact, search_exact_children, PrefixPath},
+    substitution::SubstitutionTable,
 };
 use std::{
-    cmp::{max, min, Ordering},
+    cmp::{max, min, Ordering, Reverse},
+    collections::{BinaryHeap, HashSet},
     num::NonZeroU32,
+    ops::Range,
 };
 use vague_search_core::{
     CompiledTrie, CompiledTrieNode, NaiveNode, NodeValue, PatriciaNode, RangeElement, RangeNode,
 };
 
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod simd;
+
+pub mod alignment;
+use alignment::EditOp;
+
 /// A type to store searching distances.
 pub type Distance = u16;
 
@@ -19,6 +28,7 @@ pub type WordCharCount = u16;
 /// An iteration element. Includes what is needed to continue the iteration.
 /// Similar to what the compiler would store during a recursion call.
 /// However by doing it manually, some optimizations can be applied.
+#[derive(Copy, Clone)]
 pub struct IterationElement<'a> {
     /// The current trie node.
     node: &'a CompiledTrieNode,
@@ -33,7 +43,7 @@ pub struct IterationElement<'a> {
 }
 
 /// A stack of iterations, used to linearise the recursive searching algorithm.
-/// The end of a layer is represented by a "dummy node", which is a None element.
+/// The end of the layers of a node is represented by a "dummy node", which is a None element.
 pub type IterationStack<'a> = Vec<Option<IterationElement<'a>>>;
 
 /// A word that have been found by a search query.
@@ -42,6 +52,10 @@ pub struct FoundWord {
     pub word: String,
     pub freq: NonZeroU32,
     pub dist: Distance,
+
+    /// The edit operations from the query to the word, only set on demand
+    /// by [align_found_words](alignment::align_found_words).
+    pub edits: Option<Vec<EditOp>>,
 }
 
 impl PartialOrd for FoundWord {
@@ -59,6 +73,397 @@ impl Ord for FoundWord {
     }
 }
 
+/// The costs of the edit operations of the distance between the query and a word of the trie.
+///
+/// The search relies on every operation costing at least 1, and on a transposition
+/// not being cheaper than a substitution or an insertion, so that it can stop its traversal
+/// as soon as the distance exceeds the maximum (see [new](EditCosts::new)).
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct EditCosts<'a> {
+    insert: Distance,
+    delete: Distance,
+    substitute: Distance,
+    transpose: Distance,
+    substitution_table: Option<&'a SubstitutionTable>,
+    unrestricted_transpositions: bool,
+    transpositions: bool,
+}
+
+impl<'a> EditCosts<'a> {
+    /// Create the costs of each edit operation, from the query to the word of the trie:
+    /// - `insert`: a character of the word is missing from the query
+    /// - `delete`: a character of the query is missing from the word
+    /// - `substitute`: a character of the query is replaced by another one in the word
+    /// - `transpose`: two adjacent characters of the query are swapped in the word
+    ///
+    /// Return None if a cost is 0, or if `transpose` is less than `insert` or `substitute`.
+    pub fn new(
+        insert: Distance,
+        delete: Distance,
+        substitute: Distance,
+        transpose: Distance,
+    ) -> Option<Self> {
+        let valid =
+            min(min(insert, delete), substitute) > 0 && transpose >= max(insert, substitute);
+        if valid {
+            Some(Self {
+                insert,
+                delete,
+                substitute,
+                transpose,
+                substitution_table: None,
+                unrestricted_transpositions: false,
+                transpositions: true,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Use the costs of the table for the pairs of characters it contains,
+    /// instead of the substitution cost.
+    ///
+    /// Return None if a cost of the table is 0 or greater than the transposition cost,
+    /// for the same reasons as [new](EditCosts::new).
+    pub fn with_substitution_table(self, table: &'a SubstitutionTable) -> Option<Self> {
+        if table.costs().all(|cost| cost > 0 && cost <= self.transpose) {
+            Some(Self {
+                substitution_table: Some(table),
+                ..self
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Compute the true [Damerau-Levenshtein](https://en.wikipedia.org/wiki/Damerau%E2%80%93Levenshtein_distance#Distance_with_adjacent_transpositions)
+    /// distance instead of the optimal string alignment distance.
+    ///
+    /// By default, the characters of a transposition cannot be edited afterwards,
+    /// nor can characters be inserted between them: `"CA"` and `"ABC"` are at a distance
+    /// of 3 since `"CA"` to `"AC"` to `"ABC"` edits the transposed characters. With the
+    /// unrestricted transpositions, they are at a distance of 2. The search is then slower,
+    /// since each cell looks for the last transposable characters of the query and word.
+    pub fn with_unrestricted_transpositions(self) -> Self {
+        Self {
+            unrestricted_transpositions: true,
+            ..self
+        }
+    }
+
+    /// Compute the Levenshtein distance, without any transposition
+    /// (see [transposition](SearchOptions::transposition)).
+    pub(crate) fn without_transpositions(self) -> Self {
+        Self {
+            transpositions: false,
+            unrestricted_transpositions: false,
+            ..self
+        }
+    }
+
+    /// Return the cost of substituting the character of the query by the one of the word,
+    /// which are known to be different.
+    #[inline(always)]
+    fn substitute_cost(&self, query_char: char, trie_char: char) -> Distance {
+        self.substitution_table
+            .and_then(|table| table.get(query_char, trie_char))
+            .unwrap_or(self.substitute)
+    }
+}
+
+impl Default for EditCosts<'_> {
+    /// The unit costs of the Damerau-Levenshtein distance.
+    fn default() -> Self {
+        Self {
+            insert: 1,
+            delete: 1,
+            substitute: 1,
+            transpose: 1,
+            substitution_table: None,
+            unrestricted_transpositions: false,
+            transpositions: true,
+        }
+    }
+}
+
+/// The characters of the query which the found words must also have at the same position,
+/// since the first and last characters of a typo are usually correct.
+///
+/// Pinning the first character also prunes all the other subtrees of the roots.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct PinnedChars {
+    /// The words must begin with the first character of the query.
+    pub first: bool,
+
+    /// The words must end with the last character of the query.
+    pub last: bool,
+}
+
+/// How the frequencies of the words are dampened when ranking the results,
+/// so that extremely frequent words do not always win the frequency tie-break.
+///
+/// This only changes the order of the results, not their returned frequency.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FreqDamping {
+    /// Rank the words by their raw frequency.
+    None,
+
+    /// Rank all frequencies above the cap as if they were equal to it.
+    Cap(NonZeroU32),
+
+    /// Rank the words by the logarithm of their frequency,
+    /// so that frequencies of the same order of magnitude (power of 2) are equal.
+    Log,
+}
+
+impl FreqDamping {
+    /// Return the frequency used to rank a word of the given frequency.
+    #[inline]
+    pub fn effective_freq(self, freq: NonZeroU32) -> u32 {
+        match self {
+            Self::None => freq.get(),
+            Self::Cap(cap) => min(freq, cap).get(),
+            // Floor of log2(freq) + 1, without using floating point numbers
+            Self::Log => 32 - freq.get().leading_zeros(),
+        }
+    }
+}
+
+/// A set of words ranked higher than the others at the same distance,
+/// e.g. the words previously used by the user.
+#[derive(Debug, Clone, Default)]
+pub struct WordBoost {
+    words: HashSet<String>,
+    factor: u32,
+}
+
+impl WordBoost {
+    /// Create a boost multiplying the ranking frequency of the given words by `factor`.
+    pub fn new(words: impl IntoIterator<Item = String>, factor: u32) -> Self {
+        Self {
+            words: words.into_iter().collect(),
+            factor,
+        }
+    }
+
+    /// Return whether the word is boosted.
+    #[inline]
+    pub fn contains(&self, word: &str) -> bool {
+        self.words.contains(word)
+    }
+}
+
+/// How the words found at the same distance are ranked by their frequency.
+///
+/// This only changes the order of the results, not their returned frequency.
+#[derive(Debug, Copy, Clone)]
+pub struct Ranking<'a> {
+    /// How the frequencies are dampened.
+    pub damping: FreqDamping,
+
+    /// The words whose dampened frequency is multiplied by a boost factor.
+    pub boost: Option<&'a WordBoost>,
+
+    /// The trie whose trigram model ranks the words with the same ranking frequency,
+    /// the most word-like first (see [ngram_logprob](CompiledTrie::ngram_logprob)).
+    #[cfg(feature = "ngram-model")]
+    pub ngram_model: Option<&'a CompiledTrie<'a>>,
+}
+
+impl Default for Ranking<'_> {
+    fn default() -> Self {
+        Self {
+            damping: FreqDamping::None,
+            boost: None,
+            #[cfg(feature = "ngram-model")]
+            ngram_model: None,
+        }
+    }
+}
+
+impl Ranking<'_> {
+    /// Return the frequency used to rank the given word.
+    #[inline]
+    pub fn effective_freq(&self, word: &str, freq: NonZeroU32) -> u64 {
+        let freq = u64::from(self.damping.effective_freq(freq));
+        match self.boost {
+            Some(boost) if boost.contains(word) => freq * u64::from(boost.factor),
+            _ => freq,
+        }
+    }
+
+    /// Return the log-probability per trigram of the word in the ngram model, higher for
+    /// the most word-like words. All the words have the same likeness without a model.
+    #[cfg(feature = "ngram-model")]
+    pub fn word_likeness(&self, word: &str) -> f64 {
+        match self.ngram_model {
+            Some(model) => model.ngram_logprob(word) / (word.chars().count() + 1) as f64,
+            None => 0.0,
+        }
+    }
+
+    /// Compute the rank of the found word, to compare it with the others.
+    #[inline]
+    fn rank_key(&self, found: &FoundWord) -> RankKey {
+        RankKey {
+            dist: found.dist,
+            effective_freq: self.effective_freq(&found.word, found.freq),
+            #[cfg(feature = "ngram-model")]
+            likeness: self.word_likeness(&found.word),
+        }
+    }
+}
+
+/// The rank of a found word with a [Ranking](Ranking), computed once per word
+/// since the ranking frequency and the ngram model score are costly to compute.
+#[derive(Debug, Copy, Clone)]
+struct RankKey {
+    dist: Distance,
+    effective_freq: u64,
+    #[cfg(feature = "ngram-model")]
+    likeness: f64,
+}
+
+impl RankKey {
+    /// Compare the ranks like [cmp_ranked](FoundWord::cmp_ranked), without the words.
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        let ord = self
+            .dist
+            .cmp(&other.dist)
+            .then(other.effective_freq.cmp(&self.effective_freq));
+
+        #[cfg(feature = "ngram-model")]
+        let ord = ord.then_with(|| {
+            other
+                .likeness
+                .partial_cmp(&self.likeness)
+                .unwrap_or(Ordering::Equal)
+        });
+
+        ord
+    }
+}
+
+impl FoundWord {
+    /// Compare two words like the [FoundWord](FoundWord) order,
+    /// but using the ranking frequencies (then the ngram model, if any).
+    ///
+    /// The ranks of both words are computed on each call, [sort_ranked](sort_ranked)
+    /// only computes them once per word.
+    pub fn cmp_ranked(&self, other: &Self, ranking: &Ranking) -> Ordering {
+        ranking
+            .rank_key(self)
+            .cmp(&ranking.rank_key(other))
+            .then_with(|| self.word.cmp(&other.word))
+    }
+}
+
+/// A pool of reusable [String](String) buffers for the words of [FoundWord](FoundWord).
+///
+/// The results of a search own their words, which stay valid until they are given back
+/// to the pool with [recycle](WordPool::recycle). The next searches then reuse these buffers
+/// instead of allocating a new string for every word found.
+///
+/// The pool keeps at most as many words as its capacity, so that a single search with many
+/// results does not keep their buffers for all the next ones.
+#[derive(Debug)]
+pub struct WordPool {
+    words: Vec<String>,
+    max_words: usize,
+}
+
+impl WordPool {
+    /// The number of words kept by the pools created by [new](WordPool::new),
+    /// the default number of results of a [SearcherBuilder](crate::SearcherBuilder).
+    pub const DEFAULT_CAPACITY: usize = 1000;
+
+    /// Create an empty pool, keeping at most [DEFAULT_CAPACITY](WordPool::DEFAULT_CAPACITY) words.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an empty pool which keeps at most `capacity` words, without reallocating.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            words: Vec::with_capacity(capacity),
+            max_words: capacity,
+        }
+    }
+
+    /// Return a string containing the given word, reusing a pooled buffer if there is one.
+    fn take_word(&mut self, word: &str) -> String {
+        match self.words.pop() {
+            Some(mut buffer) => {
+                buffer.clear();
+                buffer.push_str(word);
+                buffer
+            }
+            None => word.to_owned(),
+        }
+    }
+
+    /// Give a word back to the pool, which drops it if it is full.
+    pub fn give_back(&mut self, word: String) {
+        if self.words.len() < self.max_words {
+            self.words.push(word);
+        }
+    }
+
+    /// Give the words of the results back to the pool, leaving the results empty.
+    pub fn recycle(&mut self, results: &mut Vec<FoundWord>) {
+        for found in results.drain(..) {
+            self.give_back(found.word);
+        }
+    }
+}
+
+impl Default for WordPool {
+    fn default() -> Self {
+        Self::with_capacity(Self::DEFAULT_CAPACITY)
+    }
+}
+
+/// Merge multiple result lists, each one already sorted by the [FoundWord](FoundWord) order,
+/// into a single sorted list of at most `limit` words.
+///
+/// If the same word is present in multiple lists, only the best one is kept.
+pub fn merge_results(
+    results: impl IntoIterator<Item = Vec<FoundWord>>,
+    limit: usize,
+) -> Vec<FoundWord> {
+    let mut lists: Vec<_> = results.into_iter().map(Vec::into_iter).collect();
+
+    // Min-heap of the next word of each list, along with the index of its list
+    let mut heap = BinaryHeap::with_capacity(lists.len());
+    for (i, list) in lists.iter_mut().enumerate() {
+        if let Some(found_word) = list.next() {
+            heap.push(Reverse((found_word, i)));
+        }
+    }
+
+    let mut seen_words = HashSet::new();
+    let mut merged = Vec::new();
+    while merged.len() < limit {
+        let (found_word, i) = match heap.pop() {
+            Some(Reverse(e)) => e,
+            None => break,
+        };
+
+        // Replace the popped word by the next one of its list
+        if let Some(next_word) = lists[i].next() {
+            heap.push(Reverse((next_word, i)));
+        }
+
+        // Words are popped in order, so the first occurrence is the best one
+        if seen_words.insert(found_word.word.clone()) {
+            merged.push(found_word);
+        }
+    }
+
+    merged
+}
+
 /// Retrieve and push the root nodes in the iteration stack.
 /// Also push a dummy node (None) as the first element to indicate the end of the layer.
 fn push_layer_nodes<'a>(
@@ -81,76 +486,278 @@ fn push_layer_nodes<'a>(
     }
 }
 
+/// Iterate over the iteration elements of the roots of the trie, with an element
+/// for each character of a root range node, so that each one is the root of its own subtree.
+fn root_elements<'a>(
+    trie: &'a CompiledTrie,
+) -> impl DoubleEndedIterator<Item = IterationElement<'a>> {
+    let roots = trie.get_root_siblings().unwrap_or(&[]);
+    roots.iter().flat_map(move |node| {
+        let range = match node.node_value() {
+            NodeValue::Range(n) => Some(trie.get_range(n.start_index, n.end_index)),
+            _ => None,
+        };
+
+        // The holes of the ranges are not nodes of the trie
+        let nb_elements = range.map_or(1, |range| range.len());
+        (0..nb_elements)
+            .filter(move |&offset| match range {
+                Some(range) => {
+                    range[offset].index_first_child.is_some() || range[offset].word_freq.is_some()
+                }
+                None => true,
+            })
+            .map(move |offset| IterationElement {
+                node,
+                last_char: None,
+                range_offset: offset as u32,
+            })
+    })
+}
+
 /// Create and push the first layer in the layer stack.
 fn push_first_layer(
     layer_stack: &mut LayerStack<Distance, WordCharCount>,
     layer_char: Option<char>,
     char_count: WordCharCount,
+    costs: &EditCosts,
 ) {
     // word_size + 1 because the first cell is for "no character" in the distance algorithm
     let layer = layer_stack.push_layer(layer_char, char_count + 1);
     for (i, e) in layer.iter_mut().enumerate() {
-        *e = i as _;
+        // Deleting the first i characters of the query
+        *e = (i as Distance).saturating_mul(costs.delete);
     }
 }
 
+/// Check if both characters are the same, ignoring their case if asked.
+///
+/// The case is ignored by comparing the full lowercase mappings of the characters.
+/// A character whose lowercase mapping has multiple characters (e.g. `'İ'` to `"i̇"`)
+/// thus only matches a character with the same mapping, and a character never matches
+/// multiple characters (e.g. `'ß'` does not match `"ss"`).
+#[inline(always)]
+fn chars_match(a: char, b: char, ignore_case: bool) -> bool {
+    a == b || (ignore_case && a.to_lowercase().eq(b.to_lowercase()))
+}
+
 /// Fill the layer with the [Damerau-Levenshtein](https://en.wikipedia.org/wiki/Damerau%E2%80%93Levenshtein_distance)
-/// distance computation.
+/// distance computation, weighted by the costs of the edit operations.
+/// Without the transpositions of the costs, it is the Levenshtein distance
+/// and the parent layer is not read (see [fetch_compute_layers](fetch_compute_layers)).
+///
+/// The layer is the one of the `depth`-th character of the trie word.
+/// Since every edit costs at least 1, the cells farther than `dist_max` from the diagonal
+/// are always greater than `dist_max`: only the cells of this band are computed
+/// ([Ukkonen's cutoff](https://doi.org/10.1016/S0019-9958(85)80046-2)) and the others
+/// are set to `Distance::MAX`. A cell greater than `dist_max` may then be greater
+/// than its actual distance, but a cell at most `dist_max` is always exact.
+#[allow(clippy::too_many_arguments)]
 fn compute_layer(
     layer: &mut [Distance],
     last_layer: &[Distance],
     parent_layer: &[Distance],
-    word: &str,
+    word: &[char],
+    depth: usize,
+    dist_max: Distance,
     last_char: Option<char>,
     cur_trie_char: char,
+    ignore_case: bool,
+    costs: &EditCosts,
 ) {
-    debug_assert_ne!(word, "");
-    debug_assert_eq!(layer.len(), word.chars().count() + 1);
+    debug_assert!(!word.is_empty());
+    debug_assert_eq!(layer.len(), word.len() + 1);
     debug_assert_eq!(last_layer.len(), layer.len());
 
-    let mut prev_word_char_opt = None;
+    // Only compute the cells of the band, i.e. at most dist_max away from the diagonal
+    let band_start = min(depth.saturating_sub(dist_max as usize), layer.len());
+    let band_end = min(depth.saturating_add(dist_max as usize + 1), layer.len());
+    let (before_band, rest) = layer.split_at_mut(band_start);
+    let after_band = &mut rest[band_end - band_start..];
+    for cell in before_band.iter_mut().chain(after_band) {
+        *cell = Distance::MAX;
+    }
+
+    if band_start == 0 {
+        layer[0] = last_layer[0].saturating_add(costs.insert);
+    }
+
+    let start = max(band_start, 1);
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    {
+        // The vectorized computation only compares the characters exactly
+        if !ignore_case && costs.substitution_table.is_none() {
+            simd::compute_cells(
+                layer,
+                last_layer,
+                parent_layer,
+                word,
+                start..band_end,
+                last_char,
+                cur_trie_char,
+                costs,
+            );
+            return;
+        }
+    }
+    compute_cells(
+        layer,
+        last_layer,
+        parent_layer,
+        word,
+        start..band_end,
+        last_char,
+        cur_trie_char,
+        ignore_case,
+        costs,
+    );
+}
+
+/// Lower the cells of the last layer of the stack, computed by [compute_layer](compute_layer),
+/// with the transpositions of the true Damerau-Levenshtein distance
+/// (see [with_unrestricted_transpositions](EditCosts::with_unrestricted_transpositions)).
+///
+/// With `i` the current query character and `j` the current trie character,
+/// a transposition can now begin at the last query character `k < i` equal to `j`
+/// and the last trie character `l < j` equal to `i`, the characters between them
+/// being deleted from the query or inserted in the word.
+fn apply_unrestricted_transpositions(
+    layer_stack: &mut LayerStack<Distance, WordCharCount>,
+    word: &[char],
+    dist_max: Distance,
+    ignore_case: bool,
+    costs: &EditCosts,
+) {
+    let (trie_word, previous_layers, layer) = layer_stack.split_last_layer();
+    let layer_len = layer.len();
+    let depth = previous_layers.len() / layer_len;
+    let cur_trie_char = match trie_word.chars().next_back() {
+        Some(c) => c,
+        None => return,
+    };
+    let edits_cost = |nb: usize, cost: Distance| {
+        (min(nb, Distance::MAX as usize) as Distance).saturating_mul(cost)
+    };
+
+    // Only the cells of the band, like compute_layer
+    let band_start = max(depth.saturating_sub(dist_max as usize), 1);
+    let band_end = min(depth.saturating_add(dist_max as usize + 1), layer_len);
+    for i in band_start..band_end {
+        let last_query_match = (1..i)
+            .rev()
+            .find(|&k| chars_match(word[k - 1], cur_trie_char, ignore_case));
+        let last_trie_match = trie_word
+            .chars()
+            .rev()
+            .skip(1)
+            .position(|c| chars_match(c, word[i - 1], ignore_case))
+            .map(|pos| depth - 1 - pos);
+
+        if let (Some(k), Some(l)) = (last_query_match, last_trie_match) {
+            let transpose_cost = previous_layers[(l - 1) * layer_len + k - 1]
+                .saturating_add(edits_cost(i - k - 1, costs.delete))
+                .saturating_add(costs.transpose)
+                .saturating_add(edits_cost(depth - l - 1, costs.insert));
+            layer[i] = min(layer[i], transpose_cost);
+        }
+
+        // The next cells may now be cheaper by deleting the query character
+        layer[i] = min(layer[i], layer[i - 1].saturating_add(costs.delete));
+    }
+}
+
+/// Compute the cells of the layer in the given range, which does not contain the first cell.
+/// The cell before the range must already be computed.
+#[allow(clippy::too_many_arguments)]
+fn compute_cells(
+    layer: &mut [Distance],
+    last_layer: &[Distance],
+    parent_layer: &[Distance],
+    word: &[char],
+    cells: Range<usize>,
+    last_char: Option<char>,
+    cur_trie_char: char,
+    ignore_case: bool,
+    costs: &EditCosts,
+) {
+    debug_assert!(cells.start >= 1 && cells.end <= layer.len());
 
-    layer[0] = last_layer[0] + 1;
-    for (i, cur_word_char) in word.chars().enumerate() {
-        let i = i + 1;
-        let diff_character = cur_word_char != cur_trie_char;
+    for i in cells {
+        let cur_word_char = word[i - 1];
+        let replace = if chars_match(cur_word_char, cur_trie_char, ignore_case) {
+            0
+        } else {
+            costs.substitute_cost(cur_word_char, cur_trie_char)
+        };
 
         // Compute the costs for insert/delete/replace
-        let insert_cost = layer[i - 1] + 1;
-        let delete_cost = last_layer[i] + 1;
-        let replace_cost = last_layer[i - 1] + diff_character as Distance;
+        // (the trie character is inserted, or the query character is deleted)
+        let insert_cost = last_layer[i].saturating_add(costs.insert);
+        let delete_cost = layer[i - 1].saturating_add(costs.delete);
+        let replace_cost = last_layer[i - 1].saturating_add(replace);
 
-        // Compute transposition cost
-        let trans1_match = prev_word_char_opt.filter(|&c| c == cur_trie_char);
-        let trans2_match = trans1_match.and_then(|_| last_char.filter(|&c| c == cur_word_char));
-        let trans_cost = trans2_match.and_then(|_| parent_layer.get(i - 2));
-        let min_trans_replace = trans_cost.map_or(replace_cost, |c| min(c + 1, replace_cost));
+        // Compute transposition cost, if they are allowed
+        let trans_cost = if costs.transpositions {
+            let prev_word_char_opt = i.checked_sub(2).map(|prev_i| word[prev_i]);
+            let trans1_match =
+                prev_word_char_opt.filter(|&c| chars_match(c, cur_trie_char, ignore_case));
+            let trans2_match = trans1_match
+                .and_then(|_| last_char.filter(|&c| chars_match(c, cur_word_char, ignore_case)));
+            trans2_match.and_then(|_| parent_layer.get(i - 2))
+        } else {
+            None
+        };
+        let min_trans_replace = trans_cost.map_or(replace_cost, |c| {
+            min(c.saturating_add(costs.transpose), replace_cost)
+        });
 
         // Set the current cell value to the minimum of all costs
         layer[i] = min(min(insert_cost, delete_cost), min_trans_replace);
+    }
+}
 
-        // Save the current character for the next iteration
-        prev_word_char_opt = Some(cur_word_char);
+/// Fetch the last layer of the stack to compute, with the previous and parent layers
+/// needed by [compute_layer](compute_layer).
+///
+/// Only the transpositions need the parent layer, so it is empty without them
+/// and the layers are then found with a single split of the stack.
+fn fetch_compute_layers(
+    layer_stack: &mut LayerStack<Distance, WordCharCount>,
+    transpositions: bool,
+) -> (&mut [Distance], &[Distance], &[Distance]) {
+    if !transpositions {
+        // All the layers have the same size
+        let (_, previous_layers, cur_layer) = layer_stack.split_last_layer();
+        let last_start = previous_layers.len().saturating_sub(cur_layer.len());
+        return (cur_layer, &previous_layers[last_start..], &[]);
     }
+
+    let [cur_layer, last_layer, parent_layer] = if layer_stack.nb_layers() >= 3 {
+        unsafe { layer_stack.fetch_last_3_layers_unsafe() }
+    } else {
+        layer_stack.fetch_last_3_layers()
+    };
+    (cur_layer, last_layer, parent_layer)
 }
 
 /// Push the distance layers corresponding to the current [NaiveNode](NaiveNode).
+#[allow(clippy::too_many_arguments)]
 fn push_layers_naive(
     node: &NaiveNode,
     iter_elem: &IterationElement,
-    word: &str,
-    word_char_count: WordCharCount,
+    word: &[char],
+    dist_max: Distance,
     layer_stack: &mut LayerStack<Distance, WordCharCount>,
+    ignore_case: bool,
+    costs: &EditCosts,
 ) {
     // Create a new empty layer
-    layer_stack.push_layer(Some(node.character), word_char_count + 1);
+    layer_stack.push_layer(Some(node.character), word.len() as WordCharCount + 1);
+    let depth = layer_stack.nb_layers() - 1;
 
-    // Get the last 3 layers needed for the distance computation
-    let [cur_layer, last_layer, parent_layer] = if layer_stack.nb_layers() >= 3 {
-        unsafe { layer_stack.fetch_last_3_layers_unsafe() }
-    } else {
-        layer_stack.fetch_last_3_layers()
-    };
+    // Get the last layers needed for the distance computation
+    let (cur_layer, last_layer, parent_layer) =
+        fetch_compute_layers(layer_stack, costs.transpositions);
 
     // Compute the distances and fill the layer with them
     compute_layer(
@@ -158,20 +765,35 @@ fn push_layers_naive(
         last_layer,
         parent_layer,
         word,
+        depth,
+        dist_max,
         iter_elem.last_char,
         node.character,
+        ignore_case,
+        costs,
     );
+    if costs.unrestricted_transpositions {
+        apply_unrestricted_transpositions(layer_stack, word, dist_max, ignore_case, costs);
+    }
 }
 
 /// Push the distance layers corresponding to the current [PatriciaNode](PatriciaNode).
+///
+/// The layers of its characters are computed like the one of a naive node each, in a single
+/// group of the layer stack so that they are popped together once the node is processed.
+/// The characters after a layer whose distances all exceed `dist_max` get no layer:
+/// the distances of the next ones can only be greater, so the node is then skipped
+/// like with the layers of all its characters.
+#[allow(clippy::too_many_arguments)]
 fn push_layers_patricia(
     _node: &PatriciaNode,
     iter_elem: &IterationElement,
-    word: &str,
-    word_char_count: WordCharCount,
+    word: &[char],
+    dist_max: Distance,
     layer_stack: &mut LayerStack<Distance, WordCharCount>,
-    iter_stack: &mut IterationStack<'_>,
     trie: &CompiledTrie,
+    ignore_case: bool,
+    costs: &EditCosts,
 ) {
     // Retrieve the patricia characters
     // SAFETY: Safe because in a patricia node
@@ -179,42 +801,57 @@ fn push_layers_patricia(
     let pat_chars = trie.get_chars(range_chars.start, range_chars.end);
     let mut last_char = iter_elem.last_char;
 
-    let mut has_at_least_3_layers = layer_stack.nb_layers() >= 3;
-
-    // Do the same computation as a naive node for each character in the patricia node
-    // It will create a new layer for each character, which is not the most performant but the easiest
-    for ch in pat_chars.chars() {
-        // Create a new empty layer
-        layer_stack.push_layer(Some(ch), word_char_count + 1);
+    let layer_size = word.len() as WordCharCount + 1;
 
-        // Get the last 3 layers needed for the distance computation
-        let [cur_layer, last_layer, parent_layer] = if has_at_least_3_layers {
-            unsafe { layer_stack.fetch_last_3_layers_unsafe() }
+    // Each character has a byte at least, so reserve all the layers at once
+    layer_stack.reserve_layers(pat_chars.len(), layer_size);
+    for (i, ch) in pat_chars.chars().enumerate() {
+        // Create a new empty layer, the first one beginning the group of the node
+        if i == 0 {
+            layer_stack.push_layer(Some(ch), layer_size);
         } else {
-            layer_stack.fetch_last_3_layers()
-        };
+            layer_stack.push_grouped_layer(ch, layer_size);
+        }
+        let depth = layer_stack.nb_layers() - 1;
 
-        has_at_least_3_layers = true;
+        // Get the last layers needed for the distance computation
+        let (cur_layer, last_layer, parent_layer) =
+            fetch_compute_layers(layer_stack, costs.transpositions);
 
         // Compute the distances and fill the layer with them
-        compute_layer(cur_layer, last_layer, parent_layer, word, last_char, ch);
+        compute_layer(
+            cur_layer,
+            last_layer,
+            parent_layer,
+            word,
+            depth,
+            dist_max,
+            last_char,
+            ch,
+            ignore_case,
+            costs,
+        );
+        if costs.unrestricted_transpositions {
+            apply_unrestricted_transpositions(layer_stack, word, dist_max, ignore_case, costs);
+        }
 
-        // Append a dummy node to indicate the end of the layer (character)
-        push_layer_nodes(iter_stack, &[], None);
+        // SAFETY: The layer has just been pushed
+        let cur_layer = layer_stack
+            .fetch_layer()
+            .unwrap_or_else(|| unsafe { std::hint::unreachable_unchecked() });
+        if cur_layer.iter().all(|&dist| dist > dist_max) {
+            break;
+        }
 
         // Modify the last char to the one which was just processed
         last_char = Some(ch);
     }
-
-    let has_multiple_chars = pat_chars.chars().nth(1).is_some();
-    if has_multiple_chars {
-        // Remove the last dummy node since the last character is handled in the main parent loop
-        let popped_node = iter_stack.pop();
-        debug_assert!(matches!(popped_node, Some(None)));
-    }
 }
 
 /// Find the index of the next range element
+///
+/// This scans the empty elements before it, of which there are at most 2 with the default
+/// range gap, but as many as the gap the trie has been compiled with allows otherwise.
 fn find_next_range_node(trie_ranges: &[RangeElement], current_range_index: usize) -> Option<usize> {
     // Find the position (after current index) of the first Some element
     let pos_opt = trie_ranges[current_range_index..]
@@ -228,33 +865,34 @@ fn find_next_range_node(trie_ranges: &[RangeElement], current_range_index: usize
 
 /// Check if the current index is the last of the range
 fn is_last_index_of_range(index: u32, range_node: &RangeNode) -> bool {
-    let range_len = u32::from(range_node.end_index) - u32::from(range_node.start_index);
-    index + 1 >= range_len
+    let range_len = usize::from(range_node.end_index) - usize::from(range_node.start_index);
+    index as usize + 1 >= range_len
 }
 
 /// Push the distance layers corresponding to the current [RangeNode](RangeNode).
+#[allow(clippy::too_many_arguments)]
 fn push_layers_range<'a>(
     node: &RangeNode,
     iter_elem: &IterationElement<'a>,
-    word: &str,
-    word_char_count: WordCharCount,
+    word: &[char],
+    dist_max: Distance,
     layer_stack: &mut LayerStack<Distance, WordCharCount>,
     iter_stack: &mut IterationStack<'a>,
     trie: &CompiledTrie,
+    ignore_case: bool,
+    costs: &EditCosts,
 ) {
     // SAFETY: Safety checked during dictionary compilation
     let cur_trie_char =
         unsafe { std::char::from_u32_unchecked(node.first_char as u32 + iter_elem.range_offset) };
 
     // Create a new empty layer
-    layer_stack.push_layer(Some(cur_trie_char), word_char_count + 1);
+    layer_stack.push_layer(Some(cur_trie_char), word.len() as WordCharCount + 1);
+    let depth = layer_stack.nb_layers() - 1;
 
-    // Get the last 3 layers needed for the distance computation
-    let [cur_layer, last_layer, parent_layer] = if layer_stack.nb_layers() >= 3 {
-        unsafe { layer_stack.fetch_last_3_layers_unsafe() }
-    } else {
-        layer_stack.fetch_last_3_layers()
-    };
+    // Get the last layers needed for the distance computation
+    let (cur_layer, last_layer, parent_layer) =
+        fetch_compute_layers(layer_stack, costs.transpositions);
 
     // Compute the distances and fill the layer with them
     compute_layer(
@@ -262,12 +900,21 @@ fn push_layers_range<'a>(
         last_layer,
         parent_layer,
         word,
+        depth,
+        dist_max,
         iter_elem.last_char,
         cur_trie_char,
+        ignore_case,
+        costs,
     );
+    if costs.unrestricted_transpositions {
+        apply_unrestricted_transpositions(layer_stack, word, dist_max, ignore_case, costs);
+    }
 
     // Push the next range element if the current is not the last in the range
-    if !is_last_index_of_range(iter_elem.range_offset, node) {
+    // (the elements of the root ranges are all pushed at the beginning of the search)
+    let is_root = iter_elem.last_char.is_none();
+    if !is_root && !is_last_index_of_range(iter_elem.range_offset, node) {
         // There remains some elements to do in the range
         // So the next one is pushed in the nodes stack
         let trie_ranges = trie.get_range(node.start_index, node.end_index);
@@ -288,33 +935,47 @@ fn push_layers_range<'a>(
 }
 
 /// Process the current node and update the layer stack with the node's new layers.
+#[allow(clippy::too_many_arguments)]
 fn push_layers_current_node<'a>(
     iter_elem: &IterationElement<'a>,
-    word: &str,
-    word_char_count: WordCharCount,
+    word: &[char],
+    dist_max: Distance,
     trie: &CompiledTrie,
     layer_stack: &mut LayerStack<Distance, WordCharCount>,
     iter_stack: &mut IterationStack<'a>,
+    ignore_case: bool,
+    costs: &EditCosts,
 ) {
     match iter_elem.node.node_value() {
-        NodeValue::Naive(n) => push_layers_naive(n, iter_elem, word, word_char_count, layer_stack),
+        NodeValue::Naive(n) => push_layers_naive(
+            n,
+            iter_elem,
+            word,
+            dist_max,
+            layer_stack,
+            ignore_case,
+            costs,
+        ),
         NodeValue::Patricia(n) => push_layers_patricia(
             n,
             iter_elem,
             word,
-            word_char_count,
+            dist_max,
             layer_stack,
-            iter_stack,
             trie,
+            ignore_case,
+            costs,
         ),
         NodeValue::Range(n) => push_layers_range(
             n,
             iter_elem,
             word,
-            word_char_count,
+            dist_max,
             layer_stack,
             iter_stack,
             trie,
+            ignore_case,
+            costs,
         ),
     }
 }
@@ -345,6 +1006,7 @@ fn check_add_word_to_result(
     dist_max: Distance,
     layer_word: &str,
     trie: &CompiledTrie,
+    word_pool: &mut WordPool,
     result_buffer: &mut Vec<FoundWord>,
 ) {
     // If end word and less than max dist => Add to result
@@ -352,9 +1014,10 @@ fn check_add_word_to_result(
     if dist <= dist_max {
         if let Some(freq) = get_node_frequency(iter_elem, trie) {
             result_buffer.push(FoundWord {
-                word: layer_word.to_owned(),
+                word: word_pool.take_word(layer_word),
                 freq,
                 dist,
+                edits: None,
             })
         }
     }
@@ -447,6 +1110,7 @@ fn check_potential_damerau(
     word: &str,
     equals: &[usize],
     dist_max: Distance,
+    transpose_cost: Distance,
 ) -> bool {
     let first_equal_diag_i = max(2, equals[0]) - 2;
     let last_equal_diag_i = max(2, *equals.last().unwrap()) - 2;
@@ -458,7 +1122,7 @@ fn check_potential_damerau(
     let mut chars_to_check = slice_to_check
         .iter()
         .enumerate()
-        .filter(|(_, &d)| d < dist_max)
+        .filter(|(_, &d)| d.saturating_add(transpose_cost) <= dist_max)
         .map(|(i, _)| first_equal_diag_i + i)
         .filter_map(|i| {
             let c = chars.nth(i - cur_i);
@@ -475,52 +1139,610 @@ fn check_potential_damerau(
     chars_to_check.any(char_in_children)
 }
 
-/// Search for all words in the trie at a given distance (or less) of the query.
+/// Return the lower bound of the distance between a query and a word
+/// from their number of characters, i.e. the difference of their lengths:
+/// each character of the longest one without a counterpart in the other needs an edit.
+///
+/// It is cheap to compute, so it can be used to discard a candidate
+/// before computing its distance.
+pub fn min_possible_distance(query_len: usize, word_len: usize) -> Distance {
+    let diff = max(query_len, word_len) - min(query_len, word_len);
+    min(diff, Distance::MAX as usize) as Distance
+}
+
+/// The options of an approximate search (see [search_approx](search_approx)).
+///
+/// The default options only find the query itself, with the unit costs and no ranking.
+#[derive(Debug, Copy, Clone)]
+pub struct SearchOptions<'a> {
+    /// The maximum distance of the found words from the query.
+    pub dist_max: Distance,
+
+    /// Only return the best `limit` words, following the [FoundWord](FoundWord) order
+    /// with the frequencies given by the ranking.
+    pub limit: Option<usize>,
+
+    /// Compare the characters case-insensitively (see [chars_match](chars_match) for the details).
+    pub ignore_case: bool,
+
+    /// The costs of each edit operation of the distance.
+    pub costs: EditCosts<'a>,
+
+    /// Allow the transpositions of the Damerau-Levenshtein distance. Without them,
+    /// the distance is the Levenshtein one (e.g. `"ba"` is at a distance of 2 from `"ab"`),
+    /// which is cheaper to compute since the layers do not need their parent one.
+    pub transposition: bool,
+
+    /// The characters of the query that the found words must have too.
+    pub pinned: PinnedChars,
+
+    /// Do not traverse the trie past this depth, so that only the words
+    /// of at most `max_depth` characters are found.
+    pub max_depth: Option<usize>,
+
+    /// How the words found at the same distance are ranked.
+    pub ranking: Ranking<'a>,
+}
+
+impl<'a> SearchOptions<'a> {
+    /// Return the costs of the edit operations of the search,
+    /// without the transpositions if they are not allowed.
+    pub fn edit_costs(&self) -> EditCosts<'a> {
+        if self.transposition {
+            self.costs
+        } else {
+            self.costs.without_transpositions()
+        }
+    }
+}
+
+impl Default for SearchOptions<'_> {
+    fn default() -> Self {
+        Self {
+            dist_max: 0,
+            limit: None,
+            ignore_case: false,
+            costs: EditCosts::default(),
+            transposition: true,
+            pinned: PinnedChars::default(),
+            max_depth: None,
+            ranking: Ranking::default(),
+        }
+    }
+}
+
+/// Search for all words in the trie at a distance of at most `dist_max` of the query,
+/// with the given [options](SearchOptions).
 ///
-/// Return a vector of all found words with their respective frequency.
+/// Return a vector of all found words with their respective frequency,
+/// each word being found once at its distance.
+/// If a limit is given, only the best `limit` words are kept while searching, and returned sorted
+/// by their rank (the words already in the result buffer being ranked with the found ones).
+///
+/// The words of the results are taken from the word pool when it is not empty.
 pub fn search_approx<'a>(
     trie: &'a CompiledTrie,
     word: &str,
-    dist_max: Distance,
+    options: &SearchOptions,
     layer_stack: &mut LayerStack<Distance, WordCharCount>,
     iter_stack: &mut IterationStack<'a>,
+    word_pool: &mut WordPool,
     mut result_buffer: Vec<FoundWord>,
 ) -> Vec<FoundWord> {
-    // Early return if nothing to search
-    if word.is_empty() {
-        return result_buffer;
+    // Every edit costs at least 1, so only the word itself is at a distance 0,
+    // unless its characters match others regardless of their case
+    if options.dist_max == 0 && !options.ignore_case {
+        let within_depth = match options.max_depth {
+            Some(max_depth) => word.chars().count() <= max_depth,
+            None => true,
+        };
+        if within_depth && !word.is_empty() {
+            if let Some(freq) = search_exact(trie, word, None) {
+                result_buffer.push(FoundWord {
+                    word: word_pool.take_word(word),
+                    freq,
+                    dist: 0,
+                    edits: None,
+                });
+            }
+        }
+        return keep_best_sorted(result_buffer, options.limit, &options.ranking, word_pool);
+    }
+
+    search_approx_from(
+        trie,
+        root_elements(trie),
+        word,
+        options,
+        layer_stack,
+        iter_stack,
+        word_pool,
+        result_buffer,
+    )
+}
+
+/// Search for all words in the trie beginning with the prefix and at a given distance
+/// (or less) of the query, like [search_approx](search_approx) with the same options.
+///
+/// The trie is first descended exactly along the prefix, even if it ends inside
+/// a patricia node, so that only the subtree of the prefix is searched. The distance
+/// is the one of the whole words, which include the prefix, so the query usually begins
+/// with it too (e.g. the prefix typed with confidence by a user).
+#[allow(clippy::too_many_arguments)]
+pub fn search_approx_prefix<'a>(
+    trie: &'a CompiledTrie,
+    prefix: &str,
+    word: &str,
+    options: &SearchOptions,
+    layer_stack: &mut LayerStack<Distance, WordCharCount>,
+    iter_stack: &mut IterationStack<'a>,
+    word_pool: &mut WordPool,
+    mut result_buffer: Vec<FoundWord>,
+) -> Vec<FoundWord> {
+    // The roots of the trie need their own elements for the characters of their ranges
+    if prefix.is_empty() {
+        return search_approx(
+            trie,
+            word,
+            options,
+            layer_stack,
+            iter_stack,
+            word_pool,
+            result_buffer,
+        );
     }
 
+    // Early return if nothing to search
+    let query = ApproxQuery::new(trie, word, options);
+    let (query, path) = match (query, descend_prefix(trie, prefix)) {
+        (Some(query), Some(path)) => (query, path),
+        _ => return result_buffer,
+    };
+
     let mut equals_buf = Vec::new();
+    query.init_stacks_path(
+        &path,
+        layer_stack,
+        iter_stack,
+        word_pool,
+        &mut result_buffer,
+    );
+    while query.step(
+        layer_stack,
+        iter_stack,
+        word_pool,
+        &mut equals_buf,
+        &mut result_buffer,
+    ) {}
 
-    // Retrieve the root nodes
-    let roots = trie.get_root_siblings().unwrap();
-    let word_char_count = word.chars().count();
+    keep_best_sorted(result_buffer, options.limit, &options.ranking, word_pool)
+}
+
+/// Search for all words in the trie at a given distance (or less) of the query,
+/// like [search_approx](search_approx) but on several threads.
+///
+/// The subtrees of the root characters of the trie are shared between `nb_threads` threads
+/// (or the [available parallelism](std::thread::available_parallelism) if None),
+/// each with its own stacks, and the best `limit` words of each thread are then merged.
+/// The results are the same as with [search_approx](search_approx), in an unspecified order.
+#[cfg(feature = "parallel")]
+pub fn search_approx_parallel(
+    trie: &CompiledTrie,
+    word: &str,
+    options: &SearchOptions,
+    nb_threads: Option<std::num::NonZeroUsize>,
+) -> Vec<FoundWord> {
+    use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
 
-    // Initialize both stacks
-    push_layer_nodes(iter_stack, roots, None);
-    push_first_layer(layer_stack, None, word_char_count as _);
+    let roots: Vec<_> = root_elements(trie).collect();
+    let nb_threads = nb_threads
+        .or_else(|| std::thread::available_parallelism().ok())
+        .map_or(1, |n| n.get())
+        .min(roots.len().max(1));
+
+    // The threads take the next root subtree to search until there is none left
+    let next_root = AtomicUsize::new(0);
+    let search_roots = || {
+        let mut layer_stack = LayerStack::with_capacity(0, 0);
+        let mut iter_stack = IterationStack::new();
+        let mut word_pool = WordPool::new();
+        let mut result_buffer = Vec::new();
+        while let Some(&root) = roots.get(next_root.fetch_add(1, AtomicOrdering::Relaxed)) {
+            result_buffer = search_approx_from(
+                trie,
+                std::iter::once(root),
+                word,
+                options,
+                &mut layer_stack,
+                &mut iter_stack,
+                &mut word_pool,
+                result_buffer,
+            );
+        }
+        result_buffer
+    };
+
+    let results = std::thread::scope(|scope| {
+        let threads: Vec<_> = (1..nb_threads).map(|_| scope.spawn(search_roots)).collect();
+        let mut results = search_roots();
+        for thread in threads {
+            results.extend(thread.join().unwrap());
+        }
+        results
+    });
+
+    // The best words of each thread contain the best words overall
+    keep_best(
+        results,
+        options.limit,
+        &options.ranking,
+        &mut WordPool::new(),
+    )
+}
+
+/// A found word ordered by its rank with the ranking frequencies
+/// (see [cmp_ranked](FoundWord::cmp_ranked)), the worst one being the greatest.
+struct RankedWord {
+    key: RankKey,
+    found: FoundWord,
+}
+
+impl RankedWord {
+    fn new(found: FoundWord, ranking: &Ranking) -> Self {
+        Self {
+            key: ranking.rank_key(&found),
+            found,
+        }
+    }
+}
+
+impl PartialEq for RankedWord {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for RankedWord {}
+
+impl PartialOrd for RankedWord {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for RankedWord {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key
+            .cmp(&other.key)
+            .then_with(|| self.found.word.cmp(&other.found.word))
+    }
+}
+
+/// The best `limit` words found so far by a search, in a max-heap whose top
+/// is the worst word kept. The other words are given back to the word pool.
+struct BestWords<'r> {
+    heap: BinaryHeap<RankedWord>,
+    limit: usize,
+    ranking: &'r Ranking<'r>,
+}
+
+impl<'r> BestWords<'r> {
+    fn new(limit: usize, ranking: &'r Ranking<'r>) -> Self {
+        Self {
+            heap: BinaryHeap::new(),
+            limit,
+            ranking,
+        }
+    }
+
+    /// Keep the best words of the found ones, which are all removed from the vector.
+    fn keep(&mut self, found_words: &mut Vec<FoundWord>, word_pool: &mut WordPool) {
+        for found in found_words.drain(..) {
+            let ranked = RankedWord::new(found, self.ranking);
+            if self.heap.len() < self.limit {
+                self.heap.push(ranked);
+                continue;
+            }
+
+            // Only replace the worst word if the found one is better
+            let discarded = match self.heap.peek_mut() {
+                Some(mut worst) if ranked < *worst => std::mem::replace(&mut *worst, ranked),
+                _ => ranked,
+            };
+            word_pool.give_back(discarded.found.word);
+        }
+    }
+
+    /// Append the kept words to the vector, from the best one to the worst one.
+    fn append_sorted(self, result_buffer: &mut Vec<FoundWord>) {
+        result_buffer.extend(
+            self.heap
+                .into_sorted_vec()
+                .into_iter()
+                .map(|ranked| ranked.found),
+        );
+    }
+}
+
+/// Only keep the best `limit` words of the results (if any), sorted by their rank,
+/// and give the others back to the word pool.
+fn keep_best_sorted(
+    mut result_buffer: Vec<FoundWord>,
+    limit: Option<usize>,
+    ranking: &Ranking,
+    word_pool: &mut WordPool,
+) -> Vec<FoundWord> {
+    if let Some(limit) = limit {
+        let mut best_words = BestWords::new(limit, ranking);
+        best_words.keep(&mut result_buffer, word_pool);
+        best_words.append_sorted(&mut result_buffer);
+    }
+    result_buffer
+}
+
+/// Only keep the best `limit` words of the results (if any), in an unspecified order,
+/// and give the others back to the word pool.
+pub fn keep_best(
+    mut result_buffer: Vec<FoundWord>,
+    limit: Option<usize>,
+    ranking: &Ranking,
+    word_pool: &mut WordPool,
+) -> Vec<FoundWord> {
+    // Only keep the best words, without having to sort all of them
+    if let Some(limit) = limit {
+        if limit < result_buffer.len() {
+            let mut ranked = rank_words(&mut result_buffer, ranking);
+            if limit > 0 {
+                ranked.select_nth_unstable(limit - 1);
+            }
+
+            // Give the words of the discarded results back to the pool
+            for discarded in ranked.drain(limit..) {
+                word_pool.give_back(discarded.found.word);
+            }
+            result_buffer.extend(ranked.into_iter().map(|ranked| ranked.found));
+        }
+    }
+
+    result_buffer
+}
+
+/// Sort the found words by their rank with the ranking frequencies
+/// (see [cmp_ranked](FoundWord::cmp_ranked)), computed once per word.
+pub fn sort_ranked(found_words: &mut Vec<FoundWord>, ranking: &Ranking) {
+    let mut ranked = rank_words(found_words, ranking);
+    ranked.sort_unstable();
+    found_words.extend(ranked.into_iter().map(|ranked| ranked.found));
+}
+
+/// Compute the rank of the found words, which are all removed from the vector.
+fn rank_words(found_words: &mut Vec<FoundWord>, ranking: &Ranking) -> Vec<RankedWord> {
+    found_words
+        .drain(..)
+        .map(|found| RankedWord::new(found, ranking))
+        .collect()
+}
+
+/// Split the results of an approximate search of the word into the words beginning
+/// with the same character as the word (the likely typos), and the others,
+/// keeping their order.
+///
+/// The first characters are compared like the search does (see [chars_match](chars_match)),
+/// and all the words are in the others if the query is empty.
+pub fn split_by_first_char(
+    results: Vec<FoundWord>,
+    word: &str,
+    ignore_case: bool,
+) -> (Vec<FoundWord>, Vec<FoundWord>) {
+    let first_char = word.chars().next();
+    results
+        .into_iter()
+        .partition(|found| match (found.word.chars().next(), first_char) {
+            (Some(a), Some(b)) => chars_match(a, b, ignore_case),
+            _ => false,
+        })
+}
+
+/// The parameters of an approximate search of a non-empty word,
+/// shared by all the iterations of the search.
+struct ApproxQuery<'a, 'w> {
+    trie: &'a CompiledTrie<'a>,
+    word: &'w str,
+    word_chars: Vec<char>,
+    first_char: char,
+    last_char: char,
+    dist_max: Distance,
+    ignore_case: bool,
+    costs: EditCosts<'w>,
+    pinned: PinnedChars,
+    max_depth: Option<usize>,
+}
+
+impl<'a, 'w> ApproxQuery<'a, 'w> {
+    /// Create the parameters of the search, or None if the word is empty
+    /// since there is nothing to search.
+    fn new(
+        trie: &'a CompiledTrie<'a>,
+        word: &'w str,
+        options: &'w SearchOptions<'w>,
+    ) -> Option<Self> {
+        let first_char = word.chars().next()?;
+        let last_char = word.chars().next_back()?;
+        Some(Self {
+            trie,
+            word,
+            word_chars: word.chars().collect(),
+            first_char,
+            last_char,
+            dist_max: options.dist_max,
+            ignore_case: options.ignore_case,
+            costs: options.edit_costs(),
+            pinned: options.pinned,
+            max_depth: options.max_depth,
+        })
+    }
+
+    /// Initialize both stacks, with the given root nodes and a dummy node to end their layer.
+    fn init_stacks(
+        &self,
+        roots: impl DoubleEndedIterator<Item = IterationElement<'a>>,
+        layer_stack: &mut LayerStack<Distance, WordCharCount>,
+        iter_stack: &mut IterationStack<'a>,
+    ) {
+        iter_stack.push(None);
+        iter_stack.extend(roots.rev().map(Some));
+        push_first_layer(layer_stack, None, self.word_chars.len() as _, &self.costs);
+    }
+
+    /// Initialize both stacks below the path of the trie, with a layer for each character
+    /// of the path, and add the word of the path to the results if it is found.
+    ///
+    /// The children of the path are then the roots of the search, unless none of their words
+    /// can be found.
+    fn init_stacks_path(
+        &self,
+        path: &PrefixPath<'a>,
+        layer_stack: &mut LayerStack<Distance, WordCharCount>,
+        iter_stack: &mut IterationStack<'a>,
+        word_pool: &mut WordPool,
+        result_buffer: &mut Vec<FoundWord>,
+    ) {
+        let word_len = self.word_chars.len() as WordCharCount;
+        let within_depth = |depth: usize| match self.max_depth {
+            Some(max_depth) => depth <= max_depth,
+            None => true,
+        };
+        let chars_match = |c: Option<char>, other| match c {
+            Some(c) => chars_match(c, other, self.ignore_case),
+            None => false,
+        };
+
+        iter_stack.push(None);
+        push_first_layer(layer_stack, None, word_len, &self.costs);
+        if self.pinned.first && !chars_match(path.word.chars().next(), self.first_char) {
+            return;
+        }
+
+        // Each layer is popped by its dummy node, like the ones of the nodes
+        let mut last_char = None;
+        for (i, c) in path.word.chars().enumerate() {
+            if !within_depth(i + 1) {
+                return;
+            }
+
+            iter_stack.push(None);
+            layer_stack.push_layer(Some(c), word_len + 1);
+            let (cur_layer, last_layer, parent_layer) =
+                fetch_compute_layers(layer_stack, self.costs.transpositions);
+            compute_layer(
+                cur_layer,
+                last_layer,
+                parent_layer,
+                &self.word_chars,
+                i + 1,
+                self.dist_max,
+                last_char,
+                c,
+                self.ignore_case,
+                &self.costs,
+            );
+            if self.costs.unrestricted_transpositions {
+                apply_unrestricted_transpositions(
+                    layer_stack,
+                    &self.word_chars,
+                    self.dist_max,
+                    self.ignore_case,
+                    &self.costs,
+                );
+            }
+            last_char = Some(c);
+        }
+
+        // SAFETY: The layer stack is not empty at this point
+        let cur_layer = layer_stack
+            .fetch_layer()
+            .unwrap_or_else(|| unsafe { std::hint::unreachable_unchecked() });
+        let dist = get_current_distance(cur_layer);
+        if let Some(freq) = path.word_freq {
+            if dist <= self.dist_max
+                && (!self.pinned.last || chars_match(last_char, self.last_char))
+            {
+                result_buffer.push(FoundWord {
+                    word: word_pool.take_word(&path.word),
+                    freq,
+                    dist,
+                    edits: None,
+                });
+            }
+        }
+
+        // The children are pushed above the dummy nodes of the path, with its last character
+        let can_continue = cur_layer.iter().any(|&d| d <= self.dist_max);
+        if let Some(children) = path.children.filter(|_| can_continue) {
+            if within_depth(layer_stack.nb_layers()) {
+                iter_stack.extend(children.iter().rev().map(|node| {
+                    Some(IterationElement {
+                        node,
+                        last_char,
+                        range_offset: 0,
+                    })
+                }));
+            }
+        }
+    }
+
+    /// Process the next element of the iteration stack, adding the words found
+    /// to the result buffer.
+    ///
+    /// Return false if the iteration stack was empty, i.e. the search is over.
+    fn step(
+        &self,
+        layer_stack: &mut LayerStack<Distance, WordCharCount>,
+        iter_stack: &mut IterationStack<'a>,
+        word_pool: &mut WordPool,
+        equals_buf: &mut Vec<usize>,
+        result_buffer: &mut Vec<FoundWord>,
+    ) -> bool {
+        let Self {
+            trie,
+            word,
+            first_char,
+            last_char,
+            dist_max,
+            ignore_case,
+            ref costs,
+            pinned,
+            max_depth,
+            ..
+        } = *self;
+        let word_chars = &self.word_chars;
+        let word_char_count = word_chars.len();
+
+        let iter_elem_opt = match iter_stack.pop() {
+            Some(e) => e,
+            None => return false,
+        };
 
-    // Loop over the iteration stack until empty
-    while let Some(iter_elem_opt) = iter_stack.pop() {
         // Extract the node or process the dummy node
         let iter_elem = match iter_elem_opt {
             Some(n) => n,
             None => {
-                // Dummy node => represents the end of a layer
-                layer_stack.pop_layer();
-                continue;
+                // Dummy node => represents the end of the layers of a node
+                layer_stack.pop_group();
+                return true;
             }
         };
 
         // Compute and push the distance layers of the current node
         push_layers_current_node(
             &iter_elem,
-            word,
-            word_char_count as _,
+            word_chars,
+            dist_max,
             trie,
             layer_stack,
             iter_stack,
+            ignore_case,
+            costs,
         );
 
         // SAFETY: The layer stack is not empty at this point
@@ -530,19 +1752,56 @@ pub fn search_approx<'a>(
 
         let layer_word = layer_stack.get_layers_word();
 
+        // Skip the whole subtree if it does not begin with the pinned first character
+        let pinned_char_match = |c: Option<char>, pinned_char| match c {
+            Some(c) => chars_match(c, pinned_char, ignore_case),
+            None => false,
+        };
+        if pinned.first && !pinned_char_match(layer_word.chars().next(), first_char) {
+            layer_stack.pop_group();
+            return true;
+        }
+
+        // Skip the whole subtree if it is past the maximum depth, which can only happen
+        // with the last characters of a patricia node since the deeper children are not pushed
+        let within_depth = |depth: usize| match max_depth {
+            Some(max_depth) => depth <= max_depth,
+            None => true,
+        };
+        let depth = layer_stack.nb_layers() - 1;
+        if !within_depth(depth) {
+            layer_stack.pop_group();
+            return true;
+        }
+
         // Add trie node's word to result if it can be
-        check_add_word_to_result(
-            &iter_elem,
-            cur_layer,
-            dist_max,
-            layer_word,
-            trie,
-            &mut result_buffer,
-        );
+        if !pinned.last || pinned_char_match(layer_word.chars().next_back(), last_char) {
+            check_add_word_to_result(
+                &iter_elem,
+                cur_layer,
+                dist_max,
+                layer_word,
+                trie,
+                word_pool,
+                result_buffer,
+            );
+        }
 
         if let Some(children) = get_node_children(trie, &iter_elem) {
+            // The words of the children have at least one more character than the current
+            // layer word (the first layer has none), so skip them without looking at
+            // the layer if this is already too many characters for the query
+            let children_min_len = layer_stack.nb_layers();
+            if !within_depth(children_min_len)
+                || children_min_len > word_char_count
+                    && min_possible_distance(word_char_count, children_min_len) > dist_max
+            {
+                layer_stack.pop_group();
+                return true;
+            }
+
             // If children, compare the minimum distance of the layer with the max_dist
-            match cmp_min_with_max_dist(cur_layer, dist_max, &mut equals_buf) {
+            match cmp_min_with_max_dist(cur_layer, dist_max, equals_buf) {
                 // If it is less, add all children and continue with the next iteration
                 (Ordering::Less, _) => {
                     // Get the last character of the current node
@@ -555,12 +1814,24 @@ pub fn search_approx<'a>(
                 // If it is equal, it is now a problem of exact search, which can have
                 // a better optimized algorithm than the approximate search
                 (Ordering::Equal, equals) => {
+                    // The exact search does not ignore the case, and a transposition
+                    // can begin at any previous layer with the unrestricted transpositions,
+                    // so the approximate search must continue in these cases
                     let [_, last_layer, _] = layer_stack.fetch_last_3_layers();
-                    let can_transpose = check_potential_damerau(
-                        trie, children, last_layer, word, &equals, dist_max,
-                    );
+                    let continue_approx = ignore_case
+                        || costs.unrestricted_transpositions
+                        || costs.transpositions
+                            && check_potential_damerau(
+                                trie,
+                                children,
+                                last_layer,
+                                word,
+                                equals,
+                                dist_max,
+                                costs.transpose,
+                            );
 
-                    if can_transpose {
+                    if continue_approx {
                         // Get the last character of the current node
                         let last_char = get_current_last_char(trie, &iter_elem);
 
@@ -579,50 +1850,342 @@ pub fn search_approx<'a>(
                                     continue;
                                 };
 
+                            // The split index is obtained from char_indices, so it always lands
+                            // on a character boundary, even for multibyte characters
+                            debug_assert!(word.is_char_boundary(split_index));
+
                             // Find the portion of the word to search (remove the already searched part)
                             let subword_to_search = &word[split_index..];
 
                             // Search the subword from the children
+                            if !within_depth(depth + word_char_count - *equal_i) {
+                                continue;
+                            }
                             let freq_opt = search_exact_children(trie, subword_to_search, children);
                             let layer_word = layer_stack.get_layers_word();
                             if let Some(freq) = freq_opt {
                                 // Concatenate the already search subword with the newly searched subword
                                 // to find the word that have been found
-                                let mut word = layer_word.to_owned();
+                                let mut word = word_pool.take_word(layer_word);
                                 word.push_str(subword_to_search);
 
                                 result_buffer.push(FoundWord {
                                     word,
                                     freq,
                                     dist: dist_max,
+                                    edits: None,
                                 })
                             }
                         }
 
                         // Since all children have been processed, we can safely remove the current layer
-                        layer_stack.pop_layer();
+                        layer_stack.pop_group();
                     }
                 }
 
                 // If it is greater, no children will have a result word,
                 // so we can safely ignore them and pop the current layer
                 (Ordering::Greater, _) => {
-                    layer_stack.pop_layer();
+                    layer_stack.pop_group();
                 }
             }
         } else {
             // If no children, remove its layer and continue with next iteration
-            layer_stack.pop_layer();
+            layer_stack.pop_group();
+        }
+
+        true
+    }
+}
+
+/// Search for all words of the subtrees of the given roots at a given distance (or less)
+/// of the query, like [search_approx](search_approx).
+///
+/// The roots must be [root_elements](root_elements) of the trie (or some of them).
+#[allow(clippy::too_many_arguments)]
+fn search_approx_from<'a>(
+    trie: &'a CompiledTrie,
+    roots: impl DoubleEndedIterator<Item = IterationElement<'a>>,
+    word: &str,
+    options: &SearchOptions,
+    layer_stack: &mut LayerStack<Distance, WordCharCount>,
+    iter_stack: &mut IterationStack<'a>,
+    word_pool: &mut WordPool,
+    mut result_buffer: Vec<FoundWord>,
+) -> Vec<FoundWord> {
+    // Early return if nothing to search
+    let query = ApproxQuery::new(trie, word, options);
+    let query = match query {
+        Some(query) => query,
+        None => return keep_best_sorted(result_buffer, options.limit, &options.ranking, word_pool),
+    };
+
+    let mut equals_buf = Vec::new();
+    query.init_stacks(roots, layer_stack, iter_stack);
+
+    // With a limit, the words are moved to the best ones as soon as they are found
+    let mut best_words = options
+        .limit
+        .map(|limit| BestWords::new(limit, &options.ranking));
+    if let Some(best_words) = &mut best_words {
+        best_words.keep(&mut result_buffer, word_pool);
+    }
+
+    // Loop over the iteration stack until empty
+    while query.step(
+        layer_stack,
+        iter_stack,
+        word_pool,
+        &mut equals_buf,
+        &mut result_buffer,
+    ) {
+        if let Some(best_words) = &mut best_words {
+            if !result_buffer.is_empty() {
+                best_words.keep(&mut result_buffer, word_pool);
+            }
         }
     }
 
     // Return the result buffer that has been filled in the stack loop
+    if let Some(best_words) = best_words {
+        best_words.append_sorted(&mut result_buffer);
+    }
     result_buffer
 }
 
+/// A lazy approximate search, which only traverses the trie as far as needed
+/// to find the next word. See [search_approx_iter](search_approx_iter).
+pub struct SearchApproxIter<'a, 'w> {
+    query: Option<ApproxQuery<'a, 'w>>,
+    layer_stack: LayerStack<Distance, WordCharCount>,
+    iter_stack: IterationStack<'a>,
+    word_pool: WordPool,
+    equals_buf: Vec<usize>,
+
+    /// The words found by the last step not yet returned, in reverse order.
+    pending: Vec<FoundWord>,
+}
+
+impl Iterator for SearchApproxIter<'_, '_> {
+    type Item = FoundWord;
+
+    fn next(&mut self) -> Option<FoundWord> {
+        let query = self.query.as_ref()?;
+        loop {
+            if let Some(found) = self.pending.pop() {
+                return Some(found);
+            }
+
+            if !query.step(
+                &mut self.layer_stack,
+                &mut self.iter_stack,
+                &mut self.word_pool,
+                &mut self.equals_buf,
+                &mut self.pending,
+            ) {
+                return None;
+            }
+
+            // A step can find several words, return them in the order they were found
+            self.pending.reverse();
+        }
+    }
+}
+
+/// Lazily search for all words in the trie at a given distance (or less) of the query.
+///
+/// Return an iterator over the found words with their respective frequency,
+/// in the order of the trie traversal (not ranked), like [search_approx](search_approx)
+/// with the same options but without their limit nor ranking. The trie is only traversed
+/// when the next word is asked, so a caller which stops early (e.g. with [take](Iterator::take))
+/// does not pay for the whole search.
+pub fn search_approx_iter<'a, 'w>(
+    trie: &'a CompiledTrie<'a>,
+    word: &'w str,
+    options: &'w SearchOptions<'w>,
+) -> SearchApproxIter<'a, 'w> {
+    let query = ApproxQuery::new(trie, word, options);
+    let mut layer_stack = LayerStack::with_capacity(0, 0);
+    let mut iter_stack = IterationStack::new();
+    if let Some(query) = &query {
+        query.init_stacks(root_elements(trie), &mut layer_stack, &mut iter_stack);
+    }
+
+    SearchApproxIter {
+        query,
+        layer_stack,
+        iter_stack,
+        word_pool: WordPool::new(),
+        equals_buf: Vec::new(),
+        pending: Vec::new(),
+    }
+}
+
+/// Search for the words in the trie at the smallest distance (at most `dist_max`)
+/// for which at least one word exists.
+///
+/// Return a vector of all found words at this closest distance with their respective frequency.
+/// Words found at greater distances are not returned.
+pub fn search_closest_tier<'a>(
+    trie: &'a CompiledTrie,
+    word: &str,
+    dist_max: Distance,
+    layer_stack: &mut LayerStack<Distance, WordCharCount>,
+    iter_stack: &mut IterationStack<'a>,
+    word_pool: &mut WordPool,
+    result_buffer: Vec<FoundWord>,
+) -> Vec<FoundWord> {
+    let mut found_words = search_approx(
+        trie,
+        word,
+        &SearchOptions {
+            dist_max,
+            ..SearchOptions::default()
+        },
+        layer_stack,
+        iter_stack,
+        word_pool,
+        result_buffer,
+    );
+
+    // Only keep the lowest non-empty distance bucket
+    if let Some(closest_dist) = found_words.iter().map(|w| w.dist).min() {
+        // Give the words of the discarded results back to the pool
+        let mut i = 0;
+        while i < found_words.len() {
+            if found_words[i].dist == closest_dist {
+                i += 1;
+            } else {
+                let discarded = found_words.swap_remove(i);
+                word_pool.give_back(discarded.word);
+            }
+        }
+    }
+
+    found_words
+}
+
+/// Search for the closest word in the trie, whatever its distance to the query.
+///
+/// Return the word at the smallest distance with its frequency (the best one following
+/// the [FoundWord](FoundWord) order with the frequencies given by `ranking`), or nothing
+/// if the query or the trie is empty. The searches are done with the unit costs
+/// at increasing maximum distances (0, 1, 2, 4...) until a word is found,
+/// each of them only keeping the best word found so far.
+#[allow(clippy::too_many_arguments)]
+pub fn search_nearest<'a>(
+    trie: &'a CompiledTrie,
+    word: &str,
+    ignore_case: bool,
+    ranking: &Ranking,
+    layer_stack: &mut LayerStack<Distance, WordCharCount>,
+    iter_stack: &mut IterationStack<'a>,
+    word_pool: &mut WordPool,
+    mut result_buffer: Vec<FoundWord>,
+) -> Vec<FoundWord> {
+    debug_assert!(result_buffer.is_empty());
+
+    // Any word is at a distance of at most its length or the query length,
+    // so the searches always end on a word unless there is none
+    if word.is_empty() || trie.get_root_siblings().is_none() {
+        return result_buffer;
+    }
+
+    let mut dist_max = 0;
+    loop {
+        layer_stack.clear();
+        iter_stack.clear();
+        let options = SearchOptions {
+            dist_max,
+            limit: Some(1),
+            ignore_case,
+            ranking: *ranking,
+            ..SearchOptions::default()
+        };
+        result_buffer = search_approx(
+            trie,
+            word,
+            &options,
+            layer_stack,
+            iter_stack,
+            word_pool,
+            result_buffer,
+        );
+        if !result_buffer.is_empty() || dist_max == Distance::MAX {
+            return result_buffer;
+        }
+        dist_max = max(1, dist_max.saturating_mul(2));
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::test_utils::{create_trie, nb_allocations};
+
+    fn run_search_approx(
+        trie: &CompiledTrie,
+        word: &str,
+        dist_max: Distance,
+    ) -> Vec<(String, Distance)> {
+        run_search_approx_limit(trie, word, dist_max, None)
+    }
+
+    fn run_search_approx_limit(
+        trie: &CompiledTrie,
+        word: &str,
+        dist_max: Distance,
+        limit: Option<usize>,
+    ) -> Vec<(String, Distance)> {
+        run_search_approx_with(trie, word, dist_max, limit, false)
+    }
+
+    fn run_search_approx_with(
+        trie: &CompiledTrie,
+        word: &str,
+        dist_max: Distance,
+        limit: Option<usize>,
+        ignore_case: bool,
+    ) -> Vec<(String, Distance)> {
+        let mut layer_stack = LayerStack::with_capacity(0, 0);
+        let mut iter_stack = IterationStack::with_capacity(0);
+        let mut found_words = search_approx(
+            trie,
+            word,
+            &SearchOptions {
+                dist_max,
+                limit,
+                ignore_case,
+                ..SearchOptions::default()
+            },
+            &mut layer_stack,
+            &mut iter_stack,
+            &mut WordPool::new(),
+            Vec::new(),
+        );
+        found_words.sort_unstable();
+        found_words.into_iter().map(|w| (w.word, w.dist)).collect()
+    }
+
+    fn run_search_closest_tier(
+        trie: &CompiledTrie,
+        word: &str,
+        dist_max: Distance,
+    ) -> Vec<(String, Distance)> {
+        let mut layer_stack = LayerStack::with_capacity(0, 0);
+        let mut iter_stack = IterationStack::with_capacity(0);
+        let mut found_words = search_closest_tier(
+            trie,
+            word,
+            dist_max,
+            &mut layer_stack,
+            &mut iter_stack,
+            &mut WordPool::new(),
+            Vec::new(),
+        );
+        found_words.sort_unstable();
+        found_words.into_iter().map(|w| (w.word, w.dist)).collect()
+    }
 
     fn check_compute_layer_word(word: &str, trie_word: &str, target_layers: &[&[Distance]]) {
         let layer_len = word.chars().count() + 1;
@@ -634,8 +2197,20 @@ mod test {
         let mut parent_layer = vec![];
         let mut last_char = None;
 
-        for (ch, target_layer) in trie_word.chars().zip(target_layers) {
-            compute_layer(&mut layer, &last_layer, &parent_layer, word, last_char, ch);
+        let word: Vec<char> = word.chars().collect();
+        for (depth, (ch, target_layer)) in trie_word.chars().zip(target_layers).enumerate() {
+            compute_layer(
+                &mut layer,
+                &last_layer,
+                &parent_layer,
+                &word,
+                depth + 1,
+                Distance::MAX,
+                last_char,
+                ch,
+                false,
+                &EditCosts::default(),
+            );
             assert_eq!(&layer, target_layer);
 
             parent_layer = last_layer;
@@ -728,9 +2303,97 @@ mod test {
         check_compute_layer_word(word, trie_word, &target_layers);
     }
 
+    /// Compute all the layers of the trie word, keeping only the cells around the diagonal
+    /// at most `dist_max` away, and return them.
+    fn compute_all_layers(word: &[char], trie_word: &[char], dist_max: Distance) -> Vec<Distance> {
+        let layer_len = word.len() + 1;
+        let mut layers: Vec<Distance> = (0..layer_len as Distance).collect();
+        let mut layer = vec![0; layer_len];
+        for (depth, &ch) in trie_word.iter().enumerate() {
+            let layers_len = layers.len();
+            let last_layer = &layers[layers_len - layer_len..];
+            let parent_layer = layers_len
+                .checked_sub(2 * layer_len)
+                .map_or(&[][..], |start| &layers[start..start + layer_len]);
+            let last_char = depth.checked_sub(1).map(|i| trie_word[i]);
+            compute_layer(
+                &mut layer,
+                last_layer,
+                parent_layer,
+                word,
+                depth + 1,
+                dist_max,
+                last_char,
+                ch,
+                false,
+                &EditCosts::default(),
+            );
+            layers.extend_from_slice(&layer);
+        }
+        layers
+    }
+
     #[test]
-    fn test_cmp_min_with_max_dist_less() {
-        let layer = vec![5, 3, 2, 6];
+    fn test_compute_layer_banded() {
+        let words = [
+            "abcdef",
+            "badcfe",
+            "abacabadabacaba",
+            "aabbaabb",
+            "xyz",
+            "a",
+        ];
+        for word in &words {
+            for trie_word in &words {
+                let word: Vec<char> = word.chars().collect();
+                let trie_word: Vec<char> = trie_word.chars().collect();
+                let full = compute_all_layers(&word, &trie_word, Distance::MAX);
+
+                // The cells within the maximum distance are the same as without any band,
+                // and the others are still greater than it
+                for dist_max in 0..4 {
+                    let banded = compute_all_layers(&word, &trie_word, dist_max);
+                    for (&band_cell, &full_cell) in banded.iter().zip(&full) {
+                        if full_cell <= dist_max {
+                            assert_eq!(band_cell, full_cell);
+                        } else {
+                            assert!(band_cell > dist_max);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Run with `cargo test --release -- --ignored --nocapture bench_compute_layer`.
+    #[test]
+    #[ignore]
+    fn bench_compute_layer_banded() {
+        let word: Vec<char> = "pneumonoultramicroscopicsilicovolcanoconiosis"
+            .repeat(4)
+            .chars()
+            .collect();
+        let mut trie_word = word.clone();
+        trie_word.swap(10, 11);
+
+        let time = |dist_max| {
+            let start = std::time::Instant::now();
+            let mut distances = 0;
+            for _ in 0..10_000 {
+                let layers = compute_all_layers(&word, &trie_word, dist_max);
+                distances += u64::from(*layers.last().unwrap());
+            }
+            (start.elapsed(), distances)
+        };
+        let (full, full_distances) = time(Distance::MAX);
+        let (banded, banded_distances) = time(1);
+        assert_eq!(full_distances, banded_distances);
+        println!("full: {:?}, banded (distance 1): {:?}", full, banded);
+    }
+
+    #[test]
+    fn test_cmp_min_with_max_dist_less() {
+        let layer = vec![5, 3, 2, 6];
         let dist_max = 3;
         let mut buf = vec![];
         let (ord, _v) = cmp_min_with_max_dist(&layer, dist_max, &mut buf);
@@ -765,4 +2428,2005 @@ mod test {
         let (ord, _v) = cmp_min_with_max_dist(&layer, dist_max, &mut buf);
         assert_eq!(ord, Ordering::Greater);
     }
+
+    /// The weighted (restricted) Damerau-Levenshtein distance between a query and a word,
+    /// computed with the whole matrix (the Levenshtein one without transpositions).
+    fn weighted_distance(query: &str, word: &str, costs: &EditCosts) -> Distance {
+        let query: Vec<char> = query.chars().collect();
+        let word: Vec<char> = word.chars().collect();
+        let mut d = vec![vec![0; query.len() + 1]; word.len() + 1];
+        for (i, cell) in d[0].iter_mut().enumerate() {
+            *cell = i as Distance * costs.delete;
+        }
+        for r in 1..=word.len() {
+            d[r][0] = r as Distance * costs.insert;
+            for i in 1..=query.len() {
+                let replace = if query[i - 1] == word[r - 1] {
+                    0
+                } else {
+                    costs.substitute_cost(query[i - 1], word[r - 1])
+                };
+                let mut cost = min(
+                    min(d[r - 1][i] + costs.insert, d[r][i - 1] + costs.delete),
+                    d[r - 1][i - 1] + replace,
+                );
+                if costs.transpositions
+                    && r > 1
+                    && i > 1
+                    && query[i - 1] == word[r - 2]
+                    && query[i - 2] == word[r - 1]
+                {
+                    cost = min(cost, d[r - 2][i - 2] + costs.transpose);
+                }
+                d[r][i] = cost;
+            }
+        }
+        d[word.len()][query.len()]
+    }
+
+    /// The weighted true Damerau-Levenshtein distance between a query and a word,
+    /// computed with the whole matrix and the last row of each character of the query.
+    fn unrestricted_distance(query: &str, word: &str, costs: &EditCosts) -> Distance {
+        let query: Vec<char> = query.chars().collect();
+        let word: Vec<char> = word.chars().collect();
+        let mut d = vec![vec![0; query.len() + 1]; word.len() + 1];
+        for (i, cell) in d[0].iter_mut().enumerate() {
+            *cell = i as Distance * costs.delete;
+        }
+        let mut last_row = std::collections::HashMap::new();
+        for r in 1..=word.len() {
+            d[r][0] = r as Distance * costs.insert;
+            let mut last_col = 0;
+            for i in 1..=query.len() {
+                let replace = if query[i - 1] == word[r - 1] {
+                    0
+                } else {
+                    costs.substitute_cost(query[i - 1], word[r - 1])
+                };
+                let mut cost = min(
+                    min(d[r - 1][i] + costs.insert, d[r][i - 1] + costs.delete),
+                    d[r - 1][i - 1] + replace,
+                );
+                let l = last_row.get(&query[i - 1]).copied().unwrap_or(0);
+                if l > 0 && last_col > 0 {
+                    let between = (r - l - 1) as Distance * costs.insert
+                        + (i - last_col - 1) as Distance * costs.delete;
+                    cost = min(cost, d[l - 1][last_col - 1] + between + costs.transpose);
+                }
+                if replace == 0 {
+                    last_col = i;
+                }
+                d[r][i] = cost;
+            }
+            last_row.insert(word[r - 1], r);
+        }
+        d[word.len()][query.len()]
+    }
+
+    #[test]
+    fn test_edit_costs_new() {
+        assert_eq!(EditCosts::new(1, 1, 1, 1), Some(EditCosts::default()));
+        assert!(EditCosts::new(2, 3, 1, 2).is_some());
+        assert!(EditCosts::new(0, 1, 1, 1).is_none());
+        assert!(EditCosts::new(1, 1, 0, 1).is_none());
+        assert!(EditCosts::new(2, 2, 1, 1).is_none());
+        assert!(EditCosts::new(1, 1, 2, 1).is_none());
+    }
+
+    #[test]
+    fn test_search_approx_edit_costs() {
+        let words = [
+            "abc", "acb", "ab", "abcd", "xbc", "bac", "b", "abcabc", "ca", "cab", "ba", "dabc",
+        ];
+        let entries: Vec<_> = words.iter().map(|&w| (w, 1)).collect();
+        let trie = create_trie(&entries);
+
+        let mut table = SubstitutionTable::new();
+        table.insert('a', 'b', 1);
+        table.insert('c', 'x', 3);
+        let all_costs = [
+            EditCosts::default(),
+            EditCosts::new(2, 2, 2, 3)
+                .unwrap()
+                .with_substitution_table(&table)
+                .unwrap(),
+            // Substitutions cheaper than an insertion and a deletion
+            EditCosts::new(2, 2, 1, 2).unwrap(),
+            EditCosts::new(1, 3, 2, 2).unwrap(),
+            EditCosts::new(3, 1, 1, 5).unwrap(),
+        ];
+        for costs in &all_costs {
+            for &query in &["abc", "ba", "cb", "abcab", "d"] {
+                for dist_max in 0..=4 {
+                    let mut expected: Vec<_> = words
+                        .iter()
+                        .map(|&w| (w.to_string(), weighted_distance(query, w, costs)))
+                        .filter(|(_, d)| *d <= dist_max)
+                        .collect();
+                    expected.sort_unstable_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+
+                    let mut found = search_approx(
+                        &trie,
+                        query,
+                        &SearchOptions {
+                            dist_max,
+                            costs: *costs,
+                            ..SearchOptions::default()
+                        },
+                        &mut LayerStack::with_capacity(0, 0),
+                        &mut IterationStack::new(),
+                        &mut WordPool::new(),
+                        Vec::new(),
+                    );
+                    found.sort_unstable();
+                    let found: Vec<_> = found.into_iter().map(|w| (w.word, w.dist)).collect();
+                    assert_eq!(found, expected, "{:?} {} {}", costs, query, dist_max);
+                }
+            }
+        }
+
+        // A cheaper substitution ranks the replacement before the insertion and deletion
+        let costs = EditCosts::new(2, 2, 1, 2).unwrap();
+        assert_eq!(weighted_distance("abc", "xbc", &costs), 1);
+        assert_eq!(weighted_distance("abc", "ab", &costs), 2);
+    }
+
+    #[test]
+    fn test_search_approx_levenshtein() {
+        let words = [
+            "ab", "ba", "abc", "bac", "acb", "cab", "ca", "abcd", "badc", "b",
+        ];
+        let entries: Vec<_> = words.iter().map(|&w| (w, 1)).collect();
+        let trie = create_trie(&entries);
+        let search = |query, options: &SearchOptions| {
+            let mut found = search_approx(
+                &trie,
+                query,
+                options,
+                &mut LayerStack::with_capacity(0, 0),
+                &mut IterationStack::new(),
+                &mut WordPool::new(),
+                Vec::new(),
+            );
+            found.sort_unstable();
+            found
+                .into_iter()
+                .map(|w| (w.word, w.dist))
+                .collect::<Vec<_>>()
+        };
+
+        // A transposition is a single edit of the Damerau-Levenshtein distance only
+        let damerau = SearchOptions {
+            dist_max: 1,
+            ..SearchOptions::default()
+        };
+        let levenshtein = SearchOptions {
+            transposition: false,
+            ..damerau
+        };
+        assert!(search("ba", &damerau).contains(&("ab".to_string(), 1)));
+        assert!(!search("ba", &levenshtein).iter().any(|(w, _)| w == "ab"));
+        let levenshtein = SearchOptions {
+            dist_max: 2,
+            ..levenshtein
+        };
+        assert!(search("ba", &levenshtein).contains(&("ab".to_string(), 2)));
+
+        let all_costs = [
+            EditCosts::default(),
+            EditCosts::new(2, 2, 1, 2).unwrap(),
+            EditCosts::new(1, 3, 2, 2).unwrap(),
+            EditCosts::default().with_unrestricted_transpositions(),
+        ];
+        for costs in &all_costs {
+            for &query in &["ab", "ba", "abc", "bacd", "c"] {
+                for dist_max in 0..=3 {
+                    let options = SearchOptions {
+                        dist_max,
+                        costs: *costs,
+                        transposition: false,
+                        ..SearchOptions::default()
+                    };
+                    let levenshtein_costs = options.edit_costs();
+                    let mut expected: Vec<_> = words
+                        .iter()
+                        .map(|&w| {
+                            (
+                                w.to_string(),
+                                weighted_distance(query, w, &levenshtein_costs),
+                            )
+                        })
+                        .filter(|(_, d)| *d <= dist_max)
+                        .collect();
+                    expected.sort_unstable_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+                    assert_eq!(
+                        search(query, &options),
+                        expected,
+                        "{:?} {} {}",
+                        costs,
+                        query,
+                        dist_max
+                    );
+
+                    // The alignments of the words have no transposition either
+                    for (word, dist) in expected {
+                        let (edits, cost) =
+                            alignment::edit_operations(query, &word, false, &levenshtein_costs);
+                        assert_eq!(cost, dist);
+                        assert!(!edits
+                            .iter()
+                            .any(|edit| matches!(edit, EditOp::Transpose { .. })));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_approx_substitution_table() {
+        let trie = create_trie(&[("test", 1), ("trst", 1), ("tmst", 1), ("tst", 1)]);
+        let mut table = SubstitutionTable::new();
+        table.insert('e', 'r', 1);
+
+        // Substituting adjacent keys costs half of any other edit
+        let costs = EditCosts::new(2, 2, 2, 2)
+            .unwrap()
+            .with_substitution_table(&table)
+            .unwrap();
+        let run = |dist_max| {
+            let mut found = search_approx(
+                &trie,
+                "test",
+                &SearchOptions {
+                    dist_max,
+                    costs,
+                    ..SearchOptions::default()
+                },
+                &mut LayerStack::with_capacity(0, 0),
+                &mut IterationStack::new(),
+                &mut WordPool::new(),
+                Vec::new(),
+            );
+            found.sort_unstable();
+            found
+                .into_iter()
+                .map(|w| (w.word, w.dist))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            run(1),
+            vec![("test".to_string(), 0), ("trst".to_string(), 1)]
+        );
+        assert_eq!(
+            run(2),
+            vec![
+                ("test".to_string(), 0),
+                ("trst".to_string(), 1),
+                ("tmst".to_string(), 2),
+                ("tst".to_string(), 2)
+            ]
+        );
+
+        // The table costs must be valid edit costs
+        table.insert('a', 'b', 3);
+        assert!(EditCosts::new(2, 2, 2, 2)
+            .unwrap()
+            .with_substitution_table(&table)
+            .is_none());
+        table.insert('a', 'b', 0);
+        assert!(EditCosts::default()
+            .with_substitution_table(&table)
+            .is_none());
+    }
+
+    #[cfg(feature = "qwerty")]
+    #[test]
+    fn test_search_approx_qwerty() {
+        let trie = create_trie(&[("test", 1), ("trst", 1), ("tmst", 1)]);
+        let table = SubstitutionTable::qwerty(1);
+        let costs = EditCosts::new(2, 2, 2, 2)
+            .unwrap()
+            .with_substitution_table(&table)
+            .unwrap();
+        let found = search_approx(
+            &trie,
+            "tesr",
+            &SearchOptions {
+                dist_max: 2,
+                costs,
+                ..SearchOptions::default()
+            },
+            &mut LayerStack::with_capacity(0, 0),
+            &mut IterationStack::new(),
+            &mut WordPool::new(),
+            Vec::new(),
+        );
+        let found: Vec<_> = found.into_iter().map(|w| (w.word, w.dist)).collect();
+        assert_eq!(
+            found,
+            vec![("test".to_string(), 1), ("trst".to_string(), 2)]
+        );
+    }
+
+    #[test]
+    fn test_min_possible_distance() {
+        assert_eq!(min_possible_distance(4, 4), 0);
+        assert_eq!(min_possible_distance(2, 5), 3);
+        assert_eq!(min_possible_distance(5, 2), 3);
+        assert_eq!(min_possible_distance(0, 7), 7);
+        assert_eq!(min_possible_distance(0, 1 << 20), Distance::MAX);
+    }
+
+    #[test]
+    fn test_search_approx_length_bound() {
+        let trie = create_trie(&[
+            ("ab", 1),
+            ("abc", 1),
+            ("abcd", 1),
+            ("abcde", 1),
+            ("abcdefgh", 1),
+            ("xbcd", 1),
+        ]);
+
+        // The subtrees of the words longer than the query by more than the distance are skipped
+        let expected = vec![
+            ("ab".to_string(), 0),
+            ("abc".to_string(), 1),
+            ("abcd".to_string(), 2),
+        ];
+        assert_eq!(run_search_approx(&trie, "ab", 2), expected);
+        assert_eq!(run_search_approx_with(&trie, "ab", 2, None, true), expected);
+
+        // The bound does not prune the words shorter than the query
+        assert_eq!(
+            run_search_approx(&trie, "abcdefg", 1),
+            vec![("abcdefgh".to_string(), 1)]
+        );
+        assert_eq!(
+            run_search_approx(&trie, "abcdef", 2),
+            vec![
+                ("abcde".to_string(), 1),
+                ("abcd".to_string(), 2),
+                ("abcdefgh".to_string(), 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_search_approx_max_depth() {
+        // "abcdef" and "xyzw" end with patricia nodes crossing the depth 3
+        let words = [
+            ("a", 1),
+            ("ab", 1),
+            ("abc", 1),
+            ("abd", 1),
+            ("abcdef", 1),
+            ("b", 1),
+            ("bcd", 1),
+            ("bcde", 1),
+            ("xyzw", 1),
+        ];
+        let trie = create_trie(&words);
+        let run_search_approx_depth = |word, dist_max, max_depth| {
+            let mut found_words = search_approx(
+                &trie,
+                word,
+                &SearchOptions {
+                    dist_max,
+                    max_depth,
+                    ..SearchOptions::default()
+                },
+                &mut LayerStack::with_capacity(0, 0),
+                &mut IterationStack::new(),
+                &mut WordPool::new(),
+                Vec::new(),
+            );
+            found_words.sort_unstable();
+            found_words
+                .into_iter()
+                .map(|w| (w.word, w.dist))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            run_search_approx_depth("abcd", 1, Some(3)),
+            vec![
+                ("abc".to_string(), 1),
+                ("abd".to_string(), 1),
+                ("bcd".to_string(), 1)
+            ]
+        );
+        assert!(run_search_approx_depth("abcdef", 0, Some(5)).is_empty());
+        assert!(run_search_approx_depth("xyzw", 1, Some(3)).is_empty());
+        assert!(run_search_approx_depth("a", 1, Some(0)).is_empty());
+
+        // The shallower words are the same as without the limit,
+        // including those found by the exact search of the subtrees
+        for &(query, dist_max) in &[("abc", 0), ("abc", 2), ("bcde", 1), ("abcdef", 3)] {
+            let all_words = run_search_approx_depth(query, dist_max, None);
+            for max_depth in 0..7 {
+                let shallow_words: Vec<_> = all_words
+                    .iter()
+                    .filter(|(word, _)| word.chars().count() <= max_depth)
+                    .cloned()
+                    .collect();
+                assert_eq!(
+                    run_search_approx_depth(query, dist_max, Some(max_depth)),
+                    shallow_words,
+                    "{} {} {}",
+                    query,
+                    dist_max,
+                    max_depth
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_split_by_first_char() {
+        let trie = create_trie(&[
+            ("test", 5),
+            ("best", 4),
+            ("Test", 3),
+            ("tests", 2),
+            ("est", 1),
+            ("rest", 1),
+        ]);
+        let results = search_approx(
+            &trie,
+            "test",
+            &SearchOptions {
+                dist_max: 1,
+                ..SearchOptions::default()
+            },
+            &mut LayerStack::with_capacity(0, 0),
+            &mut IterationStack::new(),
+            &mut WordPool::new(),
+            Vec::new(),
+        );
+        let words = |found: &[FoundWord]| -> Vec<String> {
+            let mut words: Vec<_> = found.iter().map(|w| w.word.clone()).collect();
+            words.sort_unstable();
+            words
+        };
+        assert_eq!(words(&results).len(), 6);
+        let copy = |found: &[FoundWord]| -> Vec<FoundWord> {
+            found
+                .iter()
+                .map(|w| FoundWord {
+                    word: w.word.clone(),
+                    edits: None,
+                    ..*w
+                })
+                .collect()
+        };
+
+        // The same words as the search with the pinned first character
+        let (same, others) = split_by_first_char(copy(&results), "test", false);
+        assert_eq!(words(&same), ["test", "tests"]);
+        assert_eq!(words(&others), ["Test", "best", "est", "rest"]);
+        let pinned = PinnedChars {
+            first: true,
+            last: false,
+        };
+        let expected: Vec<_> = run_search_approx_pinned(&trie, "test", 1, false, pinned)
+            .into_iter()
+            .map(|(word, _)| word)
+            .collect();
+        assert_eq!(words(&same), expected);
+
+        // The order of the results is kept
+        let (same, _) = split_by_first_char(copy(&results), "test", true);
+        let in_order: Vec<_> = results
+            .iter()
+            .filter(|w| w.word.starts_with(&['t', 'T'][..]))
+            .map(|w| w.word.clone())
+            .collect();
+        assert_eq!(
+            same.into_iter().map(|w| w.word).collect::<Vec<_>>(),
+            in_order
+        );
+
+        let (same, others) = split_by_first_char(results, "", false);
+        assert!(same.is_empty());
+        assert_eq!(others.len(), 6);
+    }
+
+    fn run_search_approx_pinned(
+        trie: &CompiledTrie,
+        word: &str,
+        dist_max: Distance,
+        ignore_case: bool,
+        pinned: PinnedChars,
+    ) -> Vec<(String, Distance)> {
+        let mut found_words = search_approx(
+            trie,
+            word,
+            &SearchOptions {
+                dist_max,
+                ignore_case,
+                pinned,
+                ..SearchOptions::default()
+            },
+            &mut LayerStack::with_capacity(0, 0),
+            &mut IterationStack::new(),
+            &mut WordPool::new(),
+            Vec::new(),
+        );
+        found_words.sort_unstable();
+        found_words.into_iter().map(|w| (w.word, w.dist)).collect()
+    }
+
+    #[test]
+    fn test_search_approx_pinned_chars() {
+        let trie = create_trie(&[
+            ("test", 1),
+            ("best", 1),
+            ("tesa", 1),
+            ("Test", 1),
+            ("tests", 1),
+            ("est", 1),
+            ("t", 1),
+        ]);
+        let first = PinnedChars {
+            first: true,
+            last: false,
+        };
+        let last = PinnedChars {
+            first: false,
+            last: true,
+        };
+        let both = PinnedChars {
+            first: true,
+            last: true,
+        };
+
+        assert_eq!(
+            run_search_approx_pinned(&trie, "test", 1, false, PinnedChars::default()),
+            run_search_approx(&trie, "test", 1)
+        );
+
+        // The words differing only by their first (or last) character are excluded
+        assert_eq!(
+            run_search_approx_pinned(&trie, "test", 1, false, first),
+            vec![
+                ("test".to_string(), 0),
+                ("tesa".to_string(), 1),
+                ("tests".to_string(), 1)
+            ]
+        );
+        assert_eq!(
+            run_search_approx_pinned(&trie, "test", 1, false, last),
+            vec![
+                ("test".to_string(), 0),
+                ("Test".to_string(), 1),
+                ("best".to_string(), 1),
+                ("est".to_string(), 1)
+            ]
+        );
+        assert_eq!(
+            run_search_approx_pinned(&trie, "test", 1, false, both),
+            vec![("test".to_string(), 0)]
+        );
+
+        // The pinned characters follow the case sensitivity of the search
+        assert_eq!(
+            run_search_approx_pinned(&trie, "test", 1, true, both),
+            vec![("Test".to_string(), 0), ("test".to_string(), 0)]
+        );
+
+        // A single character is both the first and the last one
+        assert_eq!(
+            run_search_approx_pinned(&trie, "x", 1, false, first),
+            vec![]
+        );
+        assert_eq!(
+            run_search_approx_pinned(&trie, "t", 3, false, both),
+            vec![("t".to_string(), 0), ("test".to_string(), 3)]
+        );
+    }
+
+    #[test]
+    fn test_search_closest_tier_dist_1() {
+        let trie = create_trie(&[("best", 5), ("tent", 3), ("test", 10), ("tests", 1)]);
+        let found = run_search_closest_tier(&trie, "tes", 2);
+        assert_eq!(found, vec![("test".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_search_closest_tier_only_dist_2() {
+        let trie = create_trie(&[("abcd", 1), ("xyz", 1), ("abdc", 2)]);
+        let found = run_search_closest_tier(&trie, "ab", 3);
+        assert_eq!(
+            found,
+            vec![("abdc".to_string(), 2), ("abcd".to_string(), 2)]
+        );
+    }
+
+    #[test]
+    fn test_search_closest_tier_no_match() {
+        let trie = create_trie(&[("abcd", 1), ("xyz", 1)]);
+        let found = run_search_closest_tier(&trie, "klmnop", 2);
+        assert!(found.is_empty());
+    }
+
+    #[test]
+    fn test_search_nearest() {
+        let trie = create_trie(&[("abcd", 1), ("xyz", 3), ("abdc", 2), ("Klmnop", 4)]);
+        let nearest = |word, ignore_case| -> Vec<_> {
+            search_nearest(
+                &trie,
+                word,
+                ignore_case,
+                &Ranking::default(),
+                &mut LayerStack::with_capacity(0, 0),
+                &mut IterationStack::new(),
+                &mut WordPool::new(),
+                Vec::new(),
+            )
+            .into_iter()
+            .map(|w| (w.word, w.freq.get(), w.dist))
+            .collect()
+        };
+
+        // The ties at the smallest distance are broken by the frequency
+        assert_eq!(nearest("abcd", false), [("abcd".to_string(), 1, 0)]);
+        assert_eq!(nearest("ab", false), [("abdc".to_string(), 2, 2)]);
+        assert_eq!(nearest("xyzxyzxyz", false), [("xyz".to_string(), 3, 6)]);
+        assert_eq!(nearest("klmnop", false), [("Klmnop".to_string(), 4, 1)]);
+        assert_eq!(nearest("klmnop", true), [("Klmnop".to_string(), 4, 0)]);
+        assert_eq!(
+            nearest("qqqqqqqqqqqqqqqqqqqq", false),
+            [("Klmnop".to_string(), 4, 20)]
+        );
+        assert!(nearest("", false).is_empty());
+
+        let empty = create_trie(&[]);
+        let found = search_nearest(
+            &empty,
+            "abc",
+            false,
+            &Ranking::default(),
+            &mut LayerStack::with_capacity(0, 0),
+            &mut IterationStack::new(),
+            &mut WordPool::new(),
+            Vec::new(),
+        );
+        assert!(found.is_empty());
+    }
+
+    #[test]
+    fn test_search_approx_single_letter_words() {
+        let trie = create_trie(&[("I", 3), ("a", 5), ("ab", 2), ("b", 1)]);
+
+        let found = run_search_approx(&trie, "a", 0);
+        assert_eq!(found, vec![("a".to_string(), 0)]);
+
+        let found = run_search_approx(&trie, "I", 0);
+        assert_eq!(found, vec![("I".to_string(), 0)]);
+
+        let found = run_search_approx(&trie, "a", 1);
+        assert_eq!(
+            found,
+            vec![
+                ("a".to_string(), 0),
+                ("I".to_string(), 1),
+                ("ab".to_string(), 1),
+                ("b".to_string(), 1),
+            ]
+        );
+
+        let found = run_search_approx(&trie, "c", 1);
+        assert_eq!(
+            found,
+            vec![
+                ("a".to_string(), 1),
+                ("I".to_string(), 1),
+                ("b".to_string(), 1),
+            ]
+        );
+    }
+
+    fn found_word(word: &str, freq: u32, dist: Distance) -> FoundWord {
+        FoundWord {
+            word: word.to_string(),
+            freq: NonZeroU32::new(freq).unwrap(),
+            dist,
+            edits: None,
+        }
+    }
+
+    fn to_tuples(found_words: Vec<FoundWord>) -> Vec<(String, u32, Distance)> {
+        found_words
+            .into_iter()
+            .map(|w| (w.word, w.freq.get(), w.dist))
+            .collect()
+    }
+
+    #[test]
+    fn test_merge_results_disjoint() {
+        let en = vec![found_word("cat", 10, 0), found_word("car", 5, 1)];
+        let fr = vec![found_word("chat", 7, 1), found_word("cas", 3, 1)];
+
+        let merged = merge_results(vec![en, fr], 10);
+        assert_eq!(
+            to_tuples(merged),
+            vec![
+                ("cat".to_string(), 10, 0),
+                ("chat".to_string(), 7, 1),
+                ("car".to_string(), 5, 1),
+                ("cas".to_string(), 3, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_results_overlapping() {
+        let en = vec![found_word("table", 10, 1), found_word("tablet", 2, 2)];
+        let fr = vec![found_word("table", 20, 1), found_word("fable", 4, 2)];
+        let es = vec![found_word("tablet", 1, 1)];
+
+        let merged = merge_results(vec![en, fr, es], 10);
+        assert_eq!(
+            to_tuples(merged),
+            vec![
+                ("table".to_string(), 20, 1),
+                ("tablet".to_string(), 1, 1),
+                ("fable".to_string(), 4, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_results_limit() {
+        let en = vec![found_word("a", 3, 0), found_word("b", 2, 1)];
+        let fr = vec![found_word("c", 5, 1), found_word("d", 1, 1)];
+
+        let merged = merge_results(vec![en, fr], 2);
+        assert_eq!(
+            to_tuples(merged),
+            vec![("a".to_string(), 3, 0), ("c".to_string(), 5, 1)]
+        );
+
+        assert!(merge_results(Vec::<Vec<FoundWord>>::new(), 5).is_empty());
+        assert!(merge_results(vec![vec![found_word("a", 1, 0)]], 0).is_empty());
+    }
+
+    #[test]
+    fn test_search_approx_multibyte_equal_split() {
+        let trie = create_trie(&[("日本人", 4), ("日本語", 8), ("日本語です", 2)]);
+
+        // The handoff to the exact search happens after "日本", on the "語" character
+        let found = run_search_approx(&trie, "x本語です", 1);
+        assert_eq!(found, vec![("日本語です".to_string(), 1)]);
+
+        let found = run_search_approx(&trie, "月本語", 1);
+        assert_eq!(found, vec![("日本語".to_string(), 1)]);
+
+        let found = run_search_approx(&trie, "日本語でず", 1);
+        assert_eq!(found, vec![("日本語です".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_search_approx_multibyte_equal_split_emojis() {
+        let trie = create_trie(&[("🦀🐍", 1), ("🦀🦀🐍", 2), ("🐍🦀", 3)]);
+
+        let found = run_search_approx(&trie, "a🦀🐍", 1);
+        assert_eq!(
+            found,
+            vec![("🦀🦀🐍".to_string(), 1), ("🦀🐍".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn test_search_approx_limit() {
+        let trie = create_trie(&[("bat", 2), ("cat", 9), ("cut", 4), ("cats", 1), ("at", 7)]);
+
+        let all = run_search_approx_limit(&trie, "cat", 2, None);
+        assert_eq!(all.len(), 5);
+
+        for limit in 0..=6 {
+            let found = run_search_approx_limit(&trie, "cat", 2, Some(limit));
+            let expected: Vec<_> = all.iter().take(limit).cloned().collect();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn test_search_approx_limit_sorted() {
+        let trie = create_trie(&[
+            ("bat", 2),
+            ("cat", 9),
+            ("cut", 4),
+            ("cats", 1),
+            ("at", 7),
+            ("coat", 5),
+            ("chat", 3),
+        ]);
+        let boost = WordBoost::new(vec!["cats".to_string()], 10);
+        let boosted = Ranking {
+            boost: Some(&boost),
+            ..Ranking::default()
+        };
+
+        let words = |found: &[FoundWord]| -> Vec<_> {
+            found
+                .iter()
+                .map(|w| (w.word.clone(), w.freq, w.dist))
+                .collect()
+        };
+
+        for &ranking in &[Ranking::default(), boosted] {
+            for &dist_max in &[0, 1, 2] {
+                let mut word_pool = WordPool::new();
+                let search = |limit, word_pool: &mut WordPool, result_buffer| {
+                    search_approx(
+                        &trie,
+                        "cat",
+                        &SearchOptions {
+                            dist_max,
+                            limit,
+                            ranking,
+                            ..SearchOptions::default()
+                        },
+                        &mut LayerStack::with_capacity(0, 0),
+                        &mut IterationStack::new(),
+                        word_pool,
+                        result_buffer,
+                    )
+                };
+                let mut all = search(None, &mut word_pool, Vec::new());
+                all.sort_unstable_by(|a, b| a.cmp_ranked(b, &ranking));
+
+                // The limited results are already sorted, without having to sort them
+                for limit in 0..=all.len() + 1 {
+                    let found = search(Some(limit), &mut word_pool, Vec::new());
+                    let expected = words(&all[..limit.min(all.len())]);
+                    assert_eq!(words(&found), expected, "{} {}", dist_max, limit);
+
+                    // The discarded words are given back to the pool (and may be reused)
+                    assert_eq!(word_pool.words.is_empty(), found.len() == all.len());
+                    word_pool.words.clear();
+                }
+
+                // The words already in the buffer are ranked with the found ones
+                let buffer = all
+                    .iter()
+                    .map(|w| FoundWord {
+                        word: w.word.clone(),
+                        edits: None,
+                        ..*w
+                    })
+                    .collect();
+                let found = search(Some(2), &mut word_pool, buffer);
+                let expected: Vec<_> = words(&all)
+                    .into_iter()
+                    .flat_map(|w| vec![w.clone(), w])
+                    .take(2)
+                    .collect();
+                assert_eq!(words(&found), expected, "{}", dist_max);
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_approx_reuses_pooled_words() {
+        let trie = create_trie(&[("bat", 2), ("cat", 9), ("cut", 4), ("cats", 1), ("at", 7)]);
+        let mut layer_stack = LayerStack::with_capacity(0, 0);
+        let mut iter_stack = IterationStack::with_capacity(0);
+        let mut word_pool = WordPool::new();
+
+        let mut found_words = search_approx(
+            &trie,
+            "cat",
+            &SearchOptions {
+                dist_max: 2,
+                ..SearchOptions::default()
+            },
+            &mut layer_stack,
+            &mut iter_stack,
+            &mut word_pool,
+            Vec::new(),
+        );
+        assert_eq!(found_words.len(), 5);
+        assert!(word_pool.words.is_empty());
+
+        // The results stay valid until they are recycled
+        let buffers: HashSet<_> = found_words.iter().map(|w| w.word.as_ptr()).collect();
+        word_pool.recycle(&mut found_words);
+        assert!(found_words.is_empty());
+        assert_eq!(word_pool.words.len(), 5);
+
+        // The next search takes its words from the pool instead of allocating them
+        layer_stack.clear();
+        iter_stack.clear();
+        let mut found_words = search_approx(
+            &trie,
+            "cut",
+            &SearchOptions {
+                dist_max: 1,
+                ..SearchOptions::default()
+            },
+            &mut layer_stack,
+            &mut iter_stack,
+            &mut word_pool,
+            found_words,
+        );
+        found_words.sort_unstable();
+        assert!(found_words
+            .iter()
+            .all(|w| buffers.contains(&w.word.as_ptr())));
+        assert_eq!(word_pool.words.len(), 3);
+        assert_eq!(
+            to_tuples(found_words),
+            vec![("cut".to_string(), 4, 0), ("cat".to_string(), 9, 1)]
+        );
+    }
+
+    #[test]
+    fn test_word_pool_capacity() {
+        let trie = create_trie(&[("bat", 2), ("cat", 9), ("cut", 4), ("cats", 1), ("at", 7)]);
+        let mut word_pool = WordPool::with_capacity(2);
+        let search = |limit, word_pool: &mut WordPool| {
+            search_approx(
+                &trie,
+                "cat",
+                &SearchOptions {
+                    dist_max: 2,
+                    limit,
+                    ..SearchOptions::default()
+                },
+                &mut LayerStack::with_capacity(0, 0),
+                &mut IterationStack::new(),
+                word_pool,
+                Vec::new(),
+            )
+        };
+
+        // The pool keeps no more words than its capacity
+        let mut found_words = search(None, &mut word_pool);
+        assert_eq!(found_words.len(), 5);
+        word_pool.recycle(&mut found_words);
+        assert_eq!(word_pool.words.len(), 2);
+
+        // Neither with the words discarded by a limited search (some of them being reused)
+        word_pool.words.clear();
+        let found_words = search(Some(1), &mut word_pool);
+        assert_eq!(found_words.len(), 1);
+        assert!(!word_pool.words.is_empty() && word_pool.words.len() <= 2);
+
+        let mut word_pool = WordPool::with_capacity(0);
+        word_pool.give_back("cat".to_string());
+        assert!(word_pool.words.is_empty());
+        assert_eq!(WordPool::new().max_words, WordPool::DEFAULT_CAPACITY);
+    }
+
+    #[test]
+    fn test_search_approx_ignore_case() {
+        let trie = create_trie(&[("Paris", 5), ("paris", 1), ("pari", 2), ("STRASSE", 3)]);
+
+        // The found words keep the case of the trie
+        assert_eq!(
+            run_search_approx_with(&trie, "paris", 0, None, true),
+            vec![("Paris".to_string(), 0), ("paris".to_string(), 0)]
+        );
+        assert_eq!(
+            run_search_approx_with(&trie, "PARIS", 1, None, true),
+            vec![
+                ("Paris".to_string(), 0),
+                ("paris".to_string(), 0),
+                ("pari".to_string(), 1)
+            ]
+        );
+        assert_eq!(
+            run_search_approx_with(&trie, "paris", 0, None, false),
+            vec![("paris".to_string(), 0)]
+        );
+
+        // Transpositions also ignore the case
+        assert_eq!(
+            run_search_approx_with(&trie, "pAirs", 1, None, true),
+            vec![("Paris".to_string(), 1), ("paris".to_string(), 1)]
+        );
+
+        // A character is never folded into multiple ones
+        assert_eq!(
+            run_search_approx_with(&trie, "straße", 1, None, true),
+            vec![]
+        );
+        assert_eq!(
+            run_search_approx_with(&trie, "straße", 2, None, true),
+            vec![("STRASSE".to_string(), 2)]
+        );
+    }
+
+    fn run_search_approx_ranked(
+        trie: &CompiledTrie,
+        word: &str,
+        dist_max: Distance,
+        limit: Option<usize>,
+        ranking: Ranking,
+    ) -> Vec<String> {
+        let mut layer_stack = LayerStack::with_capacity(0, 0);
+        let mut iter_stack = IterationStack::with_capacity(0);
+        let mut found_words = search_approx(
+            trie,
+            word,
+            &SearchOptions {
+                dist_max,
+                limit,
+                ranking,
+                ..SearchOptions::default()
+            },
+            &mut layer_stack,
+            &mut iter_stack,
+            &mut WordPool::new(),
+            Vec::new(),
+        );
+        found_words.sort_unstable_by(|a, b| a.cmp_ranked(b, &ranking));
+        found_words.into_iter().map(|w| w.word).collect()
+    }
+
+    #[test]
+    fn test_freq_damping_effective_freq() {
+        let freq = |f| NonZeroU32::new(f).unwrap();
+        assert_eq!(FreqDamping::None.effective_freq(freq(12345)), 12345);
+        assert_eq!(FreqDamping::Cap(freq(100)).effective_freq(freq(12345)), 100);
+        assert_eq!(FreqDamping::Cap(freq(100)).effective_freq(freq(42)), 42);
+        assert_eq!(FreqDamping::Log.effective_freq(freq(1)), 1);
+        assert_eq!(FreqDamping::Log.effective_freq(freq(5)), 3);
+        assert_eq!(FreqDamping::Log.effective_freq(freq(7)), 3);
+        assert_eq!(FreqDamping::Log.effective_freq(freq(u32::MAX)), 32);
+    }
+
+    fn damped(damping: FreqDamping) -> Ranking<'static> {
+        Ranking {
+            damping,
+            ..Ranking::default()
+        }
+    }
+
+    #[test]
+    fn test_search_approx_freq_damping() {
+        let trie = create_trie(&[
+            ("the", 1_000_000),
+            ("that", 900),
+            ("than", 800),
+            ("tea", 700),
+            ("thai", 3),
+        ]);
+
+        // The frequent word wins with the raw frequencies
+        assert_eq!(
+            run_search_approx_ranked(&trie, "tha", 1, None, damped(FreqDamping::None)),
+            vec!["the", "that", "than", "tea", "thai"]
+        );
+        assert_eq!(
+            run_search_approx_ranked(&trie, "tha", 1, Some(1), damped(FreqDamping::None)),
+            vec!["the"]
+        );
+
+        // Capped, the words above the cap are ranked with the lexicographic order
+        let cap = FreqDamping::Cap(NonZeroU32::new(500).unwrap());
+        assert_eq!(
+            run_search_approx_ranked(&trie, "tha", 1, None, damped(cap)),
+            vec!["tea", "than", "that", "the", "thai"]
+        );
+        assert_eq!(
+            run_search_approx_ranked(&trie, "tha", 1, Some(1), damped(cap)),
+            vec!["tea"]
+        );
+
+        // With the logarithm, only the order of magnitude matters
+        assert_eq!(
+            run_search_approx_ranked(&trie, "tha", 1, None, damped(FreqDamping::Log)),
+            vec!["the", "tea", "than", "that", "thai"]
+        );
+
+        // The distance always comes first
+        assert_eq!(
+            run_search_approx_ranked(&trie, "thai", 1, Some(2), damped(cap)),
+            vec!["thai", "than"]
+        );
+    }
+
+    #[test]
+    fn test_search_approx_word_boost() {
+        let trie = create_trie(&[
+            ("their", 5000),
+            ("there", 20000),
+            ("three", 10),
+            ("thee", 1),
+        ]);
+        let boost = WordBoost::new(vec!["their".to_string(), "thee".to_string()], 10);
+        let boosted = Ranking {
+            boost: Some(&boost),
+            ..Ranking::default()
+        };
+
+        // The boosted words outrank the more frequent ones at the same distance
+        assert_eq!(
+            run_search_approx_ranked(&trie, "theer", 1, None, damped(FreqDamping::None)),
+            vec!["there", "their", "thee"]
+        );
+        assert_eq!(
+            run_search_approx_ranked(&trie, "theer", 1, None, boosted),
+            vec!["their", "there", "thee"]
+        );
+        assert_eq!(
+            run_search_approx_ranked(&trie, "theer", 1, Some(1), boosted),
+            vec!["their"]
+        );
+
+        // The distance still comes first
+        assert_eq!(
+            run_search_approx_ranked(&trie, "there", 2, None, boosted),
+            vec!["there", "thee", "three", "their"]
+        );
+
+        // The boost applies to the dampened frequency
+        let capped = Ranking {
+            damping: FreqDamping::Cap(NonZeroU32::new(1000).unwrap()),
+            ..boosted
+        };
+        assert_eq!(
+            capped.effective_freq("their", NonZeroU32::new(5000).unwrap()),
+            10_000
+        );
+        assert_eq!(
+            capped.effective_freq("there", NonZeroU32::new(20000).unwrap()),
+            1000
+        );
+    }
+
+    #[cfg(feature = "ngram-model")]
+    #[test]
+    fn test_search_approx_ngram_ranking() {
+        let trie = create_trie(&[
+            ("than", 1),
+            ("that", 1),
+            ("the", 1),
+            ("then", 1),
+            ("there", 1),
+            ("thin", 1),
+            ("this", 1),
+            ("thxn", 1),
+            ("xhan", 1),
+        ]);
+        let ranking = Ranking {
+            ngram_model: Some(&trie),
+            ..Ranking::default()
+        };
+
+        // The equally frequent words are ranked lexicographically without the model
+        assert_eq!(
+            run_search_approx_ranked(&trie, "thqn", 1, None, Ranking::default()),
+            vec!["than", "then", "thin", "thxn"]
+        );
+
+        // With it, the words looking less like the others come last
+        let ranked = run_search_approx_ranked(&trie, "thqn", 1, None, ranking);
+        assert_eq!(ranked.last(), Some(&"thxn".to_string()));
+        assert_eq!(
+            run_search_approx_ranked(&trie, "xhan", 1, Some(1), ranking),
+            vec!["xhan"]
+        );
+        assert_eq!(
+            run_search_approx_ranked(&trie, "qhan", 1, Some(1), ranking),
+            vec!["than"]
+        );
+
+        // Sorting with the ranks computed once per word gives the same order
+        let found = |word: &str, freq, dist| FoundWord {
+            word: word.to_string(),
+            freq: NonZeroU32::new(freq).unwrap(),
+            dist,
+            edits: None,
+        };
+        let create_words = || {
+            vec![
+                found("thxn", 1, 1),
+                found("xhan", 2, 1),
+                found("then", 1, 1),
+                found("than", 1, 1),
+                found("thin", 1, 0),
+                found("that", 1, 2),
+            ]
+        };
+        let mut expected = create_words();
+        expected.sort_unstable_by(|a, b| a.cmp_ranked(b, &ranking));
+        let mut sorted = create_words();
+        sort_ranked(&mut sorted, &ranking);
+        let words = |found: &[FoundWord]| found.iter().map(|w| w.word.clone()).collect::<Vec<_>>();
+        assert_eq!(words(&sorted), words(&expected));
+        assert_eq!(words(&sorted)[..2], ["thin", "xhan"]);
+    }
+
+    #[test]
+    fn test_search_approx_no_duplicates() {
+        // A xorshift generator, to try many small tries of a few letters
+        let mut state = 0x9E37_79B9_7F4A_7C15_u64;
+        let mut below = |bound: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound) as usize
+        };
+        let letters = ['a', 'b', 'A'];
+        let mut random_word = || -> String {
+            let len = below(5) + 1;
+            (0..len).map(|_| letters[below(3)]).collect()
+        };
+        let costs = [
+            EditCosts::default(),
+            EditCosts::new(1, 2, 2, 3).unwrap(),
+            EditCosts::new(2, 1, 3, 3).unwrap(),
+        ];
+
+        // The words found by the exact search of the end of a word (when the minimum
+        // distance of a layer is the maximum) each follow a different position of the query,
+        // so they are all different and not found by the approximate search
+        for i in 0..1000 {
+            let mut words: Vec<String> = (0..=i % 12).map(|_| random_word()).collect();
+            words.sort();
+            words.dedup();
+            let entries: Vec<_> = words.iter().map(|w| (w.as_str(), 1)).collect();
+            let trie = create_trie(&entries);
+            let query = random_word();
+
+            for &ignore_case in &[false, true] {
+                for costs in &costs {
+                    for dist_max in 0..4 {
+                        let mut found: Vec<_> = search_approx(
+                            &trie,
+                            &query,
+                            &SearchOptions {
+                                dist_max,
+                                ignore_case,
+                                costs: *costs,
+                                ..SearchOptions::default()
+                            },
+                            &mut LayerStack::with_capacity(0, 0),
+                            &mut IterationStack::new(),
+                            &mut WordPool::new(),
+                            Vec::new(),
+                        )
+                        .into_iter()
+                        .map(|w| w.word)
+                        .collect();
+                        let nb_found = found.len();
+                        found.sort_unstable();
+                        found.dedup();
+                        assert_eq!(found.len(), nb_found, "{:?} {}", words, query);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_approx_unrestricted_transpositions() {
+        let trie = create_trie(&[("ABC", 1), ("AC", 1), ("BDDE", 1)]);
+        let search = |query, dist_max, costs: &EditCosts| -> Vec<(String, Distance)> {
+            let mut found = search_approx(
+                &trie,
+                query,
+                &SearchOptions {
+                    dist_max,
+                    costs: *costs,
+                    ..SearchOptions::default()
+                },
+                &mut LayerStack::with_capacity(0, 0),
+                &mut IterationStack::new(),
+                &mut WordPool::new(),
+                Vec::new(),
+            );
+            found.sort_unstable();
+            found.into_iter().map(|w| (w.word, w.dist)).collect()
+        };
+        let restricted = EditCosts::default();
+        let unrestricted = EditCosts::default().with_unrestricted_transpositions();
+
+        // "CA" to "AC" to "ABC" inserts a character between the transposed ones
+        assert_eq!(weighted_distance("CA", "ABC", &restricted), 3);
+        assert_eq!(unrestricted_distance("CA", "ABC", &restricted), 2);
+        assert_eq!(search("CA", 2, &restricted), vec![("AC".to_string(), 1)]);
+        assert_eq!(
+            search("CA", 2, &unrestricted),
+            vec![("AC".to_string(), 1), ("ABC".to_string(), 2)]
+        );
+
+        // The words at a distance of 1 are the same
+        assert_eq!(
+            search("BAC", 1, &restricted),
+            search("BAC", 1, &unrestricted)
+        );
+    }
+
+    #[test]
+    fn test_search_approx_unrestricted_transpositions_overflow() {
+        // Deleting and inserting the 80 characters between the transposed ones costs more
+        // than a distance can hold, so the word is only found with its two substitutions
+        let middle = "z".repeat(80);
+        let word = format!("b{}a", middle);
+        let query = format!("a{}b", middle);
+        let trie = create_trie(&[(&word, 1)]);
+        let found = search_approx(
+            &trie,
+            &query,
+            &SearchOptions {
+                dist_max: 3,
+                costs: EditCosts::new(1, 1000, 1, 1000)
+                    .unwrap()
+                    .with_unrestricted_transpositions(),
+                ..SearchOptions::default()
+            },
+            &mut LayerStack::with_capacity(0, 0),
+            &mut IterationStack::new(),
+            &mut WordPool::new(),
+            Vec::new(),
+        );
+        let found: Vec<_> = found.into_iter().map(|w| (w.word, w.dist)).collect();
+        assert_eq!(found, [(word, 2)]);
+    }
+
+    #[test]
+    fn test_search_approx_unrestricted_transpositions_random() {
+        // A xorshift generator, to compare with the whole matrix on many small tries
+        let mut state = 0x2545_F491_4F6C_DD1D_u64;
+        let mut below = |bound: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound) as usize
+        };
+        let letters = ['a', 'b', 'c'];
+        let mut random_word = || -> String {
+            let len = below(6) + 1;
+            (0..len).map(|_| letters[below(3)]).collect()
+        };
+        let all_costs = [
+            EditCosts::default(),
+            EditCosts::new(1, 2, 2, 3).unwrap(),
+            EditCosts::new(2, 1, 1, 2).unwrap(),
+        ];
+
+        for i in 0..500 {
+            let mut words: Vec<String> = (0..=i % 12).map(|_| random_word()).collect();
+            words.sort();
+            words.dedup();
+            let entries: Vec<_> = words.iter().map(|w| (w.as_str(), 1)).collect();
+            let trie = create_trie(&entries);
+            let query = random_word();
+
+            for costs in &all_costs {
+                let costs = costs.with_unrestricted_transpositions();
+                for dist_max in 0..5 {
+                    let mut expected: Vec<_> = words
+                        .iter()
+                        .map(|w| (w.clone(), unrestricted_distance(&query, w, &costs)))
+                        .filter(|(_, d)| *d <= dist_max)
+                        .collect();
+                    expected.sort_unstable_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+
+                    let mut found = search_approx(
+                        &trie,
+                        &query,
+                        &SearchOptions {
+                            dist_max,
+                            costs,
+                            ..SearchOptions::default()
+                        },
+                        &mut LayerStack::with_capacity(0, 0),
+                        &mut IterationStack::new(),
+                        &mut WordPool::new(),
+                        Vec::new(),
+                    );
+                    found.sort_unstable();
+                    let found: Vec<_> = found.into_iter().map(|w| (w.word, w.dist)).collect();
+                    assert_eq!(found, expected, "{:?} {} {}", words, query, dist_max);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_approx_long_queries_same_as_matrix() {
+        // Long queries at a distance of at least 4, whose band of at least 9 cells
+        // is computed by blocks of 8 cells with the `simd` feature
+        fn below(state: &mut u64, bound: u64) -> usize {
+            *state ^= *state << 13;
+            *state ^= *state >> 7;
+            *state ^= *state << 17;
+            (*state % bound) as usize
+        }
+        let letters = ['a', 'b', 'c', 'd'];
+        let random_word = |state: &mut u64| -> String {
+            let len = below(state, 20) + 10;
+            (0..len).map(|_| letters[below(state, 4)]).collect()
+        };
+        let mut state = 0x9E37_79B9_7F4A_7C15_u64;
+        let all_costs = [EditCosts::default(), EditCosts::new(1, 2, 2, 3).unwrap()];
+
+        for i in 0..60 {
+            let query = random_word(&mut state);
+            // Words close to the query, among unrelated ones
+            let mut words: Vec<String> = (0..8).map(|_| random_word(&mut state)).collect();
+            for _ in 0..8 {
+                let mut close: Vec<char> = query.chars().collect();
+                for _ in 0..below(&mut state, 6) {
+                    let at = below(&mut state, close.len() as u64);
+                    match below(&mut state, 3) {
+                        0 => close[at] = letters[below(&mut state, 4)],
+                        1 => close.insert(at, letters[below(&mut state, 4)]),
+                        _ if close.len() > 1 => drop(close.remove(at)),
+                        _ => {}
+                    }
+                }
+                words.push(close.into_iter().collect());
+            }
+            words.sort();
+            words.dedup();
+            let entries: Vec<_> = words.iter().map(|w| (w.as_str(), 1)).collect();
+            let trie = create_trie(&entries);
+
+            let costs = &all_costs[i % all_costs.len()];
+            for dist_max in 4..7 {
+                let mut expected: Vec<_> = words
+                    .iter()
+                    .map(|w| (w.clone(), weighted_distance(&query, w, costs)))
+                    .filter(|(_, d)| *d <= dist_max)
+                    .collect();
+                expected.sort_unstable_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+
+                let mut found = search_approx(
+                    &trie,
+                    &query,
+                    &SearchOptions {
+                        dist_max,
+                        costs: *costs,
+                        ..SearchOptions::default()
+                    },
+                    &mut LayerStack::with_capacity(0, 0),
+                    &mut IterationStack::new(),
+                    &mut WordPool::new(),
+                    Vec::new(),
+                );
+                found.sort_unstable();
+                let found: Vec<_> = found.into_iter().map(|w| (w.word, w.dist)).collect();
+                assert_eq!(found, expected, "{:?} {} {}", words, query, dist_max);
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_approx_long_patricia() {
+        // Long patricia nodes, split by a few branches and past the maximum depth
+        let words = [
+            "internationalization",
+            "internationalisation",
+            "internationalizations",
+            "internationally",
+            "interplanetary",
+            "日本語のテキストマイニング",
+            "日本語のテキスト",
+        ];
+        let entries: Vec<_> = words.iter().map(|w| (*w, 1)).collect();
+        let trie = create_trie(&entries);
+        let all_costs = [
+            EditCosts::default(),
+            EditCosts::new(1, 2, 2, 3).unwrap(),
+            EditCosts::default().with_unrestricted_transpositions(),
+        ];
+
+        for &query in &[
+            "internationalisation",
+            "intrenationalization",
+            "internationaly",
+            "interplanteary",
+            "日本語のテキストマイニグ",
+            "日本語テキスト",
+        ] {
+            for costs in &all_costs {
+                for &(dist_max, max_depth) in &[(0, None), (1, None), (2, None), (3, Some(15))] {
+                    // The distance of each word, computed on its own
+                    let within_depth = |w: &str| match max_depth {
+                        Some(max_depth) => w.chars().count() <= max_depth,
+                        None => true,
+                    };
+                    let mut expected: Vec<_> = words
+                        .iter()
+                        .filter(|w| within_depth(w))
+                        .map(|w| {
+                            let dist = if costs.unrestricted_transpositions {
+                                unrestricted_distance(query, w, costs)
+                            } else {
+                                weighted_distance(query, w, costs)
+                            };
+                            (w.to_string(), dist)
+                        })
+                        .filter(|&(_, dist)| dist <= dist_max)
+                        .collect();
+                    expected.sort_unstable_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+
+                    let mut found = search_approx(
+                        &trie,
+                        query,
+                        &SearchOptions {
+                            dist_max,
+                            costs: *costs,
+                            max_depth,
+                            ..SearchOptions::default()
+                        },
+                        &mut LayerStack::with_capacity(0, 0),
+                        &mut IterationStack::new(),
+                        &mut WordPool::new(),
+                        Vec::new(),
+                    );
+                    found.sort_unstable();
+                    let found: Vec<_> = found.into_iter().map(|w| (w.word, w.dist)).collect();
+                    assert_eq!(found, expected, "{} {} {:?}", query, dist_max, max_depth);
+                }
+            }
+        }
+    }
+
+    /// Run with `cargo test --release -- --ignored --nocapture bench_search_approx_long_patricia`.
+    #[test]
+    #[ignore]
+    fn bench_search_approx_long_patricia() {
+        // Random words of 30 to 60 letters, mostly made of long patricia nodes
+        let mut state = 0x2545_F491_4F6C_DD1D_u64;
+        let mut below = |bound: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound) as usize
+        };
+        let mut words: Vec<String> = (0..50_000)
+            .map(|_| {
+                let len = below(31) + 30;
+                (0..len).map(|_| (b'a' + below(26) as u8) as char).collect()
+            })
+            .collect();
+        words.sort();
+        words.dedup();
+        let entries: Vec<_> = words.iter().map(|w| (w.as_str(), 1)).collect();
+        let trie = create_trie(&entries);
+
+        // Queries with a substitution from the words
+        let queries: Vec<String> = (0..500)
+            .map(|_| {
+                let mut query: Vec<char> = words[below(words.len() as u64)].chars().collect();
+                let i = below(query.len() as u64);
+                query[i] = (b'a' + below(26) as u8) as char;
+                query.into_iter().collect()
+            })
+            .collect();
+
+        let mut layer_stack = LayerStack::with_capacity(0, 0);
+        let mut iter_stack = IterationStack::new();
+        let mut word_pool = WordPool::new();
+        for &dist_max in &[1, 2] {
+            let start = std::time::Instant::now();
+            let mut nb_found = 0;
+            for query in &queries {
+                layer_stack.clear();
+                iter_stack.clear();
+                let found = search_approx(
+                    &trie,
+                    query,
+                    &SearchOptions {
+                        dist_max,
+                        ..SearchOptions::default()
+                    },
+                    &mut layer_stack,
+                    &mut iter_stack,
+                    &mut word_pool,
+                    Vec::new(),
+                );
+                nb_found += found.len();
+            }
+            assert!(nb_found >= queries.len());
+            println!(
+                "distance {}: {:?} for {} queries",
+                dist_max,
+                start.elapsed(),
+                queries.len()
+            );
+        }
+    }
+
+    /// Run with `cargo test --release -- --ignored --nocapture bench_search_approx_word_pool`.
+    #[test]
+    #[ignore]
+    fn bench_search_approx_word_pool() {
+        // Random words of 3 to 8 letters, many of them found at a distance 2 of the queries
+        let mut state = 0x2545_F491_4F6C_DD1D_u64;
+        let mut below = |bound: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound) as usize
+        };
+        let mut words: Vec<String> = (0..50_000)
+            .map(|_| {
+                let len = below(6) + 3;
+                (0..len).map(|_| (b'a' + below(10) as u8) as char).collect()
+            })
+            .collect();
+        words.sort();
+        words.dedup();
+        let entries: Vec<_> = words.iter().map(|w| (w.as_str(), 1)).collect();
+        let trie = create_trie(&entries);
+        let queries: Vec<&str> = (0..500)
+            .map(|_| words[below(words.len() as u64)].as_str())
+            .collect();
+
+        let mut layer_stack = LayerStack::with_capacity(0, 0);
+        let mut iter_stack = IterationStack::new();
+        // Before: every found word is allocated, after: the words of the results are pooled
+        for &recycle in &[false, true] {
+            let mut word_pool = WordPool::new();
+            let mut found_words = Vec::new();
+            let allocations_before = nb_allocations();
+            let start = std::time::Instant::now();
+            let mut nb_found = 0;
+            for query in &queries {
+                layer_stack.clear();
+                iter_stack.clear();
+                if recycle {
+                    word_pool.recycle(&mut found_words);
+                } else {
+                    found_words = Vec::new();
+                }
+                found_words = search_approx(
+                    &trie,
+                    query,
+                    &SearchOptions {
+                        dist_max: 2,
+                        ..SearchOptions::default()
+                    },
+                    &mut layer_stack,
+                    &mut iter_stack,
+                    &mut word_pool,
+                    found_words,
+                );
+                nb_found += found_words.len();
+            }
+            let elapsed = start.elapsed();
+            let nb_allocations = nb_allocations() - allocations_before;
+            assert!(nb_found >= queries.len());
+            println!(
+                "pooled words {}: {:?} and {} allocations for {} queries with {} words found",
+                recycle,
+                elapsed,
+                nb_allocations,
+                queries.len(),
+                nb_found
+            );
+        }
+    }
+
+    #[test]
+    fn test_search_approx_dist_0() {
+        let trie = create_trie(&[
+            ("a", 1),
+            ("ab", 2),
+            ("abc", 3),
+            ("abd", 4),
+            ("b", 5),
+            ("test", 6),
+            ("tests", 7),
+            ("tset", 8),
+            ("Test", 9),
+            ("日本", 10),
+            ("日本語", 11),
+            ("patricianode", 12),
+        ]);
+
+        // At a distance 0, the approximate search only finds the word itself
+        for &word in &[
+            "a",
+            "ab",
+            "abc",
+            "abe",
+            "b",
+            "c",
+            "test",
+            "tes",
+            "testss",
+            "tset",
+            "TEST",
+            "Test",
+            "日本",
+            "日",
+            "日本語",
+            "patricia",
+            "patricianode",
+            "patricianodes",
+        ] {
+            let expected: Vec<_> = search_exact(&trie, word, None)
+                .map(|_| (word.to_string(), 0))
+                .into_iter()
+                .collect();
+            assert_eq!(run_search_approx(&trie, word, 0), expected, "{:?}", word);
+
+            // The same words are found by the traversal of the trie
+            let traversed: Vec<_> = search_approx_iter(&trie, word, &SearchOptions::default())
+                .map(|found| (found.word, found.dist))
+                .collect();
+            assert_eq!(traversed, expected, "{:?}", word);
+        }
+        assert!(run_search_approx(&trie, "", 0).is_empty());
+    }
+
+    #[test]
+    fn test_search_empty_trie() {
+        // An empty word list compiles to a trie without any node
+        let trie = create_trie(&[]);
+        assert_eq!(trie.get_root_siblings(), None);
+
+        for &dist_max in &[0, 1, 3] {
+            assert!(run_search_approx(&trie, "test", dist_max).is_empty());
+            assert!(run_search_approx_with(&trie, "test", dist_max, Some(5), true).is_empty());
+            assert!(run_search_closest_tier(&trie, "test", dist_max).is_empty());
+        }
+        assert!(run_search_approx(&trie, "", 1).is_empty());
+
+        let options = SearchOptions {
+            dist_max: 1,
+            ..SearchOptions::default()
+        };
+        let iter = search_approx_iter(&trie, "test", &options);
+        assert_eq!(iter.count(), 0);
+        for prefix in &["", "t"] {
+            let found_words = search_approx_prefix(
+                &trie,
+                prefix,
+                "test",
+                &options,
+                &mut LayerStack::with_capacity(0, 0),
+                &mut IterationStack::new(),
+                &mut WordPool::new(),
+                Vec::new(),
+            );
+            assert!(found_words.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_search_approx_prefix() {
+        let trie = create_trie(&[
+            ("a", 3),
+            ("b", 1),
+            ("c", 2),
+            ("car", 5),
+            ("card", 2),
+            ("care", 4),
+            ("cat", 5),
+            ("test", 10),
+            ("testing", 3),
+            ("tests", 1),
+            ("text", 2),
+            ("日本", 6),
+            ("日本語", 7),
+        ]);
+        let costs = EditCosts::default();
+        let run = |prefix: &str, word: &str, dist_max, pinned: PinnedChars| {
+            let mut found_words = search_approx_prefix(
+                &trie,
+                prefix,
+                word,
+                &SearchOptions {
+                    dist_max,
+                    costs,
+                    pinned,
+                    ..SearchOptions::default()
+                },
+                &mut LayerStack::with_capacity(0, 0),
+                &mut IterationStack::new(),
+                &mut WordPool::new(),
+                Vec::new(),
+            );
+            found_words.sort_unstable();
+            found_words
+                .into_iter()
+                .map(|w| (w.word, w.dist))
+                .collect::<Vec<_>>()
+        };
+
+        // The words of the approximate search beginning with the prefix,
+        // including prefixes ending inside a patricia node ("tes" in "test")
+        let pins = [
+            PinnedChars::default(),
+            PinnedChars {
+                first: true,
+                last: true,
+            },
+        ];
+        for &(prefix, word, dist_max) in &[
+            ("c", "car", 1),
+            ("ca", "cax", 1),
+            ("car", "car", 2),
+            ("t", "tesst", 2),
+            ("tes", "tesst", 2),
+            ("testi", "testnig", 2),
+            ("text", "test", 1),
+            ("日", "日本x", 1),
+            ("", "car", 1),
+            ("x", "car", 1),
+            ("cb", "car", 1),
+        ] {
+            for &pinned in &pins {
+                let mut found_words = search_approx(
+                    &trie,
+                    word,
+                    &SearchOptions {
+                        dist_max,
+                        costs,
+                        pinned,
+                        ..SearchOptions::default()
+                    },
+                    &mut LayerStack::with_capacity(0, 0),
+                    &mut IterationStack::new(),
+                    &mut WordPool::new(),
+                    Vec::new(),
+                );
+                found_words.retain(|w| w.word.starts_with(prefix));
+                found_words.sort_unstable();
+                let expected: Vec<_> = found_words.into_iter().map(|w| (w.word, w.dist)).collect();
+                assert_eq!(
+                    run(prefix, word, dist_max, pinned),
+                    expected,
+                    "{} {}",
+                    prefix,
+                    word
+                );
+            }
+        }
+
+        assert_eq!(
+            run("tes", "tesst", 1, PinnedChars::default()),
+            [("test".to_string(), 1), ("tests".to_string(), 1)]
+        );
+        assert!(run("x", "x", 1, PinnedChars::default()).is_empty());
+    }
+
+    #[test]
+    fn test_search_approx_iter_same_as_eager() {
+        let trie = create_trie(&[
+            ("a", 3),
+            ("car", 5),
+            ("card", 2),
+            ("care", 4),
+            ("cat", 5),
+            ("test", 10),
+            ("tests", 1),
+            ("text", 2),
+            ("日本", 6),
+        ]);
+        let to_tuples = |found_words: Vec<FoundWord>| -> Vec<_> {
+            found_words
+                .into_iter()
+                .map(|w| (w.word, w.freq.get(), w.dist))
+                .collect()
+        };
+
+        for &(word, dist_max) in &[("car", 1), ("car", 0), ("tesx", 2), ("日", 1), ("", 1)] {
+            for &ignore_case in &[false, true] {
+                let options = SearchOptions {
+                    dist_max,
+                    ignore_case,
+                    ..SearchOptions::default()
+                };
+                let eager = search_approx(
+                    &trie,
+                    word,
+                    &options,
+                    &mut LayerStack::with_capacity(0, 0),
+                    &mut IterationStack::new(),
+                    &mut WordPool::new(),
+                    Vec::new(),
+                );
+                let lazy: Vec<_> = search_approx_iter(&trie, word, &options).collect();
+
+                // Same words in the same order
+                assert_eq!(to_tuples(lazy), to_tuples(eager), "{}", word);
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_approx_iter_lazy() {
+        let trie = create_trie(&[("aa", 1), ("ab", 1), ("ba", 1), ("bb", 1)]);
+        let options = SearchOptions {
+            dist_max: 2,
+            ..SearchOptions::default()
+        };
+        let mut iter = search_approx_iter(&trie, "aa", &options);
+
+        // The first word is found without traversing the whole trie
+        assert!(iter.next().is_some());
+        assert!(!iter.iter_stack.is_empty());
+
+        assert_eq!(iter.by_ref().count(), 3);
+        assert!(iter.iter_stack.is_empty());
+        assert!(iter.next().is_none());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_search_approx_parallel_same_as_sequential() {
+        use std::num::NonZeroUsize;
+
+        fn assert_sync<T: Sync>() {}
+        assert_sync::<CompiledTrie>();
+
+        let trie = create_trie(&[
+            ("a", 3),
+            ("car", 5),
+            ("card", 2),
+            ("care", 4),
+            ("cat", 5),
+            ("cut", 1),
+            ("test", 10),
+            ("tests", 1),
+            ("text", 2),
+            ("zest", 7),
+            ("日本", 6),
+        ]);
+        let run_sorted = |found_words: Vec<FoundWord>| {
+            let mut found_words: Vec<_> = found_words
+                .into_iter()
+                .map(|w| (w.word, w.freq.get(), w.dist))
+                .collect();
+            found_words.sort_unstable();
+            found_words
+        };
+
+        for &(word, dist_max) in &[("car", 1), ("tesx", 2), ("日", 1), ("x", 0), ("", 1)] {
+            for &limit in &[None, Some(0), Some(1), Some(3)] {
+                let expected = run_sorted(search_approx(
+                    &trie,
+                    word,
+                    &SearchOptions {
+                        dist_max,
+                        limit,
+                        ..SearchOptions::default()
+                    },
+                    &mut LayerStack::with_capacity(0, 0),
+                    &mut IterationStack::new(),
+                    &mut WordPool::new(),
+                    Vec::new(),
+                ));
+
+                for &nb_threads in &[None, NonZeroUsize::new(1), NonZeroUsize::new(3)] {
+                    let found = run_sorted(search_approx_parallel(
+                        &trie,
+                        word,
+                        &SearchOptions {
+                            dist_max,
+                            limit,
+                            ..SearchOptions::default()
+                        },
+                        nb_threads,
+                    ));
+                    assert_eq!(found, expected, "{} {:?} {:?}", word, limit, nb_threads);
+                }
+            }
+        }
+
+        let empty = create_trie(&[]);
+        assert!(search_approx_parallel(
+            &empty,
+            "a",
+            &SearchOptions {
+                dist_max: 1,
+                ..SearchOptions::default()
+            },
+            None
+        )
+        .is_empty());
+    }
 }
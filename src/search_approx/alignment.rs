@@ -0,0 +1,575 @@
+//! Reconstruction of the edit operations between the query and the words found
+//! by the approximate search, e.g. to highlight the differences in a user interface.
+//!
+//! The search only keeps the distance layers of the current trie path, and finds some words
+//! without computing their layers at all (with the exact search of their end),
+//! so the operations are not computed during the search. Instead, the layers of each
+//! accepted word are computed again like the search does, with the same costs,
+//! and backtracked from the last cell to one of the cheapest alignments.
+
+use super::{
+    apply_unrestricted_transpositions, chars_match, compute_layer, fetch_compute_layers,
+    push_first_layer, Distance, EditCosts, FoundWord, WordCharCount,
+};
+use crate::layer_stack::LayerStack;
+use std::cmp::min;
+
+/// An edit operation from the query to a found word, with the positions (in characters)
+/// of the concerned characters in both of them.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum EditOp {
+    /// The character of the word at `word_pos` is missing from the query.
+    Insert { word_pos: usize },
+
+    /// The character of the query at `query_pos` is missing from the word.
+    Delete { query_pos: usize },
+
+    /// The character of the query at `query_pos` is replaced by the one of the word
+    /// at `word_pos`.
+    Substitute { query_pos: usize, word_pos: usize },
+
+    /// The characters of the query at `query_pos` and `query_pos + query_gap + 1` are swapped
+    /// in the word at `word_pos` and `word_pos + word_gap + 1`.
+    ///
+    /// The gaps are only used by the [unrestricted transpositions](EditCosts::with_unrestricted_transpositions):
+    /// the `query_gap` characters of the query between the swapped ones are deleted,
+    /// and the `word_gap` characters of the word between them are inserted.
+    Transpose {
+        query_pos: usize,
+        word_pos: usize,
+        query_gap: usize,
+        word_gap: usize,
+    },
+}
+
+/// Return the edit operations of one of the cheapest alignments from the query to the word,
+/// in the order of their positions, along with their total cost.
+///
+/// The distances are computed like in the search, so this is the distance of the word
+/// found by [search_approx](super::search_approx) with the same arguments, including
+/// its [unrestricted transpositions](EditCosts::with_unrestricted_transpositions).
+/// There are no transpositions with the costs of a search without them
+/// (see [edit_costs](super::SearchOptions::edit_costs)).
+pub fn edit_operations(
+    query: &str,
+    word: &str,
+    ignore_case: bool,
+    costs: &EditCosts,
+) -> (Vec<EditOp>, Distance) {
+    let query: Vec<char> = query.chars().collect();
+    let word_chars: Vec<char> = word.chars().collect();
+    let edits_cost = |nb: usize, cost: Distance| {
+        (min(nb, Distance::MAX as usize) as Distance).saturating_mul(cost)
+    };
+
+    // The characters of the word are all inserted in an empty query
+    if query.is_empty() {
+        let edits = (0..word_chars.len())
+            .map(|word_pos| EditOp::Insert { word_pos })
+            .collect();
+        return (edits, edits_cost(word_chars.len(), costs.insert));
+    }
+
+    // The layer of each character of the word, without the band of a maximum distance
+    let width = query.len() + 1;
+    let nb_layers = word_chars.len() + 1;
+    let mut layer_stack = LayerStack::with_capacity(width * nb_layers, nb_layers);
+    push_first_layer(&mut layer_stack, None, query.len() as WordCharCount, costs);
+    let mut last_char = None;
+    for (depth, &c) in (1..).zip(&word_chars) {
+        layer_stack.push_layer(Some(c), width as WordCharCount);
+        let (cur_layer, last_layer, parent_layer) =
+            fetch_compute_layers(&mut layer_stack, costs.transpositions);
+        compute_layer(
+            cur_layer,
+            last_layer,
+            parent_layer,
+            &query,
+            depth,
+            Distance::MAX,
+            last_char,
+            c,
+            ignore_case,
+            costs,
+        );
+        if costs.unrestricted_transpositions {
+            apply_unrestricted_transpositions(
+                &mut layer_stack,
+                &query,
+                Distance::MAX,
+                ignore_case,
+                costs,
+            );
+        }
+        last_char = Some(c);
+    }
+
+    // dist(j, i) is the distance between the first i characters of the query
+    // and the first j characters of the word, in the layer of the j-th character
+    let (_, previous_layers, last_layer) = layer_stack.split_last_layer();
+    let dist = |j: usize, i: usize| match previous_layers.get(j * width + i) {
+        Some(&d) => d,
+        None => last_layer[i],
+    };
+    let matches = |i: usize, j: usize| chars_match(query[i - 1], word_chars[j - 1], ignore_case);
+    let replace_cost = |i: usize, j: usize| {
+        if matches(i, j) {
+            0
+        } else {
+            costs.substitute_cost(query[i - 1], word_chars[j - 1])
+        }
+    };
+    // Backtrack from the last cell, following the operations giving each distance
+    // (a match or substitution first, so that the alignment is as diagonal as possible)
+    let mut edits = Vec::new();
+    let (mut i, mut j) = (query.len(), word_chars.len());
+    while i > 0 || j > 0 {
+        let cur = dist(j, i);
+        if i > 0 && j > 0 && dist(j - 1, i - 1).saturating_add(replace_cost(i, j)) == cur {
+            if replace_cost(i, j) > 0 {
+                edits.push(EditOp::Substitute {
+                    query_pos: i - 1,
+                    word_pos: j - 1,
+                });
+            }
+            i -= 1;
+            j -= 1;
+            continue;
+        }
+
+        // The adjacent transposition of the optimal string alignment distance
+        if costs.transpositions
+            && i >= 2
+            && j >= 2
+            && matches(i - 1, j)
+            && matches(i, j - 1)
+            && dist(j - 2, i - 2).saturating_add(costs.transpose) == cur
+        {
+            edits.push(EditOp::Transpose {
+                query_pos: i - 2,
+                word_pos: j - 2,
+                query_gap: 0,
+                word_gap: 0,
+            });
+            i -= 2;
+            j -= 2;
+            continue;
+        }
+
+        // The transposition of the last query character `k` matching the word one,
+        // and the last word character `l` matching the query one, like
+        // apply_unrestricted_transpositions
+        if costs.unrestricted_transpositions && i > 0 && j > 0 {
+            let k = (1..i).rev().find(|&k| matches(k, j));
+            let l = (1..j).rev().find(|&l| matches(i, l));
+            if let (Some(k), Some(l)) = (k, l) {
+                let transpose_cost = dist(l - 1, k - 1)
+                    .saturating_add(edits_cost(i - k - 1, costs.delete))
+                    .saturating_add(costs.transpose)
+                    .saturating_add(edits_cost(j - l - 1, costs.insert));
+                if transpose_cost == cur {
+                    edits.push(EditOp::Transpose {
+                        query_pos: k - 1,
+                        word_pos: l - 1,
+                        query_gap: i - k - 1,
+                        word_gap: j - l - 1,
+                    });
+                    i = k - 1;
+                    j = l - 1;
+                    continue;
+                }
+            }
+        }
+
+        if i > 0 && dist(j, i - 1).saturating_add(costs.delete) == cur {
+            edits.push(EditOp::Delete { query_pos: i - 1 });
+            i -= 1;
+        } else {
+            debug_assert!(j > 0 && dist(j - 1, i).saturating_add(costs.insert) == cur);
+            edits.push(EditOp::Insert { word_pos: j - 1 });
+            j -= 1;
+        }
+    }
+
+    edits.reverse();
+    (edits, last_layer[query.len()])
+}
+
+/// Return the spans of the characters of the word which match the query in the alignment
+/// of the edit operations, as `(start, end)` character positions with an exclusive end,
+/// in order and without empty spans. `word_len` is the number of characters of the word.
+///
+/// The inserted, substituted and transposed characters are not part of any span,
+/// e.g. to highlight the matching parts of a suggestion.
+pub fn match_spans(edits: &[EditOp], word_len: usize) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = 0;
+    for edit in edits {
+        let (edit_pos, edit_len) = match *edit {
+            EditOp::Insert { word_pos } | EditOp::Substitute { word_pos, .. } => (word_pos, 1),
+            EditOp::Transpose {
+                word_pos, word_gap, ..
+            } => (word_pos, word_gap + 2),
+            EditOp::Delete { .. } => continue,
+        };
+        if edit_pos > start {
+            spans.push((start, edit_pos));
+        }
+        start = edit_pos + edit_len;
+    }
+    if word_len > start {
+        spans.push((start, word_len));
+    }
+    spans
+}
+
+/// Set the [edits](FoundWord::edits) of the words found by an approximate search
+/// of the query, which must have been done with the same `ignore_case` and costs.
+///
+/// This is opt-in since it computes the distance layers of each word again: it is better
+/// done on the results which are going to be shown, e.g. after limiting them.
+pub fn align_found_words(
+    found_words: &mut [FoundWord],
+    query: &str,
+    ignore_case: bool,
+    costs: &EditCosts,
+) {
+    for found in found_words {
+        let (edits, dist) = edit_operations(query, &found.word, ignore_case, costs);
+        debug_assert_eq!(dist, found.dist);
+        found.edits = Some(edits);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        layer_stack::LayerStack,
+        search_approx::{search_approx, IterationStack, SearchOptions, WordPool},
+        test_utils::create_trie,
+    };
+
+    /// Apply the edit operations to the query, taking the inserted and substituted
+    /// characters from the word.
+    fn apply_edits(query: &str, word: &str, edits: &[EditOp]) -> String {
+        let query: Vec<char> = query.chars().collect();
+        let word: Vec<char> = word.chars().collect();
+        let mut result = String::new();
+        let mut query_pos = 0;
+        for &edit in edits {
+            let edit_query_pos = match edit {
+                // The characters of the result before an edit are the ones of the word
+                EditOp::Insert { word_pos } => query_pos + word_pos - result.chars().count(),
+                EditOp::Delete { query_pos }
+                | EditOp::Substitute { query_pos, .. }
+                | EditOp::Transpose { query_pos, .. } => query_pos,
+            };
+            result.extend(&query[query_pos..edit_query_pos]);
+            query_pos = edit_query_pos;
+            match edit {
+                EditOp::Insert { word_pos } => result.push(word[word_pos]),
+                EditOp::Delete { .. } => query_pos += 1,
+                EditOp::Substitute { word_pos, .. } => {
+                    result.push(word[word_pos]);
+                    query_pos += 1;
+                }
+                EditOp::Transpose {
+                    word_pos,
+                    query_gap,
+                    word_gap,
+                    ..
+                } => {
+                    result.push(query[query_pos + query_gap + 1]);
+                    result.extend(&word[word_pos + 1..word_pos + word_gap + 1]);
+                    result.push(query[query_pos]);
+                    query_pos += query_gap + 2;
+                }
+            }
+        }
+        result.extend(&query[query_pos..]);
+        result
+    }
+
+    /// Return the total cost of the edit operations from the query to the word.
+    fn cost_of_edits(query: &str, word: &str, edits: &[EditOp], costs: &EditCosts) -> Distance {
+        let query: Vec<char> = query.chars().collect();
+        let word: Vec<char> = word.chars().collect();
+        edits
+            .iter()
+            .map(|&edit| match edit {
+                EditOp::Insert { .. } => costs.insert,
+                EditOp::Delete { .. } => costs.delete,
+                EditOp::Substitute {
+                    query_pos,
+                    word_pos,
+                } => costs.substitute_cost(query[query_pos], word[word_pos]),
+                EditOp::Transpose {
+                    query_gap,
+                    word_gap,
+                    ..
+                } => {
+                    costs.transpose
+                        + query_gap as Distance * costs.delete
+                        + word_gap as Distance * costs.insert
+                }
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_edit_operations() {
+        let costs = EditCosts::default();
+        let ops = |query, word| edit_operations(query, word, false, &costs);
+
+        assert_eq!(ops("test", "test"), (vec![], 0));
+        assert_eq!(
+            ops("test", "tests"),
+            (vec![EditOp::Insert { word_pos: 4 }], 1)
+        );
+        assert_eq!(
+            ops("tesst", "test"),
+            (vec![EditOp::Delete { query_pos: 2 }], 1)
+        );
+        assert_eq!(
+            ops("cat", "cut"),
+            (
+                vec![EditOp::Substitute {
+                    query_pos: 1,
+                    word_pos: 1
+                }],
+                1
+            )
+        );
+        assert_eq!(
+            ops("test", "tset"),
+            (
+                vec![EditOp::Transpose {
+                    query_pos: 1,
+                    word_pos: 1,
+                    query_gap: 0,
+                    word_gap: 0,
+                }],
+                1
+            )
+        );
+        assert_eq!(ops("", "ab").1, 2);
+        assert_eq!(ops("日本", "").0.len(), 2);
+
+        // The case is ignored like in the search
+        assert_eq!(edit_operations("Test", "test", true, &costs), (vec![], 0));
+
+        // The costs of the operations are the ones given
+        let costs = EditCosts::new(1, 3, 5, 5).unwrap();
+        assert_eq!(
+            edit_operations("cat", "cut", false, &costs),
+            (
+                vec![
+                    EditOp::Insert { word_pos: 1 },
+                    EditOp::Delete { query_pos: 1 }
+                ],
+                4
+            )
+        );
+    }
+
+    #[test]
+    fn test_edit_operations_unrestricted_transpositions() {
+        let costs = EditCosts::default();
+        let unrestricted = costs.with_unrestricted_transpositions();
+
+        // A character is inserted between the transposed ones
+        assert_eq!(edit_operations("ca", "abc", false, &costs).1, 3);
+        let (edits, dist) = edit_operations("ca", "abc", false, &unrestricted);
+        assert_eq!(dist, 2);
+        assert_eq!(
+            edits,
+            vec![EditOp::Transpose {
+                query_pos: 0,
+                word_pos: 0,
+                query_gap: 0,
+                word_gap: 1,
+            }]
+        );
+        assert_eq!(apply_edits("ca", "abc", &edits), "abc");
+        assert_eq!(match_spans(&edits, 3), vec![]);
+
+        // A character of the query is deleted between them, after a matching prefix
+        let (edits, dist) = edit_operations("xaeb", "xba", false, &unrestricted);
+        assert_eq!(dist, 2);
+        assert_eq!(
+            edits,
+            vec![EditOp::Transpose {
+                query_pos: 1,
+                word_pos: 1,
+                query_gap: 1,
+                word_gap: 0,
+            }]
+        );
+        assert_eq!(apply_edits("xaeb", "xba", &edits), "xba");
+        assert_eq!(match_spans(&edits, 3), vec![(0, 1)]);
+    }
+
+    #[test]
+    fn test_match_spans() {
+        let costs = EditCosts::default();
+        let spans = |query, word: &str| {
+            let (edits, _) = edit_operations(query, word, false, &costs);
+            match_spans(&edits, word.chars().count())
+        };
+
+        assert_eq!(spans("apple", "apple"), vec![(0, 5)]);
+        assert_eq!(spans("aple", "apple"), vec![(0, 1), (2, 5)]);
+        assert_eq!(spans("appel", "apple"), vec![(0, 3)]);
+        assert_eq!(spans("xpple", "apple"), vec![(1, 5)]);
+        assert_eq!(spans("applex", "apple"), vec![(0, 5)]);
+        assert_eq!(spans("", "ab"), vec![]);
+        assert_eq!(spans("ab", ""), vec![]);
+        assert_eq!(spans("日本語", "日本人"), vec![(0, 2)]);
+
+        // With a single edit, the spans are the common prefix and suffix of the query and word
+        for &(query, word) in &[
+            ("tset", "test"),
+            ("cat", "cut"),
+            ("card", "cad"),
+            ("ca", "cat"),
+        ] {
+            let query_chars: Vec<char> = query.chars().collect();
+            let word_chars: Vec<char> = word.chars().collect();
+            let prefix = query_chars
+                .iter()
+                .zip(&word_chars)
+                .take_while(|(a, b)| a == b)
+                .count();
+            let suffix = query_chars
+                .iter()
+                .rev()
+                .zip(word_chars.iter().rev())
+                .take_while(|(a, b)| a == b)
+                .count();
+            let (edits, dist) = edit_operations(query, word, false, &costs);
+            assert_eq!(dist, 1);
+
+            // The spans of a deletion are contiguous in the word
+            let mut expected = if prefix + suffix >= word_chars.len() {
+                vec![(0, word_chars.len())]
+            } else {
+                vec![(0, prefix), (word_chars.len() - suffix, word_chars.len())]
+            };
+            expected.retain(|&(start, end)| start < end);
+            assert_eq!(match_spans(&edits, word_chars.len()), expected, "{}", word);
+        }
+    }
+
+    #[test]
+    fn test_align_found_words() {
+        let trie = create_trie(&[
+            ("car", 5),
+            ("card", 2),
+            ("care", 4),
+            ("cat", 5),
+            ("tac", 1),
+            ("test", 10),
+            ("tset", 3),
+            ("日本語", 6),
+        ]);
+        let costs = EditCosts::default();
+
+        for &(query, dist_max) in &[("car", 2), ("tset", 2), ("日本", 1), ("act", 2)] {
+            let mut found_words = search_approx(
+                &trie,
+                query,
+                &SearchOptions {
+                    dist_max,
+                    ..SearchOptions::default()
+                },
+                &mut LayerStack::with_capacity(0, 0),
+                &mut IterationStack::new(),
+                &mut WordPool::new(),
+                Vec::new(),
+            );
+            assert!(found_words.iter().all(|w| w.edits.is_none()));
+
+            align_found_words(&mut found_words, query, false, &costs);
+            for found in &found_words {
+                // One edit per unit of distance, which give the word back from the query
+                let edits = found.edits.as_ref().unwrap();
+                assert_eq!(edits.len(), usize::from(found.dist), "{}", found.word);
+                assert_eq!(apply_edits(query, &found.word, edits), found.word);
+            }
+        }
+    }
+
+    #[test]
+    fn test_align_found_words_random() {
+        // A xorshift generator, to align the words found on many small tries
+        let mut state = 0x2545_F491_4F6C_DD1D_u64;
+        let mut below = |bound: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound) as usize
+        };
+        let letters = ['a', 'b', 'c', 'A'];
+        let mut random_word =
+            |len: usize| -> String { (0..len).map(|_| letters[below(4)]).collect() };
+        let all_costs = [
+            EditCosts::default(),
+            EditCosts::new(1, 2, 2, 3).unwrap(),
+            EditCosts::new(2, 1, 1, 2).unwrap(),
+        ];
+
+        let mut nb_gaps = 0;
+        for i in 0..300 {
+            let mut words: Vec<String> = (0..=i % 12).map(|n| random_word(n % 7 + 1)).collect();
+            words.sort();
+            words.dedup();
+            let entries: Vec<_> = words.iter().map(|w| (w.as_str(), 1)).collect();
+            let trie = create_trie(&entries);
+            let query = random_word(i % 6 + 1);
+
+            for costs in &all_costs {
+                for &costs in &[*costs, costs.with_unrestricted_transpositions()] {
+                    let ignore_case = i % 2 == 0;
+                    let mut found_words = search_approx(
+                        &trie,
+                        &query,
+                        &SearchOptions {
+                            dist_max: 4,
+                            ignore_case,
+                            costs,
+                            ..SearchOptions::default()
+                        },
+                        &mut LayerStack::with_capacity(0, 0),
+                        &mut IterationStack::new(),
+                        &mut WordPool::new(),
+                        Vec::new(),
+                    );
+
+                    // The alignments cost the distance of the search, and give the words back
+                    align_found_words(&mut found_words, &query, ignore_case, &costs);
+                    for found in &found_words {
+                        let edits = found.edits.as_ref().unwrap();
+                        let cost = cost_of_edits(&query, &found.word, edits, &costs);
+                        assert_eq!(cost, found.dist, "{} {}", query, found.word);
+                        if !ignore_case {
+                            assert_eq!(apply_edits(&query, &found.word, edits), found.word);
+                        }
+                        nb_gaps += edits
+                            .iter()
+                            .filter(|edit| match edit {
+                                EditOp::Transpose {
+                                    query_gap,
+                                    word_gap,
+                                    ..
+                                } => query_gap + word_gap > 0,
+                                _ => false,
+                            })
+                            .count();
+                    }
+                }
+            }
+        }
+        assert!(nb_gaps > 0);
+    }
+}
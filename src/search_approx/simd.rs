@@ -0,0 +1,195 @@
+//! Vectorized computation of the distance layers, enabled by the `simd` feature on x86_64.
+//!
+//! The insert and replace costs of a cell only depend on the last layer, so they are
+//! computed for 8 cells at once with SSE2, which is always available on x86_64.
+//! The delete cost depends on the previous cell of the same layer, and the transposition
+//! rarely applies, so both are then applied to the cells in a scalar pass.
+//!
+//! Only the layers computing at least 8 cells have a vectorized block: the band of
+//! `2 * dist_max + 1` cells around the diagonal needs a distance of at least 4
+//! (and a query long enough), the smaller ones being computed like without the feature.
+
+use super::{Distance, EditCosts};
+use std::{arch::x86_64::*, cmp::min, ops::Range};
+
+/// The number of cells computed at once.
+const LANES: usize = 8;
+
+/// Load the 8 distances beginning at the start of the slice.
+///
+/// SAFETY: The slice must contain at least 8 distances.
+#[inline(always)]
+unsafe fn load(distances: &[Distance]) -> __m128i {
+    debug_assert!(distances.len() >= LANES);
+    _mm_loadu_si128(distances.as_ptr() as *const __m128i)
+}
+
+/// Return the minimum of each pair of unsigned distances (SSE2 only has the signed one).
+#[inline(always)]
+unsafe fn min_epu16(a: __m128i, b: __m128i) -> __m128i {
+    _mm_sub_epi16(a, _mm_subs_epu16(a, b))
+}
+
+/// Compute the cells of the layer in the given range, which does not contain the first cell,
+/// with the same result as [compute_cells](super::compute_cells).
+/// The cell before the range must already be computed.
+///
+/// The characters are compared exactly, so the case cannot be ignored,
+/// and every substitution has the same cost (without substitution table).
+#[allow(clippy::too_many_arguments)]
+pub(super) fn compute_cells(
+    layer: &mut [Distance],
+    last_layer: &[Distance],
+    parent_layer: &[Distance],
+    word: &[char],
+    cells: Range<usize>,
+    last_char: Option<char>,
+    cur_trie_char: char,
+    costs: &EditCosts,
+) {
+    debug_assert!(cells.start >= 1 && cells.end <= layer.len());
+    debug_assert!(costs.substitution_table.is_none());
+
+    // Compute the minimum of the insert and replace costs of the cells, by blocks
+    let mut i = cells.start;
+    // SAFETY: SSE2 is always available on x86_64. The cells i..i + 8 of a block are in the
+    // layers, and so are its characters i - 1..i + 7 in the word (one less than the layers)
+    unsafe {
+        let insert = _mm_set1_epi16(costs.insert as i16);
+        let substitute = _mm_set1_epi16(costs.substitute as i16);
+        let trie_char = _mm_set1_epi32(cur_trie_char as i32);
+        while i + LANES <= cells.end {
+            let inserted = _mm_adds_epu16(load(&last_layer[i..]), insert);
+
+            // Each character is a u32, so they are compared in two halves then packed
+            // as 0xFFFF (equal) or 0 (different) for each cell
+            let chars_lo = _mm_loadu_si128(word[i - 1..].as_ptr() as *const __m128i);
+            let chars_hi = _mm_loadu_si128(word[i + 3..].as_ptr() as *const __m128i);
+            let equal = _mm_packs_epi32(
+                _mm_cmpeq_epi32(chars_lo, trie_char),
+                _mm_cmpeq_epi32(chars_hi, trie_char),
+            );
+            let replace = _mm_andnot_si128(equal, substitute);
+            let replaced = _mm_adds_epu16(load(&last_layer[i - 1..]), replace);
+
+            let cur_cells = layer[i..].as_mut_ptr() as *mut __m128i;
+            _mm_storeu_si128(cur_cells, min_epu16(inserted, replaced));
+            i += LANES;
+        }
+    }
+
+    // Same computation for the remaining cells
+    for i in i..cells.end {
+        let replace = if word[i - 1] == cur_trie_char {
+            0
+        } else {
+            costs.substitute
+        };
+        let insert_cost = last_layer[i].saturating_add(costs.insert);
+        let replace_cost = last_layer[i - 1].saturating_add(replace);
+        layer[i] = min(insert_cost, replace_cost);
+    }
+
+    // Apply the transpositions then the deletions, from the first cell to the last
+    for i in cells {
+        if costs.transpositions
+            && i >= 2
+            && word[i - 2] == cur_trie_char
+            && last_char == Some(word[i - 1])
+        {
+            if let Some(&trans) = parent_layer.get(i - 2) {
+                layer[i] = min(layer[i], trans.saturating_add(costs.transpose));
+            }
+        }
+
+        let delete_cost = layer[i - 1].saturating_add(costs.delete);
+        layer[i] = min(layer[i], delete_cost);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A [xorshift](https://en.wikipedia.org/wiki/Xorshift) generator, good enough for the tests.
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn below(&mut self, bound: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % bound as u64) as usize
+        }
+
+        /// Return a random word of at most `max_len` characters among a few letters,
+        /// so that the matches and transpositions are frequent.
+        fn word(&mut self, max_len: usize) -> Vec<char> {
+            let letters = ['a', 'b', 'c', 'é', '日'];
+            let len = self.below(max_len) + 1;
+            (0..len)
+                .map(|_| letters[self.below(letters.len())])
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_compute_cells_same_as_scalar() {
+        let mut rng = XorShift(0x9E37_79B9_7F4A_7C15);
+        let costs = [
+            EditCosts::default(),
+            EditCosts::new(2, 3, 1, 3).unwrap(),
+            EditCosts::new(1, 1, 2, 2).unwrap(),
+        ];
+
+        for _ in 0..500 {
+            let word = rng.word(40);
+            let trie_word = rng.word(40);
+            let costs = &costs[rng.below(costs.len())];
+            let layer_len = word.len() + 1;
+
+            // Compute each layer with both, from the same previous layers
+            let mut parent_layer = vec![];
+            let mut last_layer: Vec<_> = (0..layer_len as Distance)
+                .map(|i| i.saturating_mul(costs.delete))
+                .collect();
+            let mut last_char = None;
+            for &ch in &trie_word {
+                // Random cells, like the band of the distance, and the same cells before them
+                let start = rng.below(layer_len - 1) + 1;
+                let cells = start..start + rng.below(layer_len - start) + 1;
+                let first_cell = last_layer[0].saturating_add(costs.insert);
+
+                let mut expected = vec![first_cell; layer_len];
+                super::super::compute_cells(
+                    &mut expected,
+                    &last_layer,
+                    &parent_layer,
+                    &word,
+                    cells.clone(),
+                    last_char,
+                    ch,
+                    false,
+                    costs,
+                );
+
+                let mut layer = vec![first_cell; layer_len];
+                compute_cells(
+                    &mut layer,
+                    &last_layer,
+                    &parent_layer,
+                    &word,
+                    cells,
+                    last_char,
+                    ch,
+                    costs,
+                );
+                assert_eq!(layer, expected, "{:?} {:?}", word, trie_word);
+
+                parent_layer = last_layer;
+                last_layer = layer;
+                last_char = Some(ch);
+            }
+        }
+    }
+}
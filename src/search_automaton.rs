@@ -0,0 +1,416 @@
+//! Approximate search with a precompiled automaton of the query.
+//!
+//! The [Damerau-Levenshtein](https://en.wikipedia.org/wiki/Damerau%E2%80%93Levenshtein_distance)
+//! distance layers only depend on the query and the characters read so far,
+//! so they can be computed once as the states of a deterministic automaton.
+//! Searching the same query multiple times (e.g. in multiple dictionaries)
+//! then only needs to follow the automaton transitions.
+
+use crate::search_approx::{Distance, FoundWord};
+use std::{cmp::min, collections::HashMap};
+use vague_search_core::CompiledTrie;
+
+/// The index of a state in the automaton.
+type StateId = u32;
+
+/// The transition to a state from which no word can be at the maximum distance or less.
+const DEAD_STATE: StateId = StateId::MAX;
+
+/// The index of the first state of the automaton.
+const START_STATE: StateId = 0;
+
+/// A deterministic automaton recognizing the words at a given distance (or less) of a query.
+///
+/// A state holds the distance layer of the characters read so far, along with the costs
+/// of a transposition with the next character, all capped at `max_dist + 1`.
+///
+/// The automaton only knows its query and maximum distance, and ignores every other
+/// [SearchOptions](crate::search_approx::SearchOptions): the characters are compared
+/// case-sensitively, every edit costs 1 and the transpositions are always allowed,
+/// no character of the query is pinned, the depth is not limited, and the recognized
+/// words are all returned without being ranked.
+#[derive(Debug, Clone)]
+pub struct QueryAutomaton {
+    /// The maximum distance of the words to recognize.
+    max_dist: Distance,
+
+    /// The sorted and deduplicated characters of the query.
+    /// Any other character is represented by the symbol `alphabet.len()`.
+    alphabet: Vec<char>,
+
+    /// The transitions of each state, for each symbol of the alphabet.
+    transitions: Vec<StateId>,
+
+    /// The distance to the query of the words ending at each state, if at most `max_dist`.
+    final_dists: Vec<Option<Distance>>,
+}
+
+/// Compute the state reached from the given state by reading a character,
+/// or any character not in the query if None.
+/// A state is the concatenation of the distance layer and its transposition costs.
+fn next_state(
+    state: &[Distance],
+    query: &[char],
+    c: Option<char>,
+    capped: Distance,
+) -> Vec<Distance> {
+    let len = query.len() + 1;
+    let (layer, trans) = state.split_at(len);
+
+    let mut next = vec![capped; 2 * len];
+    let (next_layer, next_trans) = next.split_at_mut(len);
+
+    next_layer[0] = min(layer[0].saturating_add(1), capped);
+    for i in 1..len {
+        let same_char = Some(query[i - 1]) == c;
+
+        // Compute the costs for insert/delete/replace/transposition
+        let insert_cost = next_layer[i - 1].saturating_add(1);
+        let delete_cost = layer[i].saturating_add(1);
+        let replace_cost = layer[i - 1].saturating_add(!same_char as Distance);
+        let trans_cost = if i >= 2 && Some(query[i - 2]) == c {
+            trans[i]
+        } else {
+            capped
+        };
+        next_layer[i] = min(min(min(insert_cost, delete_cost), replace_cost), trans_cost);
+        next_layer[i] = min(next_layer[i], capped);
+
+        // A transposition is possible with the next character if it is the previous query one
+        if i >= 2 && same_char {
+            next_trans[i] = min(layer[i - 2].saturating_add(1), capped);
+        }
+    }
+
+    next
+}
+
+impl QueryAutomaton {
+    /// Create the automaton recognizing the words at a distance of at most `max_dist` of the word.
+    pub fn new(word: &str, max_dist: Distance) -> Self {
+        let query: Vec<char> = word.chars().collect();
+        let mut alphabet = query.clone();
+        alphabet.sort_unstable();
+        alphabet.dedup();
+
+        let nb_symbols = alphabet.len() + 1;
+        let len = query.len() + 1;
+        let capped = max_dist.saturating_add(1);
+
+        // The first state is the first distance layer, without any possible transposition
+        let mut start = vec![capped; 2 * len];
+        for (i, e) in start[..len].iter_mut().enumerate() {
+            *e = min(i, capped as usize) as Distance;
+        }
+
+        let mut states = vec![start.clone()];
+        let mut state_ids = HashMap::new();
+        state_ids.insert(start, START_STATE);
+
+        // Create the states reachable from the first one, in a breadth-first order
+        let mut transitions = Vec::new();
+        let mut final_dists = Vec::new();
+        let mut cur = 0;
+        while cur < states.len() {
+            let state = states[cur].clone();
+            let dist = state[len - 1];
+            final_dists.push(if dist <= max_dist { Some(dist) } else { None });
+
+            for symbol in 0..nb_symbols {
+                let c = alphabet.get(symbol).copied();
+                let next = next_state(&state, &query, c, capped);
+
+                // No word can be found from a state whose layer is over the maximum distance
+                let next_id = if next[..len].iter().all(|&d| d > max_dist) {
+                    DEAD_STATE
+                } else if let Some(&id) = state_ids.get(&next) {
+                    id
+                } else {
+                    let id = states.len() as StateId;
+                    states.push(next.clone());
+                    state_ids.insert(next, id);
+                    id
+                };
+                transitions.push(next_id);
+            }
+            cur += 1;
+        }
+
+        Self {
+            max_dist,
+            alphabet,
+            transitions,
+            final_dists,
+        }
+    }
+
+    /// Return the number of states of the automaton.
+    pub fn nb_states(&self) -> usize {
+        self.final_dists.len()
+    }
+
+    /// Return the state reached from the given state by reading the character.
+    fn step(&self, state: StateId, c: char) -> StateId {
+        let symbol = self
+            .alphabet
+            .binary_search(&c)
+            .unwrap_or(self.alphabet.len());
+        self.transitions[state as usize * (self.alphabet.len() + 1) + symbol]
+    }
+
+    /// Return the state reached from the given state by reading all characters of the string.
+    fn step_str(&self, mut state: StateId, s: &str) -> StateId {
+        for c in s.chars() {
+            state = self.step(state, c);
+            if state == DEAD_STATE {
+                break;
+            }
+        }
+        state
+    }
+}
+
+/// Search the trie by following the transitions of a [QueryAutomaton](QueryAutomaton).
+pub trait AutomatonSearch {
+    /// Search for all words in the trie recognized by the automaton,
+    /// i.e. at its maximum distance (or less) of its query.
+    ///
+    /// Return a vector of all found words with their respective frequency, in an unspecified order.
+    fn search_automaton(&self, aut: &QueryAutomaton) -> Vec<FoundWord>;
+}
+
+impl AutomatonSearch for CompiledTrie<'_> {
+    fn search_automaton(&self, aut: &QueryAutomaton) -> Vec<FoundWord> {
+        let mut result = Vec::new();
+
+        // Like the approximate search, an empty query does not match anything
+        if aut.alphabet.is_empty() {
+            return result;
+        }
+
+        // Follow the automaton transitions for each node, skipping the children
+        // of the nodes from which no word can be recognized
+        self.walk(START_STATE, |step, &state| {
+            let state = aut.step_str(state, step.chars());
+            if state == DEAD_STATE {
+                return None;
+            }
+
+            if let (Some(freq), Some(dist)) = (step.word_freq, aut.final_dists[state as usize]) {
+                result.push(FoundWord {
+                    word: step.word.to_string(),
+                    freq,
+                    dist,
+                    edits: None,
+                });
+            }
+            Some(state)
+        });
+
+        debug_assert!(result.iter().all(|w| w.dist <= aut.max_dist));
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        layer_stack::LayerStack,
+        search_approx::{search_approx, IterationStack, SearchOptions, WordPool},
+        test_utils::create_trie,
+    };
+
+    fn run_search_automaton(trie: &CompiledTrie, aut: &QueryAutomaton) -> Vec<(String, Distance)> {
+        let mut found_words = trie.search_automaton(aut);
+        found_words.sort_unstable();
+        found_words.into_iter().map(|w| (w.word, w.dist)).collect()
+    }
+
+    fn run_search_approx(
+        trie: &CompiledTrie,
+        word: &str,
+        dist_max: Distance,
+    ) -> Vec<(String, Distance)> {
+        let mut found_words = search_approx(
+            trie,
+            word,
+            &SearchOptions {
+                dist_max,
+                ..SearchOptions::default()
+            },
+            &mut LayerStack::with_capacity(0, 0),
+            &mut IterationStack::new(),
+            &mut WordPool::new(),
+            Vec::new(),
+        );
+        found_words.sort_unstable();
+        found_words.into_iter().map(|w| (w.word, w.dist)).collect()
+    }
+
+    fn create_dictionary() -> CompiledTrie<'static> {
+        create_trie(&[
+            ("a", 1),
+            ("abcd", 2),
+            ("abdc", 3),
+            ("acbd", 4),
+            ("b", 5),
+            ("bacd", 6),
+            ("c", 7),
+            ("cat", 8),
+            ("cats", 9),
+            ("test", 10),
+            ("tset", 11),
+            ("日本", 12),
+            ("日本語", 13),
+        ])
+    }
+
+    #[test]
+    fn test_search_automaton_basic() {
+        let trie = create_dictionary();
+
+        let aut = QueryAutomaton::new("cat", 1);
+        assert_eq!(
+            run_search_automaton(&trie, &aut),
+            vec![("cat".to_string(), 0), ("cats".to_string(), 1)]
+        );
+
+        let aut = QueryAutomaton::new("tset", 0);
+        assert_eq!(
+            run_search_automaton(&trie, &aut),
+            vec![("tset".to_string(), 0)]
+        );
+
+        // Transposition
+        let aut = QueryAutomaton::new("test", 1);
+        assert_eq!(
+            run_search_automaton(&trie, &aut),
+            vec![("test".to_string(), 0), ("tset".to_string(), 1)]
+        );
+
+        let aut = QueryAutomaton::new("", 2);
+        assert_eq!(run_search_automaton(&trie, &aut), vec![]);
+    }
+
+    #[test]
+    fn test_search_automaton_same_as_approx() {
+        let trie = create_dictionary();
+        let queries = [
+            "a", "abcd", "badc", "ca", "tste", "日語", "本日", "zzz", "tests",
+        ];
+
+        for &query in &queries {
+            for dist in 0..=3 {
+                let aut = QueryAutomaton::new(query, dist);
+                assert_eq!(
+                    run_search_automaton(&trie, &aut),
+                    run_search_approx(&trie, query, dist),
+                    "query: {}, dist: {}",
+                    query,
+                    dist
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_automaton_reuse() {
+        let aut = QueryAutomaton::new("cat", 1);
+        assert!(aut.nb_states() > 0);
+
+        // The same automaton can search multiple dictionaries
+        let first = create_trie(&[("bat", 1), ("cat", 2), ("dog", 3)]);
+        let second = create_trie(&[("at", 4), ("cart", 5), ("cats", 6)]);
+        assert_eq!(
+            run_search_automaton(&first, &aut),
+            vec![("cat".to_string(), 0), ("bat".to_string(), 1)]
+        );
+        assert_eq!(
+            run_search_automaton(&second, &aut),
+            vec![
+                ("cats".to_string(), 1),
+                ("cart".to_string(), 1),
+                ("at".to_string(), 1)
+            ]
+        );
+    }
+
+    /// Run with `cargo test --release -- --ignored --nocapture bench_search_automaton`.
+    #[test]
+    #[ignore]
+    fn bench_search_automaton() {
+        // Random words of 3 to 10 letters, searched with queries from the words
+        let mut state = 0x2545_F491_4F6C_DD1D_u64;
+        let mut below = |bound: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound) as usize
+        };
+        let mut words: Vec<String> = (0..50_000)
+            .map(|_| {
+                let len = below(8) + 3;
+                (0..len).map(|_| (b'a' + below(26) as u8) as char).collect()
+            })
+            .collect();
+        words.sort();
+        words.dedup();
+        let entries: Vec<_> = words.iter().map(|w| (w.as_str(), 1)).collect();
+        let trie = create_trie(&entries);
+        let queries: Vec<&str> = (0..500)
+            .map(|_| words[below(words.len() as u64)].as_str())
+            .collect();
+
+        for &dist_max in &[1, 2] {
+            // The automata are built once per query, as when searching multiple dictionaries
+            let start = std::time::Instant::now();
+            let automata: Vec<_> = queries
+                .iter()
+                .map(|query| QueryAutomaton::new(query, dist_max))
+                .collect();
+            let build_time = start.elapsed();
+            let start = std::time::Instant::now();
+            let nb_found_automaton: usize = automata
+                .iter()
+                .map(|aut| trie.search_automaton(aut).len())
+                .sum();
+            let automaton_time = start.elapsed();
+
+            let mut layer_stack = LayerStack::with_capacity(0, 0);
+            let mut iter_stack = IterationStack::new();
+            let mut word_pool = WordPool::new();
+            let start = std::time::Instant::now();
+            let mut nb_found_approx = 0;
+            for query in &queries {
+                layer_stack.clear();
+                iter_stack.clear();
+                let mut found = search_approx(
+                    &trie,
+                    query,
+                    &SearchOptions {
+                        dist_max,
+                        ..SearchOptions::default()
+                    },
+                    &mut layer_stack,
+                    &mut iter_stack,
+                    &mut word_pool,
+                    Vec::new(),
+                );
+                nb_found_approx += found.len();
+                word_pool.recycle(&mut found);
+            }
+            let approx_time = start.elapsed();
+
+            assert_eq!(nb_found_automaton, nb_found_approx);
+            println!(
+                "distance {}: automaton {:?} (+ {:?} to build), approx {:?} for {} queries",
+                dist_max,
+                automaton_time,
+                build_time,
+                approx_time,
+                queries.len()
+            );
+        }
+    }
+}
@@ -1,37 +1,36 @@
 use std::{cmp::Ordering, num::NonZeroU32};
 use vague_search_core::{CompiledTrie, CompiledTrieNode, IndexNodeNonZero, NodeValue};
 
-/// Compare the node characters with the character.
+/// Compare the node characters with the character, following the collation of the trie.
 /// If the character is in the node's range, return Equal.
 /// If the character is before the node's range, return Greater.
-pub fn compare_keys(
+pub(crate) fn compare_keys(
     trie_node: &CompiledTrieNode,
     node_value: &NodeValue,
     character: char,
     trie: &CompiledTrie,
 ) -> Ordering {
     match node_value {
-        NodeValue::Naive(node) => node.character.cmp(&character),
+        NodeValue::Naive(node) => trie.collation().cmp(node.character, character),
         NodeValue::Patricia(_) => {
             // SAFETY: Safe because in a patricia node
             let pat_range = unsafe { &trie_node.patricia_range() };
             let pat_first_char = unsafe { trie.get_char_unchecked(pat_range.start) };
-            pat_first_char.cmp(&character)
+            trie.collation().cmp(pat_first_char, character)
         }
         NodeValue::Range(node) => {
-            let range_len = u32::from(node.end_index) - u32::from(node.start_index);
-            if node.first_char > character {
-                Ordering::Greater
-            } else if node.first_char as u32 + range_len <= character as u32 {
-                Ordering::Less
-            } else {
+            let range_len = usize::from(node.end_index) - usize::from(node.start_index);
+            if (character as usize).wrapping_sub(node.first_char as usize) < range_len {
                 Ordering::Equal
+            } else {
+                trie.collation().cmp(node.first_char, character)
             }
         }
     }
 }
 
-fn search_child<'a>(
+/// Search the child beginning with the given character among the sorted children.
+pub(crate) fn search_child<'a>(
     children: &'a [CompiledTrieNode],
     first_char: char,
     trie: &CompiledTrie,
@@ -83,7 +82,7 @@ pub fn search_exact(
     search_exact_children(trie, word, children)
 }
 
-pub fn search_exact_children<'a>(
+pub(crate) fn search_exact_children<'a>(
     trie: &'a CompiledTrie,
     mut word: &str,
     mut children: &'a [CompiledTrieNode],
@@ -134,75 +133,227 @@ pub fn search_exact_children<'a>(
     }
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use vague_search_core::TrieNodeDrainer;
+/// The path of the trie along a prefix, to the end of the node where the prefix ends.
+pub(crate) struct PrefixPath<'a> {
+    /// The characters of the path, which are the prefix followed by the rest
+    /// of the characters of its last patricia node if it ends inside one.
+    pub word: String,
 
-    #[derive(Debug, Default, Clone, Eq, PartialEq)]
-    struct NodeDrainer {
-        pub characters: String,
-        pub frequency: Option<NonZeroU32>,
-        pub children: Vec<Self>,
-    }
+    /// The frequency of the word of the path, if it is one.
+    pub word_freq: Option<NonZeroU32>,
 
-    impl TrieNodeDrainer for NodeDrainer {
-        fn drain_characters(&mut self) -> String {
-            std::mem::replace(&mut self.characters, String::new())
-        }
+    /// The children of the last node of the path, or the roots of the trie for an empty prefix.
+    pub children: Option<&'a [CompiledTrieNode]>,
+}
 
-        fn frequency(&self) -> Option<NonZeroU32> {
-            self.frequency
-        }
+/// Descend the trie along the characters of the prefix, like [search_exact](search_exact),
+/// but also accepting a prefix which ends inside a patricia node.
+///
+/// Return None if no word of the trie begins with the prefix.
+pub(crate) fn descend_prefix<'a>(trie: &'a CompiledTrie, prefix: &str) -> Option<PrefixPath<'a>> {
+    let mut path = PrefixPath {
+        word: String::with_capacity(prefix.len()),
+        word_freq: None,
+        children: trie.get_root_siblings(),
+    };
 
-        fn drain_children(&mut self) -> Vec<Self> {
-            std::mem::replace(&mut self.children, Vec::new())
-        }
+    let mut rest = prefix;
+    while let Some(first_char) = rest.chars().next() {
+        let (child, child_value) = search_child(path.children?, first_char, trie)?;
+        let (index_first_child, word_freq) = match child_value {
+            NodeValue::Naive(node) => {
+                path.word.push(node.character);
+                rest = &rest[node.character.len_utf8()..];
+                (node.index_first_child, node.word_freq)
+            }
+            NodeValue::Patricia(node) => {
+                // SAFETY: Safe because in a patricia node
+                let patricia_range = unsafe { child.patricia_range() };
+                let chars = trie.get_chars(patricia_range.start, patricia_range.end);
+
+                if rest.starts_with(chars) {
+                    rest = &rest[chars.len()..];
+                } else if chars.starts_with(rest) {
+                    // The prefix ends inside the node, all its words begin with it
+                    rest = "";
+                } else {
+                    return None;
+                }
+                path.word.push_str(chars);
+                (node.index_first_child, node.word_freq)
+            }
+            NodeValue::Range(node) => {
+                // SAFETY: node.first_char is in the range (checked inside search_child)
+                let range = unsafe {
+                    trie.get_range_element_unchecked(
+                        node.start_index,
+                        first_char as usize - node.first_char as usize,
+                    )
+                };
+
+                path.word.push(first_char);
+                rest = &rest[first_char.len_utf8()..];
+                (range.index_first_child, range.word_freq)
+            }
+        };
+
+        path.word_freq = word_freq;
+        path.children = index_first_child.map(|index| trie.get_siblings(index));
     }
 
-    fn create_simple(character: char, freq: u32, children: Vec<NodeDrainer>) -> NodeDrainer {
-        NodeDrainer {
-            characters: character.to_string(),
-            frequency: NonZeroU32::new(freq),
-            children,
+    Some(path)
+}
+
+/// Set the membership of the sorted words in the subtree of the children,
+/// at the index of each word in the result.
+/// The characters of the path to the children must already be removed from the words,
+/// which must not be empty.
+///
+/// The words with a common prefix are next to each other once sorted,
+/// so each node of the trie is searched once for all of them.
+fn contains_sorted(
+    trie: &CompiledTrie,
+    children: &[CompiledTrieNode],
+    mut words: &mut [(usize, &str)],
+    result: &mut [bool],
+) {
+    while let Some(first_char) = words.first().and_then(|(_, word)| word.chars().next()) {
+        // Take the words beginning with the same character
+        let group_len = words
+            .iter()
+            .position(|(_, word)| !word.starts_with(first_char))
+            .unwrap_or(words.len());
+        let (group, rest) = std::mem::take(&mut words).split_at_mut(group_len);
+        words = rest;
+
+        let (child, child_value) = match search_child(children, first_char, trie) {
+            Some(c) => c,
+            None => continue,
+        };
+        let (index_first_child, word_freq, matched, substr_len) = match child_value {
+            NodeValue::Naive(node) => (
+                node.index_first_child,
+                node.word_freq,
+                group,
+                first_char.len_utf8(),
+            ),
+            NodeValue::Patricia(node) => {
+                // SAFETY: Safe because in a patricia node
+                let patricia_range = unsafe { child.patricia_range() };
+                let chars = trie.get_chars(patricia_range.start, patricia_range.end);
+
+                // The words beginning with the patricia characters are also next to each other
+                let start = match group.iter().position(|(_, word)| word.starts_with(chars)) {
+                    Some(start) => start,
+                    None => continue,
+                };
+                let len = group[start..]
+                    .iter()
+                    .position(|(_, word)| !word.starts_with(chars))
+                    .unwrap_or(group.len() - start);
+                (
+                    node.index_first_child,
+                    node.word_freq,
+                    &mut group[start..start + len],
+                    chars.len(),
+                )
+            }
+            NodeValue::Range(node) => {
+                // SAFETY: node.first_char is in the range (checked inside search_child)
+                let range = unsafe {
+                    trie.get_range_element_unchecked(
+                        node.start_index,
+                        first_char as usize - node.first_char as usize,
+                    )
+                };
+                (
+                    range.index_first_child,
+                    range.word_freq,
+                    group,
+                    first_char.len_utf8(),
+                )
+            }
+        };
+
+        // Remove the characters of the child, the words ending here are sorted first
+        for (_, word) in matched.iter_mut() {
+            *word = &word[substr_len..];
+        }
+        let nb_ended = matched
+            .iter()
+            .take_while(|(_, word)| word.is_empty())
+            .count();
+        for (index, _) in &matched[..nb_ended] {
+            result[*index] = word_freq.is_some();
         }
-    }
 
-    fn create_patricia(s: &str, freq: u32, children: Vec<NodeDrainer>) -> NodeDrainer {
-        NodeDrainer {
-            characters: s.to_string(),
-            frequency: NonZeroU32::new(freq),
-            children,
+        if let Some(index) = index_first_child {
+            let continued = &mut matched[nb_ended..];
+            if !continued.is_empty() {
+                contains_sorted(trie, trie.get_siblings(index), continued, result);
+            }
         }
     }
+}
 
-    #[test]
-    fn mixed_search() {
-        let root = create_simple(
-            '-',
+/// Return whether each word is in the trie, in the order of the words,
+/// like [contains](CompiledTrie::contains) called for each of them.
+///
+/// The words are sorted to search them in a single traversal of the trie,
+/// where the nodes of a common prefix are only searched once.
+pub fn contains_all(trie: &CompiledTrie, words: &[&str]) -> Vec<bool> {
+    let mut result = vec![false; words.len()];
+    let roots = match trie.get_root_siblings() {
+        Some(roots) => roots,
+        None => return result,
+    };
+
+    let mut sorted: Vec<(usize, &str)> = words.iter().copied().enumerate().collect();
+    sorted.sort_unstable_by_key(|&(_, word)| word);
+
+    // The empty word is never in the trie
+    let nb_empty = sorted
+        .iter()
+        .take_while(|(_, word)| word.is_empty())
+        .count();
+    contains_sorted(trie, roots, &mut sorted[nb_empty..], &mut result);
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::{create_node, create_trie};
+    fn create_mixed() -> CompiledTrie<'static> {
+        CompiledTrie::from(create_node(
+            "-",
             0,
             vec![
-                create_simple('a', 0, vec![create_patricia("la", 20, vec![])]),
-                create_simple('b', 1, vec![]),
-                create_patricia(
+                create_node("a", 0, vec![create_node("la", 20, vec![])]),
+                create_node("b", 1, vec![]),
+                create_node(
                     "cata",
                     1,
-                    vec![create_simple('d', 2, vec![]), create_simple('f', 1, vec![])],
+                    vec![create_node("d", 2, vec![]), create_node("f", 1, vec![])],
                 ),
-                create_simple(
-                    'd',
+                create_node(
+                    "d",
                     0,
                     vec![
-                        create_simple('a', 9, vec![]),
-                        create_simple('r', 6, vec![]),
-                        create_simple('t', 1, vec![]),
-                        create_simple('w', 7, vec![]),
+                        create_node("a", 9, vec![]),
+                        create_node("r", 6, vec![]),
+                        create_node("t", 1, vec![]),
+                        create_node("w", 7, vec![]),
                     ],
                 ),
-                create_simple('f', 5, vec![create_patricia("ade", 10, vec![])]),
+                create_node("f", 5, vec![create_node("ade", 10, vec![])]),
             ],
-        );
-        let compiled = CompiledTrie::from(root);
+        ))
+    }
+
+    #[test]
+    fn mixed_search() {
+        let compiled = create_mixed();
 
         let search_cata = search_exact(&compiled, "cata", None);
         assert!(search_cata.is_some());
@@ -220,4 +371,121 @@ mod test {
         assert!(search_ala.is_some());
         assert_eq!(search_ala.unwrap(), NonZeroU32::new(20).unwrap());
     }
+
+    #[test]
+    fn single_letter_search() {
+        let compiled = create_trie(&[("I", 3), ("a", 5), ("ab", 2), ("b", 1)]);
+
+        assert_eq!(search_exact(&compiled, "a", None), NonZeroU32::new(5));
+        assert_eq!(search_exact(&compiled, "I", None), NonZeroU32::new(3));
+        assert_eq!(search_exact(&compiled, "b", None), NonZeroU32::new(1));
+        assert_eq!(search_exact(&compiled, "ab", None), NonZeroU32::new(2));
+        assert_eq!(search_exact(&compiled, "c", None), None);
+        assert_eq!(search_exact(&compiled, "i", None), None);
+    }
+
+    #[test]
+    fn long_word_search() {
+        // Longer than a single patricia node can hold
+        let long = "ab".repeat(2500);
+        let longer = long.clone() + "c";
+        let compiled = create_trie(&[(&long, 4), (&longer, 2), ("abc", 1)]);
+
+        assert_eq!(search_exact(&compiled, &long, None), NonZeroU32::new(4));
+        assert_eq!(search_exact(&compiled, &longer, None), NonZeroU32::new(2));
+        assert_eq!(search_exact(&compiled, "abc", None), NonZeroU32::new(1));
+        assert_eq!(search_exact(&compiled, &long[..4096], None), None);
+    }
+
+    #[test]
+    fn empty_trie_search() {
+        // An empty word list compiles to a trie without any node
+        let trie = create_trie(&[]);
+        assert_eq!(search_exact(&trie, "test", None), None);
+        assert_eq!(contains_all(&trie, &["", "test"]), [false, false]);
+        assert!(descend_prefix(&trie, "t").is_none());
+        assert!(descend_prefix(&trie, "").unwrap().children.is_none());
+    }
+
+    #[test]
+    fn collated_search() {
+        use vague_search_core::Collation;
+
+        // The siblings sorted by case-folded characters, giving "A" and "a" in the same range
+        // in the code point order but not in the case-folded one
+        let trie = CompiledTrie::from_collated(
+            create_node(
+                "-",
+                0,
+                vec![
+                    create_node("A", 1, vec![]),
+                    create_node("a", 2, vec![create_node("b", 3, vec![])]),
+                    create_node("B", 4, vec![]),
+                    create_node("b", 5, vec![]),
+                    create_node("c", 6, vec![]),
+                    create_node("dé", 7, vec![]),
+                ],
+            ),
+            Collation::CASE_FOLDED,
+        );
+        assert!(trie.validate().is_ok());
+
+        for &(word, freq) in &[
+            ("A", 1),
+            ("a", 2),
+            ("ab", 3),
+            ("B", 4),
+            ("b", 5),
+            ("c", 6),
+            ("dé", 7),
+        ] {
+            assert_eq!(
+                search_exact(&trie, word, None),
+                NonZeroU32::new(freq),
+                "{}",
+                word
+            );
+        }
+        for word in &["C", "d", "aB", "Ab", "e"] {
+            assert_eq!(search_exact(&trie, word, None), None, "{}", word);
+        }
+    }
+
+    #[test]
+    fn contains_all_same_as_contains() {
+        let words = [
+            "cata", "da", "fade", "ala", "", "catad", "cat", "catz", "catafe", "catad", "dr", "d",
+            "dw", "dx", "f", "fa", "fadez", "a", "al", "alb", "b", "bb", "z", "日本",
+        ];
+
+        let check = |trie: &CompiledTrie| {
+            let expected: Vec<bool> = words.iter().map(|w| trie.contains(w)).collect();
+            assert!(expected.iter().any(|&found| found));
+            assert!(expected.iter().any(|&found| !found));
+            assert_eq!(contains_all(trie, &words), expected);
+
+            let mut reversed = words;
+            reversed.reverse();
+            let expected: Vec<bool> = reversed.iter().map(|w| trie.contains(w)).collect();
+            assert_eq!(contains_all(trie, &reversed), expected);
+        };
+
+        check(&create_mixed());
+        check(&create_trie(&[
+            ("a", 1),
+            ("al", 1),
+            ("b", 2),
+            ("cat", 3),
+            ("catad", 4),
+            ("d", 1),
+            ("dw", 1),
+            ("日本", 1),
+        ]));
+
+        assert!(contains_all(&create_mixed(), &[]).is_empty());
+        assert_eq!(
+            contains_all(&create_trie(&[]), &["a", ""]),
+            vec![false, false]
+        );
+    }
 }
@@ -0,0 +1,178 @@
+//! Approximate search with the [Hamming distance](https://en.wikipedia.org/wiki/Hamming_distance),
+//! e.g. to match codes of a fixed length.
+//!
+//! Only the substitutions are counted, so the found words have as many characters as the query:
+//! the traversal does not need any distance layer, and stops as soon as a node is longer
+//! than the query or has more substitutions than the maximum distance.
+
+use crate::search_approx::{Distance, FoundWord};
+use vague_search_core::CompiledTrie;
+
+/// The parameters of a Hamming search, shared by all the nodes of the traversal.
+struct HammingQuery<'q> {
+    query: &'q [char],
+    dist_max: Distance,
+}
+
+impl HammingQuery<'_> {
+    /// Return the number of substitutions of the query once the characters are read
+    /// from the position `pos` of the query, or None if there are too many of them
+    /// or if the characters go past the end of the query.
+    fn step(
+        &self,
+        pos: usize,
+        dist: Distance,
+        chars: impl Iterator<Item = char>,
+    ) -> Option<(usize, Distance)> {
+        let mut pos = pos;
+        let mut dist = dist;
+        for c in chars {
+            if c != *self.query.get(pos)? {
+                dist += 1;
+                if dist > self.dist_max {
+                    return None;
+                }
+            }
+            pos += 1;
+        }
+        Some((pos, dist))
+    }
+}
+
+/// Search for all words in the trie with as many characters as the query
+/// and at most `dist_max` of them substituted.
+///
+/// Return a vector of all found words with their respective frequency and number
+/// of substitutions as their distance, in lexicographic order.
+/// Like the approximate search, an empty query does not match anything.
+pub fn search_hamming(trie: &CompiledTrie, word: &str, dist_max: Distance) -> Vec<FoundWord> {
+    let mut result = Vec::new();
+    let query: Vec<char> = word.chars().collect();
+    if query.is_empty() {
+        return result;
+    }
+
+    let query = HammingQuery {
+        query: &query,
+        dist_max,
+    };
+    // The state of a node is its position in the query and its number of substitutions,
+    // skipping the nodes which cannot lead to a found word
+    trie.walk((0, 0), |step, &(pos, dist)| {
+        let (pos, dist) = query.step(pos, dist, step.chars().chars())?;
+        if pos < query.query.len() {
+            return Some((pos, dist));
+        }
+
+        // The children would have more characters than the query
+        if let Some(freq) = step.word_freq {
+            result.push(FoundWord {
+                word: step.word.to_string(),
+                freq,
+                dist,
+                edits: None,
+            });
+        }
+        None
+    });
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::create_trie;
+
+    fn run_search_hamming(
+        trie: &CompiledTrie,
+        word: &str,
+        dist_max: Distance,
+    ) -> Vec<(String, u32, Distance)> {
+        search_hamming(trie, word, dist_max)
+            .into_iter()
+            .map(|w| (w.word, w.freq.get(), w.dist))
+            .collect()
+    }
+
+    /// The number of substitutions between two words, if they have as many characters.
+    fn hamming_distance(a: &str, b: &str) -> Option<Distance> {
+        if a.chars().count() != b.chars().count() {
+            return None;
+        }
+        Some(a.chars().zip(b.chars()).filter(|(a, b)| a != b).count() as Distance)
+    }
+
+    #[test]
+    fn test_search_hamming() {
+        let trie = create_trie(&[
+            ("1234", 1),
+            ("1235", 2),
+            ("1243", 3),
+            ("123", 4),
+            ("12345", 5),
+            ("9234", 6),
+            ("日本語", 7),
+            ("日本人", 8),
+        ]);
+
+        assert_eq!(
+            run_search_hamming(&trie, "1234", 0),
+            [("1234".into(), 1, 0)]
+        );
+        assert_eq!(
+            run_search_hamming(&trie, "1234", 1),
+            [
+                ("1234".into(), 1, 0),
+                ("1235".into(), 2, 1),
+                ("9234".into(), 6, 1),
+            ]
+        );
+
+        // The transposed characters are two substitutions, and the lengths must be the same
+        assert_eq!(
+            run_search_hamming(&trie, "1234", 2),
+            [
+                ("1234".into(), 1, 0),
+                ("1235".into(), 2, 1),
+                ("1243".into(), 3, 2),
+                ("9234".into(), 6, 1),
+            ]
+        );
+        assert_eq!(
+            run_search_hamming(&trie, "日本人", 1),
+            [("日本人".into(), 8, 0), ("日本語".into(), 7, 1)]
+        );
+        assert!(run_search_hamming(&trie, "12", 2).is_empty());
+        assert!(run_search_hamming(&trie, "", 2).is_empty());
+        assert!(run_search_hamming(&create_trie(&[]), "1234", 2).is_empty());
+    }
+
+    #[test]
+    fn test_search_hamming_same_as_brute_force() {
+        // Patricia nodes, ranges and naive nodes of a few letters
+        let words = [
+            "a", "b", "c", "d", "e", "ab", "ba", "abc", "abd", "bbc", "cab", "abcdef", "abcxef",
+            "xbcdef", "abcd", "dcba",
+        ];
+        let entries: Vec<_> = words.iter().map(|&w| (w, 1)).collect();
+        let trie = create_trie(&entries);
+
+        for &query in &["a", "ab", "abc", "abcd", "abcdef", "zzz", "dbca"] {
+            for dist_max in 0..=4 {
+                let mut expected: Vec<_> = words
+                    .iter()
+                    .filter_map(|&w| Some((w.to_string(), 1, hamming_distance(query, w)?)))
+                    .filter(|&(_, _, dist)| dist <= dist_max)
+                    .collect();
+                expected.sort_unstable();
+                assert_eq!(
+                    run_search_hamming(&trie, query, dist_max),
+                    expected,
+                    "{} {}",
+                    query,
+                    dist_max
+                );
+            }
+        }
+    }
+}
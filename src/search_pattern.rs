@@ -0,0 +1,300 @@
+//! Search of the words matching a pattern, where the [ANY_CHAR](ANY_CHAR) wildcard
+//! stands for any one character and the [ANY_CHARS](ANY_CHARS) wildcard for any number
+//! of characters (even none), e.g. `t??t` for the words of 4 characters beginning and ending
+//! with a `t`, or `f*o` for the words beginning with a `f` and ending with an `o`.
+//!
+//! The traversal [walks](vague_search_core::CompiledTrie::walk) the trie, skipping the nodes
+//! which do not begin with the character of the pattern, so that it only branches into all
+//! the characters of the nodes at the wildcards. A wildcard reaching a patricia node consumes
+//! the characters of its stored string one at a time, so the rest of the pattern can continue
+//! inside the node.
+//!
+//! At an `*`, the traversal both consumes the next character of the trie and advances
+//! the pattern. Instead of backtracking through each of these choices, which is exponential
+//! in the number of `*`, all the pattern positions reached at a node are followed together:
+//! they are deduplicated before the children of the node are walked, so each
+//! (node, pattern position) state is only continued once.
+
+use std::num::NonZeroU32;
+use vague_search_core::CompiledTrie;
+
+/// The wildcard of a pattern, matching any one character.
+pub const ANY_CHAR: char = '?';
+
+/// The wildcard of a pattern, matching any number of characters, even none.
+pub const ANY_CHARS: char = '*';
+
+/// Add the pattern position to the sorted positions, along with the following ones
+/// as long as they are after an [ANY_CHARS](ANY_CHARS), which can match no character.
+fn insert_position(pattern: &[char], positions: &mut Vec<usize>, pos: usize) {
+    let mut pos = pos;
+    loop {
+        match positions.binary_search(&pos) {
+            // The following positions have been inserted with it
+            Ok(_) => return,
+            Err(i) => positions.insert(i, pos),
+        }
+        if pattern.get(pos) != Some(&ANY_CHARS) {
+            return;
+        }
+        pos += 1;
+    }
+}
+
+/// Return the pattern positions reached once the characters are read from the positions,
+/// which is empty if none of them matches the characters.
+fn step(pattern: &[char], positions: &[usize], chars: impl Iterator<Item = char>) -> Vec<usize> {
+    let mut current = positions.to_vec();
+    for c in chars {
+        let mut next = Vec::new();
+        for &pos in &current {
+            match pattern.get(pos) {
+                Some(&ANY_CHARS) => insert_position(pattern, &mut next, pos),
+                Some(&p) if p == ANY_CHAR || p == c => insert_position(pattern, &mut next, pos + 1),
+                _ => {}
+            }
+        }
+        if next.is_empty() {
+            return next;
+        }
+        current = next;
+    }
+    current
+}
+
+/// Return the character of the pattern which must begin the next node,
+/// if there is a single pattern position which is not a wildcard.
+fn next_literal(pattern: &[char], positions: &[usize]) -> Option<char> {
+    match positions {
+        [pos] => pattern
+            .get(*pos)
+            .copied()
+            .filter(|&c| c != ANY_CHAR && c != ANY_CHARS),
+        _ => None,
+    }
+}
+
+/// Search for all words in the trie matching the pattern, where each [ANY_CHAR](ANY_CHAR)
+/// matches any one character, each [ANY_CHARS](ANY_CHARS) any number of characters,
+/// and the other characters only match themselves.
+///
+/// Return a vector of all found words with their respective frequency,
+/// in lexicographic order. Like the approximate search, an empty pattern does not match anything.
+pub fn search_pattern(trie: &CompiledTrie, pattern: &str) -> Vec<(String, NonZeroU32)> {
+    let mut result = Vec::new();
+    let pattern: Vec<char> = pattern.chars().collect();
+    if pattern.is_empty() {
+        return result;
+    }
+
+    let mut positions = Vec::new();
+    insert_position(&pattern, &mut positions, 0);
+    trie.walk(positions, |node, positions| {
+        // Only the nodes beginning with the character of the pattern can match it
+        let chars = node.chars();
+        if let Some(c) = next_literal(&pattern, positions) {
+            if !chars.starts_with(c) {
+                return None;
+            }
+        }
+        let mut next = step(&pattern, positions, chars.chars());
+
+        // The positions are sorted, so the end of the pattern can only be the last one
+        if next.last() == Some(&pattern.len()) {
+            if let Some(freq) = node.word_freq {
+                result.push((node.word.to_string(), freq));
+            }
+            next.pop();
+        }
+
+        // The children are only walked if the pattern has not ended
+        if next.is_empty() {
+            None
+        } else {
+            Some(next)
+        }
+    });
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::create_trie;
+
+    fn run_search_pattern(trie: &CompiledTrie, pattern: &str) -> Vec<(String, u32)> {
+        search_pattern(trie, pattern)
+            .into_iter()
+            .map(|(word, freq)| (word, freq.get()))
+            .collect()
+    }
+
+    /// Whether the word matches the pattern, trying all the lengths of each `*`.
+    fn matches(pattern: &[char], word: &[char]) -> bool {
+        match (pattern.split_first(), word.split_first()) {
+            (Some((&ANY_CHARS, p)), _) => (0..=word.len()).any(|i| matches(p, &word[i..])),
+            (Some((&p, pattern)), Some((&c, word))) => {
+                (p == ANY_CHAR || p == c) && matches(pattern, word)
+            }
+            (p, w) => p.is_none() && w.is_none(),
+        }
+    }
+
+    #[test]
+    fn test_search_pattern_any_char() {
+        let trie = create_trie(&[
+            ("test", 1),
+            ("text", 2),
+            ("tent", 3),
+            ("tests", 4),
+            ("tea", 5),
+            ("toast", 6),
+            ("日本語", 7),
+            ("日本人", 8),
+        ]);
+
+        assert_eq!(
+            run_search_pattern(&trie, "te?t"),
+            [("tent".into(), 3), ("test".into(), 1), ("text".into(), 2)]
+        );
+        assert_eq!(
+            run_search_pattern(&trie, "t??t"),
+            [("tent".into(), 3), ("test".into(), 1), ("text".into(), 2)]
+        );
+        assert_eq!(
+            run_search_pattern(&trie, "???"),
+            [
+                ("tea".into(), 5),
+                ("日本人".into(), 8),
+                ("日本語".into(), 7)
+            ]
+        );
+        assert_eq!(run_search_pattern(&trie, "test"), [("test".into(), 1)]);
+        assert_eq!(run_search_pattern(&trie, "te?"), [("tea".into(), 5)]);
+        assert_eq!(run_search_pattern(&trie, "日?語"), [("日本語".into(), 7)]);
+        assert!(run_search_pattern(&trie, "??????").is_empty());
+        assert!(run_search_pattern(&trie, "").is_empty());
+        assert!(run_search_pattern(&create_trie(&[]), "????").is_empty());
+    }
+
+    #[test]
+    fn test_search_pattern_inside_patricia() {
+        // A single word is a patricia node, matched one character at a time
+        let trie = create_trie(&[("abcdef", 1)]);
+        assert_eq!(run_search_pattern(&trie, "a?c?e?"), [("abcdef".into(), 1)]);
+        assert_eq!(run_search_pattern(&trie, "??????"), [("abcdef".into(), 1)]);
+        assert!(run_search_pattern(&trie, "a?c?e").is_empty());
+        assert!(run_search_pattern(&trie, "a?c?e??").is_empty());
+        assert!(run_search_pattern(&trie, "a?x?e?").is_empty());
+    }
+
+    #[test]
+    fn test_search_pattern_any_chars() {
+        let trie = create_trie(&[
+            ("a", 1),
+            ("ab", 2),
+            ("acb", 3),
+            ("ba", 4),
+            ("bab", 5),
+            ("foo", 6),
+            ("fo", 7),
+            ("f", 8),
+        ]);
+
+        assert_eq!(
+            run_search_pattern(&trie, "*"),
+            [
+                ("a".into(), 1),
+                ("ab".into(), 2),
+                ("acb".into(), 3),
+                ("ba".into(), 4),
+                ("bab".into(), 5),
+                ("f".into(), 8),
+                ("fo".into(), 7),
+                ("foo".into(), 6),
+            ]
+        );
+        assert_eq!(
+            run_search_pattern(&trie, "a*"),
+            [("a".into(), 1), ("ab".into(), 2), ("acb".into(), 3)]
+        );
+        assert_eq!(
+            run_search_pattern(&trie, "*a"),
+            [("a".into(), 1), ("ba".into(), 4)]
+        );
+        assert_eq!(
+            run_search_pattern(&trie, "a*b"),
+            [("ab".into(), 2), ("acb".into(), 3)]
+        );
+
+        // The words matched in multiple ways are only found once
+        assert_eq!(
+            run_search_pattern(&trie, "*b*"),
+            [
+                ("ab".into(), 2),
+                ("acb".into(), 3),
+                ("ba".into(), 4),
+                ("bab".into(), 5),
+            ]
+        );
+        assert_eq!(
+            run_search_pattern(&trie, "f*o"),
+            [("fo".into(), 7), ("foo".into(), 6)]
+        );
+        assert_eq!(
+            run_search_pattern(&trie, "*?*??"),
+            run_search_pattern(&trie, "???*")
+        );
+        assert!(run_search_pattern(&trie, "*z*").is_empty());
+        assert!(run_search_pattern(&create_trie(&[]), "*").is_empty());
+    }
+
+    #[test]
+    fn test_search_pattern_any_chars_inside_patricia() {
+        // A single word is a patricia node, where the stars match some of its characters
+        let trie = create_trie(&[("abcabcabc", 1)]);
+        assert_eq!(run_search_pattern(&trie, "a*c"), [("abcabcabc".into(), 1)]);
+        assert_eq!(
+            run_search_pattern(&trie, "*b*b*b*"),
+            [("abcabcabc".into(), 1)]
+        );
+        assert_eq!(
+            run_search_pattern(&trie, "abc*abc"),
+            [("abcabcabc".into(), 1)]
+        );
+        assert!(run_search_pattern(&trie, "*b*b*b*b*").is_empty());
+        assert!(run_search_pattern(&trie, "*a").is_empty());
+
+        // Backtracking through each choice of the stars would take too long
+        let trie = create_trie(&[(&"a".repeat(200), 1)]);
+        let pattern = format!("{}b", "*a".repeat(20));
+        assert!(run_search_pattern(&trie, &pattern).is_empty());
+        assert_eq!(run_search_pattern(&trie, &"*a".repeat(20)).len(), 1);
+    }
+
+    #[test]
+    fn test_search_pattern_same_as_brute_force() {
+        // Patricia nodes, ranges and naive nodes of a few letters
+        let words = [
+            "a", "b", "c", "d", "e", "ab", "ba", "abc", "abd", "bbc", "cab", "abcdef", "abcxef",
+            "xbcdef", "abcd", "dcba",
+        ];
+        let entries: Vec<_> = words.iter().map(|&w| (w, 1)).collect();
+        let trie = create_trie(&entries);
+
+        for &pattern in &[
+            "?", "a?", "?b", "??", "ab?", "?b?", "???", "abc?", "????", "abc?ef", "?bc?ef", "z?",
+            "*", "**", "a*", "*a", "a*b", "*b*", "a*c*", "*c*f", "?*", "*?", "a*?", "??*?", "*d*",
+            "a*d*f", "*x*", "z*",
+        ] {
+            let chars: Vec<char> = pattern.chars().collect();
+            let mut expected: Vec<_> = words
+                .iter()
+                .filter(|&&w| matches(&chars, &w.chars().collect::<Vec<_>>()))
+                .map(|&w| (w.to_string(), 1))
+                .collect();
+            expected.sort_unstable();
+            assert_eq!(run_search_pattern(&trie, pattern), expected, "{}", pattern);
+        }
+    }
+}
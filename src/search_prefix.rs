@@ -0,0 +1,272 @@
+use crate::search_exact::descend_prefix;
+use std::{cmp::Reverse, collections::BinaryHeap, num::NonZeroU32};
+use vague_search_core::CompiledTrie;
+
+/// Visit all words in the trie beginning with the given prefix in lexicographic order,
+/// including the prefix itself if it is a word.
+fn visit_prefix(trie: &CompiledTrie, prefix: &str, mut visitor: impl FnMut(&str, NonZeroU32)) {
+    let mut path = match descend_prefix(trie, prefix) {
+        Some(path) => path,
+        None => return,
+    };
+
+    if let Some(freq) = path.word_freq {
+        visitor(&path.word, freq);
+    }
+    if let Some(children) = path.children {
+        trie.walk_siblings(children, &mut path.word, &(), &mut |step, _| {
+            if let Some(freq) = step.word_freq {
+                visitor(step.word, freq);
+            }
+            Some(())
+        });
+    }
+}
+
+/// Search for all words in the trie beginning with the given prefix,
+/// including the prefix itself if it is a word.
+///
+/// Return a vector of all found words with their respective frequency,
+/// in lexicographic order.
+pub fn search_prefix(trie: &CompiledTrie, prefix: &str) -> Vec<(String, NonZeroU32)> {
+    let mut result = Vec::new();
+    visit_prefix(trie, prefix, |word, freq| {
+        result.push((word.to_owned(), freq))
+    });
+    result
+}
+
+/// Search for the `limit` most frequent words in the trie beginning with the given prefix,
+/// including the prefix itself if it is a word.
+///
+/// Return a vector of the found words with their respective frequency,
+/// by descending frequency then in lexicographic order.
+pub fn search_completions(
+    trie: &CompiledTrie,
+    prefix: &str,
+    limit: usize,
+) -> Vec<(String, NonZeroU32)> {
+    if limit == 0 {
+        return Vec::new();
+    }
+
+    // Max-heap on (Reverse(freq), word): its top is the worst completion kept so far
+    let mut heap: BinaryHeap<(Reverse<NonZeroU32>, String)> = BinaryHeap::with_capacity(limit);
+    visit_prefix(trie, prefix, |word, freq| {
+        if heap.len() < limit {
+            heap.push((Reverse(freq), word.to_owned()));
+        } else if let Some(mut worst) = heap.peek_mut() {
+            // Only replace the worst completion (and allocate) if the word is better
+            if (Reverse(freq), word) < (worst.0, worst.1.as_str()) {
+                worst.0 = Reverse(freq);
+                worst.1.clear();
+                worst.1.push_str(word);
+            }
+        }
+    });
+
+    heap.into_sorted_vec()
+        .into_iter()
+        .map(|(Reverse(freq), word)| (word, freq))
+        .collect()
+}
+
+/// Autocompletion of a prefix with the most frequent words of the trie.
+pub trait TopCompletions {
+    /// Return the `k` most frequent words beginning with the prefix, with their frequency,
+    /// by descending frequency then in lexicographic order.
+    ///
+    /// Only the `k` best completions found so far are kept during the traversal
+    /// of the prefix subtree, so a common prefix does not allocate all its completions.
+    fn top_completions(&self, prefix: &str, k: usize) -> Vec<(String, NonZeroU32)>;
+}
+
+impl TopCompletions for CompiledTrie<'_> {
+    fn top_completions(&self, prefix: &str, k: usize) -> Vec<(String, NonZeroU32)> {
+        search_completions(self, prefix, k)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::create_trie;
+
+    fn run_search_prefix(trie: &CompiledTrie, prefix: &str) -> Vec<(String, u32)> {
+        search_prefix(trie, prefix)
+            .into_iter()
+            .map(|(word, freq)| (word, freq.get()))
+            .collect()
+    }
+
+    fn create_dictionary() -> CompiledTrie<'static> {
+        create_trie(&[
+            ("a", 3),
+            ("b", 2),
+            ("c", 1),
+            ("car", 5),
+            ("card", 2),
+            ("cards", 1),
+            ("care", 4),
+            ("test", 10),
+            ("tests", 1),
+            ("日本", 6),
+            ("日本語", 7),
+        ])
+    }
+
+    #[test]
+    fn test_search_prefix_completions() {
+        let trie = create_dictionary();
+        assert_eq!(
+            run_search_prefix(&trie, "car"),
+            vec![
+                ("car".to_string(), 5),
+                ("card".to_string(), 2),
+                ("cards".to_string(), 1),
+                ("care".to_string(), 4),
+            ]
+        );
+        assert_eq!(
+            run_search_prefix(&trie, "card"),
+            vec![("card".to_string(), 2), ("cards".to_string(), 1)]
+        );
+        assert_eq!(
+            run_search_prefix(&trie, "日"),
+            vec![("日本".to_string(), 6), ("日本語".to_string(), 7)]
+        );
+        assert_eq!(
+            run_search_prefix(&trie, "cards"),
+            vec![("cards".to_string(), 1)]
+        );
+        assert_eq!(run_search_prefix(&trie, "cart"), vec![]);
+        assert_eq!(run_search_prefix(&trie, "d"), vec![]);
+    }
+
+    #[test]
+    fn test_search_prefix_inside_patricia() {
+        let trie = create_dictionary();
+
+        // "test" is stored in a single patricia node
+        assert_eq!(
+            run_search_prefix(&trie, "te"),
+            vec![("test".to_string(), 10), ("tests".to_string(), 1)]
+        );
+        assert_eq!(run_search_prefix(&trie, "tex"), vec![]);
+        assert_eq!(run_search_prefix(&trie, "testing"), vec![]);
+    }
+
+    #[test]
+    fn test_search_prefix_all_words() {
+        let trie = create_dictionary();
+        let words: Vec<_> = run_search_prefix(&trie, "")
+            .into_iter()
+            .map(|(word, _)| word)
+            .collect();
+
+        let mut sorted = words.clone();
+        sorted.sort();
+        assert_eq!(words.len(), 11);
+        assert_eq!(words, sorted);
+
+        assert_eq!(run_search_prefix(&create_trie(&[]), ""), vec![]);
+        assert_eq!(run_search_prefix(&create_trie(&[]), "a"), vec![]);
+    }
+
+    fn run_search_completions(
+        trie: &CompiledTrie,
+        prefix: &str,
+        limit: usize,
+    ) -> Vec<(String, u32)> {
+        search_completions(trie, prefix, limit)
+            .into_iter()
+            .map(|(word, freq)| (word, freq.get()))
+            .collect()
+    }
+
+    #[test]
+    fn test_search_completions() {
+        let trie = create_dictionary();
+        assert_eq!(
+            run_search_completions(&trie, "car", 2),
+            vec![("car".to_string(), 5), ("care".to_string(), 4)]
+        );
+        assert_eq!(
+            run_search_completions(&trie, "c", 10),
+            vec![
+                ("car".to_string(), 5),
+                ("care".to_string(), 4),
+                ("card".to_string(), 2),
+                ("c".to_string(), 1),
+                ("cards".to_string(), 1),
+            ]
+        );
+        assert_eq!(run_search_completions(&trie, "c", 0), vec![]);
+        assert_eq!(run_search_completions(&trie, "x", 3), vec![]);
+    }
+
+    #[test]
+    fn test_search_completions_ties() {
+        let trie = create_trie(&[
+            ("ab", 2),
+            ("aa", 2),
+            ("ad", 2),
+            ("ac", 2),
+            ("a", 1),
+            ("ae", 3),
+        ]);
+        assert_eq!(
+            run_search_completions(&trie, "a", 3),
+            vec![
+                ("ae".to_string(), 3),
+                ("aa".to_string(), 2),
+                ("ab".to_string(), 2)
+            ]
+        );
+
+        // Same ordering as sorting all completions
+        let mut all = run_search_prefix(&trie, "a");
+        all.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        for limit in 0..=all.len() + 1 {
+            let expected: Vec<_> = all.iter().take(limit).cloned().collect();
+            assert_eq!(run_search_completions(&trie, "a", limit), expected);
+        }
+    }
+
+    #[test]
+    fn test_top_completions_many_words() {
+        // Every word of 3 letters among "abcd" beginning with "a", word i having a frequency i+1
+        let words: Vec<String> = (0..16)
+            .map(|i| {
+                let letters = ['a', 'b', 'c', 'd'];
+                format!("a{}{}", letters[i / 4], letters[i % 4])
+            })
+            .collect();
+        let entries: Vec<(&str, u32)> = words
+            .iter()
+            .enumerate()
+            .map(|(i, w)| (w.as_str(), i as u32 + 1))
+            .chain(vec![("b", 100), ("xyz", 200)])
+            .collect();
+        let trie = create_trie(&entries);
+
+        let top: Vec<_> = trie
+            .top_completions("a", 3)
+            .into_iter()
+            .map(|(word, freq)| (word, freq.get()))
+            .collect();
+        assert_eq!(
+            top,
+            vec![
+                ("add".to_string(), 16),
+                ("adc".to_string(), 15),
+                ("adb".to_string(), 14)
+            ]
+        );
+
+        assert_eq!(trie.top_completions("ab", 2).len(), 2);
+        assert_eq!(trie.top_completions("a", 100).len(), 16);
+        assert_eq!(trie.top_completions("", 1)[0].0, "xyz");
+        assert!(trie.top_completions("a", 0).is_empty());
+    }
+}
@@ -0,0 +1,221 @@
+//! A reusable searcher of the words of a [CompiledTrie](CompiledTrie).
+
+use crate::{
+    layer_stack::LayerStack,
+    search_approx::{
+        search_approx, Distance, FoundWord, IterationStack, SearchOptions, WordCharCount, WordPool,
+    },
+    search_exact::search_exact,
+};
+use vague_search_core::CompiledTrie;
+
+/// Search for the words of a trie, keeping the buffers of the searches
+/// between the queries to reduce the allocation overhead.
+///
+/// The approximate searches use the unit costs and no ranking (see [search_approx](search_approx)
+/// for the other options).
+pub struct Searcher<'a> {
+    trie: &'a CompiledTrie<'a>,
+    pub(crate) layer_stack: LayerStack<Distance, WordCharCount>,
+    pub(crate) iter_stack: IterationStack<'a>,
+    pub(crate) word_pool: WordPool,
+    pub(crate) result_buffer: Vec<FoundWord>,
+}
+
+impl<'a> Searcher<'a> {
+    /// Create a searcher of the words of the trie, with the default capacities
+    /// of the [SearcherBuilder](SearcherBuilder).
+    pub fn new(trie: &'a CompiledTrie<'a>) -> Self {
+        SearcherBuilder::new().build(trie)
+    }
+
+    /// Return the trie searched by the searcher.
+    pub fn trie(&self) -> &'a CompiledTrie<'a> {
+        self.trie
+    }
+
+    /// Search for the word itself, returning it at a distance 0 if it is in the trie.
+    ///
+    /// The results are valid until the next search, which reuses their buffers.
+    pub fn exact(&mut self, word: &str) -> &[FoundWord] {
+        self.word_pool.recycle(&mut self.result_buffer);
+        if word.is_empty() {
+            return &self.result_buffer;
+        }
+        if let Some(freq) = search_exact(self.trie, word, None) {
+            self.result_buffer.push(FoundWord {
+                word: word.to_string(),
+                freq,
+                dist: 0,
+                edits: None,
+            });
+        }
+        &self.result_buffer
+    }
+
+    /// Search for all words at a distance of at most `dist_max` of the query,
+    /// sorted by the [FoundWord](FoundWord) order.
+    ///
+    /// The results are valid until the next search, which reuses their buffers.
+    pub fn approx(&mut self, word: &str, dist_max: Distance) -> &[FoundWord] {
+        self.layer_stack.clear();
+        self.iter_stack.clear();
+        self.word_pool.recycle(&mut self.result_buffer);
+
+        self.result_buffer = search_approx(
+            self.trie,
+            word,
+            &SearchOptions {
+                dist_max,
+                ..SearchOptions::default()
+            },
+            &mut self.layer_stack,
+            &mut self.iter_stack,
+            &mut self.word_pool,
+            std::mem::take(&mut self.result_buffer),
+        );
+        self.result_buffer.sort_unstable();
+        &self.result_buffer
+    }
+}
+
+/// Create a [Searcher](Searcher) with the capacities of its buffers,
+/// to tune its memory usage to the expected queries.
+///
+/// The buffers still grow when a search needs more, their capacities only avoid
+/// the reallocations of the first searches. The defaults fit the queries of
+/// the query protocol of the binary:
+/// - 2000 elements and 50 layers for the layer stack, a layer of the length of the query
+///   for each character of the trie words (see [max_word_len](SearcherBuilder::max_word_len))
+/// - 500 elements for the iteration stack, the siblings of the nodes being traversed
+/// - 1000 results, and a pool keeping at most as many words
+#[derive(Debug, Copy, Clone)]
+pub struct SearcherBuilder {
+    layer_stack_elements: usize,
+    layer_stack_layers: usize,
+    iteration_stack: usize,
+    results: usize,
+}
+
+impl SearcherBuilder {
+    /// Create a builder with the default capacities.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the capacity of the layer stack, in distances and in layers.
+    pub fn layer_stack_capacity(mut self, elements: usize, layers: usize) -> Self {
+        self.layer_stack_elements = elements;
+        self.layer_stack_layers = layers;
+        self
+    }
+
+    /// Set the capacity of the layer stack for the queries and the words of at most
+    /// `len` characters, which need a layer of `len + 1` distances for each character
+    /// of the words and for the empty word.
+    pub fn max_word_len(self, len: usize) -> Self {
+        let nb_layers = len.saturating_add(1);
+        self.layer_stack_capacity(nb_layers.saturating_mul(nb_layers), nb_layers)
+    }
+
+    /// Set the capacity of the iteration stack, in nodes.
+    pub fn iteration_stack_capacity(mut self, nodes: usize) -> Self {
+        self.iteration_stack = nodes;
+        self
+    }
+
+    /// Set the capacity of the results and of the pool of their words.
+    pub fn result_capacity(mut self, results: usize) -> Self {
+        self.results = results;
+        self
+    }
+
+    /// Create the searcher of the words of the trie, with the preallocated buffers.
+    pub fn build<'a>(self, trie: &'a CompiledTrie<'a>) -> Searcher<'a> {
+        Searcher {
+            trie,
+            layer_stack: LayerStack::with_capacity(
+                self.layer_stack_elements,
+                self.layer_stack_layers,
+            ),
+            iter_stack: IterationStack::with_capacity(self.iteration_stack),
+            word_pool: WordPool::with_capacity(self.results),
+            result_buffer: Vec::with_capacity(self.results),
+        }
+    }
+}
+
+impl Default for SearcherBuilder {
+    fn default() -> Self {
+        Self {
+            layer_stack_elements: 2000,
+            layer_stack_layers: 50,
+            iteration_stack: 500,
+            results: 1000,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::create_trie;
+
+    fn found(words: &[FoundWord]) -> Vec<(&str, u32, Distance)> {
+        words
+            .iter()
+            .map(|w| (w.word.as_str(), w.freq.get(), w.dist))
+            .collect()
+    }
+
+    #[test]
+    fn test_searcher_exact() {
+        let trie = create_trie(&[("car", 3), ("cart", 1), ("care", 2)]);
+        let mut searcher = Searcher::new(&trie);
+        assert_eq!(found(searcher.exact("cart")), [("cart", 1, 0)]);
+        assert!(searcher.exact("ca").is_empty());
+        assert!(searcher.exact("").is_empty());
+    }
+
+    #[test]
+    fn test_searcher_approx() {
+        let trie = create_trie(&[("car", 3), ("cart", 1), ("care", 2), ("dog", 5)]);
+        let mut searcher = Searcher::new(&trie);
+
+        // The buffers are reused by the next queries
+        assert_eq!(
+            found(searcher.approx("cat", 1)),
+            [("car", 3, 1), ("cart", 1, 1)]
+        );
+        assert_eq!(
+            found(searcher.approx("car", 1)),
+            [("car", 3, 0), ("care", 2, 1), ("cart", 1, 1)]
+        );
+        assert_eq!(found(searcher.approx("dog", 0)), [("dog", 5, 0)]);
+        assert!(searcher.approx("xyz", 1).is_empty());
+        assert_eq!(found(searcher.exact("dog")), [("dog", 5, 0)]);
+    }
+
+    #[test]
+    fn test_searcher_builder() {
+        let trie = create_trie(&[("car", 3), ("cart", 1)]);
+        let searcher = SearcherBuilder::new()
+            .max_word_len(9)
+            .iteration_stack_capacity(20)
+            .result_capacity(30)
+            .build(&trie);
+        assert!(searcher.iter_stack.capacity() >= 20);
+        assert!(searcher.result_buffer.capacity() >= 30);
+
+        // The buffers grow past their capacities
+        let mut searcher = SearcherBuilder::new()
+            .layer_stack_capacity(0, 0)
+            .iteration_stack_capacity(0)
+            .result_capacity(0)
+            .build(&trie);
+        assert_eq!(
+            found(searcher.approx("cat", 1)),
+            [("car", 3, 1), ("cart", 1, 1)]
+        );
+    }
+}
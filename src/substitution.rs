@@ -0,0 +1,138 @@
+//! Costs of substituting specific pairs of characters in the approximate search,
+//! e.g. to make the typos between adjacent keys of a keyboard cheaper.
+
+use crate::{error::*, search_approx::Distance};
+use snafu::*;
+use std::collections::HashMap;
+
+#[cfg(feature = "qwerty")]
+use std::cmp::min;
+
+/// The cost of substituting some pairs of characters, instead of the substitution cost
+/// of the [EditCosts](crate::search_approx::EditCosts).
+///
+/// The costs are symmetric: substituting `a` for `b` costs the same as `b` for `a`.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct SubstitutionTable {
+    costs: HashMap<(char, char), Distance>,
+}
+
+impl SubstitutionTable {
+    /// Create an empty table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the cost of substituting the characters with each other.
+    pub fn insert(&mut self, a: char, b: char, cost: Distance) {
+        self.costs.insert((a, b), cost);
+        self.costs.insert((b, a), cost);
+    }
+
+    /// Return the cost of substituting the characters, if it is in the table.
+    #[inline]
+    pub fn get(&self, a: char, b: char) -> Option<Distance> {
+        self.costs.get(&(a, b)).copied()
+    }
+
+    /// Return an iterator over all costs of the table.
+    pub fn costs(&self) -> impl Iterator<Item = Distance> + '_ {
+        self.costs.values().copied()
+    }
+
+    /// Parse a table from lines of `<CHAR> <CHAR> <COST>`, ignoring the empty lines.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut table = Self::new();
+        for (number, line) in text.lines().enumerate() {
+            let mut split = line.split_whitespace();
+            let first = match split.next() {
+                Some(first) => first,
+                None => continue,
+            };
+
+            let mut parse_line = || {
+                let a = single_char(first)?;
+                let b = single_char(split.next()?)?;
+                let cost = split.next()?.parse().ok()?;
+                match split.next() {
+                    None => Some((a, b, cost)),
+                    Some(_) => None,
+                }
+            };
+            let (a, b, cost) = parse_line().context(SubstitutionParse { number, line })?;
+            table.insert(a, b, cost);
+        }
+        Ok(table)
+    }
+
+    /// The table of the adjacent keys of a QWERTY keyboard, on the same row or diagonally,
+    /// with the given cost for each pair of lowercase letters.
+    #[cfg(feature = "qwerty")]
+    pub fn qwerty(adjacent_cost: Distance) -> Self {
+        const ROWS: [&str; 3] = ["qwertyuiop", "asdfghjkl", "zxcvbnm"];
+
+        let mut table = Self::new();
+        for (i, row) in ROWS.iter().enumerate() {
+            let keys: Vec<char> = row.chars().collect();
+            for pair in keys.windows(2) {
+                table.insert(pair[0], pair[1], adjacent_cost);
+            }
+
+            // Each key of the next row is shifted to the right, between two keys of this row
+            if let Some(next_row) = ROWS.get(i + 1) {
+                for (j, below) in next_row.chars().enumerate() {
+                    for &above in &keys[j..min(j + 2, keys.len())] {
+                        table.insert(above, below, adjacent_cost);
+                    }
+                }
+            }
+        }
+        table
+    }
+}
+
+/// Return the character of the string if it is made of a single one.
+fn single_char(s: &str) -> Option<char> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_table() {
+        let table = SubstitutionTable::parse("e r 1\n\n  a s 2  \né e 1\n").unwrap();
+        assert_eq!(table.get('e', 'r'), Some(1));
+        assert_eq!(table.get('r', 'e'), Some(1));
+        assert_eq!(table.get('s', 'a'), Some(2));
+        assert_eq!(table.get('é', 'e'), Some(1));
+        assert_eq!(table.get('e', 'm'), None);
+
+        assert!(SubstitutionTable::parse("e r").is_err());
+        assert!(SubstitutionTable::parse("e r x").is_err());
+        assert!(SubstitutionTable::parse("er r 1").is_err());
+        assert!(SubstitutionTable::parse("e r 1 2").is_err());
+        assert!(SubstitutionTable::parse("a b 1\ne").is_err());
+    }
+
+    #[cfg(feature = "qwerty")]
+    #[test]
+    fn test_qwerty_table() {
+        let table = SubstitutionTable::qwerty(1);
+        assert_eq!(table.get('e', 'r'), Some(1));
+        assert_eq!(table.get('e', 'w'), Some(1));
+        assert_eq!(table.get('e', 's'), Some(1));
+        assert_eq!(table.get('e', 'd'), Some(1));
+        assert_eq!(table.get('a', 'q'), Some(1));
+        assert_eq!(table.get('m', 'k'), Some(1));
+        assert_eq!(table.get('p', 'l'), Some(1));
+        assert_eq!(table.get('q', 's'), None);
+        assert_eq!(table.get('e', 'm'), None);
+        assert_eq!(table.get('e', 'f'), None);
+    }
+}
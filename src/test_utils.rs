@@ -0,0 +1,85 @@
+//! Helpers shared by the unit tests of the search engine.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    num::NonZeroU32,
+};
+use vague_search_core::{CompileOptions, CompiledTrie, TrieBuilder, TrieNodeDrainer};
+
+/// A simple trie node, used to create [CompiledTrie](CompiledTrie) in tests.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct NodeDrainer {
+    pub characters: String,
+    pub frequency: Option<NonZeroU32>,
+    pub children: Vec<Self>,
+}
+
+impl TrieNodeDrainer for NodeDrainer {
+    fn drain_characters(&mut self) -> String {
+        std::mem::take(&mut self.characters)
+    }
+
+    fn frequency(&self) -> Option<NonZeroU32> {
+        self.frequency
+    }
+
+    fn drain_children(&mut self) -> Vec<Self> {
+        std::mem::take(&mut self.children)
+    }
+}
+
+/// Create a node of the characters with its children, which is not a word if `freq` is 0.
+pub fn create_node(s: &str, freq: u32, children: Vec<NodeDrainer>) -> NodeDrainer {
+    NodeDrainer {
+        characters: s.to_string(),
+        frequency: NonZeroU32::new(freq),
+        children,
+    }
+}
+
+/// Create a compiled trie containing the given words and their frequency.
+pub fn create_trie(words: &[(&str, u32)]) -> CompiledTrie<'static> {
+    let mut builder = TrieBuilder::new(CompileOptions::default());
+    builder.extend(
+        words
+            .iter()
+            .map(|&(word, freq)| (word, NonZeroU32::new(freq).unwrap())),
+    );
+    builder.compile()
+}
+
+/// The system allocator, counting the allocations of each thread for the benchmarks.
+struct CountingAllocator;
+
+thread_local! {
+    // A const initializer is not supported by the minimum Rust version
+    #[allow(clippy::missing_const_for_thread_local)]
+    static NB_ALLOCATIONS: Cell<usize> = Cell::new(0);
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // The counter may already be destroyed while the thread exits
+        let _ = NB_ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = NB_ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Return the number of allocations (and reallocations) made by the current thread so far,
+/// unaffected by the tests running on the other threads.
+pub fn nb_allocations() -> usize {
+    NB_ALLOCATIONS.with(Cell::get)
+}
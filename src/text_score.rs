@@ -0,0 +1,145 @@
+use crate::{
+    layer_stack::LayerStack,
+    search_approx::{search_closest_tier, Distance, IterationStack, WordPool},
+    search_exact::search_exact,
+};
+use vague_search_core::CompiledTrie;
+
+/// The weights used to compute the penalty of each token in [text_score](TextScore::text_score).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ScoreWeights {
+    /// The penalty added for each edit needed to reach the nearest correction of a token.
+    pub distance_penalty: f64,
+
+    /// The penalty of an out-of-vocabulary token, i.e. without any correction
+    /// at the searched distance.
+    pub no_match_penalty: f64,
+}
+
+impl Default for ScoreWeights {
+    fn default() -> Self {
+        Self {
+            distance_penalty: 1.0,
+            no_match_penalty: 10.0,
+        }
+    }
+}
+
+/// Split the text into the tokens to check.
+/// Tokens are separated by whitespaces and their surrounding punctuation is removed.
+fn tokenize(text: &str) -> impl Iterator<Item = &str> {
+    text.split_whitespace()
+        .map(|token| token.trim_matches(|c: char| !c.is_alphanumeric()))
+        .filter(|token| !token.is_empty())
+}
+
+/// Scoring of the spelling of texts with the words of the trie.
+pub trait TextScore {
+    /// Score the "incorrectness" of a text by summing the penalty of each of its tokens.
+    ///
+    /// The penalty of a token is computed as follows:
+    /// - `0` if the token is in the dictionary
+    /// - `d * weights.distance_penalty` if the nearest correction is at a distance `d <= dist`
+    /// - `weights.no_match_penalty` if there is no correction at a distance of at most `dist`
+    ///
+    /// A perfectly spelled text thus has a score of 0.
+    fn text_score(&self, text: &str, dist: Distance, weights: &ScoreWeights) -> f64;
+}
+
+impl TextScore for CompiledTrie<'_> {
+    fn text_score(&self, text: &str, dist: Distance, weights: &ScoreWeights) -> f64 {
+        let mut layer_stack = LayerStack::with_capacity(0, 0);
+        let mut iter_stack = IterationStack::new();
+        let mut word_pool = WordPool::new();
+        let mut result_buffer = Vec::new();
+
+        let mut score = 0.0;
+        for token in tokenize(text) {
+            // Exact matches do not need the more expensive approximate search
+            if search_exact(self, token, None).is_some() {
+                continue;
+            }
+
+            layer_stack.clear();
+            iter_stack.clear();
+            word_pool.recycle(&mut result_buffer);
+
+            result_buffer = search_closest_tier(
+                self,
+                token,
+                dist,
+                &mut layer_stack,
+                &mut iter_stack,
+                &mut word_pool,
+                std::mem::take(&mut result_buffer),
+            );
+
+            // All the words found are at the same (closest) distance
+            score += match result_buffer.first() {
+                Some(found_word) => f64::from(found_word.dist) * weights.distance_penalty,
+                None => weights.no_match_penalty,
+            };
+        }
+
+        score
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::create_trie;
+
+    fn create_dictionary() -> CompiledTrie<'static> {
+        create_trie(&[
+            ("a", 10),
+            ("brown", 3),
+            ("dog", 4),
+            ("fox", 2),
+            ("jumps", 1),
+            ("lazy", 2),
+            ("over", 5),
+            ("quick", 3),
+            ("the", 20),
+        ])
+    }
+
+    #[test]
+    fn test_text_score_clean_sentence() {
+        let trie = create_dictionary();
+        let text = "The quick brown fox jumps over a lazy dog.";
+        let weights = ScoreWeights::default();
+
+        // "The" is not in the dictionary, but "the" is at a distance 1
+        assert_eq!(trie.text_score(text, 2, &weights), 1.0);
+        assert_eq!(trie.text_score(&text.to_lowercase(), 2, &weights), 0.0);
+    }
+
+    #[test]
+    fn test_text_score_typos() {
+        let trie = create_dictionary();
+        let weights = ScoreWeights::default();
+
+        let clean = trie.text_score("the quick brown fox", 2, &weights);
+        let typos = trie.text_score("teh qiuck brwn fxo", 2, &weights);
+        assert_eq!(clean, 0.0);
+        assert_eq!(typos, 4.0);
+        assert!(typos > clean);
+
+        // "zzzzzz" is out of vocabulary
+        let oov = trie.text_score("the zzzzzz dgo, dog!", 1, &weights);
+        assert_eq!(oov, 11.0);
+    }
+
+    #[test]
+    fn test_text_score_custom_weights() {
+        let trie = create_dictionary();
+        let weights = ScoreWeights {
+            distance_penalty: 0.5,
+            no_match_penalty: 100.0,
+        };
+
+        assert_eq!(trie.text_score("", 2, &weights), 0.0);
+        assert_eq!(trie.text_score("teh brwn xyzxyz", 2, &weights), 101.0);
+    }
+}
@@ -0,0 +1,150 @@
+//! The ordering of the characters used to sort the siblings of a [CompiledTrie](crate::CompiledTrie).
+//!
+//! The exact searches find a child with a binary search on its siblings, so a trie
+//! must be searched with the collation it has been compiled with. The trie keeps its collation,
+//! and the dictionary file stores its identifier so that it cannot be read with another one
+//! (see [read_file_with_collation](crate::DictionaryFile::read_file_with_collation)).
+//!
+//! With a collation other than the code point order, a range node only holds consecutive
+//! code points, so that the characters of its range are also sorted by the collation.
+
+use std::{cmp::Ordering, num::NonZeroU32};
+
+/// How the characters are compared.
+#[derive(Debug, Copy, Clone)]
+enum Order {
+    CodePoint,
+    CaseFolded,
+    Custom(fn(char, char) -> Ordering),
+}
+
+/// An ordering of the characters, identified by a number stored in the dictionary file.
+#[derive(Debug, Copy, Clone)]
+pub struct Collation {
+    id: u32,
+    order: Order,
+}
+
+impl Collation {
+    /// The order of the Unicode code points, used by default.
+    pub const CODE_POINT: Self = Self {
+        id: 0,
+        order: Order::CodePoint,
+    };
+
+    /// The order of the lowercase characters, the different cases of a character being
+    /// next to each other in the order of their code points (e.g. `A`, `a`, `B`, `b`).
+    /// The characters with a lowercase mapping of multiple characters are compared as is.
+    pub const CASE_FOLDED: Self = Self {
+        id: 1,
+        order: Order::CaseFolded,
+    };
+
+    /// The first identifier of the custom collations, the ones before it
+    /// being reserved to the built-in collations.
+    pub const FIRST_CUSTOM_ID: u32 = 256;
+
+    /// Create a custom collation comparing the characters with the function,
+    /// which must be a total order.
+    ///
+    /// Return None if the identifier is reserved (before [FIRST_CUSTOM_ID](Collation::FIRST_CUSTOM_ID)).
+    pub fn custom(id: NonZeroU32, cmp: fn(char, char) -> Ordering) -> Option<Self> {
+        if id.get() >= Self::FIRST_CUSTOM_ID {
+            Some(Self {
+                id: id.get(),
+                order: Order::Custom(cmp),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Return the built-in collation of the identifier, if any.
+    pub fn from_id(id: u32) -> Option<Self> {
+        [Self::CODE_POINT, Self::CASE_FOLDED]
+            .iter()
+            .copied()
+            .find(|c| c.id == id)
+    }
+
+    /// Return the built-in collation of the name (`code-point` or `case-folded`), if any.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "code-point" => Some(Self::CODE_POINT),
+            "case-folded" => Some(Self::CASE_FOLDED),
+            _ => None,
+        }
+    }
+
+    /// Return the identifier of the collation.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Return whether this is the order of the code points.
+    pub fn is_code_point(&self) -> bool {
+        matches!(self.order, Order::CodePoint)
+    }
+
+    /// Compare two characters.
+    #[inline]
+    pub fn cmp(&self, a: char, b: char) -> Ordering {
+        match self.order {
+            Order::CodePoint => a.cmp(&b),
+            Order::CaseFolded => fold_char(a).cmp(&fold_char(b)).then(a.cmp(&b)),
+            Order::Custom(cmp) => cmp(a, b),
+        }
+    }
+}
+
+impl Default for Collation {
+    fn default() -> Self {
+        Self::CODE_POINT
+    }
+}
+
+/// Return the lowercase mapping of the character if it is a single character,
+/// else the character itself.
+fn fold_char(c: char) -> char {
+    let mut lowercase = c.to_lowercase();
+    match (lowercase.next(), lowercase.next()) {
+        (Some(folded), None) => folded,
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_collation_cmp() {
+        let mut chars = vec!['b', 'A', 'a', 'B', 'é', 'É', '1'];
+        chars.sort_by(|&a, &b| Collation::CODE_POINT.cmp(a, b));
+        assert_eq!(chars, ['1', 'A', 'B', 'a', 'b', 'É', 'é']);
+        chars.sort_by(|&a, &b| Collation::CASE_FOLDED.cmp(a, b));
+        assert_eq!(chars, ['1', 'A', 'a', 'B', 'b', 'É', 'é']);
+
+        let reverse = Collation::custom(NonZeroU32::new(256).unwrap(), |a, b| b.cmp(&a)).unwrap();
+        chars.sort_by(|&a, &b| reverse.cmp(a, b));
+        assert_eq!(chars, ['é', 'É', 'b', 'a', 'B', 'A', '1']);
+        assert!(!reverse.is_code_point());
+    }
+
+    #[test]
+    fn test_collation_ids() {
+        assert!(Collation::custom(NonZeroU32::new(1).unwrap(), |a, b| a.cmp(&b)).is_none());
+        assert_eq!(Collation::default().id(), Collation::CODE_POINT.id());
+        assert!(Collation::default().is_code_point());
+
+        for &collation in &[Collation::CODE_POINT, Collation::CASE_FOLDED] {
+            assert_eq!(
+                Collation::from_id(collation.id()).unwrap().id(),
+                collation.id()
+            );
+        }
+        assert!(Collation::from_id(Collation::FIRST_CUSTOM_ID).is_none());
+        assert_eq!(Collation::from_name("case-folded").unwrap().id(), 1);
+        assert!(Collation::from_name("unknown").is_none());
+    }
+}
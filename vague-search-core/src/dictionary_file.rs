@@ -1,26 +1,94 @@
 use crate::{
     error::*,
-    utils::{as_bytes, AsBytes},
-    CompiledTrie, CompiledTrieNode, RangeElement,
+    utils::{as_bytes, crc32, AsBytes},
+    Collation, CompiledTrie, CompiledTrieNode, RangeElement,
 };
-use snafu::ResultExt;
+
+#[cfg(feature = "source-lines")]
+use crate::SourceLineElement;
+#[cfg(feature = "ngram-model")]
+use crate::TrigramElement;
+#[cfg(feature = "length-index")]
+use crate::{LengthElement, LengthParent};
+use snafu::{OptionExt, ResultExt};
 use std::{
+    borrow::Cow,
+    convert::TryFrom,
     ffi::c_void,
     fs::{File, Metadata, OpenOptions},
     io::Write,
     mem::size_of,
+    ops::Range,
     path::Path,
 };
 
 /// The header of the dictionary file.
 /// Contains information about the file structure, helping its parsing.
+///
+/// Its fields are kept in order so that the file begins with the magic number.
+#[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct Header {
+    /// Always [MAGIC](Header::MAGIC), to recognize a dictionary file.
+    pub magic: [u8; 4],
+    /// The [VERSION](Header::VERSION) of the format the file has been written with.
+    pub version: u32,
     pub nb_nodes: usize,
     pub nb_chars_bytes: usize,
     pub nb_ranges: usize,
+    pub collation_id: u32,
+    /// The CRC-32 of the bytes of the file following the header, set when it is written.
+    /// It also fills the padding after the collation identifier, so that the header
+    /// is written without uninitialized bytes.
+    pub checksum: u32,
+    #[cfg(feature = "length-index")]
+    pub nb_lengths: usize,
+    #[cfg(feature = "length-index")]
+    pub nb_length_parents: usize,
+    #[cfg(feature = "ngram-model")]
+    pub nb_trigrams: usize,
+    #[cfg(feature = "source-lines")]
+    pub nb_source_lines: usize,
+}
+
+impl Header {
+    /// The first bytes of a dictionary file.
+    pub const MAGIC: [u8; 4] = *b"VSDF";
+
+    /// The version of the dictionary format, to change with the layout of the file.
+    pub const VERSION: u32 = 3;
+
+    /// Return the header with the bytes of each of its integers swapped,
+    /// i.e. the header read in the other byte order (the magic number is kept).
+    fn swap_bytes(self) -> Self {
+        Header {
+            magic: self.magic,
+            version: self.version.swap_bytes(),
+            nb_nodes: self.nb_nodes.swap_bytes(),
+            nb_chars_bytes: self.nb_chars_bytes.swap_bytes(),
+            nb_ranges: self.nb_ranges.swap_bytes(),
+            collation_id: self.collation_id.swap_bytes(),
+            checksum: self.checksum.swap_bytes(),
+            #[cfg(feature = "length-index")]
+            nb_lengths: self.nb_lengths.swap_bytes(),
+            #[cfg(feature = "length-index")]
+            nb_length_parents: self.nb_length_parents.swap_bytes(),
+            #[cfg(feature = "ngram-model")]
+            nb_trigrams: self.nb_trigrams.swap_bytes(),
+            #[cfg(feature = "source-lines")]
+            nb_source_lines: self.nb_source_lines.swap_bytes(),
+        }
+    }
 }
 
+/// Whether the file is written in the other byte order than the native one,
+/// i.e. in little-endian on a big-endian machine with the `portable` feature.
+const SWAP_BYTE_ORDER: bool = cfg!(all(feature = "portable", target_endian = "big"));
+
+// The conversion swaps 4-byte words, while the indices of a large index have 8 bytes
+#[cfg(all(feature = "portable", feature = "large-index", target_endian = "big"))]
+compile_error!("the `portable` feature does not support the `large-index` feature on big-endian");
+
 /// The dictionary created by the index binary and saved in a file
 /// to be later used by the search engine.
 /// The same structure can be used for reading and writing.
@@ -30,10 +98,12 @@ pub struct Header {
 /// without copying the entire file in memory.
 #[derive(Debug)]
 pub struct DictionaryFile<'a> {
-    // Read the file if mmap not available
-    #[cfg(windows)]
-    read_bytes: Vec<u8>,
+    /// The contents of the file if it has been read in memory instead of being mmaped,
+    /// as 8-byte integers so that the header and the arrays typed from it are aligned.
+    #[allow(dead_code)] // Only kept alive for the trie which borrows it
+    read_buf: Vec<u64>,
 
+    /// The mmaped file, or null if it has not been mmaped.
     mmap_ptr: *const c_void,
     ptr_len: usize,
 
@@ -41,20 +111,211 @@ pub struct DictionaryFile<'a> {
     pub trie: CompiledTrie<'a>,
 }
 
+/// Return the number of padding bytes written after the characters,
+/// so that the following arrays are correctly aligned.
+const fn chars_padding(header: &Header) -> usize {
+    const ALIGN: usize = std::mem::align_of::<RangeElement>();
+    (ALIGN - header.nb_chars_bytes % ALIGN) % ALIGN
+}
+
+/// Return the number of bytes of the file described by the header.
+///
+/// It is computed on 128 bits so that the counts of a corrupted header cannot overflow it.
+fn described_file_size(header: &Header) -> u128 {
+    let array_size = |nb: usize, elem_size: usize| nb as u128 * elem_size as u128;
+    let size = array_size(1, size_of::<Header>())
+        + array_size(header.nb_nodes, size_of::<CompiledTrieNode>())
+        + array_size(header.nb_chars_bytes, 1)
+        + chars_padding(header) as u128
+        + array_size(header.nb_ranges, size_of::<RangeElement>());
+
+    #[cfg(feature = "ngram-model")]
+    let size = size + array_size(header.nb_trigrams, size_of::<TrigramElement>());
+
+    #[cfg(feature = "source-lines")]
+    let size = size + array_size(header.nb_source_lines, size_of::<SourceLineElement>());
+
+    #[cfg(feature = "length-index")]
+    let size = size
+        + array_size(header.nb_lengths, size_of::<LengthElement>())
+        + array_size(header.nb_length_parents, size_of::<LengthParent>());
+
+    size
+}
+
+/// Return the byte ranges in the file of the arrays made of 4-byte integers
+/// described by the header, i.e. all the arrays but the characters.
+fn word_arrays(header: &Header) -> Vec<Range<usize>> {
+    let mut start = size_of::<Header>();
+    let mut next = |len: usize| {
+        let range = start..start + len;
+        start += len;
+        range
+    };
+
+    let mut arrays = vec![next(header.nb_nodes * size_of::<CompiledTrieNode>())];
+    next(header.nb_chars_bytes + chars_padding(header));
+    arrays.push(next(header.nb_ranges * size_of::<RangeElement>()));
+
+    #[cfg(feature = "length-index")]
+    arrays.push(next(header.nb_lengths * size_of::<LengthElement>()));
+    #[cfg(feature = "length-index")]
+    arrays.push(next(header.nb_length_parents * size_of::<LengthParent>()));
+
+    #[cfg(feature = "ngram-model")]
+    arrays.push(next(header.nb_trigrams * size_of::<TrigramElement>()));
+
+    #[cfg(feature = "source-lines")]
+    arrays.push(next(
+        header.nb_source_lines * size_of::<SourceLineElement>(),
+    ));
+
+    arrays
+}
+
+/// Swap the byte order of the integers of the file contents, whose arrays are described
+/// by the header in the native byte order. The characters are UTF-8 and do not depend on it.
+///
+/// The fields of all the array elements being 4-byte integers, each of their words is swapped.
+fn swap_byte_order(bytes: &mut [u8], header: &Header) {
+    // SAFETY: The contents begin with a header, which may not be aligned in the buffer
+    let file_header = unsafe { std::ptr::read_unaligned(bytes.as_ptr() as *const Header) };
+    bytes[..size_of::<Header>()].copy_from_slice(as_bytes(&file_header.swap_bytes()));
+    for range in word_arrays(header) {
+        for word in bytes[range].chunks_exact_mut(4) {
+            word.reverse();
+        }
+    }
+}
+
+/// Check that the header is the one of a dictionary file with the current format,
+/// before trusting the counts it describes.
+fn check_header_format(path: &Path, header: &Header) -> Result<()> {
+    if header.magic != Header::MAGIC {
+        Err(Error::BadMagic {
+            path: path.to_path_buf(),
+            found: header.magic,
+        })
+    } else if header.version != Header::VERSION && header.version.swap_bytes() == Header::VERSION {
+        Err(Error::ByteOrderMismatch {
+            path: path.to_path_buf(),
+        })
+    } else if header.version != Header::VERSION {
+        Err(Error::VersionMismatch {
+            path: path.to_path_buf(),
+            expected: Header::VERSION,
+            found: header.version,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Check that the header describes exactly the contents of the file,
+/// so that the arrays typed from it do not extend past the end of the file.
+fn check_file_size(path: &Path, header: &Header, file_len: usize) -> Result<()> {
+    let expected = described_file_size(header);
+    if expected == file_len as u128 {
+        Ok(())
+    } else {
+        Err(Error::SizeMismatch {
+            path: path.to_path_buf(),
+            expected,
+            len: file_len,
+        })
+    }
+}
+
+/// Check that the contents of the file following the header have the checksum of the header,
+/// i.e. that the file has not been corrupted since it has been written.
+fn check_checksum(path: &Path, header: &Header, contents: &[u8]) -> Result<()> {
+    let found = crc32(&[contents]);
+    if found == header.checksum {
+        Ok(())
+    } else {
+        Err(Error::ChecksumMismatch {
+            path: path.to_path_buf(),
+            expected: header.checksum,
+            found,
+        })
+    }
+}
+
+/// Check that the dictionary has been compiled with the given collation,
+/// or with a built-in one if None, and return it.
+fn check_collation(
+    path: &Path,
+    header: &Header,
+    collation: Option<Collation>,
+) -> Result<Collation> {
+    match collation {
+        Some(collation) if collation.id() == header.collation_id => Ok(collation),
+        Some(collation) => Err(Error::CollationMismatch {
+            path: path.to_path_buf(),
+            expected: collation.id(),
+            found: header.collation_id,
+        }),
+        None => Collation::from_id(header.collation_id).context(UnknownCollation {
+            path,
+            id: header.collation_id,
+        }),
+    }
+}
+
+/// Check that the file is large enough to contain a header, before reading it.
+fn check_header_size(path: &Path, file_len: usize) -> Result<()> {
+    if file_len >= size_of::<Header>() {
+        Ok(())
+    } else {
+        Err(Error::SizeMismatch {
+            path: path.to_path_buf(),
+            expected: size_of::<Header>() as u128,
+            len: file_len,
+        })
+    }
+}
+
 /// Helper function to get the error string from errno after a failed libc function call.
 #[cfg(not(windows))]
-unsafe fn strerror() -> Option<&'static str> {
-    let errno = *libc::__errno_location();
+unsafe fn strerror(errno: i32) -> Option<&'static str> {
     let strerror = libc::strerror(errno);
     let cstr = std::ffi::CStr::from_ptr(strerror);
     cstr.to_str().ok()
 }
 
+/// Advise the kernel that the mapping of the file is accessed randomly, as the searches
+/// jump between the nodes, but that its nodes will be needed soon. Failures are ignored
+/// since the advice does not change the contents of the mapping.
+#[cfg(not(windows))]
+unsafe fn advise_mapping(mmap_ptr: *mut c_void, len: usize, header: &Header) {
+    libc::madvise(mmap_ptr, len, libc::MADV_RANDOM);
+    let nodes_len = size_of::<Header>() + header.nb_nodes * size_of::<CompiledTrieNode>();
+    libc::madvise(mmap_ptr, nodes_len.min(len), libc::MADV_WILLNEED);
+}
+
+/// Return the error of a failed mmap of the whole file, from its errno.
+///
+/// A file too large for the address space (or for the virtual memory limit of the process)
+/// is reported as such, since mmap only gives an opaque "Cannot allocate memory".
+#[cfg(not(windows))]
+fn mmap_error(path: &Path, len: u64, errno: i32) -> Error {
+    match errno {
+        libc::ENOMEM | libc::EOVERFLOW => Error::FileTooLarge {
+            path: path.to_path_buf(),
+            len,
+        },
+        _ => Error::FileMmap {
+            path: path.to_path_buf(),
+            strerror: unsafe { strerror(errno) }.unwrap_or("Unknown").to_string(),
+        },
+    }
+}
+
 impl DictionaryFile<'_> {
     /// Return the offset pointers of the inner data which is composed of:
     /// - `Header` (offset 0, not returned)
     /// - `Vec<Node>`
-    /// - `Vec<char>`
+    /// - `Vec<char>` (followed by padding bytes)
     /// - `Vec<RangeElement>`
     unsafe fn get_offsets_ptr(
         header: &Header,
@@ -65,20 +326,168 @@ impl DictionaryFile<'_> {
 
         let nodes_ptr = ptr.add(HEADER_LEN);
         let chars_ptr = nodes_ptr.add(header.nb_nodes * NODE_LEN);
-        let ranges_ptr = chars_ptr.add(header.nb_chars_bytes);
+        let ranges_ptr = chars_ptr.add(header.nb_chars_bytes + chars_padding(header));
 
         (nodes_ptr, chars_ptr, ranges_ptr)
     }
 
+    /// Return the offset pointers of the length index data, placed right after the ranges
+    /// since its elements are aligned like them:
+    /// - `Vec<LengthElement>`
+    /// - `Vec<LengthParent>`
+    #[cfg(feature = "length-index")]
+    unsafe fn get_lengths_offsets_ptr(
+        header: &Header,
+        ranges_ptr: *const c_void,
+    ) -> (*const c_void, *const c_void) {
+        let lengths_ptr = ranges_ptr.add(header.nb_ranges * size_of::<RangeElement>());
+        let length_parents_ptr = lengths_ptr.add(header.nb_lengths * size_of::<LengthElement>());
+
+        (lengths_ptr, length_parents_ptr)
+    }
+
+    /// Return the pointer to the end of the ranges, and of the length index if any,
+    /// where the 4-byte aligned arrays begin.
+    #[cfg(any(feature = "ngram-model", feature = "source-lines"))]
+    unsafe fn get_ranges_end_ptr(header: &Header, ranges_ptr: *const c_void) -> *const c_void {
+        let end_ptr = ranges_ptr.add(header.nb_ranges * size_of::<RangeElement>());
+        #[cfg(feature = "length-index")]
+        let end_ptr = end_ptr
+            .add(header.nb_lengths * size_of::<LengthElement>())
+            .add(header.nb_length_parents * size_of::<LengthParent>());
+        end_ptr
+    }
+
+    /// Return the offset pointer of the trigram model data, placed after the ranges
+    /// (and the length index if any):
+    /// - `Vec<TrigramElement>`
+    #[cfg(feature = "ngram-model")]
+    unsafe fn get_trigrams_offset_ptr(header: &Header, ranges_ptr: *const c_void) -> *const c_void {
+        Self::get_ranges_end_ptr(header, ranges_ptr)
+    }
+
+    /// Return the offset pointer of the source line data, placed after the ranges
+    /// (and the length index and trigram model if any):
+    /// - `Vec<SourceLineElement>`
+    #[cfg(feature = "source-lines")]
+    unsafe fn get_source_lines_offset_ptr(
+        header: &Header,
+        ranges_ptr: *const c_void,
+    ) -> *const c_void {
+        let source_lines_ptr = Self::get_ranges_end_ptr(header, ranges_ptr);
+        #[cfg(feature = "ngram-model")]
+        let source_lines_ptr =
+            source_lines_ptr.add(header.nb_trigrams * size_of::<TrigramElement>());
+        source_lines_ptr
+    }
+
+    /// Type the compiled trie of the file contents, beginning at the pointer,
+    /// described by the header in the native byte order.
+    ///
+    /// The contents must have been checked to have the size described by the header,
+    /// and to live as long as the returned trie. Their strings are checked to be UTF-8,
+    /// which a corrupted or crafted file would not ensure even with a valid checksum.
+    unsafe fn type_trie<'a>(
+        path: &Path,
+        header: &Header,
+        ptr: *const c_void,
+        collation: Collation,
+    ) -> Result<CompiledTrie<'a>> {
+        // Get the offset pointers to each array
+        let (nodes_ptr, chars_ptr, ranges_ptr) = Self::get_offsets_ptr(header, ptr);
+
+        // Type each array
+        let nodes =
+            std::slice::from_raw_parts(nodes_ptr as *const CompiledTrieNode, header.nb_nodes);
+
+        let chars_u8 = std::slice::from_raw_parts(chars_ptr as *const u8, header.nb_chars_bytes);
+        let chars = std::str::from_utf8(chars_u8).context(InvalidUtf8 {
+            path,
+            array: "characters",
+        })?;
+
+        let ranges =
+            std::slice::from_raw_parts(ranges_ptr as *const RangeElement, header.nb_ranges);
+
+        // Create a borrowing compiled trie
+        let trie = CompiledTrie::from((nodes, chars, ranges)).with_collation(collation);
+
+        #[cfg(feature = "length-index")]
+        let trie = {
+            let (lengths_ptr, length_parents_ptr) =
+                Self::get_lengths_offsets_ptr(header, ranges_ptr);
+            let lengths =
+                std::slice::from_raw_parts(lengths_ptr as *const LengthElement, header.nb_lengths);
+            let length_parents = std::slice::from_raw_parts(
+                length_parents_ptr as *const LengthParent,
+                header.nb_length_parents,
+            );
+            trie.with_lengths(lengths, length_parents)
+        };
+
+        #[cfg(feature = "ngram-model")]
+        let trie = {
+            let trigrams_ptr = Self::get_trigrams_offset_ptr(header, ranges_ptr);
+            let trigrams = std::slice::from_raw_parts(
+                trigrams_ptr as *const TrigramElement,
+                header.nb_trigrams,
+            );
+            trie.with_trigrams(trigrams)
+        };
+
+        #[cfg(feature = "source-lines")]
+        let trie = {
+            let source_lines_ptr = Self::get_source_lines_offset_ptr(header, ranges_ptr);
+            let source_lines = std::slice::from_raw_parts(
+                source_lines_ptr as *const SourceLineElement,
+                header.nb_source_lines,
+            );
+            trie.with_source_lines(source_lines)
+        };
+
+        Ok(trie)
+    }
+
     /// Try to read the dictionary from a file, previously written using the
     /// [write_file](DictionaryFile::write_file) method.
     /// Uses mmap internally *on unix platforms* to reduce memory usage.
-    #[cfg(not(windows))]
+    ///
+    /// The dictionary must have been compiled with a built-in [Collation](Collation),
+    /// see [read_file_with_collation](DictionaryFile::read_file_with_collation) otherwise.
+    ///
+    /// The checksum of the file is verified before typing its contents, which reads
+    /// the whole file once.
     pub fn read_file(path: &Path) -> Result<Self> {
+        Self::read(path, None, true)
+    }
+
+    /// Same as [read_file](DictionaryFile::read_file), but the dictionary must have been
+    /// compiled with the given collation, which can be a custom one.
+    pub fn read_file_with_collation(path: &Path, collation: Collation) -> Result<Self> {
+        Self::read(path, Some(collation), true)
+    }
+
+    /// Same as [read_file](DictionaryFile::read_file) (or
+    /// [read_file_with_collation](DictionaryFile::read_file_with_collation) with a collation),
+    /// but without verifying the checksum of the file, for speed on trusted files.
+    ///
+    /// **Warning:** the contents of a corrupted file are then used as they are,
+    /// which is undefined behavior.
+    pub fn read_file_unverified(path: &Path, collation: Option<Collation>) -> Result<Self> {
+        Self::read(path, collation, false)
+    }
+
+    #[cfg(not(windows))]
+    fn read(path: &Path, collation: Option<Collation>, verify: bool) -> Result<Self> {
         // Open the file and read its length
         let file: File = File::open(path).context(FileOpen { path })?;
         let meta: Metadata = file.metadata().context(FileMeta { path })?;
-        let file_len = meta.len() as usize;
+        let file_len = usize::try_from(meta.len()).ok().context(FileTooLarge {
+            path,
+            len: meta.len(),
+        })?;
+
+        check_header_size(path, file_len)?;
 
         use std::os::unix::io::IntoRawFd;
         let fd = file.into_raw_fd();
@@ -88,46 +497,74 @@ impl DictionaryFile<'_> {
             libc::mmap(
                 std::ptr::null_mut(),
                 file_len,
-                libc::PROT_READ,
-                libc::MAP_SHARED,
+                // The private mapping is only written to convert the byte order
+                if SWAP_BYTE_ORDER {
+                    libc::PROT_READ | libc::PROT_WRITE
+                } else {
+                    libc::PROT_READ
+                },
+                if SWAP_BYTE_ORDER {
+                    libc::MAP_PRIVATE
+                } else {
+                    libc::MAP_SHARED
+                },
                 fd,
                 0,
             )
         };
 
         // Return an error if mmap failed
-        snafu::ensure!(
-            mmap_ptr != libc::MAP_FAILED,
-            FileMmap {
-                path,
-                strerror: unsafe { strerror() }.unwrap_or("Unknown")
-            }
-        );
+        if mmap_ptr == libc::MAP_FAILED {
+            let errno = unsafe { *libc::__errno_location() };
+            return Err(mmap_error(path, meta.len(), errno));
+        }
 
         // Type and read the header
-        let header = unsafe { *(mmap_ptr as *const Header) };
-
-        // Type the compiled trie
-        let trie = unsafe {
-            // Get the offset pointers to each array
-            let (nodes_ptr, chars_ptr, ranges_ptr) = Self::get_offsets_ptr(&header, mmap_ptr);
-
-            // Type each array
-            let nodes =
-                std::slice::from_raw_parts(nodes_ptr as *const CompiledTrieNode, header.nb_nodes);
+        let mut header = unsafe { *(mmap_ptr as *const Header) };
+        if SWAP_BYTE_ORDER {
+            header = header.swap_bytes();
+        }
+        let checked = check_header_format(path, &header)
+            .and_then(|_| check_file_size(path, &header, file_len))
+            .and_then(|_| match verify {
+                true => {
+                    // SAFETY: The size of the mapping has been checked
+                    let bytes =
+                        unsafe { std::slice::from_raw_parts(mmap_ptr as *const u8, file_len) };
+                    check_checksum(path, &header, &bytes[size_of::<Header>()..])
+                }
+                false => Ok(()),
+            })
+            .and_then(|_| check_collation(path, &header, collation));
+        let collation = match checked {
+            Ok(collation) => collation,
+            Err(error) => {
+                unsafe { libc::munmap(mmap_ptr, file_len) };
+                return Err(error);
+            }
+        };
 
-            let chars_u8 =
-                std::slice::from_raw_parts(chars_ptr as *const u8, header.nb_chars_bytes);
-            let chars = std::str::from_utf8_unchecked(chars_u8);
+        if SWAP_BYTE_ORDER {
+            // SAFETY: The mapping is private and writable, and its size has been checked
+            let bytes = unsafe { std::slice::from_raw_parts_mut(mmap_ptr as *mut u8, file_len) };
+            swap_byte_order(bytes, &header);
+        }
 
-            let ranges =
-                std::slice::from_raw_parts(ranges_ptr as *const RangeElement, header.nb_ranges);
+        // The advice comes after the checksum, which reads the file sequentially
+        // SAFETY: The pointer and length are the ones of the mapping
+        unsafe { advise_mapping(mmap_ptr, file_len, &header) };
 
-            // Create a borrowing compiled trie
-            CompiledTrie::from((nodes, chars, ranges))
+        // SAFETY: The size of the mapping has been checked against the header
+        let trie = match unsafe { Self::type_trie(path, &header, mmap_ptr, collation) } {
+            Ok(trie) => trie,
+            Err(error) => {
+                unsafe { libc::munmap(mmap_ptr, file_len) };
+                return Err(error);
+            }
         };
 
         Ok(Self {
+            read_buf: Vec::new(),
             mmap_ptr,
             ptr_len: file_len,
             header,
@@ -136,58 +573,153 @@ impl DictionaryFile<'_> {
     }
 
     #[cfg(windows)]
-    pub fn read_file(path: &Path) -> Result<Self> {
+    fn read(path: &Path, collation: Option<Collation>, verify: bool) -> Result<Self> {
+        Self::read_in_memory(path, collation, verify)
+    }
+
+    /// Same as [read_file](DictionaryFile::read_file), but read the whole file in memory
+    /// instead of mmaping it, so that later changes of the file do not affect the dictionary.
+    ///
+    /// This is how the files are always read on the platforms without mmap.
+    pub fn read_file_in_memory(path: &Path) -> Result<Self> {
+        Self::read_in_memory(path, None, true)
+    }
+
+    fn read_in_memory(path: &Path, collation: Option<Collation>, verify: bool) -> Result<Self> {
+        use std::io::Read;
+
         // Open the file and read its length
         let mut file: File = File::open(path).context(FileOpen { path })?;
         let meta: Metadata = file.metadata().context(FileMeta { path })?;
-        let file_len = meta.len() as usize;
+        let file_len = usize::try_from(meta.len()).ok().context(FileTooLarge {
+            path,
+            len: meta.len(),
+        })?;
 
-        let (mmap_ptr, read_bytes) = {
-            use std::io::Read;
+        check_header_size(path, file_len)?;
 
-            let mut buf = vec![0; file_len];
-            file.read_exact(&mut buf).context(FileRead { path })?;
-
-            (buf.as_ptr() as *mut c_void, buf)
-        };
+        // Read the file in an aligned buffer of at least its length
+        let mut read_buf = vec![0u64; file_len / size_of::<u64>() + 1];
+        // SAFETY: The buffer has at least file_len bytes, which can have any value
+        let bytes =
+            unsafe { std::slice::from_raw_parts_mut(read_buf.as_mut_ptr() as *mut u8, file_len) };
+        file.read_exact(bytes).context(FileRead { path })?;
 
         // Type and read the header
-        let header = unsafe { *(mmap_ptr as *const Header) };
-
-        // Type the compiled trie
-        let trie = unsafe {
-            // Get the offset pointers to each array
-            let (nodes_ptr, chars_ptr, ranges_ptr) = Self::get_offsets_ptr(&header, mmap_ptr);
-
-            // Type each array
-            let nodes =
-                std::slice::from_raw_parts(nodes_ptr as *const CompiledTrieNode, header.nb_nodes);
-
-            let chars_u8 =
-                std::slice::from_raw_parts(chars_ptr as *const u8, header.nb_chars_bytes);
-            // The string has already been checked during compilation so we don't need to here
-            // (also everything here is extremely unsafe and relies on the fact that the file has not been modified)
-            let chars = std::str::from_utf8_unchecked(chars_u8);
+        let mut header = unsafe { *(bytes.as_ptr() as *const Header) };
+        if SWAP_BYTE_ORDER {
+            header = header.swap_bytes();
+        }
+        check_header_format(path, &header)?;
+        check_file_size(path, &header, file_len)?;
+        if verify {
+            check_checksum(path, &header, &bytes[size_of::<Header>()..])?;
+        }
+        let collation = check_collation(path, &header, collation)?;
 
-            let ranges =
-                std::slice::from_raw_parts(ranges_ptr as *const RangeElement, header.nb_ranges);
+        if SWAP_BYTE_ORDER {
+            swap_byte_order(bytes, &header);
+        }
 
-            // Create a borrowing compiled trie
-            CompiledTrie::from((nodes, chars, ranges))
+        // SAFETY: The size of the buffer has been checked against the header,
+        // and its heap allocation does not move with it
+        let trie = unsafe {
+            Self::type_trie(path, &header, read_buf.as_ptr() as *const c_void, collation)?
         };
 
         Ok(Self {
-            read_bytes,
-            mmap_ptr,
-            ptr_len: file_len,
+            read_buf,
+            mmap_ptr: std::ptr::null(),
+            ptr_len: 0,
             header,
             trie,
         })
     }
 
-    /// Try to write the dictionary to a file.
-    /// The file contents is not portable and must be read using the
+    /// Return the successive parts of the file, in the order they must be written.
+    ///
+    /// The arrays are written as they are in memory, except for the padding bytes of the nodes
+    /// and ranges of the `large-index` feature which are zeroed: the same trie then always
+    /// gives the same file.
+    fn file_contents<'b>(&'b self, padding: &'b [u8]) -> Vec<Cow<'b, [u8]>> {
+        #[cfg(not(feature = "large-index"))]
+        let (nodes, ranges) = (
+            Cow::Borrowed(self.trie.nodes().as_bytes()),
+            Cow::Borrowed(self.trie.ranges().as_bytes()),
+        );
+        #[cfg(feature = "large-index")]
+        let (nodes, ranges) = (
+            Cow::Owned(crate::trie::trie_node::nodes_bytes(self.trie.nodes())),
+            Cow::Owned(crate::trie::trie_node::ranges_bytes(self.trie.ranges())),
+        );
+
+        // Write in the correct order:
+        // - Header
+        // - Nodes
+        // - Chars (padded to align the next arrays)
+        // - Ranges
+        // - Lengths and their parents (only with the `length-index` feature)
+        // - Trigrams (only with the `ngram-model` feature)
+        // - Source lines (only with the `source-lines` feature)
+        vec![
+            Cow::Borrowed(as_bytes(&self.header)),
+            nodes,
+            Cow::Borrowed(self.trie.chars().as_bytes()),
+            Cow::Borrowed(&padding[..chars_padding(&self.header)]),
+            ranges,
+            #[cfg(feature = "length-index")]
+            Cow::Borrowed(self.trie.lengths().as_bytes()),
+            #[cfg(feature = "length-index")]
+            Cow::Borrowed(self.trie.length_parents().as_bytes()),
+            #[cfg(feature = "ngram-model")]
+            Cow::Borrowed(self.trie.trigrams().as_bytes()),
+            #[cfg(feature = "source-lines")]
+            Cow::Borrowed(self.trie.source_lines().as_bytes()),
+        ]
+    }
+
+    /// Return the header of the dictionary, describing the size of its arrays.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Check a few nodes of the trie and look up one of its words
+    /// (see [validate_sample](CompiledTrie::validate_sample)), a fast alternative to
+    /// [validate](CompiledTrie::validate) for the large dictionaries.
+    ///
+    /// It catches the gross corruptions not detected by the checksum, e.g. of a file read
+    /// with [read_file_unverified](DictionaryFile::read_file_unverified).
+    pub fn quick_check(&self) -> Result<()> {
+        const NB_SAMPLES: usize = 64;
+        self.trie.validate_sample(NB_SAMPLES)
+    }
+
+    /// Touch a node of each page of the nodes, so that the first searches of
+    /// a latency-sensitive server do not wait for them to be read from the file.
+    ///
+    /// The dictionaries read in memory are already loaded, this only walks their pages.
+    pub fn prefault(&self) {
+        // The smallest usual page size, the larger ones also being touched
+        const PAGE_SIZE: usize = 4096;
+        let stride = (PAGE_SIZE / size_of::<CompiledTrieNode>()).max(1);
+        let nodes = self.trie.nodes();
+        for node in nodes.iter().step_by(stride).chain(nodes.last()) {
+            // SAFETY: The reference points to a node of the trie
+            unsafe { std::ptr::read_volatile(node) };
+        }
+    }
+
+    /// Return the number of bytes of the file written by [write_file](DictionaryFile::write_file).
+    pub fn file_size(&self) -> usize {
+        let padding = [0u8; std::mem::align_of::<RangeElement>()];
+        self.file_contents(&padding).iter().map(|b| b.len()).sum()
+    }
+
+    /// Try to write the dictionary to a file, to be read using the
     /// [read_file](DictionaryFile::read_file) method.
+    ///
+    /// The integers are written in the native byte order, or in little-endian with the
+    /// `portable` feature. In both cases the file is specific to the pointer width.
     pub fn write_file(&self, path: &Path) -> Result<()> {
         let mut file = OpenOptions::new()
             .write(true)
@@ -196,29 +728,57 @@ impl DictionaryFile<'_> {
             .open(path)
             .context(FileOpen { path })?;
 
-        // Write in the correct order:
-        // - Header
-        // - Nodes
-        // - Chars
-        // - Ranges
-        let contents = [
-            as_bytes(&self.header),
-            self.trie.nodes().as_bytes(),
-            self.trie.chars().as_bytes(),
-            self.trie.ranges().as_bytes(),
-        ];
-
-        for bytes in &contents {
-            file.write_all(bytes).context(FileWrite { path })?;
+        // The checksum is the one of the bytes as they are written
+        let padding = [0u8; std::mem::align_of::<RangeElement>()];
+        if SWAP_BYTE_ORDER {
+            let mut bytes = self.file_contents(&padding).concat();
+            swap_byte_order(&mut bytes, &self.header);
+            let header = Header {
+                checksum: crc32(&[&bytes[size_of::<Header>()..]]),
+                ..self.header
+            };
+            bytes[..size_of::<Header>()].copy_from_slice(as_bytes(&header.swap_bytes()));
+            file.write_all(&bytes).context(FileWrite { path })?;
+        } else {
+            let contents = self.file_contents(&padding);
+            let parts: Vec<&[u8]> = contents[1..].iter().map(|part| part.as_ref()).collect();
+            let header = Header {
+                checksum: crc32(&parts),
+                ..self.header
+            };
+            file.write_all(as_bytes(&header))
+                .context(FileWrite { path })?;
+            for bytes in parts {
+                file.write_all(bytes).context(FileWrite { path })?;
+            }
         }
 
         eprintln!(
             "File is composed of:\n- nodes: {} bytes\n- chars: {} bytes\n- ranges: {} bytes",
             self.trie.nodes().as_bytes().len(),
-            self.trie.chars().as_bytes().len(),
+            self.trie.chars().len(),
             self.trie.ranges().as_bytes().len()
         );
 
+        #[cfg(feature = "ngram-model")]
+        eprintln!(
+            "- trigrams: {} bytes",
+            self.trie.trigrams().as_bytes().len()
+        );
+
+        #[cfg(feature = "source-lines")]
+        eprintln!(
+            "- source lines: {} bytes",
+            self.trie.source_lines().as_bytes().len()
+        );
+
+        #[cfg(feature = "length-index")]
+        eprintln!(
+            "- lengths: {} bytes\n- length parents: {} bytes",
+            self.trie.lengths().as_bytes().len(),
+            self.trie.length_parents().as_bytes().len()
+        );
+
         Ok(())
     }
 }
@@ -226,25 +786,42 @@ impl DictionaryFile<'_> {
 #[cfg(not(windows))]
 impl Drop for DictionaryFile<'_> {
     fn drop(&mut self) {
-        // munmap the inner pointer if the struct was read from a file
-        if self.mmap_ptr.is_null() {
+        // munmap the inner pointer if the struct was mmaped from a file
+        if !self.mmap_ptr.is_null() {
             unsafe { libc::munmap(self.mmap_ptr as *mut c_void, self.ptr_len) };
         }
     }
 }
 
-impl<'a> From<CompiledTrie<'a>> for DictionaryFile<'a> {
-    fn from(trie: CompiledTrie<'a>) -> Self {
-        let header = Header {
+impl From<&CompiledTrie<'_>> for Header {
+    fn from(trie: &CompiledTrie<'_>) -> Self {
+        Header {
+            magic: Header::MAGIC,
+            version: Header::VERSION,
             nb_nodes: trie.nodes().len(),
-            nb_chars_bytes: trie.chars().as_bytes().len(),
+            nb_chars_bytes: trie.chars().len(),
             nb_ranges: trie.ranges().len(),
-        };
+            collation_id: trie.collation().id(),
+            checksum: 0,
+            #[cfg(feature = "length-index")]
+            nb_lengths: trie.lengths().len(),
+            #[cfg(feature = "length-index")]
+            nb_length_parents: trie.length_parents().len(),
+            #[cfg(feature = "ngram-model")]
+            nb_trigrams: trie.trigrams().len(),
+            #[cfg(feature = "source-lines")]
+            nb_source_lines: trie.source_lines().len(),
+        }
+    }
+}
+
+impl<'a> From<CompiledTrie<'a>> for DictionaryFile<'a> {
+    fn from(trie: CompiledTrie<'a>) -> Self {
+        let header = Header::from(&trie);
 
         // Create a dictionary that is not mapped to a file
         Self {
-            #[cfg(windows)]
-            read_bytes: Vec::new(),
+            read_buf: Vec::new(),
             mmap_ptr: std::ptr::null(),
             ptr_len: 0,
             header,
@@ -252,3 +829,420 @@ impl<'a> From<CompiledTrie<'a>> for DictionaryFile<'a> {
         }
     }
 }
+
+#[cfg(all(test, not(windows)))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_mmap_error_too_large() {
+        let path = Path::new("dict.bin");
+
+        let error = mmap_error(path, 1 << 40, libc::ENOMEM);
+        assert!(matches!(error, Error::FileTooLarge { len, .. } if len == 1 << 40));
+        assert!(error
+            .to_string()
+            .contains("does not fit in the address space"));
+
+        let error = mmap_error(path, 10, libc::EACCES);
+        assert!(matches!(error, Error::FileMmap { .. }));
+    }
+
+    #[test]
+    fn test_read_file_size_mismatch() {
+        let path =
+            std::env::temp_dir().join(format!("vague-search-dict-{}.bin", std::process::id()));
+        let nodes: &[CompiledTrieNode] = &[];
+        let ranges: &[RangeElement] = &[];
+        let mut dict = DictionaryFile::from(CompiledTrie::from((nodes, "abc", ranges)));
+        dict.write_file(&path).unwrap();
+        let read = DictionaryFile::read_file(&path).unwrap();
+        assert_eq!(read.trie.chars(), "abc");
+        drop(read);
+
+        // A header with more nodes than the file contains
+        dict.header.nb_nodes = 1 << 20;
+        dict.write_file(&path).unwrap();
+        let error = DictionaryFile::read_file(&path).unwrap_err();
+        assert!(matches!(error, Error::SizeMismatch { len, .. } if len == dict.file_size()));
+
+        // Counts which would overflow the size of the file
+        dict.header.nb_ranges = usize::MAX;
+        dict.write_file(&path).unwrap();
+        let error = DictionaryFile::read_file(&path).unwrap_err();
+        assert!(matches!(error, Error::SizeMismatch { .. }));
+
+        // A file too small to contain the header
+        std::fs::write(&path, b"abc").unwrap();
+        let error = DictionaryFile::read_file(&path).unwrap_err();
+        assert!(matches!(error, Error::SizeMismatch { expected, len: 3, .. }
+            if expected == size_of::<Header>() as u128));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_file_bad_header() {
+        let path =
+            std::env::temp_dir().join(format!("vague-search-header-{}.bin", std::process::id()));
+        let nodes: &[CompiledTrieNode] = &[];
+        let ranges: &[RangeElement] = &[];
+        let mut dict = DictionaryFile::from(CompiledTrie::from((nodes, "abc", ranges)));
+        assert_eq!(dict.header.magic, Header::MAGIC);
+        assert_eq!(dict.header.version, Header::VERSION);
+
+        // A file which is not a dictionary
+        let mut foreign = b"PK\x03\x04".to_vec();
+        foreign.resize(dict.file_size(), 0xFF);
+        std::fs::write(&path, &foreign).unwrap();
+        let error = DictionaryFile::read_file(&path).unwrap_err();
+        assert!(
+            matches!(error, Error::BadMagic { found, .. } if &found == b"PK\x03\x04"),
+            "{}",
+            error
+        );
+
+        // A dictionary of another version, even with counts describing the file
+        dict.header.version = Header::VERSION + 1;
+        dict.write_file(&path).unwrap();
+        let error = DictionaryFile::read_file(&path).unwrap_err();
+        assert!(
+            matches!(error, Error::VersionMismatch { expected, found, .. }
+            if expected == Header::VERSION && found == Header::VERSION + 1)
+        );
+
+        // The magic number is checked before the counts
+        dict.header.magic = *b"VSDX";
+        dict.header.nb_nodes = usize::MAX;
+        dict.write_file(&path).unwrap();
+        let error = DictionaryFile::read_file(&path).unwrap_err();
+        assert!(matches!(error, Error::BadMagic { .. }));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_file_checksum() {
+        let path =
+            std::env::temp_dir().join(format!("vague-search-checksum-{}.bin", std::process::id()));
+        let nodes: &[CompiledTrieNode] = &[];
+        let ranges: &[RangeElement] = &[];
+        let dict = DictionaryFile::from(CompiledTrie::from((nodes, "abc", ranges)));
+        dict.write_file(&path).unwrap();
+        // The characters are followed by padding bytes
+        let padding = vec![0; chars_padding(&dict.header)];
+        let read = DictionaryFile::read_file(&path).unwrap();
+        assert_eq!(read.header.checksum, crc32(&[b"abc", &padding]));
+        drop(read);
+
+        // A corrupted character
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[size_of::<Header>() + 1] = b'x';
+        std::fs::write(&path, &bytes).unwrap();
+        let error = DictionaryFile::read_file(&path).unwrap_err();
+        assert!(
+            matches!(error, Error::ChecksumMismatch { expected, found, .. }
+                if expected == crc32(&[b"abc", &padding]) && found == crc32(&[b"axc", &padding])),
+            "{}",
+            error
+        );
+
+        // The verification can be skipped
+        let read = DictionaryFile::read_file_unverified(&path, None).unwrap();
+        assert_eq!(read.trie.chars(), "axc");
+        drop(read);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_file_byte_order() {
+        use crate::NaiveNode;
+        use std::num::NonZeroU32;
+
+        let path =
+            std::env::temp_dir().join(format!("vague-search-endian-{}.bin", std::process::id()));
+        let naive = NaiveNode {
+            index_first_child: None,
+            word_freq: NonZeroU32::new(3),
+            character: 'a',
+        };
+        let nodes = [CompiledTrieNode::new_naive(naive, 0)];
+        let ranges = [RangeElement {
+            index_first_child: None,
+            word_freq: NonZeroU32::new(7),
+        }];
+        let dict = DictionaryFile::from(CompiledTrie::from((&nodes[..], "abc", &ranges[..])));
+        dict.write_file(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+
+        // The same dictionary written in the other byte order, where only the characters
+        // are kept as they are
+        let mut swapped = bytes.clone();
+        swap_byte_order(&mut swapped, &dict.header);
+        let word = |bytes: &[u8], i: usize| {
+            u32::from_ne_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]])
+        };
+        let arrays = word_arrays(&dict.header);
+        #[cfg(not(feature = "large-index"))]
+        assert_eq!(word(&swapped, arrays[1].start + 4), 7u32.swap_bytes());
+        assert_eq!(word(&swapped, 4), Header::VERSION.swap_bytes());
+        assert_eq!(&swapped[arrays[0].end..arrays[0].end + 3], b"abc");
+
+        std::fs::write(&path, &swapped).unwrap();
+        let error = DictionaryFile::read_file(&path).unwrap_err();
+        assert!(
+            matches!(error, Error::ByteOrderMismatch { .. }),
+            "{}",
+            error
+        );
+
+        // Converting it back gives the original file
+        swap_byte_order(&mut swapped, &dict.header);
+        assert_eq!(swapped, bytes);
+        std::fs::write(&path, &swapped).unwrap();
+        let read = DictionaryFile::read_file(&path).unwrap();
+        assert_eq!(read.trie.chars(), "abc");
+        assert_eq!(read.trie.ranges()[0].word_freq, NonZeroU32::new(7));
+        drop(read);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_file_in_memory() {
+        use crate::NaiveNode;
+        use std::num::NonZeroU32;
+
+        let path =
+            std::env::temp_dir().join(format!("vague-search-memory-{}.bin", std::process::id()));
+        let naive = NaiveNode {
+            index_first_child: None,
+            word_freq: NonZeroU32::new(3),
+            character: 'a',
+        };
+        let nodes = [CompiledTrieNode::new_naive(naive, 0)];
+        let ranges = [RangeElement {
+            index_first_child: None,
+            word_freq: NonZeroU32::new(7),
+        }];
+        let dict = DictionaryFile::from(CompiledTrie::from((&nodes[..], "abcde", &ranges[..])));
+        dict.write_file(&path).unwrap();
+
+        // The same contents as when the file is mmaped
+        let mapped = DictionaryFile::read_file(&path).unwrap();
+        let read = DictionaryFile::read_file_in_memory(&path).unwrap();
+        assert!(read.mmap_ptr.is_null());
+        assert!(read.read_buf.len() * size_of::<u64>() >= dict.file_size());
+        assert_eq!(read.trie.nodes().as_bytes(), dict.trie.nodes().as_bytes());
+        assert_eq!(read.trie.nodes().as_bytes(), mapped.trie.nodes().as_bytes());
+        assert_eq!(read.trie.chars(), "abcde");
+        assert_eq!(read.trie.ranges(), &ranges[..]);
+        assert_eq!(read.header.checksum, mapped.header.checksum);
+        drop(mapped);
+
+        // The dictionary does not change with the file
+        std::fs::write(&path, b"abc").unwrap();
+        assert_eq!(read.trie.chars(), "abcde");
+        let error = DictionaryFile::read_file_in_memory(&path).unwrap_err();
+        assert!(matches!(error, Error::SizeMismatch { len: 3, .. }));
+        drop(read);
+
+        // The file is checked like when it is mmaped
+        dict.write_file(&path).unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+        *bytes.last_mut().unwrap() ^= 1;
+        std::fs::write(&path, &bytes).unwrap();
+        let error = DictionaryFile::read_file_in_memory(&path).unwrap_err();
+        assert!(matches!(error, Error::ChecksumMismatch { .. }));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_prefault() {
+        use crate::NaiveNode;
+        use std::num::NonZeroU32;
+
+        let path =
+            std::env::temp_dir().join(format!("vague-search-prefault-{}.bin", std::process::id()));
+        let naive = |character, nb_siblings| {
+            let node = NaiveNode {
+                index_first_child: None,
+                word_freq: NonZeroU32::new(3),
+                character,
+            };
+            CompiledTrieNode::new_naive(node, nb_siblings)
+        };
+        // More nodes than in a page
+        let nodes: Vec<_> = (0..1000u32)
+            .map(|i| naive(std::char::from_u32(0x100 + i).unwrap(), 999 - i))
+            .collect();
+        let ranges: &[RangeElement] = &[];
+        let dict = DictionaryFile::from(CompiledTrie::from((&nodes[..], "", ranges)));
+        dict.write_file(&path).unwrap();
+
+        for read in &[
+            DictionaryFile::read_file(&path).unwrap(),
+            DictionaryFile::read_file_in_memory(&path).unwrap(),
+        ] {
+            read.prefault();
+            assert_eq!(read.trie.nodes().as_bytes(), dict.trie.nodes().as_bytes());
+            assert!(read.trie.contains("\u{100}"));
+            assert!(read.trie.contains("\u{4e7}"));
+        }
+
+        // The dictionaries without nodes
+        let nodes: &[CompiledTrieNode] = &[];
+        DictionaryFile::from(CompiledTrie::from((nodes, "", ranges))).prefault();
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_quick_check() {
+        use crate::NaiveNode;
+        use std::num::NonZeroU32;
+
+        let path =
+            std::env::temp_dir().join(format!("vague-search-quick-{}.bin", std::process::id()));
+        let naive = |character, nb_siblings| {
+            let node = NaiveNode {
+                index_first_child: None,
+                word_freq: NonZeroU32::new(3),
+                character,
+            };
+            CompiledTrieNode::new_naive(node, nb_siblings)
+        };
+        let nodes = [naive('a', 1), naive('b', 0)];
+        let ranges: &[RangeElement] = &[];
+        let dict = DictionaryFile::from(CompiledTrie::from((&nodes[..], "", ranges)));
+        dict.write_file(&path).unwrap();
+        let read = DictionaryFile::read_file(&path).unwrap();
+        assert!(read.quick_check().is_ok());
+        drop(read);
+
+        // The second node with more siblings than the nodes after it
+        let original = std::fs::read(&path).unwrap();
+        let mut bytes = original.clone();
+        let flags =
+            size_of::<Header>() + size_of::<CompiledTrieNode>() + CompiledTrieNode::flags_offset();
+        bytes[flags..flags + 4].copy_from_slice(&5u32.to_ne_bytes());
+        std::fs::write(&path, &bytes).unwrap();
+        let read = DictionaryFile::read_file_unverified(&path, None).unwrap();
+        assert!(matches!(
+            read.quick_check(),
+            Err(Error::TrieMalformed { index: 1, .. })
+        ));
+        drop(read);
+
+        // The second node with the type bits flipped to the fourth type, which is not a type
+        let mut bytes = original;
+        let mut node_flags = [0; 4];
+        node_flags.copy_from_slice(&bytes[flags..flags + 4]);
+        let node_flags = u32::from_ne_bytes(node_flags) ^ 0xC000_0000;
+        bytes[flags..flags + 4].copy_from_slice(&node_flags.to_ne_bytes());
+        std::fs::write(&path, &bytes).unwrap();
+        let read = DictionaryFile::read_file_unverified(&path, None).unwrap();
+        assert!(matches!(
+            read.quick_check(),
+            Err(Error::TrieMalformed { index: 1, .. })
+        ));
+        drop(read);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_file_invalid_utf8() {
+        let path =
+            std::env::temp_dir().join(format!("vague-search-utf8-{}.bin", std::process::id()));
+        let nodes: &[CompiledTrieNode] = &[];
+        let ranges: &[RangeElement] = &[];
+        let dict = DictionaryFile::from(CompiledTrie::from((nodes, "abc", ranges)));
+        dict.write_file(&path).unwrap();
+
+        // A byte of the characters replaced by a byte which never appears in UTF-8,
+        // detected even if the checksum is not verified
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[size_of::<Header>() + 1] = 0xFF;
+        std::fs::write(&path, &bytes).unwrap();
+        let error = DictionaryFile::read_file_unverified(&path, None).unwrap_err();
+        assert!(matches!(
+            error,
+            Error::InvalidUtf8 {
+                array: "characters",
+                ..
+            }
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "length-index")]
+    #[test]
+    fn test_read_file_length_index() {
+        let path =
+            std::env::temp_dir().join(format!("vague-search-lengths-{}.bin", std::process::id()));
+        let dict = DictionaryFile::from(crate::test_utils::create_trie());
+        dict.write_file(&path).unwrap();
+
+        // The words of the length index are rebuilt from the nodes of the file
+        let words = |trie: &CompiledTrie, len| trie.words_of_length(len).collect::<Vec<_>>();
+        for read in &[
+            DictionaryFile::read_file(&path).unwrap(),
+            DictionaryFile::read_file_in_memory(&path).unwrap(),
+        ] {
+            assert_eq!(read.trie.length_parents().len(), dict.trie.nodes().len());
+            for len in 0..6 {
+                assert_eq!(words(&read.trie, len), words(&dict.trie, len));
+            }
+            assert_eq!(words(&read.trie, 3).len(), 2);
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_file_collation() {
+        let path =
+            std::env::temp_dir().join(format!("vague-search-collation-{}.bin", std::process::id()));
+        let nodes: &[CompiledTrieNode] = &[];
+        let ranges: &[RangeElement] = &[];
+        let trie = || CompiledTrie::from((nodes, "abc", ranges));
+
+        // The built-in collations are found from the header
+        let dict = DictionaryFile::from(trie().with_collation(Collation::CASE_FOLDED));
+        dict.write_file(&path).unwrap();
+        let read = DictionaryFile::read_file(&path).unwrap();
+        assert_eq!(read.trie.collation().id(), Collation::CASE_FOLDED.id());
+        drop(read);
+        let read = DictionaryFile::read_file_with_collation(&path, Collation::CASE_FOLDED).unwrap();
+        assert_eq!(read.trie.collation().id(), Collation::CASE_FOLDED.id());
+        drop(read);
+        let error =
+            DictionaryFile::read_file_with_collation(&path, Collation::CODE_POINT).unwrap_err();
+        assert!(matches!(
+            error,
+            Error::CollationMismatch {
+                expected: 0,
+                found: 1,
+                ..
+            }
+        ));
+
+        // A custom collation must be given to read the file
+        let reverse =
+            Collation::custom(std::num::NonZeroU32::new(300).unwrap(), |a, b| b.cmp(&a)).unwrap();
+        let dict = DictionaryFile::from(trie().with_collation(reverse));
+        dict.write_file(&path).unwrap();
+        let error = DictionaryFile::read_file(&path).unwrap_err();
+        assert!(matches!(error, Error::UnknownCollation { id: 300, .. }));
+        let read = DictionaryFile::read_file_with_collation(&path, reverse).unwrap();
+        assert_eq!(read.trie.collation().id(), 300);
+        assert_eq!(read.trie.chars(), "abc");
+        drop(read);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -18,6 +18,92 @@ pub enum Error {
     },
     #[snafu(display("Could not mmap file {}: {}", path.display(), strerror))]
     FileMmap { path: PathBuf, strerror: String },
+    #[snafu(display(
+        "Could not map file {} of {} bytes: the whole dictionary does not fit in the address space \
+        (use a 64-bit build or raise the virtual memory limit)",
+        path.display(),
+        len
+    ))]
+    FileTooLarge { path: PathBuf, len: u64 },
+    #[snafu(display(
+        "Invalid dictionary file {}: it does not begin with the dictionary magic number \
+        (found {:?})",
+        path.display(),
+        found
+    ))]
+    BadMagic { path: PathBuf, found: [u8; 4] },
+    #[snafu(display(
+        "Invalid dictionary file {}: it has been written with another byte order \
+        (compile it again on this machine, or build both binaries with the `portable` feature)",
+        path.display()
+    ))]
+    ByteOrderMismatch { path: PathBuf },
+    #[snafu(display(
+        "Invalid dictionary file {}: it has the format version {} but version {} is expected \
+        (compile it again with the same version of the binaries)",
+        path.display(),
+        found,
+        expected
+    ))]
+    VersionMismatch {
+        path: PathBuf,
+        expected: u32,
+        found: u32,
+    },
+    #[snafu(display(
+        "Invalid dictionary file {}: its checksum is {:08x} but its contents have the checksum \
+        {:08x} (corrupted or partially written file)",
+        path.display(),
+        expected,
+        found
+    ))]
+    ChecksumMismatch {
+        path: PathBuf,
+        expected: u32,
+        found: u32,
+    },
+    #[snafu(display(
+        "Invalid dictionary file {}: its header describes {} bytes but the file has {} bytes \
+        (truncated file, or compiled with other features)",
+        path.display(),
+        expected,
+        len
+    ))]
+    SizeMismatch {
+        path: PathBuf,
+        expected: u128,
+        len: usize,
+    },
+    #[snafu(display(
+        "Invalid dictionary file {}: it has been compiled with the collation {} \
+        but is read with the collation {}",
+        path.display(),
+        found,
+        expected
+    ))]
+    CollationMismatch {
+        path: PathBuf,
+        expected: u32,
+        found: u32,
+    },
+    #[snafu(display(
+        "Invalid dictionary file {}: it has been compiled with the unknown collation {} \
+        (read it with the same custom collation)",
+        path.display(),
+        id
+    ))]
+    UnknownCollation { path: PathBuf, id: u32 },
+    #[snafu(display(
+        "Invalid dictionary file {}: its {} are not valid UTF-8 from their byte {}",
+        path.display(),
+        array,
+        source.valid_up_to()
+    ))]
+    InvalidUtf8 {
+        path: PathBuf,
+        array: &'static str,
+        source: std::str::Utf8Error,
+    },
     #[snafu(display("Could not read in file {}: {}", path.display(), source))]
     FileRead {
         path: PathBuf,
@@ -28,4 +114,19 @@ pub enum Error {
         path: PathBuf,
         source: std::io::Error,
     },
+    #[snafu(display(
+        "Invalid trie, multiple siblings begin with {:?} at node {}",
+        character,
+        index
+    ))]
+    TrieDuplicateChar { index: usize, character: char },
+    #[snafu(display("Invalid trie at node {}: {}", index, cause))]
+    TrieMalformed { index: usize, cause: &'static str },
+    #[snafu(display(
+        "Could not compile the {} children of the words beginning with {:?}: \
+        they are too many siblings to be gathered in ranges with the collation",
+        nb_children,
+        prefix
+    ))]
+    TrieTooManySiblings { prefix: String, nb_children: usize },
 }
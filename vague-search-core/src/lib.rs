@@ -2,11 +2,32 @@
 //!
 //! Define shared data structures and functions to be used in both binaries.
 
+mod collation;
 mod dictionary_file;
 mod error;
+mod phrase;
+#[cfg(test)]
+mod test_utils;
 mod trie;
 mod utils;
 
+pub use collation::Collation;
 pub use dictionary_file::*;
 pub use error::{Error, Result};
-pub use trie::{compiled_trie::*, index::*, trie_node::*, trie_node_interface::*};
+pub use phrase::{escape_phrase, unescape_phrase};
+pub use trie::{
+    builder::TrieBuilder, compiled_trie::*, from_trie::CompileOptions, index::*, trie_node::*,
+    trie_node_interface::*, walk::TrieStep,
+};
+
+#[cfg(feature = "length-index")]
+pub use trie::length_index::{LengthElement, LengthParent, LengthParentSlice, LengthSlice};
+
+#[cfg(feature = "ngram-model")]
+pub use trie::ngram_model::{TrigramElement, TrigramSlice};
+
+#[cfg(feature = "sample")]
+pub use trie::sample::Rng;
+
+#[cfg(feature = "source-lines")]
+pub use trie::source_lines::{SourceLineElement, SourceLineSlice};
@@ -0,0 +1,92 @@
+//! Escaping of the spaces inside multi-word entries (phrases), such as `new york`.
+//!
+//! The words of the dictionary file and of the queries are separated by whitespace,
+//! so a phrase is written as a single token where a separator character stands for each
+//! of its spaces, e.g. `new_york` with the `_` separator.
+//! A backslash escapes the next character, to write the separator (`\_`)
+//! or a backslash (`\\`) itself.
+//!
+//! Both binaries must be given the same separator so that the phrases round-trip:
+//! the compiled dictionary stores the real spaces.
+
+use std::borrow::Cow;
+
+/// The character escaping the next one in a phrase token.
+const ESCAPE: char = '\\';
+
+/// Return the phrase written in the token, replacing each unescaped `separator`
+/// character by a space and removing the escaping backslashes.
+/// A backslash at the end of the token is kept as is.
+pub fn unescape_phrase(token: &str, separator: char) -> Cow<'_, str> {
+    if !token.contains(&[separator, ESCAPE][..]) {
+        return Cow::Borrowed(token);
+    }
+
+    let mut phrase = String::with_capacity(token.len());
+    let mut chars = token.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            ESCAPE => phrase.push(chars.next().unwrap_or(ESCAPE)),
+            c if c == separator => phrase.push(' '),
+            c => phrase.push(c),
+        }
+    }
+    Cow::Owned(phrase)
+}
+
+/// Return the token of the phrase, the inverse of [unescape_phrase](unescape_phrase):
+/// each space is replaced by the `separator`, and the separator and backslash
+/// characters are escaped.
+pub fn escape_phrase(phrase: &str, separator: char) -> Cow<'_, str> {
+    if !phrase.contains(&[' ', separator, ESCAPE][..]) {
+        return Cow::Borrowed(phrase);
+    }
+
+    let mut token = String::with_capacity(phrase.len() + 1);
+    for c in phrase.chars() {
+        match c {
+            ' ' => token.push(separator),
+            c if c == separator || c == ESCAPE => {
+                token.push(ESCAPE);
+                token.push(c);
+            }
+            c => token.push(c),
+        }
+    }
+    Cow::Owned(token)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_unescape_phrase() {
+        assert_eq!(unescape_phrase("new_york", '_'), "new york");
+        assert_eq!(unescape_phrase("_a__b_", '_'), " a  b ");
+        assert_eq!(unescape_phrase(r"snake\_case", '_'), "snake_case");
+        assert_eq!(unescape_phrase(r"back\\_slash", '_'), r"back\ slash");
+        assert_eq!(unescape_phrase(r"end\", '_'), r"end\");
+        assert_eq!(unescape_phrase("new+york", '+'), "new york");
+        assert_eq!(unescape_phrase("new_york", '+'), "new_york");
+
+        assert!(matches!(
+            unescape_phrase("word", '_'),
+            Cow::Borrowed("word")
+        ));
+    }
+
+    #[test]
+    fn test_escape_phrase() {
+        assert_eq!(escape_phrase("new york", '_'), "new_york");
+        assert_eq!(escape_phrase(" a  b ", '_'), "_a__b_");
+        assert_eq!(escape_phrase("snake_case", '_'), r"snake\_case");
+        assert_eq!(escape_phrase(r"back\ slash", '_'), r"back\\_slash");
+        assert!(matches!(escape_phrase("word", '_'), Cow::Borrowed("word")));
+
+        for phrase in &["new york", "snake_case", r"back\ slash", r"end\", "_a b_"] {
+            let token = escape_phrase(phrase, '_');
+            assert_eq!(unescape_phrase(&token, '_'), *phrase);
+        }
+    }
+}
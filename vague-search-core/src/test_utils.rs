@@ -0,0 +1,74 @@
+//! Helpers shared by the unit tests of the core library.
+
+use crate::{CompileOptions, CompiledTrie, TrieBuilder, TrieNodeDrainer};
+use std::num::NonZeroU32;
+
+/// A simple trie node, used to create [CompiledTrie](CompiledTrie) in tests.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct NodeDrainer {
+    pub characters: String,
+    pub frequency: Option<NonZeroU32>,
+    pub children: Vec<Self>,
+}
+
+impl TrieNodeDrainer for NodeDrainer {
+    fn drain_characters(&mut self) -> String {
+        std::mem::take(&mut self.characters)
+    }
+
+    fn frequency(&self) -> Option<NonZeroU32> {
+        self.frequency
+    }
+
+    fn drain_children(&mut self) -> Vec<Self> {
+        std::mem::take(&mut self.children)
+    }
+}
+
+/// Create a node of the characters with its children, which is not a word if `freq` is 0.
+pub fn create_node(s: &str, freq: u32, children: Vec<NodeDrainer>) -> NodeDrainer {
+    NodeDrainer {
+        characters: s.to_string(),
+        frequency: NonZeroU32::new(freq),
+        children,
+    }
+}
+
+/// Create a node of a single character with its children, which is not a word if `freq` is 0.
+pub fn create_simple(character: char, freq: u32, children: Vec<NodeDrainer>) -> NodeDrainer {
+    create_node(&character.to_string(), freq, children)
+}
+
+/// Compile the words with their frequency, inserted in any order.
+pub fn create_words_trie(words: &[(&str, u32)]) -> CompiledTrie<'static> {
+    let mut builder = TrieBuilder::new(CompileOptions::default());
+    builder.extend(
+        words
+            .iter()
+            .map(|&(word, freq)| (word, NonZeroU32::new(freq).unwrap())),
+    );
+    builder.compile()
+}
+
+/// The words "a", "ab", "abcd", "ax", "b", "be", "car", "dog", "dogs" and "日本",
+/// with "a", "b" and "c" in a range node.
+pub fn create_trie() -> CompiledTrie<'static> {
+    CompiledTrie::from(create_node(
+        "",
+        0,
+        vec![
+            create_node(
+                "a",
+                1,
+                vec![
+                    create_node("b", 2, vec![create_node("cd", 3, vec![])]),
+                    create_node("x", 4, vec![]),
+                ],
+            ),
+            create_node("b", 5, vec![create_node("e", 6, vec![])]),
+            create_node("c", 0, vec![create_node("ar", 7, vec![])]),
+            create_node("dog", 8, vec![create_node("s", 9, vec![])]),
+            create_node("日本", 10, vec![]),
+        ],
+    ))
+}
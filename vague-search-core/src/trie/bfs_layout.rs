@@ -0,0 +1,226 @@
+//! Reorder the nodes of a [CompiledTrie](crate::CompiledTrie) in a breadth-first layout.
+//!
+//! The compilation stores each sibling group right before the groups of its descendants,
+//! which is a depth-first layout: a node is near its children, but the children of
+//! its siblings can be far. The breadth-first layout instead stores all sibling groups
+//! of a depth next to each other, ordered like their parents.
+//!
+//! This benefits the workloads traversing the trie depth by depth on many branches,
+//! such as the approximate search at a high distance (or with a short query),
+//! where most nodes of the first depths are visited.
+//! The exact and prefix searches, which follow a single branch, do not benefit from it.
+
+use super::{index::*, trie_node::NodeValueMut};
+use crate::{CompiledTrie, NodeValue, RangeElement};
+use std::{borrow::Cow, collections::VecDeque};
+
+/// Return the new index of a child, with the new index of each old node.
+fn map_child(
+    child: Option<IndexNodeNonZero>,
+    new_indices: &[IndexInt],
+) -> Option<IndexNodeNonZero> {
+    child.and_then(|child| IndexNodeNonZero::new_opt(new_indices[usize::from(child)]))
+}
+
+impl CompiledTrie<'_> {
+    /// Return the index of the first node of each sibling group in breadth-first order,
+    /// along with the new index of each node in this order.
+    fn bfs_order(&self) -> (Vec<usize>, Vec<IndexInt>) {
+        let mut groups = Vec::new();
+        let mut new_indices = vec![0; self.nodes.len()];
+        let mut queue: VecDeque<usize> = self.get_root_siblings().map(|_| 0).into_iter().collect();
+
+        let mut nb_placed = 0;
+        while let Some(start) = queue.pop_front() {
+            groups.push(start);
+
+            let end = start + self.nodes[start].nb_siblings() as usize + 1;
+            for (i, node) in self.nodes[start..end].iter().enumerate() {
+                new_indices[start + i] = to_index_int(nb_placed);
+                nb_placed += 1;
+
+                match node.node_value() {
+                    NodeValue::Naive(n) => queue.extend(n.index_first_child.map(usize::from)),
+                    NodeValue::Patricia(n) => queue.extend(n.index_first_child.map(usize::from)),
+                    NodeValue::Range(n) => queue.extend(
+                        self.get_range(n.start_index, n.end_index)
+                            .iter()
+                            .filter_map(|elem| elem.index_first_child.map(usize::from)),
+                    ),
+                }
+            }
+        }
+
+        (groups, new_indices)
+    }
+
+    /// Create a copy of the trie with its nodes (and ranges) stored in breadth-first order,
+    /// i.e. depth by depth instead of branch by branch.
+    ///
+    /// The nodes which cannot be reached from the root are not kept.
+    /// See the [module documentation](self) for the workloads which benefit from this layout.
+    pub fn to_bfs_layout(&self) -> CompiledTrie<'static> {
+        let (groups, new_indices) = self.bfs_order();
+        #[cfg(feature = "source-lines")]
+        let source_lines = self.reorder_source_lines(&groups, &new_indices);
+        #[cfg(feature = "length-index")]
+        let (lengths, length_parents) = self.reorder_lengths(&groups, &new_indices);
+
+        let mut nodes = Vec::with_capacity(self.nodes.len());
+        let mut ranges = Vec::with_capacity(self.ranges.len());
+        for start in groups {
+            let end = start + self.nodes[start].nb_siblings() as usize + 1;
+            for node in &self.nodes[start..end] {
+                let mut node = node.clone();
+                match node.node_value_mut() {
+                    NodeValueMut::Naive(n) => {
+                        n.index_first_child = map_child(n.index_first_child, &new_indices)
+                    }
+                    NodeValueMut::Patricia(n) => {
+                        n.index_first_child = map_child(n.index_first_child, &new_indices)
+                    }
+                    NodeValueMut::Range(n) => {
+                        let range = self.get_range(n.start_index, n.end_index);
+                        n.start_index = IndexRange::new(to_index_int(ranges.len()));
+                        ranges.extend(range.iter().map(|elem| RangeElement {
+                            index_first_child: map_child(elem.index_first_child, &new_indices),
+                            word_freq: elem.word_freq,
+                        }));
+                        n.end_index = IndexRange::new(to_index_int(ranges.len()));
+                    }
+                }
+                nodes.push(node);
+            }
+        }
+
+        CompiledTrie {
+            nodes: Cow::Owned(nodes),
+            chars: Cow::Owned(self.chars.to_string()),
+            ranges: Cow::Owned(ranges),
+            collation: self.collation,
+            // The length index refers to the nodes, so it is reordered too
+            #[cfg(feature = "length-index")]
+            lengths: Cow::Owned(lengths),
+            #[cfg(feature = "length-index")]
+            length_parents: Cow::Owned(length_parents),
+            // The trigram model does not depend on the nodes layout
+            #[cfg(feature = "ngram-model")]
+            trigrams: Cow::Owned(self.trigrams.to_vec()),
+            // The source lines are associated to the nodes, so they are reordered too
+            #[cfg(feature = "source-lines")]
+            source_lines: Cow::Owned(source_lines),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::{create_node, create_trie};
+    use crate::CompiledTrieNode;
+    use std::num::NonZeroU32;
+
+    /// Return the depth of each node of the trie, in the order of the nodes array.
+    fn node_depths(trie: &CompiledTrie) -> Vec<usize> {
+        let mut depths = vec![0; trie.nodes().len()];
+        let mut stack = vec![(0, 0)];
+        while let Some((start, depth)) = stack.pop() {
+            let end = start + trie.nodes()[start].nb_siblings() as usize + 1;
+            for (i, node) in trie.nodes()[start..end].iter().enumerate() {
+                depths[start + i] = depth;
+                let children: Vec<_> = match node.node_value() {
+                    NodeValue::Naive(n) => n.index_first_child.into_iter().collect(),
+                    NodeValue::Patricia(n) => n.index_first_child.into_iter().collect(),
+                    NodeValue::Range(n) => trie
+                        .get_range(n.start_index, n.end_index)
+                        .iter()
+                        .filter_map(|e| e.index_first_child)
+                        .collect(),
+                };
+                stack.extend(children.into_iter().map(|c| (usize::from(c), depth + 1)));
+            }
+        }
+        depths
+    }
+
+    /// Return all the words of the trie with their frequency, in lexicographic order.
+    fn words(trie: &CompiledTrie) -> Vec<(String, u32)> {
+        fn visit(
+            trie: &CompiledTrie,
+            nodes: &[CompiledTrieNode],
+            word: &mut String,
+            words: &mut Vec<(String, u32)>,
+        ) {
+            let len = word.len();
+            for node in nodes {
+                let mut visit_elem =
+                    |word: &mut String,
+                     freq: Option<NonZeroU32>,
+                     child: Option<IndexNodeNonZero>| {
+                        if let Some(freq) = freq {
+                            words.push((word.clone(), freq.get()));
+                        }
+                        if let Some(child) = child {
+                            visit(trie, trie.get_siblings(child), word, words);
+                        }
+                    };
+                match node.node_value() {
+                    NodeValue::Naive(n) => {
+                        word.push(n.character);
+                        visit_elem(word, n.word_freq, n.index_first_child);
+                    }
+                    NodeValue::Patricia(n) => {
+                        // SAFETY: Safe because in a patricia node
+                        let range = unsafe { node.patricia_range() };
+                        word.push_str(trie.get_chars(range.start, range.end));
+                        visit_elem(word, n.word_freq, n.index_first_child);
+                    }
+                    NodeValue::Range(n) => {
+                        for (offset, elem) in trie
+                            .get_range(n.start_index, n.end_index)
+                            .iter()
+                            .enumerate()
+                        {
+                            word.truncate(len);
+                            word.push(
+                                std::char::from_u32(n.first_char as u32 + offset as u32).unwrap(),
+                            );
+                            visit_elem(word, elem.word_freq, elem.index_first_child);
+                        }
+                    }
+                }
+                word.truncate(len);
+            }
+        }
+
+        let mut words = Vec::new();
+        if let Some(roots) = trie.get_root_siblings() {
+            visit(trie, roots, &mut String::new(), &mut words);
+        }
+        words
+    }
+
+    #[test]
+    fn test_bfs_layout_same_words() {
+        let trie = create_trie();
+        let bfs = trie.to_bfs_layout();
+
+        assert!(bfs.validate().is_ok());
+        assert_eq!(bfs.nodes().len(), trie.nodes().len());
+        assert_eq!(bfs.ranges().len(), trie.ranges().len());
+        assert_eq!(words(&bfs), words(&trie));
+    }
+
+    #[test]
+    fn test_bfs_layout_sorted_by_depth() {
+        let trie = create_trie();
+        let depths = node_depths(&trie);
+        assert!(depths.windows(2).any(|w| w[0] > w[1]));
+
+        let bfs_depths = node_depths(&trie.to_bfs_layout());
+        assert!(bfs_depths.windows(2).all(|w| w[0] <= w[1]));
+
+        let empty = CompiledTrie::from(create_node("", 0, vec![]));
+        assert_eq!(empty.to_bfs_layout().nodes().len(), 0);
+    }
+}
@@ -0,0 +1,187 @@
+//! A trie of words inserted in any order, to compile the words computed at runtime
+//! (e.g. the result of a set operation or a folded index)
+//! into a [CompiledTrie](crate::CompiledTrie).
+
+use crate::{CompileOptions, CompiledTrie, TrieNodeDrainer};
+use std::num::NonZeroU32;
+
+/// A node of the words before their compilation.
+#[derive(Debug, Default)]
+struct BuilderNode {
+    characters: String,
+    frequency: Option<NonZeroU32>,
+    children: Vec<BuilderNode>,
+}
+
+impl BuilderNode {
+    /// Remove the descendants without any word, which are left by
+    /// [frequency_mut](TrieBuilder::frequency_mut) when it does not set a frequency.
+    fn remove_empty_leaves(&mut self) {
+        for child in &mut self.children {
+            child.remove_empty_leaves();
+        }
+        self.children
+            .retain(|child| child.frequency.is_some() || !child.children.is_empty());
+    }
+
+    /// Merge the nodes with only one child and no frequency with their child
+    /// to create patricia nodes.
+    fn merge_single_children(&mut self) {
+        for child in &mut self.children {
+            while child.frequency.is_none() && child.children.len() == 1 {
+                let grand_child = child.children.pop().unwrap();
+                child.characters.push_str(&grand_child.characters);
+                child.frequency = grand_child.frequency;
+                child.children = grand_child.children;
+            }
+            child.merge_single_children();
+        }
+    }
+}
+
+impl TrieNodeDrainer for BuilderNode {
+    fn drain_characters(&mut self) -> String {
+        std::mem::take(&mut self.characters)
+    }
+
+    fn frequency(&self) -> Option<NonZeroU32> {
+        self.frequency
+    }
+
+    fn drain_children(&mut self) -> Vec<Self> {
+        std::mem::take(&mut self.children)
+    }
+}
+
+/// The words of a trie being built, compiled with [compile](TrieBuilder::compile)
+/// once they are all inserted.
+///
+/// The nodes have one character each while the words are inserted, so that the children
+/// stay sorted by the collation of the options, and the chains of nodes with a single child
+/// and no word are merged into patricia nodes before the compilation.
+#[derive(Debug)]
+pub struct TrieBuilder {
+    root: BuilderNode,
+    options: CompileOptions,
+}
+
+impl TrieBuilder {
+    /// Create a builder without any word, to be compiled with the options.
+    pub fn new(options: CompileOptions) -> Self {
+        Self {
+            root: BuilderNode::default(),
+            options,
+        }
+    }
+
+    /// Return the frequency of the word, None if it has not been inserted, to read or set it.
+    /// The nodes of the word are created if needed, and removed by the compilation
+    /// if it is still not a word.
+    pub fn frequency_mut(&mut self, word: &str) -> &mut Option<NonZeroU32> {
+        let collation = self.options.collation;
+        let mut node = &mut self.root;
+        for c in word.chars() {
+            let res = node.children.binary_search_by(|child| {
+                collation.cmp(child.characters.chars().next().unwrap(), c)
+            });
+            let index = match res {
+                Ok(i) => i,
+                Err(i) => {
+                    let child = BuilderNode {
+                        characters: c.to_string(),
+                        ..Default::default()
+                    };
+                    node.children.insert(i, child);
+                    i
+                }
+            };
+            node = &mut node.children[index];
+        }
+        &mut node.frequency
+    }
+
+    /// Insert the word with its frequency, replacing the previous one.
+    pub fn insert(&mut self, word: &str, freq: NonZeroU32) {
+        *self.frequency_mut(word) = Some(freq);
+    }
+
+    /// Compile the inserted words with the options of the builder.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a node has too many children to be compiled (see
+    /// [try_from_options](CompiledTrie::try_from_options)).
+    pub fn compile(mut self) -> CompiledTrie<'static> {
+        self.root.remove_empty_leaves();
+        self.root.merge_single_children();
+        CompiledTrie::from_options(self.root, self.options)
+    }
+}
+
+impl<S: AsRef<str>> Extend<(S, NonZeroU32)> for TrieBuilder {
+    fn extend<I: IntoIterator<Item = (S, NonZeroU32)>>(&mut self, words: I) {
+        for (word, freq) in words {
+            self.insert(word.as_ref(), freq);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test_utils::create_node, Collation};
+
+    #[test]
+    fn test_builder_compile() {
+        // The words in any order give the same trie as their nodes merged by hand
+        let mut builder = TrieBuilder::new(CompileOptions::default());
+        builder.extend(
+            [
+                ("dogs", 9),
+                ("car", 7),
+                ("日本", 10),
+                ("dog", 8),
+                ("a", 1),
+                ("ab", 2),
+            ]
+            .iter()
+            .map(|&(word, freq)| (word, NonZeroU32::new(freq).unwrap())),
+        );
+        let expected = CompiledTrie::from(create_node(
+            "",
+            0,
+            vec![
+                create_node("a", 1, vec![create_node("b", 2, vec![])]),
+                create_node("car", 7, vec![]),
+                create_node("dog", 8, vec![create_node("s", 9, vec![])]),
+                create_node("日本", 10, vec![]),
+            ],
+        ));
+        let trie = builder.compile();
+        assert_eq!(trie.nodes(), expected.nodes());
+        assert_eq!(trie.chars(), expected.chars());
+        assert_eq!(trie.ranges(), expected.ranges());
+
+        // The frequencies can be read back and replaced, and the nodes created
+        // for a word whose frequency is left to None are removed
+        let mut builder = TrieBuilder::new(CompileOptions {
+            collation: Collation::CASE_FOLDED,
+            ..CompileOptions::default()
+        });
+        builder.insert("b", NonZeroU32::new(1).unwrap());
+        builder.insert("A", NonZeroU32::new(2).unwrap());
+        assert_eq!(*builder.frequency_mut("b"), NonZeroU32::new(1));
+        assert_eq!(*builder.frequency_mut("cat"), None);
+        *builder.frequency_mut("b") = NonZeroU32::new(3);
+        let trie = builder.compile();
+        assert!(trie.validate().is_ok());
+        assert_eq!(
+            trie.iter_words().collect::<Vec<_>>(),
+            vec![
+                ("A".to_string(), NonZeroU32::new(2).unwrap()),
+                ("b".to_string(), NonZeroU32::new(3).unwrap())
+            ]
+        );
+        assert_eq!(trie.nodes().len(), 2);
+    }
+}
@@ -0,0 +1,148 @@
+//! Remove the nodes, characters and ranges of a [CompiledTrie](crate::CompiledTrie)
+//! which cannot be reached from its root.
+
+use crate::{CompileOptions, CompiledTrie, TrieBuilder};
+
+impl CompiledTrie<'_> {
+    /// Return the number of nodes which cannot be reached from the root of the trie.
+    pub fn nb_unreachable_nodes(&self) -> usize {
+        let mut nb_visited = 0;
+        self.walk((), |step, _| {
+            // A range node is visited once for all its elements
+            nb_visited += (step.range_offset.unwrap_or(0) == 0) as usize;
+            Some(())
+        });
+        self.nodes.len() - nb_visited
+    }
+
+    /// Create a copy of the trie without any node, character or range
+    /// which cannot be reached from its root, nor any node left without words below it.
+    ///
+    /// The words are compiled again with the options, which should be the ones the trie
+    /// has been compiled with: the returned trie is then identical to compiling
+    /// the same words again.
+    pub fn compact(&self, options: CompileOptions) -> CompiledTrie<'static> {
+        let mut builder = TrieBuilder::new(options);
+        builder.extend(self.iter_words());
+        builder.compile()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        test_utils::create_node, Collation, CompiledTrieNode, IndexChar, IndexNodeNonZero,
+        NaiveNode, PatriciaNode, RangeElement,
+    };
+    use std::num::NonZeroU32;
+
+    fn assert_same_trie(a: &CompiledTrie, b: &CompiledTrie) {
+        assert_eq!(a.nodes(), b.nodes());
+        assert_eq!(a.chars(), b.chars());
+        assert_eq!(a.ranges(), b.ranges());
+        assert_eq!(a.collation().id(), b.collation().id());
+    }
+
+    #[test]
+    fn test_compact_compiled_trie() {
+        let words = || {
+            create_node(
+                "",
+                0,
+                vec![
+                    create_node("a", 1, vec![create_node("b", 2, vec![])]),
+                    create_node("b", 3, vec![]),
+                    create_node("car", 4, vec![]),
+                    create_node("e", 5, vec![]),
+                    create_node("station", 6, vec![]),
+                    create_node("tion", 7, vec![]),
+                    create_node("日本", 8, vec![]),
+                ],
+            )
+        };
+
+        // The options change the compiled trie, e.g. no range node with a gap of 0,
+        // and it stays the same when compacted with them
+        let no_ranges = CompileOptions {
+            range_gap: 0,
+            ..CompileOptions::default()
+        };
+        assert!(CompiledTrie::from_options(words(), no_ranges)
+            .ranges()
+            .is_empty());
+        assert!(!CompiledTrie::from(words()).ranges().is_empty());
+        for &options in &[
+            CompileOptions::default(),
+            CompileOptions {
+                range_gap: 0,
+                dedup_chars: true,
+                ..CompileOptions::default()
+            },
+            CompileOptions {
+                collation: Collation::CASE_FOLDED,
+                range_gap: 10,
+                ..CompileOptions::default()
+            },
+        ] {
+            let trie = CompiledTrie::from_options(words(), options);
+            assert_eq!(trie.nb_unreachable_nodes(), 0);
+            assert_same_trie(&trie.compact(options), &trie);
+        }
+
+        let empty = CompiledTrie::from(create_node("", 0, vec![]));
+        assert_eq!(empty.nb_unreachable_nodes(), 0);
+        assert_same_trie(&empty.compact(CompileOptions::default()), &empty);
+    }
+
+    #[test]
+    fn test_compact_unreachable_nodes() {
+        // The words "ab" and "cat", where "cat" has been removed in place
+        // by detaching the patricia node "at" from "c", which is then a tombstone
+        let nodes = [
+            CompiledTrieNode::new_naive(
+                NaiveNode {
+                    index_first_child: IndexNodeNonZero::new_opt(3),
+                    word_freq: None,
+                    character: 'a',
+                },
+                1,
+            ),
+            CompiledTrieNode::new_naive(
+                NaiveNode {
+                    index_first_child: None,
+                    word_freq: None,
+                    character: 'c',
+                },
+                0,
+            ),
+            CompiledTrieNode::new_patricia(
+                PatriciaNode {
+                    index_first_child: None,
+                    word_freq: NonZeroU32::new(2),
+                    start_index: IndexChar::new(0),
+                },
+                0,
+                2,
+            ),
+            CompiledTrieNode::new_naive(
+                NaiveNode {
+                    index_first_child: None,
+                    word_freq: NonZeroU32::new(1),
+                    character: 'b',
+                },
+                0,
+            ),
+        ];
+        let ranges: [RangeElement; 0] = [];
+        let edited = CompiledTrie::from((&nodes[..], "at", &ranges[..]));
+        assert_eq!(edited.nb_unreachable_nodes(), 1);
+
+        // Neither the tombstone "c" nor the detached node and its characters stay:
+        // the trie is the one of the remaining word "ab" compiled again
+        let expected = CompiledTrie::from(create_node("", 0, vec![create_node("ab", 1, vec![])]));
+        let compacted = edited.compact(CompileOptions::default());
+        assert_eq!(compacted.nb_unreachable_nodes(), 0);
+        assert_same_trie(&compacted, &expected);
+    }
+}
@@ -1,6 +1,6 @@
 use super::index::*;
-use crate::{CompiledTrieNode, RangeElement};
-use std::borrow::Cow;
+use crate::{Collation, CompiledTrieNode, NodeValue, RangeElement};
+use std::{borrow::Cow, mem::size_of, num::NonZeroU32};
 
 /// Represent the node array of the [CompiledTrie](crate::CompiledTrie)
 pub type NodeSlice = [CompiledTrieNode];
@@ -27,6 +27,182 @@ pub struct CompiledTrie<'a> {
     pub(super) nodes: Cow<'a, NodeSlice>,
     pub(super) chars: Cow<'a, CharsSlice>,
     pub(super) ranges: Cow<'a, RangeSlice>,
+    pub(super) collation: Collation,
+    #[cfg(feature = "length-index")]
+    pub(super) lengths: Cow<'a, super::length_index::LengthSlice>,
+    #[cfg(feature = "length-index")]
+    pub(super) length_parents: Cow<'a, super::length_index::LengthParentSlice>,
+    #[cfg(feature = "ngram-model")]
+    pub(super) trigrams: Cow<'a, super::ngram_model::TrigramSlice>,
+    #[cfg(feature = "source-lines")]
+    pub(super) source_lines: Cow<'a, super::source_lines::SourceLineSlice>,
+}
+
+/// The number of nodes of each type in a [CompiledTrie](crate::CompiledTrie).
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct NodeTypeHistogram {
+    pub naive: usize,
+    pub patricia: usize,
+    pub range: usize,
+}
+
+/// The size of a [CompiledTrie](crate::CompiledTrie) compared to the one of a naive trie
+/// of the same words, with one node per character, and the bytes saved by each optimization.
+///
+/// For a trie without unreachable nodes (see [compact](crate::CompiledTrie::compact)),
+/// `naive_bytes == trie_bytes + patricia_saved_bytes + range_saved_bytes`.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct CompressionReport {
+    /// The number of nodes of the trie.
+    pub nb_nodes: usize,
+
+    /// The number of nodes of the naive trie, i.e. the number of characters
+    /// of the naive and patricia nodes and of the non-empty range elements.
+    pub nb_naive_nodes: usize,
+
+    /// The number of bytes of the node, character and range arrays of the trie.
+    pub trie_bytes: usize,
+
+    /// The number of bytes of the node array of the naive trie.
+    pub naive_bytes: usize,
+
+    /// The bytes saved by storing the characters of the patricia nodes in the character array
+    /// instead of one node per character.
+    pub patricia_saved_bytes: isize,
+
+    /// The bytes saved by storing the children of the range nodes in the range array
+    /// instead of one node per child, negative if their empty elements take more.
+    pub range_saved_bytes: isize,
+}
+
+impl CompressionReport {
+    /// Return how many times the trie is smaller than the naive trie,
+    /// or 1 if both are empty.
+    pub fn ratio(&self) -> f64 {
+        if self.trie_bytes == 0 {
+            1.0
+        } else {
+            self.naive_bytes as f64 / self.trie_bytes as f64
+        }
+    }
+}
+
+/// Statistics of the vocabulary of a [CompiledTrie](crate::CompiledTrie),
+/// to inspect the corpus it has been compiled from.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct VocabStats {
+    /// The number of words of the trie.
+    pub num_words: usize,
+
+    /// The sum of the frequencies of the words.
+    pub total_freq: u64,
+
+    /// The entropy in bits of the words, `-Σ p log2(p)` where `p` is the frequency
+    /// of a word divided by the total frequency.
+    pub entropy: f64,
+
+    /// The mean number of characters of the words, each word counting once.
+    pub mean_word_len: f64,
+
+    /// The median number of characters of the words, each word counting once.
+    /// With an even number of words, it is the mean of the two middle lengths.
+    pub median_word_len: f64,
+}
+
+/// The siblings being traversed by a [Words](Words) iterator.
+struct WordsFrame<'t> {
+    /// The siblings of the frame.
+    nodes: &'t [CompiledTrieNode],
+
+    /// The index of the next sibling to visit.
+    next_node: usize,
+
+    /// The offset of the next element to visit if the next sibling is a range node.
+    next_offset: usize,
+
+    /// The length of the word before the characters of the siblings.
+    word_len: usize,
+}
+
+/// A lazy depth-first traversal of the words of a trie, in lexicographic order.
+struct Words<'t> {
+    trie: &'t CompiledTrie<'t>,
+    stack: Vec<WordsFrame<'t>>,
+    word: String,
+}
+
+impl<'t> Words<'t> {
+    /// Visit the next node or range element of the current siblings: append its characters
+    /// to the word and push a frame for its children.
+    /// Return its frequency, or None if it is not a word (or is a hole of a range).
+    fn visit_next(&mut self) -> Option<Option<NonZeroU32>> {
+        let trie = self.trie;
+        let frame = self.stack.last_mut()?;
+        let node = match frame.nodes.get(frame.next_node) {
+            Some(node) => node,
+            None => {
+                self.stack.pop();
+                return Some(None);
+            }
+        };
+        self.word.truncate(frame.word_len);
+
+        let (word_freq, index_first_child) = match node.node_value() {
+            NodeValue::Naive(n) => {
+                frame.next_node += 1;
+                self.word.push(n.character);
+                (n.word_freq, n.index_first_child)
+            }
+            NodeValue::Patricia(n) => {
+                frame.next_node += 1;
+                // SAFETY: Safe because in a patricia node
+                let pat_range = unsafe { node.patricia_range() };
+                self.word
+                    .push_str(trie.get_chars(pat_range.start, pat_range.end));
+                (n.word_freq, n.index_first_child)
+            }
+            NodeValue::Range(n) => {
+                let range = trie.get_range(n.start_index, n.end_index);
+                let offset = frame.next_offset;
+                if offset + 1 < range.len() {
+                    frame.next_offset += 1;
+                } else {
+                    frame.next_node += 1;
+                    frame.next_offset = 0;
+                }
+
+                // The characters of the holes of the range can be invalid
+                let elem = &range[offset];
+                match std::char::from_u32(n.first_char as u32 + offset as u32) {
+                    Some(c) => self.word.push(c),
+                    None => return Some(None),
+                }
+                (elem.word_freq, elem.index_first_child)
+            }
+        };
+
+        if let Some(index) = index_first_child {
+            self.stack.push(WordsFrame {
+                nodes: trie.get_siblings(index),
+                next_node: 0,
+                next_offset: 0,
+                word_len: self.word.len(),
+            });
+        }
+        Some(word_freq)
+    }
+}
+
+impl Iterator for Words<'_> {
+    type Item = (String, NonZeroU32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(freq) = self.visit_next()? {
+                return Some((self.word.clone(), freq));
+            }
+        }
+    }
 }
 
 impl CompiledTrie<'_> {
@@ -127,6 +303,170 @@ impl CompiledTrie<'_> {
     ) -> &RangeElement {
         self.ranges.get_unchecked(usize::from(start) + offset)
     }
+
+    /// Return the collation of the characters of the siblings.
+    pub fn collation(&self) -> Collation {
+        self.collation
+    }
+
+    /// Return the number of words in the trie, i.e. the nodes and range elements
+    /// reachable from the root which have a frequency.
+    ///
+    /// This traverses the whole trie, so the result should be kept if needed multiple times.
+    pub fn word_count(&self) -> usize {
+        let mut count = 0;
+        self.walk((), |step, _| {
+            count += step.word_freq.is_some() as usize;
+            Some(())
+        });
+        count
+    }
+
+    /// Return an iterator over the words of the trie with their frequency,
+    /// in lexicographic order (following the [collation](CompiledTrie::collation) of the trie).
+    ///
+    /// The trie is traversed lazily, only keeping the path to the current word.
+    pub fn iter_words(&self) -> impl Iterator<Item = (String, NonZeroU32)> + '_ {
+        Words {
+            trie: self,
+            stack: self
+                .get_root_siblings()
+                .into_iter()
+                .map(|nodes| WordsFrame {
+                    nodes,
+                    next_node: 0,
+                    next_offset: 0,
+                    word_len: 0,
+                })
+                .collect(),
+            word: String::new(),
+        }
+    }
+
+    /// Return the sum of the frequencies of the words in the trie.
+    ///
+    /// This traverses the whole trie, so the result should be kept if needed multiple times.
+    pub fn total_frequency(&self) -> u64 {
+        let mut total = 0;
+        self.walk((), |step, _| {
+            total += step.word_freq.map_or(0, |f| u64::from(f.get()));
+            Some(())
+        });
+        total
+    }
+
+    /// Return the statistics of the vocabulary of the trie, all zero if it has no words.
+    ///
+    /// This traverses the whole trie and keeps the length and frequency of each word.
+    pub fn vocabulary_stats(&self) -> VocabStats {
+        let mut words: Vec<(usize, u64)> = self
+            .iter_words()
+            .map(|(word, freq)| (word.chars().count(), u64::from(freq.get())))
+            .collect();
+        if words.is_empty() {
+            return VocabStats::default();
+        }
+
+        let num_words = words.len();
+        let total_freq: u64 = words.iter().map(|&(_, freq)| freq).sum();
+        let entropy = words
+            .iter()
+            .map(|&(_, freq)| {
+                let p = freq as f64 / total_freq as f64;
+                -p * p.log2()
+            })
+            .sum();
+        let mean_word_len =
+            words.iter().map(|&(len, _)| len).sum::<usize>() as f64 / num_words as f64;
+
+        words.sort_unstable();
+        let middle = num_words / 2;
+        let median_word_len = if num_words % 2 == 1 {
+            words[middle].0 as f64
+        } else {
+            (words[middle - 1].0 + words[middle].0) as f64 / 2.0
+        };
+
+        VocabStats {
+            num_words,
+            total_freq,
+            entropy,
+            mean_word_len,
+            median_word_len,
+        }
+    }
+
+    /// Return the number of nodes of each type in the node array.
+    pub fn node_type_histogram(&self) -> NodeTypeHistogram {
+        let mut histogram = NodeTypeHistogram::default();
+        for node in self.nodes() {
+            match node.node_value() {
+                NodeValue::Naive(_) => histogram.naive += 1,
+                NodeValue::Patricia(_) => histogram.patricia += 1,
+                NodeValue::Range(_) => histogram.range += 1,
+            }
+        }
+        histogram
+    }
+
+    /// Return the size of the trie compared to the one of a naive trie of the same words,
+    /// to measure the gain of the patricia and range nodes on this dictionary.
+    pub fn compression_report(&self) -> CompressionReport {
+        let node_size = size_of::<CompiledTrieNode>() as isize;
+        let elem_size = size_of::<RangeElement>() as isize;
+
+        let mut nb_naive_nodes = 0;
+        let mut patricia_saved_bytes = -(self.chars.len() as isize);
+        let mut range_saved_bytes = 0;
+        for node in self.nodes() {
+            match node.node_value() {
+                NodeValue::Naive(_) => nb_naive_nodes += 1,
+                NodeValue::Patricia(_) => {
+                    // SAFETY: Safe because in a patricia node
+                    let range = unsafe { node.patricia_range() };
+                    let nb_chars = self.get_chars(range.start, range.end).chars().count();
+                    nb_naive_nodes += nb_chars;
+                    patricia_saved_bytes += (nb_chars as isize - 1) * node_size;
+                }
+                NodeValue::Range(n) => {
+                    let range = self.get_range(n.start_index, n.end_index);
+                    let nb_elems = range
+                        .iter()
+                        .filter(|e| e.word_freq.is_some() || e.index_first_child.is_some())
+                        .count();
+                    nb_naive_nodes += nb_elems;
+                    range_saved_bytes +=
+                        (nb_elems as isize - 1) * node_size - range.len() as isize * elem_size;
+                }
+            }
+        }
+
+        CompressionReport {
+            nb_nodes: self.nodes.len(),
+            nb_naive_nodes,
+            trie_bytes: self.nodes.len() * node_size as usize
+                + self.chars.len()
+                + self.ranges.len() * elem_size as usize,
+            naive_bytes: nb_naive_nodes * node_size as usize,
+            patricia_saved_bytes,
+            range_saved_bytes,
+        }
+    }
+
+    /// Return the number of characters of the longest string of a patricia node,
+    /// or 0 if there is no patricia node.
+    pub fn longest_patricia_len(&self) -> usize {
+        self.nodes()
+            .iter()
+            .filter(|node| matches!(node.node_value(), NodeValue::Patricia(_)))
+            .map(|node| {
+                // SAFETY: Safe because in a patricia node
+                let range = unsafe { node.patricia_range() };
+                self.get_chars(range.start, range.end).chars().count()
+            })
+            .max()
+            .unwrap_or(0)
+    }
 }
 
 impl<'a> From<(&'a NodeSlice, &'a CharsSlice, &'a RangeSlice)> for CompiledTrie<'a> {
@@ -135,6 +475,244 @@ impl<'a> From<(&'a NodeSlice, &'a CharsSlice, &'a RangeSlice)> for CompiledTrie<
             nodes: Cow::Borrowed(nodes),
             chars: Cow::Borrowed(chars),
             ranges: Cow::Borrowed(ranges),
+            collation: Collation::CODE_POINT,
+            #[cfg(feature = "length-index")]
+            lengths: Cow::Borrowed(&[]),
+            #[cfg(feature = "length-index")]
+            length_parents: Cow::Borrowed(&[]),
+            #[cfg(feature = "ngram-model")]
+            trigrams: Cow::Borrowed(&[]),
+            #[cfg(feature = "source-lines")]
+            source_lines: Cow::Borrowed(&[]),
         }
     }
 }
+
+impl<'a> CompiledTrie<'a> {
+    /// Set the collation the trie has been compiled with.
+    pub(crate) fn with_collation(self, collation: Collation) -> Self {
+        CompiledTrie { collation, ..self }
+    }
+}
+
+#[cfg(feature = "length-index")]
+impl<'a> CompiledTrie<'a> {
+    /// Set the borrowed length index arrays of the trie.
+    pub(crate) fn with_lengths(
+        self,
+        lengths: &'a super::length_index::LengthSlice,
+        length_parents: &'a super::length_index::LengthParentSlice,
+    ) -> Self {
+        CompiledTrie {
+            lengths: Cow::Borrowed(lengths),
+            length_parents: Cow::Borrowed(length_parents),
+            ..self
+        }
+    }
+}
+
+#[cfg(feature = "ngram-model")]
+impl<'a> CompiledTrie<'a> {
+    /// Set the borrowed trigram array of the trie.
+    pub(crate) fn with_trigrams(self, trigrams: &'a super::ngram_model::TrigramSlice) -> Self {
+        CompiledTrie {
+            trigrams: Cow::Borrowed(trigrams),
+            ..self
+        }
+    }
+}
+
+#[cfg(feature = "source-lines")]
+impl<'a> CompiledTrie<'a> {
+    /// Set the borrowed source line array of the trie.
+    pub(crate) fn with_source_lines(
+        self,
+        source_lines: &'a super::source_lines::SourceLineSlice,
+    ) -> Self {
+        CompiledTrie {
+            source_lines: Cow::Borrowed(source_lines),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::create_node;
+
+    #[test]
+    fn test_word_count() {
+        // "a", "ab", "b", "car", "d", "dogs" and "日本", with "a", "b" and "d" in a range
+        let trie = CompiledTrie::from(create_node(
+            "",
+            0,
+            vec![
+                create_node("a", 1, vec![create_node("b", 2, vec![])]),
+                create_node("b", 3, vec![]),
+                create_node("c", 0, vec![create_node("ar", 4, vec![])]),
+                create_node("d", 5, vec![create_node("ogs", 6, vec![])]),
+                create_node("日本", 7, vec![]),
+            ],
+        ));
+        assert!(!trie.ranges().is_empty());
+        assert_eq!(trie.word_count(), 7);
+        assert_eq!(trie.total_frequency(), 28);
+
+        let empty = CompiledTrie::from(create_node("", 0, vec![]));
+        assert_eq!(empty.word_count(), 0);
+        assert_eq!(empty.total_frequency(), 0);
+    }
+
+    #[test]
+    fn test_iter_words() {
+        // "a", "ab", "c", "dogs", "e" and "日本", with "a", "c" and "e" in a range with holes
+        let trie = CompiledTrie::from(create_node(
+            "",
+            0,
+            vec![
+                create_node("a", 1, vec![create_node("b", 2, vec![])]),
+                create_node("c", 3, vec![]),
+                create_node("dog", 0, vec![create_node("s", 4, vec![])]),
+                create_node("e", 5, vec![]),
+                create_node("日本", 6, vec![]),
+            ],
+        ));
+        assert!(trie
+            .ranges()
+            .iter()
+            .any(|e| e.word_freq.is_none() && e.index_first_child.is_none()));
+
+        let words: Vec<_> = trie.iter_words().map(|(w, f)| (w, f.get())).collect();
+        let expected = [
+            ("a", 1),
+            ("ab", 2),
+            ("c", 3),
+            ("dogs", 4),
+            ("e", 5),
+            ("日本", 6),
+        ];
+        let expected: Vec<_> = expected.iter().map(|&(w, f)| (w.to_string(), f)).collect();
+        assert_eq!(words, expected);
+        assert_eq!(trie.iter_words().count(), trie.word_count());
+
+        // The words are only built when iterated
+        let mut words = trie.iter_words();
+        assert_eq!(words.next().map(|(w, _)| w), Some("a".to_string()));
+        assert_eq!(words.nth(2).map(|(w, _)| w), Some("dogs".to_string()));
+
+        let empty = CompiledTrie::from(create_node("", 0, vec![]));
+        assert_eq!(empty.iter_words().next(), None);
+    }
+
+    #[test]
+    fn test_node_type_histogram() {
+        let trie = CompiledTrie::from(create_node(
+            "",
+            0,
+            vec![
+                create_node("a", 1, vec![create_node("b", 2, vec![])]),
+                create_node("b", 3, vec![]),
+                create_node("c", 0, vec![create_node("ar", 4, vec![])]),
+                create_node("d", 5, vec![create_node("ogs", 6, vec![])]),
+                create_node("日本", 7, vec![]),
+            ],
+        ));
+        let histogram = trie.node_type_histogram();
+        assert_eq!(
+            histogram.naive + histogram.patricia + histogram.range,
+            trie.nodes().len()
+        );
+        assert_eq!(histogram.range, 1);
+        assert_eq!(histogram.patricia, 3);
+        assert_eq!(trie.longest_patricia_len(), 3);
+
+        let empty = CompiledTrie::from(create_node("", 0, vec![]));
+        assert_eq!(empty.node_type_histogram(), NodeTypeHistogram::default());
+        assert_eq!(empty.longest_patricia_len(), 0);
+    }
+
+    #[test]
+    fn test_vocabulary_stats() {
+        // The probabilities 1/8, 1/8, 1/4 and 1/2, of lengths 1, 2, 2 and 3
+        let trie = CompiledTrie::from(create_node(
+            "",
+            0,
+            vec![
+                create_node("a", 1, vec![create_node("b", 1, vec![])]),
+                create_node("cd", 2, vec![]),
+                create_node("日本語", 4, vec![]),
+            ],
+        ));
+        let stats = trie.vocabulary_stats();
+        assert_eq!(stats.num_words, 4);
+        assert_eq!(stats.total_freq, trie.total_frequency());
+        assert_eq!(stats.total_freq, 8);
+        // 2 * 1/8 * 3 + 1/4 * 2 + 1/2 * 1
+        assert!((stats.entropy - 1.75).abs() < 1e-12, "{}", stats.entropy);
+        assert!((stats.mean_word_len - 2.0).abs() < 1e-12);
+        assert!((stats.median_word_len - 2.0).abs() < 1e-12);
+
+        // A single word has no entropy
+        let single = CompiledTrie::from(create_node("", 0, vec![create_node("abc", 5, vec![])]));
+        let stats = single.vocabulary_stats();
+        assert_eq!((stats.num_words, stats.total_freq), (1, 5));
+        assert_eq!(stats.entropy, 0.0);
+        assert!((stats.median_word_len - 3.0).abs() < 1e-12);
+
+        let empty = CompiledTrie::from(create_node("", 0, vec![]));
+        assert_eq!(empty.vocabulary_stats(), VocabStats::default());
+    }
+
+    #[test]
+    fn test_compression_report() {
+        // A range of "a", "c" and "d" (with an empty "b" element), the patricia nodes "ay"
+        // and "ogs" and the naive node "x": 9 characters in 4 nodes
+        let trie = CompiledTrie::from(create_node(
+            "",
+            0,
+            vec![
+                create_node("a", 1, vec![create_node("x", 2, vec![])]),
+                create_node("c", 3, vec![]),
+                create_node(
+                    "d",
+                    4,
+                    vec![create_node("ay", 5, vec![]), create_node("ogs", 6, vec![])],
+                ),
+            ],
+        ));
+        let histogram = trie.node_type_histogram();
+        assert_eq!(
+            (histogram.naive, histogram.patricia, histogram.range),
+            (1, 2, 1)
+        );
+
+        let node_size = size_of::<CompiledTrieNode>();
+        let elem_size = size_of::<RangeElement>();
+        let report = trie.compression_report();
+        assert_eq!(report.nb_nodes, trie.nodes().len());
+        assert_eq!(report.nb_naive_nodes, trie.nodes().len() + 5);
+        assert_eq!(report.naive_bytes, report.nb_naive_nodes * node_size);
+        assert_eq!(
+            report.trie_bytes,
+            std::mem::size_of_val(trie.nodes()) + trie.chars().len() + 4 * elem_size
+        );
+        assert_eq!(
+            report.patricia_saved_bytes,
+            (3 * node_size) as isize - trie.chars().len() as isize
+        );
+        assert_eq!(
+            report.range_saved_bytes,
+            (2 * node_size) as isize - (4 * elem_size) as isize
+        );
+        assert_eq!(
+            report.naive_bytes as isize,
+            report.trie_bytes as isize + report.patricia_saved_bytes + report.range_saved_bytes
+        );
+        assert!(report.ratio() > 1.0);
+
+        let empty = CompiledTrie::from(create_node("", 0, vec![]));
+        assert_eq!(empty.compression_report().nb_naive_nodes, 0);
+        assert_eq!(empty.compression_report().ratio(), 1.0);
+    }
+}
@@ -1,6 +1,9 @@
-use crate::{trie::trie_node_interface::TrieNodeDrainer, *};
+use crate::{error::*, trie::trie_node_interface::TrieNodeDrainer, *};
+use snafu::*;
 use std::{
     borrow::Cow,
+    collections::HashMap,
+    convert::TryFrom,
     num::{NonZeroU32, NonZeroUsize},
     ops::Range,
 };
@@ -10,25 +13,69 @@ use utils::char_dist;
 #[derive(Debug, Eq, PartialEq)]
 enum TrieNode<'a, N: TrieNodeDrainer> {
     Simple(&'a N, char),
-    Patricia(&'a N, String),
+    Patricia(&'a N, &'a str),
     Range(&'a [N], Vec<char>),
 }
 
+impl<N: TrieNodeDrainer> TrieNode<'_, N> {
+    /// Return the number of nodes of the trie gathered in the node.
+    fn nb_nodes(&self) -> usize {
+        match self {
+            TrieNode::Simple(..) | TrieNode::Patricia(..) => 1,
+            TrieNode::Range(nodes, _) => nodes.len(),
+        }
+    }
+}
+
 /// Create a dummy index with an undefined (but fixed) value.
 /// Useful when creating a temporary value, rewritten soon after.
 const fn dummy_index() -> Option<IndexNodeNonZero> {
     // SAFETY: Safe because != 0
     // Use of unsafe because .unwrap() is not yet const fn
     Some(IndexNodeNonZero::new(unsafe {
-        NonZeroU32::new_unchecked(u32::MAX)
+        IndexIntNonZero::new_unchecked(IndexInt::MAX)
     }))
 }
 
+/// The characters array of a trie being compiled.
+///
+/// Without an index of its strings, it only reuses the characters found in its last bytes.
+#[derive(Debug)]
+struct TrieChars {
+    chars: String,
+
+    /// The start of each string added to the array and of its suffixes
+    /// (see [add_chars](add_chars)).
+    index: Option<HashMap<Box<str>, usize>>,
+}
+
+impl TrieChars {
+    /// Create an empty characters array, with an index over the whole array if `dedup`.
+    fn new(dedup: bool) -> Self {
+        const CHARS_INIT_CAP: usize = 1024;
+
+        Self {
+            chars: String::with_capacity(CHARS_INIT_CAP),
+            index: if dedup { Some(HashMap::new()) } else { None },
+        }
+    }
+}
+
 /// Add the characters to the vector and return its range of index.
 /// If the characters are already present in the vector, it may not insert them
 /// and instead return the already present characters range of index.
-fn add_chars(big_string: &mut String, chars: &str) -> Range<IndexChar> {
+///
+/// With an index, the characters are found anywhere in the vector if they were added before,
+/// as a whole or as a suffix of at most `MAX_INDEXED_SUFFIX` bytes of the added characters.
+fn add_chars(trie_chars: &mut TrieChars, chars: &str) -> Range<IndexChar> {
     const SEARCH_LIMIT: usize = 2048;
+    const MAX_INDEXED_SUFFIX: usize = 64;
+
+    let big_string = &mut trie_chars.chars;
+    let indexed = trie_chars
+        .index
+        .as_ref()
+        .and_then(|index| index.get(chars).copied());
 
     let nb_bytes_searched = SEARCH_LIMIT.min(big_string.len());
     let search_window_index = big_string.len() - nb_bytes_searched;
@@ -36,8 +83,9 @@ fn add_chars(big_string: &mut String, chars: &str) -> Range<IndexChar> {
     let search_bytes = &big_string.as_bytes()[search_window_index..];
     let mut byte_windows = search_bytes.windows(chars.len()).take(SEARCH_LIMIT);
 
-    let found = byte_windows.position(|win| win == chars.as_bytes());
-    let pos = if let Some(search_window_pos) = found {
+    let pos = if let Some(indexed_pos) = indexed {
+        indexed_pos
+    } else if let Some(search_window_pos) = byte_windows.position(|win| win == chars.as_bytes()) {
         // The found index is relative to the search window
         // so the last SEARCH_LIMIT bytes.
 
@@ -49,11 +97,20 @@ fn add_chars(big_string: &mut String, chars: &str) -> Range<IndexChar> {
         // Save the start position where chars will be added
         let start_pos = big_string.len();
         big_string.push_str(chars);
+
+        if let Some(index) = &mut trie_chars.index {
+            let suffixes = chars
+                .char_indices()
+                .filter(|&(i, _)| i == 0 || chars.len() - i <= MAX_INDEXED_SUFFIX);
+            for (i, _) in suffixes {
+                index.entry(chars[i..].into()).or_insert(start_pos + i);
+            }
+        }
         start_pos
     };
 
-    let start = IndexChar::new(pos as u32);
-    let end = IndexChar::new((pos + chars.len()) as u32);
+    let start = IndexChar::new(to_index_int(pos));
+    let end = IndexChar::new(to_index_int(pos + chars.len()));
 
     start..end
 }
@@ -78,17 +135,24 @@ fn add_range<N: TrieNodeDrainer>(
 
     let min = *range_chars.iter().min().unwrap();
     let max = *range_chars.iter().max().unwrap();
+    // The searches rebuild the characters of a range from their offset without checking them
+    assert!(
+        !crosses_surrogates(min, max),
+        "Range crossing the surrogates: {:?}..={:?}",
+        min,
+        max
+    );
     // The range is inclusive (min and max in the range), so the length is max - min **+ 1**
     let range_len = max as usize - min as usize + 1;
 
-    let index_range = IndexRange::new(trie_ranges.len() as u32)
-        ..IndexRange::new((trie_ranges.len() + range_len) as u32);
+    let index_range = IndexRange::new(to_index_int(trie_ranges.len()))
+        ..IndexRange::new(to_index_int(trie_ranges.len() + range_len));
 
     let char_to_index = |&c| c as usize - min as usize + *index_range.start as usize;
 
     // Create None values for the range
     trie_ranges.resize(
-        trie_ranges.len() + range_len as usize,
+        trie_ranges.len() + range_len,
         RangeElement {
             index_first_child: None,
             word_freq: None,
@@ -96,7 +160,7 @@ fn add_range<N: TrieNodeDrainer>(
     );
 
     for (i, node) in range_chars.iter().map(char_to_index).zip(nodes) {
-        trie_ranges[i as usize] = RangeElement {
+        trie_ranges[i] = RangeElement {
             // Use a dummy index to differentiate the element which are not in the trie
             // and the nodes which have a frequency of 0 (but have children which will
             // be inserted after).
@@ -117,19 +181,31 @@ fn add_range<N: TrieNodeDrainer>(
     (index_range, min)
 }
 
-/// Check if the current character should be added to the current range.
-fn should_add_to_range(range: &[char], cur: char) -> bool {
-    // Because a RangeElement takes less memory than a CompiledTrieNode,
-    // we can allow empty cells between 2 elements without taking more memory.
-    // Moreover, since a range is faster than multiple nodes (indexing vs searching)
-    // it is prefered in case they both take the same amount of memory.
-    const MAX_DIST_IN_RANGE: i32 = 3;
+/// Return whether the code points between the two characters include the surrogates
+/// (U+D800 to U+DFFF), which are not characters.
+pub(crate) fn crosses_surrogates(a: char, b: char) -> bool {
+    const SURROGATES_START: u32 = 0xD800;
+    const SURROGATES_END: u32 = 0xDFFF;
+    let (low, high) = if a <= b { (a, b) } else { (b, a) };
+    (low as u32) < SURROGATES_START && (high as u32) > SURROGATES_END
+}
 
+/// Check if the current character should be added to the current range,
+/// if it is at most at `max_dist` from its last character.
+///
+/// With another collation than the code point order, only the next code point is added
+/// (unless `max_dist` is 0, which never adds any character),
+/// so that the range does not span the code point of a character sorted elsewhere.
+/// A range never spans the surrogates, whose offsets in the range would not be characters.
+fn should_add_to_range(range: &[char], cur: char, collation: Collation, max_dist: i32) -> bool {
     // Check the number of empty cells will be placed between the last character
     // in the range and the current if we add it.
-    range
-        .last()
-        .map_or(false, |&last| char_dist(last, cur) <= MAX_DIST_IN_RANGE)
+    match range.last() {
+        Some(&last) if crosses_surrogates(last, cur) => false,
+        Some(&last) if collation.is_code_point() => char_dist(last, cur) <= max_dist,
+        Some(&last) => max_dist >= 1 && char_dist(last, cur) == 1,
+        None => false,
+    }
 }
 
 /// Drain the characters of the nodes to then be used in [node_type_heuristic](node_type_heuristic).
@@ -187,11 +263,14 @@ fn process_range<'a, N: TrieNodeDrainer>(
     }
 }
 
-/// Find the best node types to create from the given nodes.
-fn node_type_heuristic<N: TrieNodeDrainer>(
-    nodes: &[N],
-    nodes_chars: Vec<String>,
-) -> Vec<TrieNode<'_, N>> {
+/// Find the best node types to create from the given nodes,
+/// with ranges of characters at most at `max_dist_in_range` from each other.
+fn node_type_heuristic<'a, N: TrieNodeDrainer>(
+    nodes: &'a [N],
+    nodes_chars: &'a [String],
+    collation: Collation,
+    max_dist_in_range: i32,
+) -> Vec<TrieNode<'a, N>> {
     let mut res_nodes = Vec::new();
     let mut cur_range = Vec::new();
 
@@ -204,7 +283,14 @@ fn node_type_heuristic<N: TrieNodeDrainer>(
         // - add a character to the range and continue the loop
         // - extract the range as a SimpleNode
         // - extract the range as a RangeNode
-        if is_one_char && should_add_to_range(&cur_range, first_char.unwrap()) {
+        if is_one_char
+            && should_add_to_range(
+                &cur_range,
+                first_char.unwrap(),
+                collation,
+                max_dist_in_range,
+            )
+        {
             // Add the character to the range => RangeNode (not finished)
             cur_range.push(first_char.unwrap());
 
@@ -220,7 +306,7 @@ fn node_type_heuristic<N: TrieNodeDrainer>(
             cur_range.push(first_char.unwrap());
         } else {
             // Multiple characters => PatriciaNode
-            res_nodes.push(TrieNode::Patricia(node, chars))
+            res_nodes.push(TrieNode::Patricia(node, chars.as_str()))
         }
     }
 
@@ -229,52 +315,122 @@ fn node_type_heuristic<N: TrieNodeDrainer>(
     res_nodes
 }
 
+/// Split the string of a patricia node into chunks of at most
+/// [MAX_PAT_STR_LENGTH](CompiledTrieNode::MAX_PAT_STR_LENGTH) bytes, on character boundaries.
+fn split_patricia_chars(chars: &str) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut rest = chars;
+    while rest.len() > CompiledTrieNode::MAX_PAT_STR_LENGTH {
+        let mut end = CompiledTrieNode::MAX_PAT_STR_LENGTH;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    chunks.push(rest);
+    chunks
+}
+
+/// Create the patricia nodes of the characters, split in chunks (see
+/// [split_patricia_chars](split_patricia_chars)), each of them to be the single child of the previous
+/// one. Only the last node ends the word of the frequency, and has the children of the word.
+fn create_patricia_chain(
+    chars: &str,
+    word_freq: Option<NonZeroU32>,
+    nb_siblings: u32,
+    trie_chars: &mut TrieChars,
+) -> Vec<CompiledTrieNode> {
+    let chunks = split_patricia_chars(chars);
+    let last_chunk = chunks.len() - 1;
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let char_range = add_chars(trie_chars, chunk);
+            CompiledTrieNode::new_patricia(
+                PatriciaNode {
+                    index_first_child: None,
+                    word_freq: if i == last_chunk { word_freq } else { None },
+                    start_index: char_range.start,
+                },
+                if i == 0 { nb_siblings } else { 0 },
+                chunk.len() as u32,
+            )
+        })
+        .collect()
+}
+
 /// Create a node which has no information about its first child index.
+///
+/// A patricia string too long for its node is split (see [split_patricia_chars](split_patricia_chars)),
+/// the node keeping the first chunk and the other ones being returned as a chain of partial nodes,
+/// each of them to be the single child of the previous one. The last node of the chain
+/// then ends the word and has the children of the node.
 fn create_partial_node<N: TrieNodeDrainer>(
     nb_siblings: u32,
     heuristic: TrieNode<N>,
-    trie_chars: &mut String,
+    trie_chars: &mut TrieChars,
     trie_ranges: &mut Vec<RangeElement>,
-) -> CompiledTrieNode {
+) -> (CompiledTrieNode, Vec<CompiledTrieNode>) {
     match heuristic {
-        TrieNode::Simple(node, character) => CompiledTrieNode::new_naive(
-            NaiveNode {
-                index_first_child: None,
-                word_freq: node.frequency(),
-                character,
-            },
-            nb_siblings,
-        ),
-        TrieNode::Patricia(node, node_chars) => {
-            let char_range = add_chars(trie_chars, &node_chars);
-            let str_len = node_chars.len() as u32;
-
-            CompiledTrieNode::new_patricia(
-                PatriciaNode {
+        TrieNode::Simple(node, character) => {
+            let naive = CompiledTrieNode::new_naive(
+                NaiveNode {
                     index_first_child: None,
                     word_freq: node.frequency(),
-                    start_index: char_range.start,
+                    character,
                 },
                 nb_siblings,
-                str_len,
-            )
+            );
+            (naive, Vec::new())
+        }
+        TrieNode::Patricia(node, node_chars) => {
+            let mut patricia_nodes =
+                create_patricia_chain(node_chars, node.frequency(), nb_siblings, trie_chars);
+            let patricia = patricia_nodes.remove(0);
+            (patricia, patricia_nodes)
         }
         TrieNode::Range(nodes, range_chars) => {
             let (range, first_char) = add_range(trie_ranges, nodes, &range_chars);
-            CompiledTrieNode::new_range(
+            let range = CompiledTrieNode::new_range(
                 RangeNode {
                     first_char,
                     start_index: range.start,
                     end_index: range.end,
                 },
                 nb_siblings,
-            )
+            );
+            (range, Vec::new())
+        }
+    }
+}
+
+/// Remove the word of the character from a partial naive or range node,
+/// the character only being the first one of the word.
+fn remove_partial_word(
+    partial_node: &mut CompiledTrieNode,
+    trie_ranges: &mut [RangeElement],
+    character: char,
+) {
+    match partial_node.node_value_mut() {
+        NodeValueMut::Naive(n) => n.word_freq = None,
+        NodeValueMut::Patricia(_) => unreachable!("The patricia nodes have all their characters"),
+        NodeValueMut::Range(n) => {
+            let index = usize::from(n.start_index) + (character as usize - n.first_char as usize);
+            trie_ranges[index].word_freq = None;
         }
     }
 }
 
 /// Find the first index >= at the current which is a dummy node
 /// (see the add_range function)
+///
+/// This scans the empty elements before it, of which there are at most 2 with the default
+/// range gap (see [should_add_to_range](should_add_to_range)), but as many as the range gap
+/// of the compilation allows otherwise, which is unbounded for the children gathered
+/// in ranges because they are too many siblings.
 fn find_next_dummy_range_node(trie_ranges: &[RangeElement], current_range_index: usize) -> usize {
     // Find the position (after current index) of the first Some element
     let pos_opt = trie_ranges
@@ -332,39 +488,90 @@ fn finish_current_partial_node(
 
 /// Append the information of the given node and its children
 /// to the three [CompiledTrie](crate::CompiledTrie) vectors.
+///
+/// The prefix is the characters of the node, from the root of the trie.
+/// Return an error if the node has too many children to be siblings, which is only possible
+/// with another collation than the code point order.
 fn fill_from_trie<N: TrieNodeDrainer>(
     mut node: N,
     trie_nodes: &mut Vec<CompiledTrieNode>,
-    trie_chars: &mut String,
+    trie_chars: &mut TrieChars,
     trie_ranges: &mut Vec<RangeElement>,
-) {
+    collation: Collation,
+    range_gap: i32,
+    prefix: &mut String,
+) -> Result<()> {
     // Drain the children from the node and their characters
     let mut children = node.drain_children();
+    let mut children_chars = extract_characters(&mut children);
+    debug_assert_eq!(
+        children_chars
+            .iter()
+            .flat_map(|s| s.chars().next())
+            .collect::<std::collections::HashSet<_>>()
+            .len(),
+        children_chars.len(),
+        "Multiple children begin with the same character"
+    );
 
-    let nb_created_nodes = {
-        let children_chars = extract_characters(&mut children);
-        debug_assert_eq!(
-            children_chars
-                .iter()
-                .flat_map(|s| s.chars().next())
-                .collect::<std::collections::HashSet<_>>()
-                .len(),
-            children_chars.len(),
-            "Multiple children begin with the same character"
-        );
+    // The number of siblings of a node is stored on 18 bits, so the characters
+    // of too many children are all gathered in ranges whatever their distance.
+    // The children of multiple characters are then split after their first one,
+    // the rest of their characters being in a patricia node, as their single child.
+    let max_siblings = CompiledTrieNode::MAX_NB_SIBLINGS as usize;
+    let (max_dist_in_range, rests) = if children.len() > max_siblings + 1 {
+        let rests = children_chars
+            .iter_mut()
+            .map(|chars| {
+                let first_len = chars.chars().next().map_or(0, char::len_utf8);
+                if chars.len() > first_len {
+                    Some(chars.split_off(first_len))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        (i32::MAX, rests)
+    } else {
+        (range_gap, Vec::new())
+    };
 
-        let heuristics = node_type_heuristic(&children, children_chars);
+    let mut chains = Vec::new();
+    let nb_created_nodes = {
+        let heuristics =
+            node_type_heuristic(&children, &children_chars, collation, max_dist_in_range);
         let nb_created_nodes = heuristics.len();
+        ensure!(
+            nb_created_nodes <= max_siblings + 1,
+            TrieTooManySiblings {
+                prefix: prefix.as_str(),
+                nb_children: children.len(),
+            }
+        );
 
         // Partially create the nodes in the heuristics.
         // Fill all information available without recursion.
+        let mut child_i = 0;
         for (nb_siblings, heuristic) in (0u32..nb_created_nodes as u32).rev().zip(heuristics) {
-            trie_nodes.push(create_partial_node(
-                nb_siblings,
-                heuristic,
-                trie_chars,
-                trie_ranges,
-            ))
+            let nb_nodes = heuristic.nb_nodes();
+            let (mut partial_node, chain) =
+                create_partial_node(nb_siblings, heuristic, trie_chars, trie_ranges);
+            if !chain.is_empty() {
+                chains.push((child_i, chain));
+            }
+
+            // The split children only end their word in the patricia node of their rest
+            for i in child_i..child_i + nb_nodes {
+                if let Some(Some(rest)) = rests.get(i) {
+                    let first_char = children_chars[i].chars().next().unwrap();
+                    remove_partial_word(&mut partial_node, trie_ranges, first_char);
+                    let chain = create_patricia_chain(rest, children[i].frequency(), 0, trie_chars);
+                    chains.push((i, chain));
+                }
+            }
+
+            trie_nodes.push(partial_node);
+            child_i += nb_nodes;
         }
 
         nb_created_nodes
@@ -373,105 +580,227 @@ fn fill_from_trie<N: TrieNodeDrainer>(
     // Call recursively and finish the partial nodes
     let mut partial_i = trie_nodes.len() - nb_created_nodes;
     let mut range_i = None;
-    for child in children {
-        let nb_nodes_before = trie_nodes.len();
+    let mut chains = chains.into_iter().peekable();
+    let prefix_len = prefix.len();
+    for (child_i, child) in children.into_iter().enumerate() {
+        // The chain of patricia nodes of a child is placed before its children,
+        // its partial node being finished by the first node of the chain
+        let mut last = (partial_i, range_i);
+        let mut next = None;
+        if chains.peek().map(|&(i, _)| i) == Some(child_i) {
+            for chain_node in chains.next().unwrap().1 {
+                let chain_index = IndexNodeNonZero::new_opt(to_index_int(trie_nodes.len()));
+                let finished =
+                    finish_current_partial_node(trie_nodes, trie_ranges, chain_index, last);
+                next.get_or_insert(finished);
+                last = (trie_nodes.len(), None);
+                trie_nodes.push(chain_node);
+            }
+        }
 
         // Call recursively with for the current node
-        fill_from_trie(child, trie_nodes, trie_chars, trie_ranges);
+        prefix.push_str(&children_chars[child_i]);
+        if let Some(Some(rest)) = rests.get(child_i) {
+            prefix.push_str(rest);
+        }
+        let nb_nodes_before = trie_nodes.len();
+        fill_from_trie(
+            child,
+            trie_nodes,
+            trie_chars,
+            trie_ranges,
+            collation,
+            range_gap,
+            prefix,
+        )?;
+        prefix.truncate(prefix_len);
 
         let index_first_child = if trie_nodes.len() == nb_nodes_before {
             // If no new node added => no child
             None
         } else {
             // The first child will be placed at the next index in the nodes vector
-            IndexNodeNonZero::new_opt(nb_nodes_before as _)
+            IndexNodeNonZero::new_opt(to_index_int(nb_nodes_before))
         };
 
-        // Finish the current partial node
-        let (new_partial_i, new_range_i) = finish_current_partial_node(
-            trie_nodes,
-            trie_ranges,
-            index_first_child,
-            (partial_i, range_i),
-        );
-
+        // Finish the current partial node, or the last node of its chain
+        let finished =
+            finish_current_partial_node(trie_nodes, trie_ranges, index_first_child, last);
+        let (new_partial_i, new_range_i) = next.unwrap_or(finished);
         partial_i = new_partial_i;
         range_i = new_range_i;
     }
+
+    Ok(())
+}
+
+/// The options of the compilation of a trie (see [from_options](CompiledTrie::from_options)).
+#[derive(Debug, Copy, Clone)]
+pub struct CompileOptions {
+    /// The order of the siblings, by their first character.
+    pub collation: Collation,
+
+    /// The maximum number of code points between two consecutive characters
+    /// of the siblings gathered in a range node.
+    ///
+    /// A range node indexes its characters instead of searching them among the siblings,
+    /// with an empty element for each code point without a character. A larger gap gives
+    /// fewer nodes and faster searches of sparse alphabets, but more memory for these empty
+    /// elements, which the approximate search also scans to find the next character
+    /// (e.g. 1.5 times slower with ranges 99.5% empty), while a gap of 0 gives no range
+    /// node at all. Only the consecutive code points
+    /// are gathered with another collation than the code point order.
+    pub range_gap: u32,
+
+    /// Whether to reuse the characters of the patricia nodes found anywhere in the characters
+    /// array, instead of only in its last bytes.
+    ///
+    /// This gives a smaller characters array when the same strings (e.g. suffixes) are repeated
+    /// far apart in the trie, but takes the memory and the time of an index of these strings
+    /// during the compilation.
+    pub dedup_chars: bool,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            collation: Collation::CODE_POINT,
+            range_gap: CompiledTrie::DEFAULT_RANGE_GAP,
+            dedup_chars: false,
+        }
+    }
 }
 
 impl<N: TrieNodeDrainer> From<N> for CompiledTrie<'_> {
     fn from(root: N) -> Self {
+        Self::from_collated(root, Collation::CODE_POINT)
+    }
+}
+
+impl CompiledTrie<'_> {
+    /// The maximum number of code points between two consecutive characters of the siblings
+    /// gathered in a range node, by default.
+    ///
+    /// Because a RangeElement takes less memory than a CompiledTrieNode,
+    /// we can allow empty cells between 2 elements without taking more memory.
+    /// Moreover, since a range is faster than multiple nodes (indexing vs searching)
+    /// it is prefered in case they both take the same amount of memory.
+    pub const DEFAULT_RANGE_GAP: u32 = 3;
+
+    /// Compile the trie whose children are sorted by the collation
+    /// (by their first character), like [from](CompiledTrie::from) does
+    /// for the code point order.
+    pub fn from_collated<N: TrieNodeDrainer>(root: N, collation: Collation) -> Self {
+        Self::from_collated_with_range_gap(root, collation, Self::DEFAULT_RANGE_GAP)
+    }
+
+    /// Same as [from_collated](CompiledTrie::from_collated), with the maximum number
+    /// of code points between two consecutive characters of a range node
+    /// (see [CompileOptions::range_gap](CompileOptions::range_gap)).
+    pub fn from_collated_with_range_gap<N: TrieNodeDrainer>(
+        root: N,
+        collation: Collation,
+        range_gap: u32,
+    ) -> Self {
+        Self::from_options(
+            root,
+            CompileOptions {
+                collation,
+                range_gap,
+                ..CompileOptions::default()
+            },
+        )
+    }
+
+    /// Compile the trie with the options, whose children are sorted by their collation.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a node has too many children to be compiled (see
+    /// [try_from_options](CompiledTrie::try_from_options)).
+    pub fn from_options<N: TrieNodeDrainer>(root: N, options: CompileOptions) -> Self {
+        Self::try_from_options(root, options).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Compile the trie with the options, whose children are sorted by their collation.
+    ///
+    /// The children of a node are all gathered in ranges when they are too many to be siblings,
+    /// which always succeeds with the code point order. Return an error if they are still
+    /// too many with another collation, whose ranges only have consecutive code points.
+    pub fn try_from_options<N: TrieNodeDrainer>(root: N, options: CompileOptions) -> Result<Self> {
         const NODES_INIT_CAP: usize = 1024;
-        const CHARS_INIT_CAP: usize = 1024;
         const RANGES_INIT_CAP: usize = 1024;
 
         let mut nodes = Vec::with_capacity(NODES_INIT_CAP);
-        let mut big_string = String::with_capacity(CHARS_INIT_CAP);
+        let mut trie_chars = TrieChars::new(options.dedup_chars);
         let mut ranges = Vec::with_capacity(RANGES_INIT_CAP);
-
-        fill_from_trie(root, &mut nodes, &mut big_string, &mut ranges);
-
-        Self {
+        let collation = options.collation;
+
+        fill_from_trie(
+            root,
+            &mut nodes,
+            &mut trie_chars,
+            &mut ranges,
+            collation,
+            i32::try_from(options.range_gap).unwrap_or(i32::MAX),
+            &mut String::new(),
+        )?;
+
+        // The optional side tables are created from the words of the trie
+        #[cfg_attr(
+            not(any(feature = "length-index", feature = "ngram-model")),
+            allow(unused_mut)
+        )]
+        let mut trie = Self {
             nodes: Cow::Owned(nodes),
-            chars: Cow::Owned(big_string),
+            chars: Cow::Owned(trie_chars.chars),
             ranges: Cow::Owned(ranges),
+            collation,
+            #[cfg(feature = "length-index")]
+            lengths: Cow::Owned(Vec::new()),
+            #[cfg(feature = "length-index")]
+            length_parents: Cow::Owned(Vec::new()),
+            #[cfg(feature = "ngram-model")]
+            trigrams: Cow::Owned(Vec::new()),
+            // The source lines are only known by the caller, see set_source_lines
+            #[cfg(feature = "source-lines")]
+            source_lines: Cow::Owned(Vec::new()),
+        };
+
+        #[cfg(feature = "length-index")]
+        {
+            let (lengths, length_parents) = super::length_index::create_length_index(&trie);
+            trie.lengths = Cow::Owned(lengths);
+            trie.length_parents = Cow::Owned(length_parents);
+        }
+
+        #[cfg(feature = "ngram-model")]
+        {
+            trie.trigrams = Cow::Owned(super::ngram_model::create_trigram_model(&trie));
         }
+
+        Ok(trie)
     }
 }
 
 #[cfg(test)]
 mod test {
     // Allow 0-width spaces since they are tested
-    #![allow(clippy::zero_width_space)]
+    #![allow(clippy::invisible_characters)]
+    // Ranges are exclusive on purpose in the tests
+    #![allow(clippy::almost_complete_range)]
 
     use super::*;
+    use crate::test_utils::{create_node, create_simple, NodeDrainer};
     use std::num::NonZeroU32;
 
-    #[derive(Debug, Default, Clone, Eq, PartialEq)]
-    struct NodeDrainer {
-        pub characters: String,
-        pub frequency: Option<NonZeroU32>,
-        pub children: Vec<Self>,
-    }
-
-    impl TrieNodeDrainer for NodeDrainer {
-        fn drain_characters(&mut self) -> String {
-            std::mem::replace(&mut self.characters, String::new())
-        }
-
-        fn frequency(&self) -> Option<NonZeroU32> {
-            self.frequency
-        }
-
-        fn drain_children(&mut self) -> Vec<Self> {
-            std::mem::replace(&mut self.children, Vec::new())
-        }
-    }
-
-    fn create_simple(character: char, freq: u32, children: Vec<NodeDrainer>) -> NodeDrainer {
-        NodeDrainer {
-            characters: character.to_string(),
-            frequency: NonZeroU32::new(freq),
-            children,
-        }
-    }
-
-    fn create_patricia(s: &str, freq: u32, children: Vec<NodeDrainer>) -> NodeDrainer {
-        NodeDrainer {
-            characters: s.to_string(),
-            frequency: NonZeroU32::new(freq),
-            children,
-        }
-    }
-
     fn run_assert_heuristic(
         nodes: &[NodeDrainer],
         nodes_chars: Vec<String>,
         target: Vec<TrieNode<NodeDrainer>>,
     ) {
         let nb_nodes = nodes.len();
-        let ret = node_type_heuristic(nodes, nodes_chars);
+        let ret = node_type_heuristic(nodes, &nodes_chars, Collation::CODE_POINT, 3);
         assert_eq!(nodes.len(), nb_nodes);
         assert_eq!(ret, target);
     }
@@ -480,7 +809,7 @@ mod test {
     fn test_heuristic_empty() {
         let mut nodes: Vec<NodeDrainer> = vec![];
         let nodes_chars = extract_characters(&mut nodes);
-        let _ = run_assert_heuristic(&nodes, nodes_chars, vec![]);
+        run_assert_heuristic(&nodes, nodes_chars, vec![]);
     }
 
     #[test]
@@ -510,18 +839,18 @@ mod test {
             "NΘ stop the an​*̶͑̾̾​̅ͫ͏̙̤g͇̫͛͆̾ͫ̑͆l͖͉̗̩̳̟̍ͫͥͨe̠̅s ͎a̧͈͖r̽̾̈́͒͑e n​ot rè̑ͧ̌aͨl̘̝̙̃ͤ͂̾̆ ZA̡͊͠͝LGΌ ISͮ̂҉̯͈͕̹̘̱ TO͇̹̺ͅƝ̴ȳ̳ TH̘Ë͖́̉ ͠P̯͍̭O̚​N̐Y̡ H̸̡̪̯ͨ͊̽̅̾̎Ȩ̬̩̾͛ͪ̈́̀́͘ ̶̧̨̱̹̭̯ͧ̾ͬC̷̙̲̝͖ͭ̏ͥͮ͟Oͮ͏̮̪̝͍M̲̖͊̒ͪͩͬ̚̚͜Ȇ̴̟̟͙̞ͩ͌͝S̨̥̫͎̭ͯ̿̔̀ͅ";
 
         let mut nodes = vec![
-            create_patricia("abaca", 0, vec![]),
-            create_patricia("foobar", 0, vec![]),
-            create_patricia(WEIRD_STRING, 0, vec![]),
+            create_node("abaca", 0, vec![]),
+            create_node("foobar", 0, vec![]),
+            create_node(WEIRD_STRING, 0, vec![]),
         ];
         let nodes_chars = extract_characters(&mut nodes);
         run_assert_heuristic(
             &nodes,
             nodes_chars,
             vec![
-                TrieNode::Patricia(&nodes[0], "abaca".to_string()),
-                TrieNode::Patricia(&nodes[1], "foobar".to_string()),
-                TrieNode::Patricia(&nodes[2], WEIRD_STRING.to_string()),
+                TrieNode::Patricia(&nodes[0], "abaca"),
+                TrieNode::Patricia(&nodes[1], "foobar"),
+                TrieNode::Patricia(&nodes[2], WEIRD_STRING),
             ],
         );
     }
@@ -601,6 +930,87 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_heuristic_sparse_ranges() {
+        // One character out of 3 stays in the same range, but not one out of 4
+        let mut nodes: Vec<_> = "adgjmptx"
+            .chars()
+            .map(|c| create_simple(c, 1, vec![]))
+            .collect();
+        let trie = CompiledTrie::from(NodeDrainer {
+            children: nodes.clone(),
+            ..Default::default()
+        });
+        let nodes_chars = extract_characters(&mut nodes);
+        run_assert_heuristic(
+            &nodes,
+            nodes_chars,
+            vec![
+                TrieNode::Range(&nodes[..6], "adgjmp".chars().collect()),
+                TrieNode::Simple(&nodes[6], 't'),
+                TrieNode::Simple(&nodes[7], 'x'),
+            ],
+        );
+
+        // The next element of a range is at most 3 elements away
+        let max_empty_run = trie
+            .ranges()
+            .split(|e| e.word_freq.is_some() || e.index_first_child.is_some())
+            .map(|empty_run| empty_run.len())
+            .max();
+        assert_eq!(max_empty_run, Some(2));
+    }
+
+    #[test]
+    fn test_heuristic_surrogates() {
+        // The characters around the surrogates, in reach of each other with a large distance
+        let chars = ['\u{D7FE}', '\u{D7FF}', '\u{E000}', '\u{E001}'];
+        let mut nodes: Vec<_> = chars.iter().map(|&c| create_simple(c, 1, vec![])).collect();
+        let nodes_chars = extract_characters(&mut nodes);
+        let ret = node_type_heuristic(&nodes, &nodes_chars, Collation::CODE_POINT, 0x1000);
+        assert_eq!(
+            ret,
+            vec![
+                TrieNode::Range(&nodes[..2], chars[..2].to_vec()),
+                TrieNode::Range(&nodes[2..], chars[2..].to_vec()),
+            ]
+        );
+
+        // No range of the compiled trie spans them
+        let nodes: Vec<_> = chars.iter().map(|&c| create_simple(c, 1, vec![])).collect();
+        let trie = CompiledTrie::from(NodeDrainer {
+            children: nodes,
+            ..Default::default()
+        });
+        assert!(trie.validate().is_ok());
+        for node in trie.nodes().iter() {
+            if let NodeValue::Range(n) = node.node_value() {
+                let range_len = usize::from(n.end_index) - usize::from(n.start_index);
+                let last = n.first_char as u32 + range_len as u32 - 1;
+                assert!(std::char::from_u32(last).is_some());
+                assert!(!crosses_surrogates(
+                    n.first_char,
+                    std::char::from_u32(last).unwrap()
+                ));
+            }
+        }
+        let words: Vec<_> = trie.iter_words().map(|(word, _)| word).collect();
+        assert_eq!(
+            words,
+            chars.iter().map(|c| c.to_string()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Range crossing the surrogates")]
+    fn test_add_range_surrogates() {
+        let nodes = [
+            create_simple('\u{D7FF}', 1, vec![]),
+            create_simple('\u{E000}', 1, vec![]),
+        ];
+        add_range(&mut Vec::new(), &nodes, &['\u{D7FF}', '\u{E000}']);
+    }
+
     #[test]
     fn test_heuristic_mixed() {
         let (chars1, nodes1) = create_range('←'..'⇿', 2);
@@ -610,13 +1020,13 @@ mod test {
 
         let parts = vec![
             vec![
-                create_patricia("abaca", 0, vec![]),
+                create_node("abaca", 0, vec![]),
                 create_simple('b', 0, vec![]),
-                create_patricia("foobar", 0, vec![]),
+                create_node("foobar", 0, vec![]),
             ],
             nodes1,
             vec![
-                create_patricia(WEIRD_STRING, 0, vec![]),
+                create_node(WEIRD_STRING, 0, vec![]),
                 create_simple('🀄', 0, vec![]),
             ],
         ];
@@ -628,11 +1038,11 @@ mod test {
             &nodes,
             nodes_chars,
             vec![
-                TrieNode::Patricia(&nodes[0], "abaca".to_string()),
+                TrieNode::Patricia(&nodes[0], "abaca"),
                 TrieNode::Simple(&nodes[1], 'b'),
-                TrieNode::Patricia(&nodes[2], "foobar".to_string()),
+                TrieNode::Patricia(&nodes[2], "foobar"),
                 TrieNode::Range(&nodes[3..(3 + len1)], chars1),
-                TrieNode::Patricia(&nodes[3 + len1], WEIRD_STRING.to_string()),
+                TrieNode::Patricia(&nodes[3 + len1], WEIRD_STRING),
                 TrieNode::Simple(&nodes[4 + len1], '🀄'),
             ],
         );
@@ -735,16 +1145,13 @@ mod test {
             '-',
             0,
             vec![
-                create_patricia(
+                create_node(
                     "aba",
                     1,
-                    vec![
-                        create_patricia("baba", 2, vec![]),
-                        create_patricia("ca", 1, vec![]),
-                    ],
+                    vec![create_node("baba", 2, vec![]), create_node("ca", 1, vec![])],
                 ),
-                create_patricia("rota", 0, vec![create_patricia("ba", 1, vec![])]),
-                create_patricia("super", 5, vec![]),
+                create_node("rota", 0, vec![create_node("ba", 1, vec![])]),
+                create_node("super", 5, vec![]),
             ],
         );
         let target_nodes = vec![
@@ -931,7 +1338,7 @@ mod test {
             '-',
             0,
             vec![
-                create_patricia(
+                create_node(
                     "apata",
                     1,
                     vec![create_simple('d', 2, vec![]), create_simple('f', 1, vec![])],
@@ -941,13 +1348,13 @@ mod test {
                     0,
                     vec![
                         create_simple('a', 9, vec![]),
-                        create_patricia(HE_COMES, 1, vec![]),
+                        create_node(HE_COMES, 1, vec![]),
                         create_simple('r', 6, vec![]),
                         create_simple('t', 1, vec![]),
                         create_simple('w', 7, vec![]),
                     ],
                 ),
-                create_simple('f', 5, vec![create_patricia(RUST_IS_LOVE, 999, vec![])]),
+                create_simple('f', 5, vec![create_node(RUST_IS_LOVE, 999, vec![])]),
             ],
         );
         let target_nodes = vec![
@@ -1005,7 +1412,7 @@ mod test {
                 PatriciaNode {
                     index_first_child: None,
                     word_freq: NonZeroU32::new(999),
-                    start_index: IndexChar::new(5 + HE_COMES.len() as u32),
+                    start_index: IndexChar::new(5 + HE_COMES.len() as IndexInt),
                 },
                 0,
                 RUST_IS_LOVE.len() as u32,
@@ -1049,4 +1456,258 @@ mod test {
         ];
         run_assert_from(root, &target_nodes, &target_chars, &target_ranges);
     }
+
+    #[test]
+    fn test_from_long_patricia() {
+        // 5000 characters of 1 and 2 bytes, split on the characters boundaries
+        let long: String = (0..5000)
+            .map(|i| if i % 3 == 0 { 'é' } else { 'a' })
+            .collect();
+        let root = create_simple(
+            '-',
+            0,
+            vec![
+                create_simple('b', 3, vec![]),
+                create_node(&long, 1, vec![create_simple('s', 2, vec![])]),
+            ],
+        );
+        let trie = CompiledTrie::from(root);
+        assert!(trie.validate().is_ok());
+
+        let patricia_lens: Vec<_> = trie
+            .nodes()
+            .iter()
+            .filter(|n| matches!(n.node_value(), NodeValue::Patricia(_)))
+            // SAFETY: Safe because in a patricia node
+            .map(|n| unsafe { n.patricia_range() })
+            .map(|r| usize::from(r.end) - usize::from(r.start))
+            .collect();
+        assert_eq!(patricia_lens.len(), 2);
+        assert_eq!(patricia_lens.iter().sum::<usize>(), long.len());
+        assert!(patricia_lens
+            .iter()
+            .all(|&len| len <= CompiledTrieNode::MAX_PAT_STR_LENGTH));
+
+        assert_eq!(trie.frequency_of(&long), NonZeroU32::new(1));
+        assert_eq!(trie.frequency_of(&(long.clone() + "s")), NonZeroU32::new(2));
+        assert_eq!(trie.frequency_of("b"), NonZeroU32::new(3));
+        let prefix: String = long.chars().take(4000).collect();
+        assert_eq!(trie.frequency_of(&prefix), None);
+        assert_eq!(
+            trie.iter_words().map(|(w, _)| w.len()).collect::<Vec<_>>(),
+            vec![1, long.len(), long.len() + 1]
+        );
+    }
+
+    /// Return the given number of valid characters, the first ones `gap` code points apart
+    /// and the others consecutive.
+    fn spread_chars(nb_chars: usize, nb_spread: usize, gap: u32) -> Vec<char> {
+        let mut code_point = 1;
+        let mut chars = Vec::with_capacity(nb_chars);
+        while chars.len() < nb_chars {
+            if let Some(c) = std::char::from_u32(code_point) {
+                chars.push(c);
+                code_point += if chars.len() < nb_spread { gap } else { 1 };
+            } else {
+                code_point += 1;
+            }
+        }
+        chars
+    }
+
+    #[test]
+    fn test_from_many_siblings() {
+        // 265,000 characters too far from each other for a range, then 35,000 consecutive ones
+        let chars = spread_chars(300_000, 265_000, CompiledTrie::DEFAULT_RANGE_GAP + 1);
+        let children = chars
+            .iter()
+            .enumerate()
+            .map(|(i, &c)| create_simple(c, i as u32 + 1, vec![]))
+            .collect();
+        let trie = CompiledTrie::from(create_simple('-', 0, children));
+        assert!(trie.validate().is_ok());
+
+        // The characters are gathered in ranges instead of more nodes than their siblings can count
+        let roots = trie.get_root_siblings().unwrap();
+        assert!(roots.len() <= CompiledTrieNode::MAX_NB_SIBLINGS as usize + 1);
+        assert!(roots
+            .iter()
+            .all(|n| matches!(n.node_value(), NodeValue::Range(_))));
+        for (i, c) in chars.iter().enumerate().step_by(997) {
+            assert_eq!(
+                trie.frequency_of(&c.to_string()),
+                NonZeroU32::new(i as u32 + 1)
+            );
+        }
+        assert_eq!(trie.word_count(), chars.len());
+    }
+
+    #[test]
+    fn test_from_range_gap() {
+        // Characters 1, 2 and 5 code points apart
+        let chars: Vec<char> = "abcegjo".chars().collect();
+        let children: Vec<_> = chars
+            .iter()
+            .enumerate()
+            .map(|(i, &c)| create_simple(c, i as u32 + 1, vec![]))
+            .collect();
+        let compile = |range_gap| {
+            let root = create_simple('-', 0, children.clone());
+            CompiledTrie::from_collated_with_range_gap(root, Collation::CODE_POINT, range_gap)
+        };
+
+        // (range gap, number of nodes, number of range elements)
+        for &(range_gap, nb_nodes, nb_ranges) in &[
+            (0, 7, 0),
+            (1, 5, 3),
+            (CompiledTrie::DEFAULT_RANGE_GAP, 2, 10),
+            (5, 1, 15),
+            (u32::MAX, 1, 15),
+        ] {
+            let trie = compile(range_gap);
+            assert!(trie.validate().is_ok());
+            assert_eq!(trie.nodes().len(), nb_nodes, "{}", range_gap);
+            assert_eq!(trie.ranges().len(), nb_ranges, "{}", range_gap);
+            for (i, c) in chars.iter().enumerate() {
+                let freq = trie.frequency_of(&c.to_string());
+                assert_eq!(freq, NonZeroU32::new(i as u32 + 1));
+            }
+        }
+
+        // The default gap is the one of the other compilations
+        let trie = CompiledTrie::from(create_simple('-', 0, children.clone()));
+        assert_eq!(trie.nodes().len(), 2);
+        assert_eq!(trie.ranges(), compile(3).ranges());
+    }
+
+    #[test]
+    fn test_from_dedup_chars() {
+        // The same suffixes, far apart in the characters array
+        let create = || {
+            let separators = (0..300u32)
+                .map(|i| {
+                    let c = std::char::from_u32('一' as u32 + i).unwrap();
+                    create_node(&c.to_string().repeat(10), 1, vec![])
+                })
+                .collect();
+            let children = vec![
+                create_node("ab", 0, vec![create_node("ation", 1, vec![])]),
+                create_node("cd", 0, separators),
+                create_node("ef", 0, vec![create_node("tion", 2, vec![])]),
+                create_node("gh", 0, vec![create_node("ation", 3, vec![])]),
+            ];
+            create_simple('-', 0, children)
+        };
+        let compile = |dedup_chars| {
+            let options = CompileOptions {
+                dedup_chars,
+                ..CompileOptions::default()
+            };
+            CompiledTrie::from_options(create(), options)
+        };
+
+        let trie = compile(false);
+        let dedup_trie = compile(true);
+        assert!(dedup_trie.validate().is_ok());
+        assert_eq!(
+            dedup_trie.iter_words().collect::<Vec<_>>(),
+            trie.iter_words().collect::<Vec<_>>()
+        );
+
+        // Both the suffix "tion" and "ation" are found instead of appended again
+        assert_eq!(
+            dedup_trie.chars().len() + "ationtion".len(),
+            trie.chars().len()
+        );
+        assert_eq!(dedup_trie.nodes().len(), trie.nodes().len());
+    }
+
+    /// The patricia nodes are split after their first character to be gathered in ranges.
+    #[test]
+    fn test_from_too_many_patricia_siblings() {
+        let limit = CompiledTrieNode::MAX_NB_SIBLINGS as usize + 1;
+        let chars = spread_chars(limit + 1, 0, 1);
+        let long_rest = "b".repeat(CompiledTrieNode::MAX_PAT_STR_LENGTH + 1);
+        let word = |i: usize, c: char| match i % 3 {
+            0 => c.to_string(),
+            // A single rest too long for a patricia node
+            _ if i == 2 => format!("{}{}", c, long_rest),
+            _ => format!("{}ab", c),
+        };
+        let children = chars
+            .iter()
+            .enumerate()
+            .map(|(i, &c)| match i % 3 {
+                0 => create_simple(c, 1, vec![]),
+                1 => create_node(&word(i, c), 2, vec![create_simple('z', 3, vec![])]),
+                _ => create_node(&word(i, c), 4, vec![]),
+            })
+            .collect();
+        let trie = CompiledTrie::from(create_simple('-', 0, children));
+        assert!(trie.validate().is_ok());
+
+        let roots = trie.get_root_siblings().unwrap();
+        assert!(roots.len() <= limit);
+        assert!(roots
+            .iter()
+            .all(|n| matches!(n.node_value(), NodeValue::Range(_))));
+        for (i, &c) in chars.iter().enumerate().take(10).chain(
+            chars
+                .iter()
+                .enumerate()
+                .step_by(997)
+                .chain(chars.iter().enumerate().rev().take(10)),
+        ) {
+            let freq = [1, 2, 4][i % 3];
+            assert_eq!(trie.frequency_of(&word(i, c)), NonZeroU32::new(freq));
+            if i % 3 != 0 {
+                // The first character of a split patricia node is not a word
+                assert_eq!(trie.frequency_of(&c.to_string()), None);
+            }
+            if i % 3 == 1 {
+                let child = format!("{}z", word(i, c));
+                assert_eq!(trie.frequency_of(&child), NonZeroU32::new(3));
+            }
+        }
+        assert_eq!(trie.word_count(), chars.len() + (chars.len() + 1) / 3);
+    }
+
+    /// With another collation, only the consecutive code points are gathered in ranges.
+    #[test]
+    fn test_from_too_many_siblings_collated() {
+        let limit = CompiledTrieNode::MAX_NB_SIBLINGS as usize + 1;
+        let children = spread_chars(limit + 1, limit + 1, 2)
+            .into_iter()
+            .map(|c| create_simple(c, 1, vec![create_node("ab", 1, vec![])]))
+            .collect();
+        let id = NonZeroU32::new(Collation::FIRST_CUSTOM_ID).unwrap();
+        let collation = Collation::custom(id, |a, b| a.cmp(&b)).unwrap();
+        let root = create_simple('-', 0, vec![create_simple('x', 0, children)]);
+        let options = CompileOptions {
+            collation,
+            ..CompileOptions::default()
+        };
+        let error = CompiledTrie::try_from_options(root, options).err().unwrap();
+        assert!(matches!(
+            &error,
+            crate::Error::TrieTooManySiblings { prefix, nb_children } if prefix == "x" && *nb_children == limit + 1
+        ));
+    }
+
+    #[test]
+    fn test_to_index_int() {
+        assert_eq!(to_index_int(0), 0);
+        assert_eq!(to_index_int(u32::MAX as usize), IndexInt::from(u32::MAX));
+        #[cfg(all(feature = "large-index", target_pointer_width = "64"))]
+        assert_eq!(to_index_int(u32::MAX as usize + 1), 1 << 32);
+    }
+
+    /// A character array longer than `u32::MAX` bytes, which is too large to be built here,
+    /// fails to be indexed when compiling the trie.
+    #[test]
+    #[cfg(all(not(feature = "large-index"), target_pointer_width = "64"))]
+    #[should_panic(expected = "enable the `large-index` feature")]
+    fn test_to_index_int_overflow() {
+        to_index_int(u32::MAX as usize + 1);
+    }
 }
@@ -4,7 +4,38 @@
 //! Also they can only be set inside this crate, so that the functions here
 //! can be sure that these indices are valid (for the trie that provided them).
 
-use std::{num::NonZeroU32, ops::Deref};
+use std::{convert::TryFrom, ops::Deref};
+
+/// The integer of the indices, `u32` by default or `u64` with the `large-index` feature
+/// for the tries whose arrays have more than `u32::MAX` elements (or characters bytes).
+#[cfg(not(feature = "large-index"))]
+pub type IndexInt = u32;
+/// The integer of the indices, `u32` by default or `u64` with the `large-index` feature
+/// for the tries whose arrays have more than `u32::MAX` elements (or characters bytes).
+#[cfg(feature = "large-index")]
+pub type IndexInt = u64;
+
+/// The non-zero version of [IndexInt](IndexInt).
+#[cfg(not(feature = "large-index"))]
+pub type IndexIntNonZero = std::num::NonZeroU32;
+/// The non-zero version of [IndexInt](IndexInt).
+#[cfg(feature = "large-index")]
+pub type IndexIntNonZero = std::num::NonZeroU64;
+
+/// Convert a position in an array of the trie to an index.
+///
+/// # Panics
+/// Panics if the position does not fit in an [IndexInt](IndexInt),
+/// the trie then needing the `large-index` feature.
+pub(crate) fn to_index_int(pos: usize) -> IndexInt {
+    match IndexInt::try_from(pos) {
+        Ok(index) => index,
+        Err(_) => panic!(
+            "the array index {} overflows the trie indices, enable the `large-index` feature",
+            pos
+        ),
+    }
+}
 
 // Macro to implement slice indexing for corresponding index wrappers
 macro_rules! index_wrappers {
@@ -14,11 +45,11 @@ macro_rules! index_wrappers {
             /// **UB Warning:** This index must not be used on another trie than the one that provided it.
             #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
             pub struct $index {
-                index: u32,
+                index: IndexInt,
             }
 
             impl Deref for $index {
-                type Target = u32;
+                type Target = IndexInt;
 
                 fn deref(&self) -> &Self::Target {
                     &self.index
@@ -45,7 +76,7 @@ macro_rules! derive_from {
         $(
             impl From<$index> for $into {
                 fn from(value: $index) -> Self {
-                    u32::from(value.index) as $into
+                    IndexInt::from(value.index) as $into
                 }
             }
         )*
@@ -57,18 +88,18 @@ macro_rules! derive_from {
 /// This enables some memory optimizations for [RangeElement](crate::RangeElement).
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub struct IndexNodeNonZero {
-    index: NonZeroU32,
+    index: IndexIntNonZero,
 }
 
 impl IndexNodeNonZero {
-    pub(super) fn new_opt(index: u32) -> Option<Self> {
-        NonZeroU32::new(index).map(Self::new)
+    pub(super) fn new_opt(index: IndexInt) -> Option<Self> {
+        IndexIntNonZero::new(index).map(Self::new)
     }
 }
 
 index_wrappers!(IndexChar, IndexRange);
-derive_new!(u32, IndexChar, IndexRange);
-derive_new!(NonZeroU32, IndexNodeNonZero);
-derive_from!(IndexChar, u32, u64, usize);
-derive_from!(IndexRange, u32, u64, usize);
-derive_from!(IndexNodeNonZero, u32, u64, usize);
+derive_new!(IndexInt, IndexChar, IndexRange);
+derive_new!(IndexIntNonZero, IndexNodeNonZero);
+derive_from!(IndexChar, u64, usize);
+derive_from!(IndexRange, u64, usize);
+derive_from!(IndexNodeNonZero, u64, usize);
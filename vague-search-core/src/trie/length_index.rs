@@ -0,0 +1,325 @@
+//! An optional index of the words of a [CompiledTrie](crate::CompiledTrie) by their length.
+//!
+//! Enabled by the `length-index` feature since it is stored in the dictionary.
+//!
+//! The words are not stored a second time: each element refers to the position of the end
+//! of its word in the trie, like the [source lines](super::source_lines), and the word is
+//! rebuilt from the parent of each node, which the index also stores.
+
+use super::index::*;
+use crate::{CompiledTrie, NodeValue};
+use std::{cmp::Ordering, convert::TryFrom, num::NonZeroU32};
+
+/// Represent the length index array of the [CompiledTrie](crate::CompiledTrie)
+pub type LengthSlice = [LengthElement];
+
+/// Represent the parent array of the length index of the [CompiledTrie](crate::CompiledTrie),
+/// with the parent of each node.
+pub type LengthParentSlice = [LengthParent];
+
+/// An element of the length index array, representing a word of the trie.
+/// The elements are sorted by their number of characters, then by their word.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LengthElement {
+    /// The number of characters (not bytes) of the word.
+    pub nb_chars: IndexInt,
+
+    /// The index of the node where the word ends.
+    pub node_index: IndexInt,
+
+    /// The offset of the last character of the word in the node if it is a range, else 0.
+    pub range_offset: IndexInt,
+}
+
+/// The position of the parent of a node in the trie, i.e. the end of the word
+/// its characters follow.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LengthParent {
+    /// The index of the parent node, or [NO_PARENT](LengthParent::NO_PARENT)
+    /// for the siblings of the root and the nodes which cannot be reached from it.
+    pub node_index: IndexInt,
+
+    /// The offset of the parent element in the parent node if it is a range, else 0.
+    pub range_offset: IndexInt,
+}
+
+impl LengthParent {
+    /// The node index of the parent of the nodes without parent.
+    pub const NO_PARENT: IndexInt = IndexInt::MAX;
+}
+
+/// Create the length index array of the trie, along with the parent of each of its nodes.
+pub(super) fn create_length_index(trie: &CompiledTrie) -> (Vec<LengthElement>, Vec<LengthParent>) {
+    let no_parent = LengthParent {
+        node_index: LengthParent::NO_PARENT,
+        range_offset: 0,
+    };
+    let mut parents = vec![no_parent.clone(); trie.nodes.len()];
+    let mut words = Vec::new();
+
+    // The sibling groups to visit, with their parent and the word it ends
+    let mut stack = Vec::new();
+    if !trie.nodes.is_empty() {
+        stack.push((0, no_parent, String::new()));
+    }
+    while let Some((start, parent, prefix)) = stack.pop() {
+        let end = start + trie.nodes[start].nb_siblings() as usize + 1;
+        parents[start..end]
+            .iter_mut()
+            .for_each(|p| *p = parent.clone());
+        for (index, node) in (start..end).zip(&trie.nodes[start..end]) {
+            // Record the word of the node and visit its children
+            let mut visit =
+                |word: String,
+                 range_offset: usize,
+                 word_freq: Option<NonZeroU32>,
+                 index_first_child: Option<IndexNodeNonZero>| {
+                    let word_end = LengthParent {
+                        node_index: to_index_int(index),
+                        range_offset: to_index_int(range_offset),
+                    };
+                    if word_freq.is_some() {
+                        let nb_chars = to_index_int(word.chars().count());
+                        words.push((nb_chars, word.clone(), word_end.clone()));
+                    }
+                    if let Some(child) = index_first_child {
+                        stack.push((usize::from(child), word_end, word));
+                    }
+                };
+
+            match node.node_value() {
+                NodeValue::Naive(n) => visit(
+                    format!("{}{}", prefix, n.character),
+                    0,
+                    n.word_freq,
+                    n.index_first_child,
+                ),
+                NodeValue::Patricia(n) => {
+                    // SAFETY: Safe because in a patricia node
+                    let pat_range = unsafe { node.patricia_range() };
+                    let chars = trie.get_chars(pat_range.start, pat_range.end);
+                    visit(
+                        format!("{}{}", prefix, chars),
+                        0,
+                        n.word_freq,
+                        n.index_first_child,
+                    );
+                }
+                NodeValue::Range(n) => {
+                    let range = trie.get_range(n.start_index, n.end_index);
+                    for (offset, elem) in range.iter().enumerate() {
+                        // The holes of the range can have invalid characters
+                        if elem.word_freq.is_none() && elem.index_first_child.is_none() {
+                            continue;
+                        }
+                        let character = range_char(n.first_char, offset);
+                        visit(
+                            format!("{}{}", prefix, character),
+                            offset,
+                            elem.word_freq,
+                            elem.index_first_child,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    words.sort_unstable_by(|(a_chars, a_word, _), (b_chars, b_word, _)| {
+        a_chars.cmp(b_chars).then_with(|| a_word.cmp(b_word))
+    });
+    let lengths = words
+        .into_iter()
+        .map(|(nb_chars, _, word_end)| LengthElement {
+            nb_chars,
+            node_index: word_end.node_index,
+            range_offset: word_end.range_offset,
+        })
+        .collect();
+
+    (lengths, parents)
+}
+
+/// Return the character of the element at the offset of a range node beginning at `first_char`,
+/// which is valid in a range of a word of a valid trie.
+fn range_char(first_char: char, offset: usize) -> char {
+    std::char::from_u32(first_char as u32 + offset as u32).expect("Invalid character of a range")
+}
+
+impl CompiledTrie<'_> {
+    /// Return a slice of the length index array.
+    pub(crate) fn lengths(&self) -> &LengthSlice {
+        &self.lengths
+    }
+
+    /// Return a slice of the parent array of the length index.
+    pub(crate) fn length_parents(&self) -> &LengthParentSlice {
+        &self.length_parents
+    }
+
+    /// Return the word ending at the node index and range offset, with its frequency,
+    /// rebuilt with the parents of its nodes.
+    fn length_word(&self, node_index: usize, range_offset: usize) -> (String, NonZeroU32) {
+        let mut word = String::new();
+        let mut word_freq = None;
+        let (mut index, mut offset) = (node_index, range_offset);
+        loop {
+            let node = &self.nodes[index];
+            let freq = match node.node_value() {
+                NodeValue::Naive(n) => {
+                    word.insert(0, n.character);
+                    n.word_freq
+                }
+                NodeValue::Patricia(n) => {
+                    // SAFETY: Safe because in a patricia node
+                    let pat_range = unsafe { node.patricia_range() };
+                    word.insert_str(0, self.get_chars(pat_range.start, pat_range.end));
+                    n.word_freq
+                }
+                NodeValue::Range(n) => {
+                    word.insert(0, range_char(n.first_char, offset));
+                    self.get_range(n.start_index, n.end_index)[offset].word_freq
+                }
+            };
+            // The frequency is the one of the last node of the word
+            word_freq = word_freq.or(freq);
+
+            let parent = &self.length_parents[index];
+            if parent.node_index == LengthParent::NO_PARENT {
+                break;
+            }
+            index = parent.node_index as usize;
+            offset = parent.range_offset as usize;
+        }
+        (
+            word,
+            word_freq.expect("Length index element which is not a word"),
+        )
+    }
+
+    /// Iterate over all words of the trie composed of exactly `len` characters,
+    /// along with their frequency.
+    ///
+    /// The words are returned in lexicographic order, without traversing the trie.
+    pub fn words_of_length(&self, len: usize) -> impl Iterator<Item = (String, NonZeroU32)> + '_ {
+        // Find the first element of the wanted length (the comparison never returns Equal),
+        // no word having more characters than the index counts
+        let (start, len) = match IndexInt::try_from(len) {
+            Ok(len) => {
+                let start = self
+                    .lengths
+                    .binary_search_by(|e| e.nb_chars.cmp(&len).then(Ordering::Greater))
+                    .unwrap_or_else(|i| i);
+                (start, len)
+            }
+            Err(_) => (self.lengths.len(), 0),
+        };
+
+        self.lengths[start..]
+            .iter()
+            .take_while(move |e| e.nb_chars == len)
+            .map(move |e| self.length_word(e.node_index as usize, e.range_offset as usize))
+    }
+
+    /// Return the length index for the nodes reordered with the given groups
+    /// and new node indices, without the nodes which are not in any group.
+    pub(super) fn reorder_lengths(
+        &self,
+        groups: &[usize],
+        new_indices: &[IndexInt],
+    ) -> (Vec<LengthElement>, Vec<LengthParent>) {
+        let new_index = |index: IndexInt| match index {
+            LengthParent::NO_PARENT => LengthParent::NO_PARENT,
+            index => new_indices[index as usize],
+        };
+
+        // The ranges are copied as they are, so the offsets do not change
+        let lengths = self
+            .lengths
+            .iter()
+            .map(|e| LengthElement {
+                node_index: new_index(e.node_index),
+                ..e.clone()
+            })
+            .collect();
+
+        let mut parents = Vec::with_capacity(self.length_parents.len());
+        for &start in groups {
+            let end = start + self.nodes[start].nb_siblings() as usize + 1;
+            parents.extend(
+                self.length_parents[start..end]
+                    .iter()
+                    .map(|p| LengthParent {
+                        node_index: new_index(p.node_index),
+                        ..p.clone()
+                    }),
+            );
+        }
+        (lengths, parents)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::create_node;
+
+    fn words_of_length(trie: &CompiledTrie, len: usize) -> Vec<(String, u32)> {
+        trie.words_of_length(len)
+            .map(|(w, f)| (w, f.get()))
+            .collect()
+    }
+
+    #[test]
+    fn test_words_of_length() {
+        let root = create_node(
+            "-",
+            0,
+            vec![
+                create_node(
+                    "a",
+                    1,
+                    vec![create_node("b", 2, vec![]), create_node("c", 3, vec![])],
+                ),
+                create_node("b", 0, vec![create_node("ée", 4, vec![])]),
+                create_node("car", 5, vec![create_node("s", 6, vec![])]),
+                create_node("日", 7, vec![]),
+            ],
+        );
+        let trie = CompiledTrie::from(root);
+
+        assert_eq!(words_of_length(&trie, 0), vec![]);
+        assert_eq!(
+            words_of_length(&trie, 1),
+            vec![("a".to_string(), 1), ("日".to_string(), 7)]
+        );
+        assert_eq!(
+            words_of_length(&trie, 2),
+            vec![("ab".to_string(), 2), ("ac".to_string(), 3)]
+        );
+        assert_eq!(
+            words_of_length(&trie, 3),
+            vec![("bée".to_string(), 4), ("car".to_string(), 5)]
+        );
+        assert_eq!(words_of_length(&trie, 4), vec![("cars".to_string(), 6)]);
+        assert_eq!(words_of_length(&trie, 5), vec![]);
+
+        // The words are rebuilt from the nodes, also once they are reordered
+        let bfs_trie = trie.to_bfs_layout();
+        for len in 0..6 {
+            assert_eq!(words_of_length(&bfs_trie, len), words_of_length(&trie, len));
+        }
+
+        // The lengths are not truncated to the ones counted by the index
+        #[cfg(target_pointer_width = "64")]
+        assert_eq!(words_of_length(&trie, (1 << 32) | 3), vec![]);
+        assert_eq!(words_of_length(&trie, usize::MAX), vec![]);
+    }
+
+    #[test]
+    fn test_words_of_length_empty() {
+        let trie = CompiledTrie::from(create_node("-", 0, vec![]));
+        assert_eq!(words_of_length(&trie, 0), vec![]);
+        assert_eq!(words_of_length(&trie, 1), vec![]);
+    }
+}
@@ -0,0 +1,189 @@
+//! Exact lookup of the words of a [CompiledTrie](crate::CompiledTrie),
+//! to use the core crate as a library without the search engine of the binary.
+
+use crate::{CompiledTrie, CompiledTrieNode, NodeValue};
+use std::{cmp::Ordering, num::NonZeroU32};
+
+impl CompiledTrie<'_> {
+    /// Return the order of the first character of the node compared to the character
+    /// with the collation of the trie, or Equal if the character is in the range of a range node.
+    fn cmp_node_char(&self, node: &CompiledTrieNode, character: char) -> Ordering {
+        match node.node_value() {
+            NodeValue::Naive(n) => self.collation.cmp(n.character, character),
+            NodeValue::Patricia(_) => {
+                // SAFETY: Safe because in a patricia node
+                let pat_range = unsafe { node.patricia_range() };
+                let chars = self.get_chars(pat_range.start, pat_range.end);
+                chars
+                    .chars()
+                    .next()
+                    .map_or(Ordering::Less, |c| self.collation.cmp(c, character))
+            }
+            NodeValue::Range(n) => {
+                let range_len = usize::from(n.end_index) - usize::from(n.start_index);
+                let offset = (character as usize).wrapping_sub(n.first_char as usize);
+                if offset < range_len {
+                    Ordering::Equal
+                } else {
+                    self.collation.cmp(n.first_char, character)
+                }
+            }
+        }
+    }
+
+    /// Return the frequency of the word, or None if it is not a word of the trie.
+    pub fn frequency_of(&self, word: &str) -> Option<NonZeroU32> {
+        let mut children = self.get_root_siblings()?;
+        let mut rest = word;
+        loop {
+            let first_char = rest.chars().next()?;
+
+            // The siblings are sorted by their first character
+            let i = children
+                .binary_search_by(|child| self.cmp_node_char(child, first_char))
+                .ok()?;
+            let child = &children[i];
+            let (word_freq, index_first_child, nb_bytes) = match child.node_value() {
+                NodeValue::Naive(n) => (n.word_freq, n.index_first_child, first_char.len_utf8()),
+                NodeValue::Patricia(n) => {
+                    // SAFETY: Safe because in a patricia node
+                    let pat_range = unsafe { child.patricia_range() };
+                    let chars = self.get_chars(pat_range.start, pat_range.end);
+                    if !rest.starts_with(chars) {
+                        return None;
+                    }
+                    (n.word_freq, n.index_first_child, chars.len())
+                }
+                NodeValue::Range(n) => {
+                    let offset = first_char as usize - n.first_char as usize;
+                    let elem = &self.get_range(n.start_index, n.end_index)[offset];
+                    (
+                        elem.word_freq,
+                        elem.index_first_child,
+                        first_char.len_utf8(),
+                    )
+                }
+            };
+
+            rest = &rest[nb_bytes..];
+            if rest.is_empty() {
+                return word_freq;
+            }
+            children = self.get_siblings(index_first_child?);
+        }
+    }
+
+    /// Return whether the word is a word of the trie.
+    pub fn contains(&self, word: &str) -> bool {
+        self.frequency_of(word).is_some()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::{create_node, create_trie};
+
+    #[test]
+    fn test_frequency_of() {
+        let trie = create_trie();
+        let has_node = |f: fn(&NodeValue) -> bool| trie.nodes().iter().any(|n| f(&n.node_value()));
+        assert!(has_node(|n| matches!(n, NodeValue::Naive(_))));
+        assert!(has_node(|n| matches!(n, NodeValue::Patricia(_))));
+        assert!(has_node(|n| matches!(n, NodeValue::Range(_))));
+
+        let words = [
+            ("a", 1),
+            ("ab", 2),
+            ("abcd", 3),
+            ("ax", 4),
+            ("b", 5),
+            ("be", 6),
+            ("car", 7),
+            ("dog", 8),
+            ("dogs", 9),
+            ("日本", 10),
+        ];
+        for &(word, freq) in &words {
+            assert_eq!(trie.frequency_of(word), NonZeroU32::new(freq), "{}", word);
+            assert!(trie.contains(word), "{}", word);
+        }
+
+        // Prefixes which are not words, including an element of the range
+        for word in &["", "c", "ca", "abc", "do", "日"] {
+            assert_eq!(trie.frequency_of(word), None, "{}", word);
+            assert!(!trie.contains(word), "{}", word);
+        }
+    }
+
+    #[test]
+    fn test_frequency_of_partial_patricia() {
+        let trie = create_trie();
+
+        // The word diverges from or stops inside a patricia node
+        for word in &[
+            "abce",
+            "abdc",
+            "cat",
+            "cars",
+            "dot",
+            "dgo",
+            "日本語",
+            "日文",
+        ] {
+            assert_eq!(trie.frequency_of(word), None, "{}", word);
+            assert!(!trie.contains(word), "{}", word);
+        }
+
+        // Words before and after all the characters
+        assert!(!trie.contains("0"));
+        assert!(!trie.contains("z"));
+        assert!(!trie.contains("\u{10FFFF}"));
+
+        let empty = CompiledTrie::from(create_node("", 0, vec![]));
+        assert!(!empty.contains("a"));
+        assert!(!empty.contains(""));
+    }
+
+    #[test]
+    fn test_frequency_of_collation() {
+        use crate::Collation;
+
+        // The siblings sorted in the reverse order of their characters
+        let reverse = Collation::custom(NonZeroU32::new(256).unwrap(), |a, b| b.cmp(&a)).unwrap();
+        let trie = CompiledTrie::from_collated(
+            create_node(
+                "",
+                0,
+                vec![
+                    create_node("日本", 9, vec![]),
+                    create_node("dog", 7, vec![create_node("s", 8, vec![])]),
+                    create_node("c", 5, vec![create_node("ar", 6, vec![])]),
+                    create_node(
+                        "a",
+                        1,
+                        vec![
+                            create_node("x", 4, vec![]),
+                            create_node("b", 2, vec![create_node("cd", 3, vec![])]),
+                        ],
+                    ),
+                ],
+            ),
+            reverse,
+        );
+        assert!(trie.validate().is_ok());
+
+        for &(word, freq) in &[("a", 1), ("ab", 2), ("abcd", 3), ("ax", 4), ("car", 6)] {
+            assert_eq!(trie.frequency_of(word), NonZeroU32::new(freq), "{}", word);
+        }
+        for word in &["b", "d", "ay", "abc", "dot", "日本語", "z"] {
+            assert!(!trie.contains(word), "{}", word);
+        }
+        assert!(trie.contains("dogs"));
+        assert!(trie.contains("日本"));
+
+        // The same trie is not sorted by the code points
+        let unsorted = trie.with_collation(Collation::CODE_POINT);
+        assert!(unsorted.validate().is_err());
+    }
+}
@@ -1,5 +1,20 @@
+pub mod bfs_layout;
+pub mod builder;
+pub mod compact;
 pub mod compiled_trie;
 pub mod from_trie;
 pub mod index;
+#[cfg(feature = "length-index")]
+pub mod length_index;
+pub mod lookup;
+#[cfg(feature = "ngram-model")]
+pub mod ngram_model;
+#[cfg(feature = "sample")]
+pub mod sample;
+pub mod set_operations;
+#[cfg(feature = "source-lines")]
+pub mod source_lines;
 pub mod trie_node;
 pub mod trie_node_interface;
+pub mod validate;
+pub mod walk;
@@ -0,0 +1,191 @@
+//! An optional character trigram model of the words of a [CompiledTrie](crate::CompiledTrie),
+//! to score how much a word looks like the words of the dictionary.
+//!
+//! Enabled by the `ngram-model` feature since it is stored in the dictionary.
+//!
+//! The model counts the trigrams of characters of every word of the trie (once per word,
+//! whatever its frequency), with each word padded by two boundaries before its first character
+//! and one after its last. The probability of a character `c` following the characters `a b`
+//! is then estimated with add-one ([Laplace](https://en.wikipedia.org/wiki/Additive_smoothing))
+//! smoothing, so that the trigrams never seen still have a small probability:
+//!
+//! `P(c | a b) = (count(a b c) + 1) / (count(a b) + V)`
+//!
+//! where `count(a b)` is the number of trigrams beginning with `a b`, and `V` is the number
+//! of distinct characters of the words plus one for the boundary.
+
+use crate::CompiledTrie;
+use std::{
+    collections::{HashMap, HashSet},
+    iter::once,
+};
+
+/// Represent the trigram array of the [CompiledTrie](crate::CompiledTrie)
+pub type TrigramSlice = [TrigramElement];
+
+/// An element of the trigram array, counting the occurrences of a trigram.
+/// The elements are sorted by their characters.
+///
+/// Two kinds of elements also use the [ANY_CHAR](ANY_CHAR) wildcard:
+/// `[a, b, ANY_CHAR]` counts all the trigrams beginning with `a b`,
+/// and the last element `[ANY_CHAR; 3]` holds the `V` of the smoothing.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct TrigramElement {
+    /// The characters of the trigram, as integers to also represent the boundaries
+    /// and the wildcard.
+    pub chars: [u32; 3],
+
+    /// The number of occurrences of the trigram.
+    pub count: u32,
+}
+
+/// The value of a word boundary in a trigram, which is not a valid character.
+pub const BOUNDARY: u32 = 0x11_0000;
+
+/// The wildcard value of the trigrams counting several ones, greater than all characters.
+pub const ANY_CHAR: u32 = u32::MAX;
+
+/// Iterate over the trigrams of the word, padded with the boundaries.
+fn trigrams(word: &str) -> impl Iterator<Item = [u32; 3]> + '_ {
+    let chars = word.chars().map(u32::from).chain(once(BOUNDARY));
+    chars.scan([BOUNDARY, BOUNDARY], |context, c| {
+        let trigram = [context[0], context[1], c];
+        *context = [context[1], c];
+        Some(trigram)
+    })
+}
+
+/// Create the trigram array of the trie.
+pub(super) fn create_trigram_model(trie: &CompiledTrie) -> Vec<TrigramElement> {
+    let mut counts: HashMap<[u32; 3], u32> = HashMap::new();
+    let mut alphabet = HashSet::new();
+    trie.walk((), |step, _| {
+        if step.word_freq.is_some() {
+            for [a, b, c] in trigrams(step.word) {
+                *counts.entry([a, b, c]).or_insert(0) += 1;
+                *counts.entry([a, b, ANY_CHAR]).or_insert(0) += 1;
+                alphabet.insert(c);
+            }
+        }
+        Some(())
+    });
+
+    // The boundary ends every word, so it is already in the alphabet if there is a word
+    let alphabet_size = alphabet.len().max(1) as u32;
+    let mut trigrams: Vec<_> = counts
+        .into_iter()
+        .map(|(chars, count)| TrigramElement { chars, count })
+        .chain(once(TrigramElement {
+            chars: [ANY_CHAR; 3],
+            count: alphabet_size,
+        }))
+        .collect();
+    trigrams.sort_unstable_by_key(|e| e.chars);
+    trigrams
+}
+
+impl CompiledTrie<'_> {
+    /// Return a slice of the trigram array.
+    pub(crate) fn trigrams(&self) -> &TrigramSlice {
+        &self.trigrams
+    }
+
+    /// Return the count of the trigram in the model, or 0 if it has never been seen.
+    fn trigram_count(&self, chars: [u32; 3]) -> u32 {
+        self.trigrams
+            .binary_search_by_key(&chars, |e| e.chars)
+            .map_or(0, |i| self.trigrams[i].count)
+    }
+
+    /// Return the natural logarithm of the probability of the word in the trigram model
+    /// of the trie words, i.e. the sum of the log-probabilities of each of its characters
+    /// (and of its end) knowing the two previous ones.
+    ///
+    /// The result is negative, and closer to 0 the more the characters of the word follow
+    /// each other like in the words of the trie. Since each character lowers it,
+    /// the words of different lengths are better compared by dividing it by their
+    /// number of characters plus one (the number of trigrams).
+    /// See the [module documentation](self) for the model.
+    pub fn ngram_logprob(&self, word: &str) -> f64 {
+        let alphabet_size = f64::from(self.trigram_count([ANY_CHAR; 3]).max(1));
+        trigrams(word)
+            .map(|[a, b, c]| {
+                let count = f64::from(self.trigram_count([a, b, c]));
+                let context_count = f64::from(self.trigram_count([a, b, ANY_CHAR]));
+                ((count + 1.0) / (context_count + alphabet_size)).ln()
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::{create_node, create_words_trie};
+
+    /// The words "than", "that", "the", "then", "there", "thin", "this" and "tin".
+    fn create_trie() -> CompiledTrie<'static> {
+        create_words_trie(&[
+            ("than", 1),
+            ("that", 2),
+            ("the", 3),
+            ("then", 4),
+            ("there", 5),
+            ("thin", 6),
+            ("this", 7),
+            ("tin", 8),
+        ])
+    }
+
+    #[test]
+    fn test_trigram_model() {
+        let trie = create_trie();
+        let trigrams = trie.trigrams();
+        assert!(trigrams.windows(2).all(|w| w[0].chars < w[1].chars));
+
+        // "t", "h", "a", "n", "e", "r", "i", "s" and the boundary
+        assert_eq!(trie.trigram_count([ANY_CHAR; 3]), 9);
+        assert_eq!(trie.trigram_count([BOUNDARY, BOUNDARY, ANY_CHAR]), 8);
+        assert_eq!(trie.trigram_count([BOUNDARY, 't' as u32, 'h' as u32]), 7);
+        assert_eq!(trie.trigram_count(['t' as u32, 'h' as u32, ANY_CHAR]), 7);
+        assert_eq!(trie.trigram_count(['h' as u32, 'e' as u32, BOUNDARY]), 1);
+        assert_eq!(trie.trigram_count(['x' as u32, 'y' as u32, 'z' as u32]), 0);
+    }
+
+    #[test]
+    fn test_ngram_logprob_plausible_words() {
+        let trie = create_trie();
+
+        // Plausible sequences of characters score higher than implausible ones of the same length
+        assert!(trie.ngram_logprob("thes") > trie.ngram_logprob("tshe"));
+        assert!(trie.ngram_logprob("thier") > trie.ngram_logprob("rtnsi"));
+        assert!(trie.ngram_logprob("thi") > trie.ngram_logprob("iht"));
+        assert!(trie.ngram_logprob("tha") > trie.ngram_logprob("xyz"));
+
+        // The words of the trie are the most plausible
+        assert!(trie.ngram_logprob("then") > trie.ngram_logprob("thne"));
+        assert!(trie.ngram_logprob("the") < 0.0);
+
+        // The probabilities of the characters (and boundary) following a context sum to 1
+        let alphabet = ['t', 'h', 'a', 'n', 'e', 'r', 'i', 's'];
+        let context = ['t' as u32, 'h' as u32];
+        let context_count = f64::from(trie.trigram_count([context[0], context[1], ANY_CHAR]));
+        let total: f64 = alphabet
+            .iter()
+            .map(|&c| u32::from(c))
+            .chain(once(BOUNDARY))
+            .map(|c| {
+                let count = f64::from(trie.trigram_count([context[0], context[1], c]));
+                (count + 1.0) / (context_count + 9.0)
+            })
+            .sum();
+        assert!((total - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_ngram_logprob_empty_trie() {
+        let trie = CompiledTrie::from(create_node("", 0, vec![]));
+        assert_eq!(trie.trigrams().len(), 1);
+        assert_eq!(trie.ngram_logprob("abc"), 0.0);
+    }
+}
@@ -0,0 +1,176 @@
+//! Random sampling of the words of a [CompiledTrie](crate::CompiledTrie),
+//! weighted by their frequency.
+//!
+//! Enabled by the `sample` feature, which does not depend on the `rand` crate:
+//! the random numbers are given by any implementation of the small [Rng](Rng) trait,
+//! so that the core library keeps its few dependencies and does not tie its users
+//! to a version of `rand`.
+
+use crate::CompiledTrie;
+use std::num::NonZeroU32;
+
+/// A source of uniformly distributed random numbers.
+///
+/// Any random number generator can implement it, e.g. a wrapper of a generator
+/// of the `rand` crate forwarding to its `RngCore::next_u64` method.
+pub trait Rng {
+    /// Return the next random `u64`.
+    fn next_u64(&mut self) -> u64;
+}
+
+/// The words to draw, as targets in the cumulative frequencies of the words
+/// in lexicographic order.
+struct Draws {
+    /// The targets in ascending order, with the index of their draw.
+    targets: Vec<(u64, usize)>,
+
+    /// The number of targets already reached.
+    nb_reached: usize,
+
+    /// The sum of the frequencies of the words visited so far.
+    cumulative_freq: u64,
+
+    /// The word drawn by each draw.
+    words: Vec<Option<(String, NonZeroU32)>>,
+}
+
+impl Draws {
+    /// Add the frequency of the word to the cumulative frequency,
+    /// and give the word to the targets it reaches.
+    fn visit_word(&mut self, word: &str, freq: NonZeroU32) {
+        self.cumulative_freq += u64::from(freq.get());
+        while let Some(&(target, draw)) = self.targets.get(self.nb_reached) {
+            if target >= self.cumulative_freq {
+                break;
+            }
+            self.words[draw] = Some((word.to_string(), freq));
+            self.nb_reached += 1;
+        }
+    }
+
+    /// Return whether all the targets have been reached.
+    fn is_done(&self) -> bool {
+        self.nb_reached == self.targets.len()
+    }
+}
+
+impl CompiledTrie<'_> {
+    /// Draw `n` words of the trie (with replacement), each with a probability proportional
+    /// to its frequency, and return them with their frequency in the order of the draws.
+    ///
+    /// The draws are sorted to find all their words in a single traversal of the trie,
+    /// after another one computing the [total frequency](Self::total_frequency).
+    /// Return an empty vector if the trie has no word.
+    pub fn sample(&self, n: usize, rng: &mut impl Rng) -> Vec<(String, NonZeroU32)> {
+        let total_freq = self.total_frequency();
+        if total_freq == 0 {
+            return Vec::new();
+        }
+
+        // Map each random number to [0, total_freq) by keeping the high bits of their product
+        let mut targets: Vec<_> = (0..n)
+            .map(|draw| {
+                let target = (u128::from(rng.next_u64()) * u128::from(total_freq)) >> 64;
+                (target as u64, draw)
+            })
+            .collect();
+        targets.sort_unstable();
+
+        let mut draws = Draws {
+            targets,
+            nb_reached: 0,
+            cumulative_freq: 0,
+            words: vec![None; n],
+        };
+        // The traversal stops descending once all the targets have been reached
+        self.walk((), |step, _| {
+            if draws.is_done() {
+                return None;
+            }
+            if let Some(freq) = step.word_freq {
+                draws.visit_word(step.word, freq);
+            }
+            Some(())
+        });
+
+        // Every target is less than the total frequency, so all have been reached
+        draws.words.into_iter().map(Option::unwrap).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::create_node;
+
+    /// A [xorshift](https://en.wikipedia.org/wiki/Xorshift) generator, good enough for the tests.
+    struct XorShift(u64);
+
+    impl Rng for XorShift {
+        fn next_u64(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_sample_weighted_by_frequency() {
+        // "a", "ab", "b" (in a range), "car" and "日本"
+        let trie = CompiledTrie::from(create_node(
+            "",
+            0,
+            vec![
+                create_node("a", 1, vec![create_node("b", 10, vec![])]),
+                create_node("b", 100, vec![]),
+                create_node("c", 0, vec![create_node("ar", 1000, vec![])]),
+                create_node("日本", 10, vec![]),
+            ],
+        ));
+
+        let mut rng = XorShift(0x2545_F491_4F6C_DD1D);
+        let sample = trie.sample(11210, &mut rng);
+        assert_eq!(sample.len(), 11210);
+
+        let words = [
+            ("a", 1),
+            ("ab", 10),
+            ("b", 100),
+            ("car", 1000),
+            ("日本", 10),
+        ];
+        let count = |word: &str| sample.iter().filter(|(w, _)| w == word).count();
+        let counts: Vec<_> = words.iter().map(|&(w, _)| count(w)).collect();
+        assert_eq!(counts.iter().sum::<usize>(), sample.len());
+
+        // Close to 10 times the frequency, and more often the more frequent
+        assert!(counts[0] < counts[1] && counts[1] < counts[2] && counts[2] < counts[3]);
+        assert!(counts[3] > 9500 && counts[3] < 10500);
+        assert!(counts[2] > 800 && counts[2] < 1200);
+        assert!(counts[4] > 50 && counts[4] < 150);
+
+        // The words are given with their frequency, in the order of the draws
+        assert!(sample
+            .iter()
+            .all(|(w, f)| words.contains(&(w.as_str(), f.get()))));
+        assert!(sample.windows(2).any(|w| w[0].0 > w[1].0));
+    }
+
+    #[test]
+    fn test_sample_empty() {
+        let mut rng = XorShift(1);
+        let empty = CompiledTrie::from(create_node("", 0, vec![]));
+        assert!(empty.sample(10, &mut rng).is_empty());
+
+        let trie = CompiledTrie::from(create_node("", 0, vec![create_node("a", 3, vec![])]));
+        assert!(trie.sample(0, &mut rng).is_empty());
+        assert_eq!(
+            trie.sample(2, &mut rng),
+            vec![
+                ("a".to_string(), NonZeroU32::new(3).unwrap()),
+                ("a".to_string(), NonZeroU32::new(3).unwrap())
+            ]
+        );
+    }
+}
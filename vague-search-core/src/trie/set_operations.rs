@@ -0,0 +1,158 @@
+//! The intersection and difference of the words of two [CompiledTrie](crate::CompiledTrie),
+//! to find the vocabulary shared by two corpora or remove a list of words from a dictionary.
+//!
+//! The resulting words are compiled again, the tries being immutable once compiled.
+
+use crate::{Collation, CompileOptions, CompiledTrie, TrieBuilder};
+use std::num::NonZeroU32;
+
+/// Compile the words with the collation, in any order.
+fn compile_words(
+    words: impl Iterator<Item = (String, NonZeroU32)>,
+    collation: Collation,
+) -> CompiledTrie<'static> {
+    let mut builder = TrieBuilder::new(CompileOptions {
+        collation,
+        ..CompileOptions::default()
+    });
+    builder.extend(words);
+    builder.compile()
+}
+
+impl CompiledTrie<'_> {
+    /// Return the trie of the words present in both tries, with the collation of this one.
+    /// The frequency of a word is given by `combine` from its frequency in this trie
+    /// and its frequency in the other one.
+    ///
+    /// The words of the smaller trie are looked up in the larger one,
+    /// and the common words are then compiled again.
+    pub fn intersect(
+        &self,
+        other: &CompiledTrie,
+        combine: fn(NonZeroU32, NonZeroU32) -> NonZeroU32,
+    ) -> CompiledTrie<'static> {
+        let (smaller, larger, is_self_smaller) = if self.word_count() <= other.word_count() {
+            (self, other, true)
+        } else {
+            (other, self, false)
+        };
+
+        let words = smaller.iter_words().filter_map(|(word, freq)| {
+            let larger_freq = larger.frequency_of(&word)?;
+            let freq = if is_self_smaller {
+                combine(freq, larger_freq)
+            } else {
+                combine(larger_freq, freq)
+            };
+            Some((word, freq))
+        });
+        compile_words(words, self.collation)
+    }
+
+    /// Return the trie of the words of this trie which are not in the other one,
+    /// with their frequency and the collation of this trie.
+    pub fn difference(&self, other: &CompiledTrie) -> CompiledTrie<'static> {
+        let words = self.iter_words().filter(|(word, _)| !other.contains(word));
+        compile_words(words, self.collation)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::create_words_trie;
+
+    fn words(trie: &CompiledTrie) -> Vec<(String, u32)> {
+        trie.iter_words().map(|(w, f)| (w, f.get())).collect()
+    }
+
+    fn sum(a: NonZeroU32, b: NonZeroU32) -> NonZeroU32 {
+        NonZeroU32::new(a.get() + b.get()).unwrap()
+    }
+
+    #[test]
+    fn test_intersect() {
+        let left =
+            create_words_trie(&[("car", 1), ("cart", 2), ("dog", 3), ("日本", 4), ("zoo", 5)]);
+        let right = create_words_trie(&[("ca", 10), ("cart", 20), ("dog", 30), ("日本", 40)]);
+
+        let common = left.intersect(&right, sum);
+        assert!(common.validate().is_ok());
+        let expected = vec![
+            ("cart".to_string(), 22),
+            ("dog".to_string(), 33),
+            ("日本".to_string(), 44),
+        ];
+        assert_eq!(words(&common), expected);
+        assert_eq!(words(&right.intersect(&left, sum)), expected);
+
+        // The frequencies are given in the order of the tries, whichever is the smaller
+        let first = |a, _| a;
+        assert_eq!(
+            words(&left.intersect(&right, first)),
+            vec![
+                ("cart".to_string(), 2),
+                ("dog".to_string(), 3),
+                ("日本".to_string(), 4)
+            ]
+        );
+        assert_eq!(words(&right.intersect(&left, first))[0].1, 20);
+
+        // The same words give the same trie as compiling them
+        let same = left.intersect(&left, first);
+        assert_eq!(words(&same), words(&left));
+        assert_eq!(same.nodes().len(), left.nodes().len());
+    }
+
+    #[test]
+    fn test_intersect_disjoint() {
+        let left = create_words_trie(&[("car", 1), ("dog", 3)]);
+        let right = create_words_trie(&[("ca", 10), ("cars", 20), ("do", 30)]);
+
+        let common = left.intersect(&right, sum);
+        assert_eq!(common.word_count(), 0);
+        assert_eq!(common.get_root_siblings(), None);
+
+        let empty = create_words_trie(&[]);
+        assert_eq!(left.intersect(&empty, sum).word_count(), 0);
+        assert_eq!(empty.intersect(&right, sum).word_count(), 0);
+    }
+
+    #[test]
+    fn test_difference() {
+        let main = create_words_trie(&[("a", 1), ("car", 2), ("cart", 3), ("dog", 4), ("日本", 5)]);
+        let stop_words = create_words_trie(&[("a", 10), ("ca", 20), ("cart", 30), ("the", 40)]);
+
+        let rest = main.difference(&stop_words);
+        assert!(rest.validate().is_ok());
+        assert_eq!(
+            words(&rest),
+            vec![
+                ("car".to_string(), 2),
+                ("dog".to_string(), 4),
+                ("日本".to_string(), 5)
+            ]
+        );
+
+        // Exactly the words of the first trie which are not in the second one
+        let added = stop_words.difference(&main);
+        assert_eq!(
+            words(&added),
+            vec![("ca".to_string(), 20), ("the".to_string(), 40)]
+        );
+        for (word, _) in main.iter_words() {
+            assert_eq!(
+                rest.contains(&word),
+                !stop_words.contains(&word),
+                "{}",
+                word
+            );
+        }
+
+        let empty = create_words_trie(&[]);
+        assert_eq!(words(&main.difference(&empty)), words(&main));
+        assert_eq!(main.difference(&empty).nodes().len(), main.nodes().len());
+        assert_eq!(main.difference(&main).word_count(), 0);
+        assert_eq!(empty.difference(&main).word_count(), 0);
+    }
+}
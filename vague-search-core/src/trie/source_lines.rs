@@ -0,0 +1,214 @@
+//! An optional record of the source line of each word of a [CompiledTrie](crate::CompiledTrie),
+//! to trace a compiled word back to the line of the words file it comes from.
+//!
+//! Enabled by the `source-lines` feature since it is stored in the dictionary.
+//!
+//! The words are not stored a second time: each line is associated to the position
+//! of the end of its word in the trie, i.e. the index of its node and its offset
+//! in the node if it is a range.
+
+use crate::{CompiledTrie, IndexInt, NodeValue};
+use std::{collections::HashMap, convert::TryFrom, num::NonZeroU32};
+
+/// Represent the source line array of the [CompiledTrie](crate::CompiledTrie)
+pub type SourceLineSlice = [SourceLineElement];
+
+/// An element of the source line array, giving the line of a word of the trie.
+/// The elements are sorted by their node index, then by their range offset.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct SourceLineElement {
+    /// The index of the node where the word ends.
+    pub node_index: u32,
+
+    /// The offset of the last character of the word in the node if it is a range, else 0.
+    pub range_offset: u32,
+
+    /// The line number of the word in its source file, beginning at 1.
+    pub line: u32,
+}
+
+impl CompiledTrie<'_> {
+    /// Return a slice of the source line array.
+    pub(crate) fn source_lines(&self) -> &SourceLineSlice {
+        &self.source_lines
+    }
+
+    /// Return the node index and range offset of the end of the word,
+    /// or None if it is not a word of the trie.
+    fn find_word_end(&self, word: &str) -> Option<(u32, u32)> {
+        let mut nodes = self.get_root_siblings()?;
+        let mut nodes_start = 0;
+        let mut rest = word;
+        loop {
+            let first_char = rest.chars().next()?;
+            let (i, range_offset, word_freq, index_first_child, nb_bytes) = nodes
+                .iter()
+                .enumerate()
+                .find_map(|(i, node)| match node.node_value() {
+                    NodeValue::Naive(n) if n.character == first_char => Some((
+                        i,
+                        0,
+                        n.word_freq,
+                        n.index_first_child,
+                        first_char.len_utf8(),
+                    )),
+                    NodeValue::Patricia(n) => {
+                        // SAFETY: Safe because in a patricia node
+                        let pat_range = unsafe { node.patricia_range() };
+                        let chars = self.get_chars(pat_range.start, pat_range.end);
+                        if rest.starts_with(chars) {
+                            Some((i, 0, n.word_freq, n.index_first_child, chars.len()))
+                        } else {
+                            None
+                        }
+                    }
+                    NodeValue::Range(n) if first_char >= n.first_char => {
+                        let offset = first_char as usize - n.first_char as usize;
+                        let elem = self.get_range(n.start_index, n.end_index).get(offset)?;
+                        Some((
+                            i,
+                            offset as u32,
+                            elem.word_freq,
+                            elem.index_first_child,
+                            first_char.len_utf8(),
+                        ))
+                    }
+                    _ => None,
+                })?;
+
+            rest = &rest[nb_bytes..];
+            if rest.is_empty() {
+                let word_end = ((nodes_start + i) as u32, range_offset);
+                return word_freq.map(|_: NonZeroU32| word_end);
+            }
+
+            let index = index_first_child?;
+            nodes = self.get_siblings(index);
+            nodes_start = usize::from(index);
+        }
+    }
+
+    /// Record the source line (beginning at 1) of the given words of the trie,
+    /// replacing the previously recorded lines.
+    ///
+    /// The words which are not in the trie are ignored,
+    /// and a word given several times keeps its last line.
+    pub fn set_source_lines<'w>(&mut self, word_lines: impl IntoIterator<Item = (&'w str, usize)>) {
+        let mut lines = HashMap::new();
+        for (word, line) in word_lines {
+            if let (Some(word_end), Ok(line)) = (self.find_word_end(word), u32::try_from(line)) {
+                lines.insert(word_end, line);
+            }
+        }
+
+        let mut source_lines: Vec<_> = lines
+            .into_iter()
+            .map(|((node_index, range_offset), line)| SourceLineElement {
+                node_index,
+                range_offset,
+                line,
+            })
+            .collect();
+        source_lines.sort_unstable_by_key(|e| (e.node_index, e.range_offset));
+        self.source_lines = source_lines.into();
+    }
+
+    /// Return the line (beginning at 1) of the source file the word has been compiled from,
+    /// or None if it is not a word of the trie or its line has not been recorded.
+    pub fn source_line(&self, word: &str) -> Option<usize> {
+        let word_end = self.find_word_end(word)?;
+        let i = self
+            .source_lines
+            .binary_search_by_key(&word_end, |e| (e.node_index, e.range_offset))
+            .ok()?;
+        Some(self.source_lines[i].line as usize)
+    }
+
+    /// Return the source line array for the nodes reordered with the given groups
+    /// and new node indices, without the nodes which are not in any group.
+    pub(super) fn reorder_source_lines(
+        &self,
+        groups: &[usize],
+        new_indices: &[IndexInt],
+    ) -> Vec<SourceLineElement> {
+        let mut in_group = vec![false; self.nodes.len()];
+        for &start in groups {
+            let end = start + self.nodes[start].nb_siblings() as usize + 1;
+            in_group[start..end].iter_mut().for_each(|b| *b = true);
+        }
+
+        // The ranges are copied as they are, so the offsets do not change
+        let mut source_lines: Vec<_> = self
+            .source_lines
+            .iter()
+            .filter(|e| in_group[e.node_index as usize])
+            .map(|e| SourceLineElement {
+                // The source lines only index the nodes on 32 bits
+                node_index: u32::try_from(new_indices[e.node_index as usize])
+                    .expect("Node index of a source line out of its range"),
+                ..*e
+            })
+            .collect();
+        source_lines.sort_unstable_by_key(|e| (e.node_index, e.range_offset));
+        source_lines
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::create_trie;
+
+    const WORD_LINES: [(&str, usize); 11] = [
+        ("dogs", 1),
+        ("a", 2),
+        ("abcd", 3),
+        ("日本", 4),
+        ("ab", 5),
+        ("car", 6),
+        ("b", 7),
+        ("be", 8),
+        ("unknown", 9),
+        ("ax", 10),
+        ("dog", 11),
+    ];
+
+    #[test]
+    fn test_source_line() {
+        let mut trie = create_trie();
+        assert!(trie
+            .nodes()
+            .iter()
+            .any(|n| matches!(n.node_value(), NodeValue::Range(_))));
+        assert_eq!(trie.source_line("a"), None);
+
+        trie.set_source_lines(WORD_LINES.iter().copied());
+        assert_eq!(trie.source_lines().len(), 10);
+        for &(word, line) in WORD_LINES.iter().filter(|(w, _)| *w != "unknown") {
+            assert_eq!(trie.source_line(word), Some(line), "{}", word);
+        }
+
+        // Not words of the trie
+        assert_eq!(trie.source_line("unknown"), None);
+        assert_eq!(trie.source_line("c"), None);
+        assert_eq!(trie.source_line("abc"), None);
+        assert_eq!(trie.source_line("do"), None);
+        assert_eq!(trie.source_line(""), None);
+
+        // A word given several times keeps its last line
+        trie.set_source_lines(vec![("be", 3), ("be", 12)]);
+        assert_eq!(trie.source_line("be"), Some(12));
+        assert_eq!(trie.source_line("a"), None);
+    }
+
+    #[test]
+    fn test_source_line_bfs_layout() {
+        let mut trie = create_trie();
+        trie.set_source_lines(WORD_LINES.iter().copied());
+
+        let bfs = trie.to_bfs_layout();
+        for &(word, _) in WORD_LINES.iter() {
+            assert_eq!(bfs.source_line(word), trie.source_line(word), "{}", word);
+        }
+    }
+}
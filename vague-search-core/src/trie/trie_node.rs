@@ -100,6 +100,7 @@ pub struct CompiledTrieNode {
     /// - 2 bits : [0-1]  : Type of node value in the union
     /// - 12 bits: [2-13] : **PatriciaNode** Length of the stored string
     /// - 18 bits: [14-31]: number of siblings at the **right** of this node
+    ///
     /// Storing the number of siblings in only 18 bits is safe because there are
     /// 143,859 characters in the current Unicode version (13.0.0) which is much
     /// less than 2^18 = 262 144.
@@ -119,6 +120,12 @@ impl CompiledTrieNode {
     const MASK_PAT_STR_LENGTH: u32 = 0x3FFC_0000; // 0011..1100..0000
     const MASK_NB_SIBLINGS: u32 = 0x0003_FFFF; // 0000..0011..0000
 
+    /// The maximum number of siblings at the right of a node.
+    pub(super) const MAX_NB_SIBLINGS: u32 = Self::MASK_NB_SIBLINGS;
+
+    /// The maximum number of bytes of the string of a patricia node.
+    pub(super) const MAX_PAT_STR_LENGTH: usize = (1 << 12) - 1;
+
     const NODE_TYPE_NAIVE: u32 = 0; // 0b0000..
     const NODE_TYPE_PATRICIA: u32 = 0x4000_0000; // 0b0100..
     const NODE_TYPE_RANGE: u32 = 0x8000_0000; // 0b1000..
@@ -130,11 +137,15 @@ impl CompiledTrieNode {
             nb_siblings,
             1 << 18
         );
-        Self {
-            nb_siblings_with_flags: Self::NODE_TYPE_NAIVE
-                | Self::value_to_mask(nb_siblings, Self::MASK_NB_SIBLINGS),
-            node_union: NodeUnion { naive: data },
-        }
+        let mut node = Self::zeroed(
+            Self::NODE_TYPE_NAIVE | Self::value_to_mask(nb_siblings, Self::MASK_NB_SIBLINGS),
+        );
+        // SAFETY: Safe because the zeroed union is a valid NaiveNode
+        let naive = unsafe { &mut node.node_union.naive };
+        naive.index_first_child = data.index_first_child;
+        naive.word_freq = data.word_freq;
+        naive.character = data.character;
+        node
     }
 
     pub fn new_patricia(data: PatriciaNode, nb_siblings: u32, str_len: u32) -> Self {
@@ -150,12 +161,17 @@ impl CompiledTrieNode {
             str_len,
             1 << 12
         );
-        Self {
-            nb_siblings_with_flags: Self::NODE_TYPE_PATRICIA
+        let mut node = Self::zeroed(
+            Self::NODE_TYPE_PATRICIA
                 | Self::value_to_mask(nb_siblings, Self::MASK_NB_SIBLINGS)
                 | Self::value_to_mask(str_len, Self::MASK_PAT_STR_LENGTH),
-            node_union: NodeUnion { patricia: data },
-        }
+        );
+        // SAFETY: Safe because the zeroed union is a valid PatriciaNode
+        let patricia = unsafe { &mut node.node_union.patricia };
+        patricia.index_first_child = data.index_first_child;
+        patricia.word_freq = data.word_freq;
+        patricia.start_index = data.start_index;
+        node
     }
 
     pub fn new_range(data: RangeNode, nb_siblings: u32) -> Self {
@@ -165,11 +181,26 @@ impl CompiledTrieNode {
             nb_siblings,
             1 << 18
         );
-        Self {
-            nb_siblings_with_flags: Self::NODE_TYPE_RANGE
-                | Self::value_to_mask(nb_siblings, Self::MASK_NB_SIBLINGS),
-            node_union: NodeUnion { range: data },
-        }
+        let mut node = Self::zeroed(
+            Self::NODE_TYPE_RANGE | Self::value_to_mask(nb_siblings, Self::MASK_NB_SIBLINGS),
+        );
+        // SAFETY: Safe because the zeroed union is a valid RangeNode
+        let range = unsafe { &mut node.node_union.range };
+        range.first_char = data.first_char;
+        range.start_index = data.start_index;
+        range.end_index = data.end_index;
+        node
+    }
+
+    /// Return a node of the flags with all its other bytes zeroed, its value being then
+    /// written field by field so that its padding bytes stay zeroed. The nodes are padded
+    /// with the `large-index` feature, and would else make the dictionary files differ.
+    fn zeroed(nb_siblings_with_flags: u32) -> Self {
+        // SAFETY: Safe because zero is a valid value of all the fields
+        // (the indices are integers or options of non-zero integers, and '\0' is a char)
+        let mut node: Self = unsafe { std::mem::zeroed() };
+        node.nb_siblings_with_flags = nb_siblings_with_flags;
+        node
     }
 
     /// Get the masked value contained in the `nb_siblings_with_flags` field.
@@ -183,7 +214,7 @@ impl CompiledTrieNode {
     }
 
     /// Return the inner value of the node.
-    pub fn node_value(&self) -> NodeValue {
+    pub fn node_value(&self) -> NodeValue<'_> {
         use std::hint::unreachable_unchecked;
 
         // SAFETY: The node type indicates the value structure of the union
@@ -198,8 +229,91 @@ impl CompiledTrieNode {
         }
     }
 
+    /// Return whether the type bits of the node are those of one of the node types,
+    /// which [node_value](CompiledTrieNode::node_value) requires.
+    /// The compiled nodes always have a valid type, but not the nodes of an untrusted file.
+    pub(crate) fn has_valid_type(&self) -> bool {
+        matches!(
+            self.nb_siblings_with_flags & Self::MASK_NODE_TYPE,
+            Self::NODE_TYPE_NAIVE | Self::NODE_TYPE_PATRICIA | Self::NODE_TYPE_RANGE
+        )
+    }
+
+    /// Return whether the character of a naive or range node is a valid `char`,
+    /// reading its bits as an integer, which is always true of the other nodes.
+    /// Its type bits must be valid.
+    pub(crate) fn has_valid_char(&self) -> bool {
+        match self.raw_char() {
+            Some(c) => std::char::from_u32(c).is_some(),
+            None => true,
+        }
+    }
+
+    /// Return the offset in the node of the character of a naive or range node,
+    /// computed on a node of the same type, or None for the other nodes.
+    fn char_offset(&self) -> Option<usize> {
+        use crate::utils::field_offset;
+
+        match self.nb_siblings_with_flags & Self::MASK_NODE_TYPE {
+            Self::NODE_TYPE_NAIVE => {
+                let node = Self::zeroed(Self::NODE_TYPE_NAIVE);
+                // SAFETY: Safe because the zeroed union is a valid NaiveNode
+                Some(field_offset(&node, unsafe {
+                    &node.node_union.naive.character
+                }))
+            }
+            Self::NODE_TYPE_RANGE => {
+                let node = Self::zeroed(Self::NODE_TYPE_RANGE);
+                // SAFETY: Safe because the zeroed union is a valid RangeNode
+                Some(field_offset(&node, unsafe {
+                    &node.node_union.range.first_char
+                }))
+            }
+            _ => None,
+        }
+    }
+
+    /// Return the bits of the character of a naive or range node, without reading them
+    /// as a `char` which they may not be, or None for the other nodes.
+    fn raw_char(&self) -> Option<u32> {
+        // SAFETY: Safe because the 4 bytes at the offset are the ones of the character
+        self.char_offset().map(|offset| unsafe {
+            std::ptr::read_unaligned((self as *const Self as *const u8).add(offset) as *const u32)
+        })
+    }
+
+    /// Return the node with the bits of its character replaced, as a corrupted file could
+    /// hold them. The node must be a naive or range node.
+    #[cfg(test)]
+    pub(crate) fn with_raw_char(mut self, raw_char: u32) -> Self {
+        let offset = self.char_offset().expect("Not a naive nor a range node");
+        // SAFETY: Safe because the 4 bytes at the offset are the ones of the character
+        unsafe {
+            std::ptr::write_unaligned(
+                (&mut self as *mut Self as *mut u8).add(offset) as *mut u32,
+                raw_char,
+            );
+        }
+        self
+    }
+
+    /// Return the node with its type bits replaced, as a corrupted file could hold them.
+    #[cfg(test)]
+    pub(crate) fn with_raw_type(mut self, raw_type: u32) -> Self {
+        self.nb_siblings_with_flags = (self.nb_siblings_with_flags & !Self::MASK_NODE_TYPE)
+            | Self::value_to_mask(raw_type, Self::MASK_NODE_TYPE);
+        self
+    }
+
+    /// Return the offset in the nodes of their flags, to corrupt them in the bytes of a file.
+    #[cfg(test)]
+    pub(crate) fn flags_offset() -> usize {
+        let node = Self::zeroed(Self::NODE_TYPE_NAIVE);
+        crate::utils::field_offset(&node, &node.nb_siblings_with_flags)
+    }
+
     /// Return the mutable inner value of the node.
-    pub(super) fn node_value_mut(&mut self) -> NodeValueMut {
+    pub(super) fn node_value_mut(&mut self) -> NodeValueMut<'_> {
         use std::hint::unreachable_unchecked;
 
         // SAFETY: The node type indicates the value structure of the union
@@ -223,7 +337,7 @@ impl CompiledTrieNode {
         let pat_str_len = self.get_masked_flags(CompiledTrieNode::MASK_PAT_STR_LENGTH);
 
         let start_index: IndexChar = self.node_union.patricia.start_index;
-        let end_index = IndexChar::new(*start_index + pat_str_len);
+        let end_index = IndexChar::new(*start_index + IndexInt::from(pat_str_len));
         start_index..end_index
     }
 
@@ -268,6 +382,52 @@ impl PartialEq for CompiledTrieNode {
 
 impl Eq for CompiledTrieNode {}
 
+/// Return the bytes of the nodes with their padding bytes zeroed,
+/// since the nodes are padded with the `large-index` feature.
+#[cfg(feature = "large-index")]
+pub(crate) fn nodes_bytes(nodes: &[CompiledTrieNode]) -> Vec<u8> {
+    use crate::utils::copy_field_bytes;
+
+    let node_size = std::mem::size_of::<CompiledTrieNode>();
+    let mut bytes = vec![0; std::mem::size_of_val(nodes)];
+    for (node, node_bytes) in nodes.iter().zip(bytes.chunks_exact_mut(node_size)) {
+        copy_field_bytes(node_bytes, node, &node.nb_siblings_with_flags);
+        match node.node_value() {
+            NodeValue::Naive(n) => {
+                copy_field_bytes(node_bytes, node, &n.index_first_child);
+                copy_field_bytes(node_bytes, node, &n.word_freq);
+                copy_field_bytes(node_bytes, node, &n.character);
+            }
+            NodeValue::Patricia(n) => {
+                copy_field_bytes(node_bytes, node, &n.index_first_child);
+                copy_field_bytes(node_bytes, node, &n.word_freq);
+                copy_field_bytes(node_bytes, node, &n.start_index);
+            }
+            NodeValue::Range(n) => {
+                copy_field_bytes(node_bytes, node, &n.first_char);
+                copy_field_bytes(node_bytes, node, &n.start_index);
+                copy_field_bytes(node_bytes, node, &n.end_index);
+            }
+        }
+    }
+    bytes
+}
+
+/// Return the bytes of the range elements with their padding bytes zeroed,
+/// since the elements are padded with the `large-index` feature.
+#[cfg(feature = "large-index")]
+pub(crate) fn ranges_bytes(ranges: &[RangeElement]) -> Vec<u8> {
+    use crate::utils::copy_field_bytes;
+
+    let elem_size = std::mem::size_of::<RangeElement>();
+    let mut bytes = vec![0; std::mem::size_of_val(ranges)];
+    for (elem, elem_bytes) in ranges.iter().zip(bytes.chunks_exact_mut(elem_size)) {
+        copy_field_bytes(elem_bytes, elem, &elem.index_first_child);
+        copy_field_bytes(elem_bytes, elem, &elem.word_freq);
+    }
+    bytes
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -325,7 +485,7 @@ mod test {
 
         let patricia = CompiledTrieNode::new_patricia(
             PatriciaNode {
-                index_first_child: NonZeroU32::new(995).map(IndexNodeNonZero::new),
+                index_first_child: IndexNodeNonZero::new_opt(995),
                 word_freq: NonZeroU32::new(875347),
                 start_index: IndexChar::new(40),
             },
@@ -340,7 +500,7 @@ mod test {
 
         let patricia = CompiledTrieNode::new_patricia(
             PatriciaNode {
-                index_first_child: NonZeroU32::new(995).map(IndexNodeNonZero::new),
+                index_first_child: IndexNodeNonZero::new_opt(995),
                 word_freq: NonZeroU32::new(875347),
                 start_index: IndexChar::new(0),
             },
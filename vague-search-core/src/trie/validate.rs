@@ -0,0 +1,484 @@
+//! Check the structure of a [CompiledTrie](crate::CompiledTrie) at runtime.
+//!
+//! The search functions rely on invariants only checked in debug mode when compiling the trie,
+//! so a trie read from an untrusted file should be validated before being searched.
+
+use super::{from_trie::crosses_surrogates, index::*};
+use crate::{error::*, CompiledTrie, NodeValue};
+use snafu::*;
+
+impl CompiledTrie<'_> {
+    /// Return the (first, last) characters spanned by the node at the given index,
+    /// checking that the indices it holds are valid.
+    fn validate_node_chars(&self, index: usize) -> Result<(char, char)> {
+        let node = &self.nodes[index];
+
+        // The node value can only be read once its type and character are known to be valid
+        ensure!(
+            node.has_valid_type(),
+            TrieMalformed {
+                index,
+                cause: "Invalid node type",
+            }
+        );
+        ensure!(
+            node.has_valid_char(),
+            TrieMalformed {
+                index,
+                cause: "Invalid character",
+            }
+        );
+
+        match node.node_value() {
+            NodeValue::Naive(n) => Ok((n.character, n.character)),
+            NodeValue::Patricia(_) => {
+                // SAFETY: Safe because in a patricia node
+                let pat_range = unsafe { node.patricia_range() };
+                let (start, end) = (usize::from(pat_range.start), usize::from(pat_range.end));
+                let first_char = self
+                    .chars
+                    .get(start..end)
+                    .and_then(|s| s.chars().next())
+                    .context(TrieMalformed {
+                        index,
+                        cause: "Invalid characters range",
+                    })?;
+                Ok((first_char, first_char))
+            }
+            NodeValue::Range(n) => {
+                let (start, end) = (usize::from(n.start_index), usize::from(n.end_index));
+                ensure!(
+                    start < end && end <= self.ranges.len(),
+                    TrieMalformed {
+                        index,
+                        cause: "Invalid range of range elements",
+                    }
+                );
+
+                let last_char = std::char::from_u32(n.first_char as u32 + (end - start - 1) as u32)
+                    .context(TrieMalformed {
+                        index,
+                        cause: "Invalid range of characters",
+                    })?;
+
+                // The search builds the characters of the range from their code points,
+                // which must then all be characters
+                ensure!(
+                    !crosses_surrogates(n.first_char, last_char),
+                    TrieMalformed {
+                        index,
+                        cause: "Range of characters crossing the surrogates",
+                    }
+                );
+
+                // The characters of the range must also be sorted by the collation
+                let collation = self.collation;
+                let mut range_chars = (n.first_char as u32..=last_char as u32)
+                    .filter_map(std::char::from_u32)
+                    .peekable();
+                let is_sorted = collation.is_code_point()
+                    || std::iter::from_fn(|| Some((range_chars.next()?, *range_chars.peek()?)))
+                        .all(|(a, b)| collation.cmp(a, b) == std::cmp::Ordering::Less);
+                ensure!(
+                    is_sorted,
+                    TrieMalformed {
+                        index,
+                        cause: "Range not sorted by the collation",
+                    }
+                );
+                Ok((n.first_char, last_char))
+            }
+        }
+    }
+
+    /// Check the siblings beginning at the given index and push the index of their children
+    /// to the stack of siblings to check.
+    fn validate_siblings(
+        &self,
+        index: usize,
+        visited: &mut [bool],
+        stack: &mut Vec<usize>,
+    ) -> Result<()> {
+        ensure!(
+            !visited[index],
+            TrieMalformed {
+                index,
+                cause: "Siblings shared by multiple nodes",
+            }
+        );
+        visited[index] = true;
+        self.validate_group(index, stack)
+    }
+
+    /// Check the node at the given index and its siblings at its right, and push the index
+    /// of their children to the stack.
+    fn validate_group(&self, index: usize, stack: &mut Vec<usize>) -> Result<()> {
+        let end = index + self.nodes[index].nb_siblings() as usize + 1;
+        ensure!(
+            end <= self.nodes.len(),
+            TrieMalformed {
+                index,
+                cause: "Too many siblings",
+            }
+        );
+
+        // Children are always stored after their parent and its siblings,
+        // which also prevents cycles
+        let mut check_child = |child: Option<IndexNodeNonZero>, index: usize| {
+            if let Some(child) = child {
+                let child = usize::from(child);
+                ensure!(
+                    child >= end && child < self.nodes.len(),
+                    TrieMalformed {
+                        index,
+                        cause: "Invalid index of the first child",
+                    }
+                );
+                stack.push(child);
+            }
+            Ok(())
+        };
+
+        let mut prev_last_char = None;
+        for i in index..end {
+            ensure!(
+                i == index || self.nodes[i].nb_siblings() + 1 == self.nodes[i - 1].nb_siblings(),
+                TrieMalformed {
+                    index: i,
+                    cause: "Invalid number of siblings",
+                }
+            );
+
+            // The search uses a binary search on the siblings, so they must not share
+            // a character and be sorted by it (with the collation of the trie)
+            let (first_char, last_char) = self.validate_node_chars(i)?;
+            if let Some(prev_last_char) = prev_last_char {
+                ensure!(
+                    first_char != prev_last_char,
+                    TrieDuplicateChar {
+                        index: i,
+                        character: first_char,
+                    }
+                );
+                ensure!(
+                    self.collation.cmp(prev_last_char, first_char) == std::cmp::Ordering::Less,
+                    TrieMalformed {
+                        index: i,
+                        cause: "Siblings not sorted by their first character",
+                    }
+                );
+            }
+            prev_last_char = Some(last_char);
+
+            match self.nodes[i].node_value() {
+                NodeValue::Naive(n) => check_child(n.index_first_child, i)?,
+                NodeValue::Patricia(n) => check_child(n.index_first_child, i)?,
+                NodeValue::Range(n) => {
+                    for elem in self.get_range(n.start_index, n.end_index) {
+                        check_child(elem.index_first_child, i)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that the trie is well-formed and can safely be searched.
+    ///
+    /// This traverses the whole trie, so it should only be used on tries
+    /// which do not come from a trusted source, such as a dictionary file.
+    pub fn validate(&self) -> Result<()> {
+        if self.nodes.is_empty() {
+            return Ok(());
+        }
+
+        let mut visited = vec![false; self.nodes.len()];
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            self.validate_siblings(index, &mut visited, &mut stack)?;
+        }
+
+        Ok(())
+    }
+
+    /// Check a sample of the trie, as a cheap alternative to [validate](CompiledTrie::validate)
+    /// catching a gross corruption of a large trie.
+    ///
+    /// The `nb_samples` nodes are drawn at random (always the same ones for the same trie)
+    /// and checked with their siblings at their right.
+    /// The word reached by following the first child of each node from the root is then
+    /// looked up, after checking the siblings of its path.
+    pub fn validate_sample(&self, nb_samples: usize) -> Result<()> {
+        if self.nodes.is_empty() {
+            return Ok(());
+        }
+
+        // A xorshift generator seeded by the size of the trie
+        let mut state = self.nodes.len() as u64 ^ 0x9E37_79B9_7F4A_7C15;
+        let mut children = Vec::new();
+        for _ in 0..nb_samples {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let index = (state % self.nodes.len() as u64) as usize;
+            children.clear();
+            self.validate_group(index, &mut children)?;
+        }
+
+        // Follow the first children until a word, whose siblings have been checked
+        let mut word = String::new();
+        let mut index = 0;
+        let word_freq = loop {
+            children.clear();
+            self.validate_group(index, &mut children)?;
+            let (word_freq, index_first_child) = match self.nodes[index].node_value() {
+                NodeValue::Naive(n) => {
+                    word.push(n.character);
+                    (n.word_freq, n.index_first_child)
+                }
+                NodeValue::Patricia(n) => {
+                    // SAFETY: Safe because in a patricia node
+                    let pat_range = unsafe { self.nodes[index].patricia_range() };
+                    word.push_str(self.get_chars(pat_range.start, pat_range.end));
+                    (n.word_freq, n.index_first_child)
+                }
+                NodeValue::Range(n) => {
+                    word.push(n.first_char);
+                    let elem = &self.get_range(n.start_index, n.end_index)[0];
+                    (elem.word_freq, elem.index_first_child)
+                }
+            };
+            match (word_freq, index_first_child) {
+                (Some(_), _) | (None, None) => break word_freq,
+                (None, Some(child)) => index = usize::from(child),
+            }
+        };
+
+        ensure!(
+            self.frequency_of(&word) == word_freq,
+            TrieMalformed {
+                index,
+                cause: "Word of the first children not found by the exact search",
+            }
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::create_node;
+    use crate::{CompiledTrieNode, Error, NaiveNode, PatriciaNode, RangeElement, RangeNode};
+    use std::num::NonZeroU32;
+
+    fn create_naive(
+        character: char,
+        index_first_child: IndexInt,
+        nb_siblings: u32,
+    ) -> CompiledTrieNode {
+        CompiledTrieNode::new_naive(
+            NaiveNode {
+                index_first_child: IndexNodeNonZero::new_opt(index_first_child),
+                word_freq: NonZeroU32::new(1),
+                character,
+            },
+            nb_siblings,
+        )
+    }
+
+    #[test]
+    fn test_validate_compiled_trie() {
+        let root = create_node(
+            "-",
+            0,
+            vec![
+                create_node("a", 1, vec![create_node("b", 2, vec![])]),
+                create_node("b", 3, vec![]),
+                create_node("c", 4, vec![]),
+                create_node("dog", 5, vec![create_node("s", 6, vec![])]),
+                create_node("日本", 7, vec![]),
+            ],
+        );
+        let trie = CompiledTrie::from(root);
+        assert!(trie.validate().is_ok());
+        assert!(trie.validate_sample(16).is_ok());
+        let empty = CompiledTrie::from(create_node("-", 0, vec![]));
+        assert!(empty.validate().is_ok());
+        assert!(empty.validate_sample(16).is_ok());
+    }
+
+    #[test]
+    fn test_validate_duplicate_first_char() {
+        // "ab" and "ac" stored as two siblings instead of a node with two children
+        let nodes = [
+            CompiledTrieNode::new_patricia(
+                PatriciaNode {
+                    index_first_child: None,
+                    word_freq: NonZeroU32::new(1),
+                    start_index: IndexChar::new(0),
+                },
+                1,
+                2,
+            ),
+            CompiledTrieNode::new_patricia(
+                PatriciaNode {
+                    index_first_child: None,
+                    word_freq: NonZeroU32::new(1),
+                    start_index: IndexChar::new(2),
+                },
+                0,
+                2,
+            ),
+        ];
+        let trie = CompiledTrie::from((&nodes[..], "abac", &[][..]));
+        assert!(matches!(
+            trie.validate(),
+            Err(Error::TrieDuplicateChar {
+                index: 1,
+                character: 'a'
+            })
+        ));
+
+        // Also detected in the children of a node
+        let nodes = [
+            create_naive('a', 1, 0),
+            create_naive('b', 0, 1),
+            create_naive('b', 0, 0),
+        ];
+        let trie = CompiledTrie::from((&nodes[..], "", &[][..]));
+        assert!(matches!(
+            trie.validate(),
+            Err(Error::TrieDuplicateChar {
+                index: 2,
+                character: 'b'
+            })
+        ));
+    }
+
+    #[test]
+    fn test_validate_malformed() {
+        // Siblings not sorted
+        let nodes = [create_naive('b', 0, 1), create_naive('a', 0, 0)];
+        let trie = CompiledTrie::from((&nodes[..], "", &[][..]));
+        assert!(matches!(
+            trie.validate(),
+            Err(Error::TrieMalformed { index: 1, .. })
+        ));
+        assert!(trie.validate_sample(4).is_err());
+
+        // More siblings than nodes
+        let nodes = [create_naive('a', 0, 2), create_naive('b', 0, 1)];
+        let trie = CompiledTrie::from((&nodes[..], "", &[][..]));
+        assert!(matches!(
+            trie.validate(),
+            Err(Error::TrieMalformed { index: 0, .. })
+        ));
+        assert!(trie.validate_sample(4).is_err());
+
+        // Child pointing to its parent
+        let nodes = [create_naive('a', 1, 1), create_naive('b', 1, 0)];
+        let trie = CompiledTrie::from((&nodes[..], "", &[][..]));
+        assert!(matches!(
+            trie.validate(),
+            Err(Error::TrieMalformed { index: 0, .. })
+        ));
+        assert!(trie.validate_sample(4).is_err());
+
+        // Characters out of the characters array
+        let nodes = [CompiledTrieNode::new_patricia(
+            PatriciaNode {
+                index_first_child: None,
+                word_freq: NonZeroU32::new(1),
+                start_index: IndexChar::new(2),
+            },
+            0,
+            3,
+        )];
+        let trie = CompiledTrie::from((&nodes[..], "abc", &[][..]));
+        assert!(matches!(
+            trie.validate(),
+            Err(Error::TrieMalformed { index: 0, .. })
+        ));
+        assert!(trie.validate_sample(4).is_err());
+    }
+
+    #[test]
+    fn test_validate_invalid_node_value() {
+        // A node of the fourth type, which is not a node type
+        let nodes = [
+            create_naive('a', 0, 1),
+            create_naive('b', 0, 0).with_raw_type(0b11),
+        ];
+        let trie = CompiledTrie::from((&nodes[..], "", &[][..]));
+        assert!(matches!(
+            trie.validate(),
+            Err(Error::TrieMalformed { index: 1, .. })
+        ));
+        assert!(trie.validate_sample(4).is_err());
+
+        // A naive node of a surrogate, which is not a character
+        let nodes = [create_naive('a', 0, 0).with_raw_char(0xD800)];
+        let trie = CompiledTrie::from((&nodes[..], "", &[][..]));
+        assert!(matches!(
+            trie.validate(),
+            Err(Error::TrieMalformed { index: 0, .. })
+        ));
+        assert!(trie.validate_sample(4).is_err());
+
+        // A range node beginning after the last character
+        let nodes = [CompiledTrieNode::new_range(
+            RangeNode {
+                first_char: 'a',
+                start_index: IndexRange::new(0),
+                end_index: IndexRange::new(1),
+            },
+            0,
+        )
+        .with_raw_char(0x11_0000)];
+        let ranges = [RangeElement {
+            index_first_child: None,
+            word_freq: NonZeroU32::new(1),
+        }];
+        let trie = CompiledTrie::from((&nodes[..], "", &ranges[..]));
+        assert!(matches!(
+            trie.validate(),
+            Err(Error::TrieMalformed { index: 0, .. })
+        ));
+        assert!(trie.validate_sample(4).is_err());
+    }
+
+    #[test]
+    fn test_validate_range_across_surrogates() {
+        let create_range = |first_char: char, nb_chars: usize| {
+            let node = CompiledTrieNode::new_range(
+                RangeNode {
+                    first_char,
+                    start_index: IndexRange::new(0),
+                    end_index: IndexRange::new(nb_chars as IndexInt),
+                },
+                0,
+            );
+            let element = RangeElement {
+                index_first_child: None,
+                word_freq: NonZeroU32::new(1),
+            };
+            (vec![node], vec![element; nb_chars])
+        };
+
+        // From U+D7FE to U+D7FF, just before the surrogates
+        let (nodes, ranges) = create_range('\u{D7FE}', 2);
+        let trie = CompiledTrie::from((&nodes[..], "", &ranges[..]));
+        assert!(trie.validate().is_ok());
+
+        // From U+D7FF to U+E000, with the surrogates between them
+        let (nodes, ranges) = create_range('\u{D7FF}', 0xE000 - 0xD7FF + 1);
+        let trie = CompiledTrie::from((&nodes[..], "", &ranges[..]));
+        assert!(matches!(
+            trie.validate(),
+            Err(Error::TrieMalformed { index: 0, .. })
+        ));
+        assert!(trie.validate_sample(4).is_err());
+    }
+}
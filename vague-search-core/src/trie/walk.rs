@@ -0,0 +1,224 @@
+//! A depth-first traversal of the nodes of a [CompiledTrie](crate::CompiledTrie),
+//! building their words and pruning the branches chosen by a visitor.
+
+use crate::{CompiledTrie, CompiledTrieNode, IndexNodeNonZero, NodeValue};
+use std::num::NonZeroU32;
+
+/// A node, or an element of a range node, visited by [walk](CompiledTrie::walk).
+#[derive(Debug, Copy, Clone)]
+pub struct TrieStep<'w> {
+    /// The word of the node, i.e. the characters of its ancestors followed by its own ones.
+    pub word: &'w str,
+
+    /// The byte index of the characters of the node in the word.
+    pub chars_start: usize,
+
+    /// The frequency of the word, or None if it is not a word of the trie.
+    pub word_freq: Option<NonZeroU32>,
+
+    /// Whether the node has children, which are only visited if it does.
+    pub has_children: bool,
+
+    /// The offset of the element in its range node, or None for the other nodes.
+    pub range_offset: Option<usize>,
+}
+
+impl<'w> TrieStep<'w> {
+    /// Return the characters of the node, at the end of its word.
+    pub fn chars(&self) -> &'w str {
+        &self.word[self.chars_start..]
+    }
+
+    /// Return whether the node is a hole of a range node, without word nor children.
+    pub fn is_hole(&self) -> bool {
+        self.range_offset.is_some() && self.word_freq.is_none() && !self.has_children
+    }
+}
+
+impl CompiledTrie<'_> {
+    /// Visit all the nodes of the trie, like [walk_siblings](CompiledTrie::walk_siblings)
+    /// from the root siblings with an empty word and the given state.
+    pub fn walk<S>(&self, state: S, mut visit: impl FnMut(&TrieStep, &S) -> Option<S>) {
+        if let Some(roots) = self.get_root_siblings() {
+            self.walk_siblings(roots, &mut String::new(), &state, &mut visit);
+        }
+    }
+
+    /// Visit the siblings and their descendants in depth-first order, i.e. in the
+    /// lexicographic order of the words (following the collation of the trie).
+    ///
+    /// The characters of each node are appended to the word, which is restored afterwards.
+    /// The visitor is given each node (or range element, even a hole) with the state of its
+    /// parent, and returns the state of its children, or None to skip them.
+    /// The holes of the ranges whose characters are invalid are not visited.
+    pub fn walk_siblings<S>(
+        &self,
+        siblings: &[CompiledTrieNode],
+        word: &mut String,
+        state: &S,
+        visit: &mut impl FnMut(&TrieStep, &S) -> Option<S>,
+    ) {
+        let word_len = word.len();
+        for node in siblings {
+            match node.node_value() {
+                NodeValue::Naive(n) => {
+                    word.push(n.character);
+                    self.walk_step(
+                        word,
+                        word_len,
+                        n.word_freq,
+                        n.index_first_child,
+                        None,
+                        state,
+                        visit,
+                    );
+                }
+                NodeValue::Patricia(n) => {
+                    // SAFETY: Safe because in a patricia node
+                    let pat_range = unsafe { node.patricia_range() };
+                    word.push_str(self.get_chars(pat_range.start, pat_range.end));
+                    self.walk_step(
+                        word,
+                        word_len,
+                        n.word_freq,
+                        n.index_first_child,
+                        None,
+                        state,
+                        visit,
+                    );
+                }
+                NodeValue::Range(n) => {
+                    let range = self.get_range(n.start_index, n.end_index);
+                    for (offset, elem) in range.iter().enumerate() {
+                        // Characters not present in the trie can be invalid
+                        let character =
+                            match std::char::from_u32(n.first_char as u32 + offset as u32) {
+                                Some(c) => c,
+                                None => continue,
+                            };
+
+                        word.push(character);
+                        self.walk_step(
+                            word,
+                            word_len,
+                            elem.word_freq,
+                            elem.index_first_child,
+                            Some(offset),
+                            state,
+                            visit,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Give the node to the visitor, and walk its children with the returned state.
+    /// The characters of the node must already be in the word, and are removed afterwards.
+    #[allow(clippy::too_many_arguments)]
+    fn walk_step<S>(
+        &self,
+        word: &mut String,
+        word_len: usize,
+        word_freq: Option<NonZeroU32>,
+        index_first_child: Option<IndexNodeNonZero>,
+        range_offset: Option<usize>,
+        state: &S,
+        visit: &mut impl FnMut(&TrieStep, &S) -> Option<S>,
+    ) {
+        let step = TrieStep {
+            word,
+            chars_start: word_len,
+            word_freq,
+            has_children: index_first_child.is_some(),
+            range_offset,
+        };
+        if let Some(child_state) = visit(&step, state) {
+            if let Some(index) = index_first_child {
+                self.walk_siblings(self.get_siblings(index), word, &child_state, visit);
+            }
+        }
+        word.truncate(word_len);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::{create_node, create_trie};
+
+    #[test]
+    fn test_walk() {
+        let trie = create_trie();
+        let mut steps = Vec::new();
+        trie.walk(0, |step, &depth| {
+            if !step.is_hole() {
+                let freq = step.word_freq.map_or(0, NonZeroU32::get);
+                steps.push((step.word.to_string(), step.chars().to_string(), freq, depth));
+            }
+            Some(depth + 1)
+        });
+
+        // The nodes are visited in lexicographic order, with the state of their parent
+        let expected = [
+            ("a", "a", 1, 0),
+            ("ab", "b", 2, 1),
+            ("abcd", "cd", 3, 2),
+            ("ax", "x", 4, 1),
+            ("b", "b", 5, 0),
+            ("be", "e", 6, 1),
+            ("c", "c", 0, 0),
+            ("car", "ar", 7, 1),
+            ("dog", "dog", 8, 0),
+            ("dogs", "s", 9, 1),
+            ("日本", "日本", 10, 0),
+        ];
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|&(w, c, f, d)| (w.to_string(), c.to_string(), f, d))
+            .collect();
+        assert_eq!(steps, expected);
+    }
+
+    #[test]
+    fn test_walk_pruned() {
+        let trie = create_trie();
+
+        // The children of the skipped nodes are not visited
+        let mut words = Vec::new();
+        trie.walk((), |step, _| {
+            if step.word_freq.is_some() {
+                words.push(step.word.to_string());
+            }
+            if step.chars() == "b" {
+                None
+            } else {
+                Some(())
+            }
+        });
+        assert_eq!(words, ["a", "ab", "ax", "b", "car", "dog", "dogs", "日本"]);
+
+        // The range elements are given with their offset
+        let mut offsets = Vec::new();
+        trie.walk((), |step, _| {
+            offsets.extend(step.range_offset.map(|o| (step.word.to_string(), o)));
+            None
+        });
+        assert_eq!(
+            offsets,
+            [
+                ("a".to_string(), 0),
+                ("b".to_string(), 1),
+                ("c".to_string(), 2)
+            ]
+        );
+
+        let mut nb_visited = 0;
+        let empty = CompiledTrie::from(create_node("", 0, vec![]));
+        empty.walk((), |_, _| {
+            nb_visited += 1;
+            Some(())
+        });
+        assert_eq!(nb_visited, 0);
+    }
+}
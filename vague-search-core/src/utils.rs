@@ -3,7 +3,7 @@ pub trait AsBytes {
     ///
     /// **Warning notes:**
     /// - If the value contains a pointer or a reference,
-    /// the address will be present, not the pointed value.
+    ///   the address will be present, not the pointed value.
     /// - This representation is not portable.
     fn as_bytes(&self) -> &[u8];
 }
@@ -14,10 +14,7 @@ pub trait AsBytes {
 impl<T> AsBytes for [T] {
     fn as_bytes(&self) -> &[u8] {
         unsafe {
-            std::slice::from_raw_parts(
-                self.as_ptr() as *const T as *const u8,
-                self.len() * std::mem::size_of::<T>(),
-            )
+            std::slice::from_raw_parts(self.as_ptr() as *const u8, std::mem::size_of_val(self))
         }
     }
 }
@@ -28,12 +25,25 @@ impl<T> AsBytes for [T] {
 ///
 /// **Warning notes:**
 /// - If the value contains a pointer or a reference,
-/// the address will be present, not the pointed value.
+///   the address will be present, not the pointed value.
 /// - This representation is not portable.
 pub fn as_bytes<T>(value: &T) -> &[u8] {
     unsafe { std::slice::from_raw_parts(value as *const T as *const u8, std::mem::size_of::<T>()) }
 }
 
+/// Copy the raw bytes of a field of the value at the same offset in the given bytes,
+/// to build the byte representation of the value without its padding bytes.
+#[cfg(feature = "large-index")]
+pub fn copy_field_bytes<S, T>(value_bytes: &mut [u8], value: &S, field: &T) {
+    let offset = field_offset(value, field);
+    value_bytes[offset..offset + std::mem::size_of::<T>()].copy_from_slice(as_bytes(field));
+}
+
+/// Return the offset in bytes of a field in the value.
+pub fn field_offset<S, T>(value: &S, field: &T) -> usize {
+    field as *const T as usize - value as *const S as usize
+}
+
 /// Compute the distance between two characters.
 /// If a < b, the returned value will be positive.
 /// If a > b, the returned value will be negative.
@@ -41,11 +51,45 @@ pub fn char_dist(a: char, b: char) -> i32 {
     b as i32 - a as i32
 }
 
+/// The table of the CRC-32 of each byte, with the reflected polynomial of IEEE 802.3.
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// Compute the CRC-32 (IEEE 802.3, as used by zlib) of the concatenation of the byte slices.
+pub fn crc32(parts: &[&[u8]]) -> u32 {
+    !parts
+        .iter()
+        .flat_map(|bytes| bytes.iter())
+        .fold(!0, |crc, &b| {
+            (crc >> 8) ^ CRC32_TABLE[((crc ^ u32::from(b)) & 0xFF) as usize]
+        })
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
     #[test]
+    #[allow(clippy::useless_vec)] // The vector is what is tested
     fn test_as_bytes_vec_i32() {
         let v = vec![i32::MIN, 42, i32::MAX];
         let bytes_custom = v.as_bytes();
@@ -87,4 +131,13 @@ mod test {
         assert_eq!(char_dist('a', 'z'), 25);
         assert_eq!(char_dist('z', 'a'), -25);
     }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(&[]), 0);
+        assert_eq!(crc32(&[b"123456789"]), 0xCBF4_3926);
+        let fox = b"The quick brown fox jumps over the lazy dog";
+        assert_eq!(crc32(&[fox]), 0x414F_A339);
+        assert_eq!(crc32(&[&fox[..10], b"", &fox[10..]]), 0x414F_A339);
+    }
 }
@@ -7,8 +7,17 @@ pub type Result<T, E = Error> = std::result::Result<T, E>;
 #[derive(Snafu)]
 #[snafu(visibility(pub(crate)))] // Make creatable enum variants crate-visible
 pub enum Error {
-    #[snafu(display("Usage: {} /path/to/word/freq.txt /path/to/output/dict.bin", bin_name))]
+    #[snafu(display(
+        "Usage: {} [--progress-json | --progress] [--bfs-layout] [--phrase-separator <CHAR>] [--optional-frequency] [--accumulate] [--lenient] [--gzip] [--collation <NAME>] [--jobs <N>] [--range-gap <N>] [--dedup-chars] /path/to/word/freq.txt /path/to/output/dict.bin\n       {} --export [--phrase-separator <CHAR>] /path/to/dict.bin",
+        bin_name,
+        bin_name
+    ))]
     CliArgs { bin_name: String },
+    #[snafu(display("The {} option requires the `{}` feature", option, feature))]
+    FeatureRequired {
+        option: &'static str,
+        feature: &'static str,
+    },
     #[snafu(display("Could not open file {}: {}", path.display(), source))]
     FileOpen {
         path: PathBuf,
@@ -25,17 +34,32 @@ pub enum Error {
         line: String,
         number: usize,
     },
+    #[snafu(display("Unexpected content after the frequency in file {} at line {}: {}", path.display(), number, line))]
+    ExtraContent {
+        path: PathBuf,
+        line: String,
+        number: usize,
+    },
     #[snafu(display("Could not parse in non zero integer in file {} at line {}: {}", path.display(), number, source))]
     Parsing {
         path: PathBuf,
         number: usize,
         source: ParseIntError,
     },
+    #[snafu(display("Could not compile the dictionary: {}", source))]
+    DictCompile { source: vague_search_core::Error },
     #[snafu(display("Could not write the dictionary in file {}: {}", path.display(), source))]
     DictWrite {
         path: PathBuf,
         source: vague_search_core::Error,
     },
+    #[snafu(display("Could not read the dictionary in file {}: {}", path.display(), source))]
+    DictRead {
+        path: PathBuf,
+        source: vague_search_core::Error,
+    },
+    #[snafu(display("Could not write the exported words: {}", source))]
+    ExportWrite { source: std::io::Error },
 }
 
 // Link Error to Display to print the message when an error is returned from main.
@@ -1,37 +1,205 @@
 //! The compiler binary of the vague-search project.
 //!
 //! Read a file composed of `<WORD> <FREQUENCY>` lines and create a compiled
-//! dictionary from it.
+//! dictionary from it, ignoring the blank lines. With `-` as the path of the file,
+//! the lines are read from the standard input instead.
+//!
+//! With the `--progress-json` flag, the progress of the compilation is also written
+//! as [JSON lines](progress) in the standard output.
+//! With the `--progress` flag, it is written as text lines in the standard error instead,
+//! every 100000 lines of the words file.
+//! With the `--bfs-layout` flag, the nodes of the dictionary are stored in
+//! [breadth-first order](vague_search_core::CompiledTrie::to_bfs_layout).
+//! With the `--phrase-separator <CHAR>` option, the character stands for a space in the words,
+//! to compile multi-word entries (see [unescape_phrase](vague_search_core::unescape_phrase)).
+//! With the `--optional-frequency` flag, a line may also be a single `<WORD>`
+//! of frequency 1, to compile a raw list of words.
+//! With the `--accumulate` flag, the frequencies of a word given several times are added
+//! together (up to `u32::MAX`) instead of keeping the last one.
+//! With the `--lenient` flag, the malformed lines are reported in the standard error
+//! and skipped, instead of stopping the compilation at the first one.
+//! With the `--gzip` flag or a `.gz` words file, the words file is decompressed
+//! while it is read, which needs the `gzip` feature.
+//! With the `--jobs <N>` option, the trie is built on `N` threads
+//! (see [create_from_reader](patricia_trie::PatriciaNode::create_from_reader)),
+//! which gives the same dictionary and needs the `parallel` feature.
+//! With the `--collation <NAME>` option (`code-point` by default, or `case-folded`),
+//! the siblings of the dictionary are sorted by this [collation](vague_search_core::Collation).
+//! With the `--range-gap <N>` option (3 by default), the siblings at most `N` code points apart
+//! are gathered in range nodes, a larger gap trading memory for fewer nodes
+//! (see [range_gap](vague_search_core::CompileOptions::range_gap)).
+//! With the `--dedup-chars` flag, the characters of the dictionary are reused wherever
+//! they already are in it, for a smaller dictionary but a slower compilation
+//! (see [dedup_chars](vague_search_core::CompileOptions::dedup_chars)).
+//!
+//! With the `--export` flag, read a compiled dictionary instead and write its words
+//! as `<WORD> <FREQUENCY>` lines in the standard output (see [export](export)),
+//! which can be compiled again into the same dictionary.
+//! With the `source-lines` feature, the line of each word in the words file is also stored
+//! (see [source_line](vague_search_core::CompiledTrie::source_line)).
 
 use error::*;
-use patricia_trie::PatriciaNode;
+use patricia_trie::{ParseOptions, PatriciaNode};
+use progress::{Progress, ProgressEvent};
 use snafu::*;
-use std::path::PathBuf;
+use std::{
+    io::{BufRead, BufWriter, Write},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+};
+use utils::gzip_reader;
 
-use vague_search_core::{CompiledTrie, DictionaryFile};
+use vague_search_core::{escape_phrase, Collation, CompileOptions, CompiledTrie, DictionaryFile};
 
 mod error;
 mod patricia_trie;
+mod progress;
 mod utils;
 
+/// The words path reading the words from the standard input.
+const STDIN_PATH: &str = "-";
+
 /// Represents the expected parsed program arguments.
 #[derive(Debug)]
 struct Args {
-    words_path: PathBuf,
+    /// The words file to compile, or None to export the dictionary.
+    words_path: Option<PathBuf>,
     dict_path: PathBuf,
+    progress_json: bool,
+    progress: bool,
+    options: IndexOptions,
+}
+
+/// The options of the compilation of a words file into a dictionary file.
+#[derive(Debug, Copy, Clone)]
+struct IndexOptions {
+    /// How the lines of the words file are parsed into a trie,
+    /// whose collation is also the one of the dictionary.
+    parse: ParseOptions,
+    /// Whether the words file is decompressed (as with a `.gz` extension).
+    gzip: bool,
+    /// Whether the nodes are stored in breadth-first order.
+    bfs_layout: bool,
+    /// The maximum number of code points between the characters gathered in a range.
+    range_gap: u32,
+    /// Whether the characters are reused from the whole dictionary.
+    dedup_chars: bool,
+}
+
+impl Default for IndexOptions {
+    fn default() -> Self {
+        Self {
+            parse: ParseOptions::default(),
+            gzip: false,
+            bfs_layout: false,
+            range_gap: CompiledTrie::DEFAULT_RANGE_GAP,
+            dedup_chars: false,
+        }
+    }
 }
 
 /// Parse the arguments and return an error if the wrong number is given or a parsing error happens.
 fn parse_args() -> Result<Args> {
     const BIN_NAME_DEFAULT: &str = "vague-search-index";
-    let mut args = std::env::args();
+    const PROGRESS_JSON_FLAG: &str = "--progress-json";
+    const PROGRESS_FLAG: &str = "--progress";
+    const BFS_LAYOUT_FLAG: &str = "--bfs-layout";
+    const PHRASE_SEPARATOR_FLAG: &str = "--phrase-separator";
+    const OPTIONAL_FREQUENCY_FLAG: &str = "--optional-frequency";
+    const ACCUMULATE_FLAG: &str = "--accumulate";
+    const LENIENT_FLAG: &str = "--lenient";
+    const GZIP_FLAG: &str = "--gzip";
+    const COLLATION_FLAG: &str = "--collation";
+    const JOBS_FLAG: &str = "--jobs";
+    const RANGE_GAP_FLAG: &str = "--range-gap";
+    const DEDUP_CHARS_FLAG: &str = "--dedup-chars";
+    const EXPORT_FLAG: &str = "--export";
+    let mut args = std::env::args().peekable();
 
     let bin_name = args.next().unwrap_or_else(|| BIN_NAME_DEFAULT.to_string());
     let cliargs_ctx = CliArgs {
         bin_name: &bin_name,
     };
 
-    let words_path = args.next().context(cliargs_ctx)?.into();
+    // Parse the options given before the paths
+    let mut progress_json = false;
+    let mut progress = false;
+    let mut bfs_layout = false;
+    let mut phrase_separator = None;
+    let mut optional_frequency = false;
+    let mut accumulate = false;
+    let mut lenient = false;
+    let mut gzip = false;
+    let mut collation = None;
+    let mut jobs = None;
+    let mut range_gap = None;
+    let mut dedup_chars = false;
+    let mut export = false;
+    while let Some(option) = args.peek().filter(|arg| arg.starts_with("--")).cloned() {
+        args.next();
+        match option.as_str() {
+            PROGRESS_JSON_FLAG => progress_json = true,
+            PROGRESS_FLAG => progress = true,
+            BFS_LAYOUT_FLAG => bfs_layout = true,
+            EXPORT_FLAG => export = true,
+            OPTIONAL_FREQUENCY_FLAG => optional_frequency = true,
+            ACCUMULATE_FLAG => accumulate = true,
+            LENIENT_FLAG => lenient = true,
+            GZIP_FLAG => gzip = true,
+            DEDUP_CHARS_FLAG => dedup_chars = true,
+            PHRASE_SEPARATOR_FLAG if phrase_separator.is_none() => {
+                let separator = args.next().and_then(|arg| single_char(&arg));
+                phrase_separator = Some(separator.context(cliargs_ctx)?);
+            }
+            COLLATION_FLAG if collation.is_none() => {
+                let name = args.next().and_then(|arg| Collation::from_name(&arg));
+                collation = Some(name.context(cliargs_ctx)?);
+            }
+            JOBS_FLAG if jobs.is_none() => {
+                // Fail instead of silently building the trie on a single thread
+                ensure!(
+                    cfg!(feature = "parallel"),
+                    FeatureRequired {
+                        option: JOBS_FLAG,
+                        feature: "parallel",
+                    }
+                );
+                let nb_jobs = args.next().and_then(|arg| arg.parse::<NonZeroUsize>().ok());
+                jobs = Some(nb_jobs.context(cliargs_ctx)?);
+            }
+            RANGE_GAP_FLAG if range_gap.is_none() => {
+                let gap = args.next().and_then(|arg| arg.parse().ok());
+                range_gap = Some(gap.context(cliargs_ctx)?);
+            }
+            _ => None.context(cliargs_ctx)?,
+        }
+    }
+
+    // The progress is written in a single format
+    if progress_json && progress {
+        None.context(cliargs_ctx)?;
+    }
+
+    // Only the dictionary is given to export it, without the compilation options
+    let words_path = if export {
+        if progress_json
+            || progress
+            || bfs_layout
+            || optional_frequency
+            || accumulate
+            || lenient
+            || gzip
+            || collation.is_some()
+            || jobs.is_some()
+            || range_gap.is_some()
+            || dedup_chars
+        {
+            None.context(cliargs_ctx)?;
+        }
+        None
+    } else {
+        Some(args.next().context(cliargs_ctx)?.into())
+    };
     let dict_path = args.next().context(cliargs_ctx)?.into();
 
     // Make sure no more argument has been given
@@ -42,22 +210,546 @@ fn parse_args() -> Result<Args> {
     Ok(Args {
         words_path,
         dict_path,
+        progress_json,
+        progress,
+        options: IndexOptions {
+            parse: ParseOptions {
+                phrase_separator,
+                optional_frequency,
+                accumulate,
+                lenient,
+                collation: collation.unwrap_or_default(),
+                jobs: jobs.map_or(1, NonZeroUsize::get),
+            },
+            gzip,
+            bfs_layout,
+            range_gap: range_gap.unwrap_or(CompiledTrie::DEFAULT_RANGE_GAP),
+            dedup_chars,
+        },
     })
 }
 
-fn main() -> Result<()> {
-    let args = parse_args()?;
+/// Return the character of the argument if it is made of a single one.
+fn single_char(arg: &str) -> Option<char> {
+    let mut chars = arg.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
 
+/// Compile the words file into a dictionary file with the options,
+/// reporting the progress of each phase.
+fn compile(
+    words_path: &Path,
+    dict_path: &Path,
+    options: IndexOptions,
+    progress: &mut Progress,
+) -> Result<()> {
     eprintln!("Creating Patricia Trie from the file...");
-    let mut patricia_trie = PatriciaNode::create_from_file(&args.words_path)?;
+    #[cfg(feature = "source-lines")]
+    let mut word_lines = Vec::new();
+    #[cfg(feature = "source-lines")]
+    let on_word = |word: &str, line| word_lines.push((word.to_string(), line));
+    #[cfg(not(feature = "source-lines"))]
+    let on_word = |_: &str, _| {};
+    let (mut patricia_trie, nb_skipped) = if words_path == Path::new(STDIN_PATH) {
+        let stdin = std::io::stdin();
+        let reader: Box<dyn BufRead + '_> = if options.gzip {
+            gzip_reader(stdin.lock()).context(FileOpen { path: words_path })?
+        } else {
+            Box::new(stdin.lock())
+        };
+        PatriciaNode::create_from_reader(reader, words_path, options.parse, progress, on_word)?
+    } else {
+        PatriciaNode::create_from_file(words_path, options.gzip, options.parse, progress, on_word)?
+    };
+
+    if nb_skipped != 0 {
+        eprintln!("Skipped {} malformed lines", nb_skipped);
+    }
 
     eprintln!("Patricia Trie created, compressing...");
+    progress.emit(ProgressEvent::Compress);
     patricia_trie.compress();
-    let compiled: CompiledTrie = patricia_trie.into();
+    let compile_options = CompileOptions {
+        collation: options.parse.collation,
+        range_gap: options.range_gap,
+        dedup_chars: options.dedup_chars,
+    };
+    let mut compiled =
+        CompiledTrie::try_from_options(patricia_trie, compile_options).context(DictCompile)?;
+    #[cfg(feature = "source-lines")]
+    compiled.set_source_lines(word_lines.iter().map(|(word, line)| (word.as_str(), *line)));
+    if options.bfs_layout {
+        eprintln!("Reordering the nodes in breadth-first order...");
+        compiled = compiled.to_bfs_layout();
+    }
     let dict_file: DictionaryFile = compiled.into();
 
     eprintln!("Writing compressed dictionary...");
-    dict_file.write_file(&args.dict_path).context(DictWrite {
-        path: &args.dict_path,
-    })
+    dict_file
+        .write_file(dict_path)
+        .context(DictWrite { path: dict_path })?;
+
+    progress.emit(ProgressEvent::Write {
+        bytes: dict_file.file_size(),
+    });
+    Ok(())
+}
+
+/// Write the words of the dictionary file as `<WORD> <FREQUENCY>` lines in the output,
+/// in the order of the collation of the dictionary (the code point order by default).
+/// With a `phrase_separator`, the words are escaped as phrases.
+///
+/// Compiling the lines with the same options gives the same dictionary, except for
+/// the source lines of the words which are the ones of the sorted lines.
+fn export(dict_path: &Path, phrase_separator: Option<char>, output: impl Write) -> Result<()> {
+    let dict = DictionaryFile::read_file(dict_path).context(DictRead { path: dict_path })?;
+
+    let mut output = BufWriter::new(output);
+    for (word, freq) in dict.trie.iter_words() {
+        let word = match phrase_separator {
+            Some(separator) => escape_phrase(&word, separator),
+            None => word.into(),
+        };
+        writeln!(output, "{} {}", word, freq).context(ExportWrite)?;
+    }
+    output.flush().context(ExportWrite)
+}
+
+fn main() -> Result<()> {
+    let args = parse_args()?;
+    let words_path = match &args.words_path {
+        Some(words_path) => words_path,
+        None => {
+            let stdout = std::io::stdout();
+            let phrase_separator = args.options.parse.phrase_separator;
+            return export(&args.dict_path, phrase_separator, stdout.lock());
+        }
+    };
+
+    let stdout = std::io::stdout();
+    let mut lock = stdout.lock();
+    let mut stderr = std::io::stderr();
+    let mut progress = if args.progress_json {
+        Progress::new(&mut lock)
+    } else if args.progress {
+        Progress::text(&mut stderr)
+    } else {
+        Progress::disabled()
+    };
+
+    compile(words_path, &args.dict_path, args.options, &mut progress)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_compile_progress_events() {
+        let dir = std::env::temp_dir();
+        let words_path = dir.join(format!("vague-search-progress-{}.txt", std::process::id()));
+        let dict_path = dir.join(format!("vague-search-progress-{}.bin", std::process::id()));
+        std::fs::write(&words_path, "test 10\ntent 3\nbest 5\n").unwrap();
+
+        let mut output = Vec::new();
+        let result = compile(
+            &words_path,
+            &dict_path,
+            IndexOptions::default(),
+            &mut Progress::new(&mut output),
+        );
+        let dict_size = std::fs::metadata(&dict_path).map(|m| m.len());
+
+        let _ = std::fs::remove_file(&words_path);
+        let _ = std::fs::remove_file(&dict_path);
+        assert!(result.is_ok());
+
+        let expected = format!(
+            "{{\"phase\":\"parse\",\"lines\":3}}\n{{\"phase\":\"compress\"}}\n{{\"phase\":\"write\",\"bytes\":{}}}\n",
+            dict_size.unwrap()
+        );
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_compile_progress_text() {
+        let dir = std::env::temp_dir();
+        let words_path = dir.join(format!(
+            "vague-search-progress-text-{}.txt",
+            std::process::id()
+        ));
+        let dict_path = dir.join(format!(
+            "vague-search-progress-text-{}.bin",
+            std::process::id()
+        ));
+        let words: String = (0..250_000).map(|i| format!("w{} 1\n", i)).collect();
+        std::fs::write(&words_path, words).unwrap();
+
+        let mut output = Vec::new();
+        let result = compile(
+            &words_path,
+            &dict_path,
+            IndexOptions::default(),
+            &mut Progress::text(&mut output),
+        );
+        let dict_size = std::fs::metadata(&dict_path).map(|m| m.len());
+
+        let _ = std::fs::remove_file(&words_path);
+        let _ = std::fs::remove_file(&dict_path);
+        assert!(result.is_ok());
+
+        // The lines are reported every PARSE_EVENT_STEP lines, then once they are all parsed
+        let expected = format!(
+            "Parsed 100000 lines\nParsed 200000 lines\nParsed 250000 lines\nCompressing the trie\nWrote {} bytes\n",
+            dict_size.unwrap()
+        );
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_compile_collation() {
+        let dir = std::env::temp_dir();
+        let words_path = dir.join(format!("vague-search-collation-{}.txt", std::process::id()));
+        let dict_path = dir.join(format!("vague-search-collation-{}.bin", std::process::id()));
+        std::fs::write(&words_path, "b 1\nTest 2\nA 3\ntest 4\na 5\nB 6\ntent 7\n").unwrap();
+
+        compile(
+            &words_path,
+            &dict_path,
+            IndexOptions {
+                parse: ParseOptions {
+                    collation: Collation::CASE_FOLDED,
+                    ..ParseOptions::default()
+                },
+                ..IndexOptions::default()
+            },
+            &mut Progress::disabled(),
+        )
+        .unwrap();
+        let dict = DictionaryFile::read_file(&dict_path).unwrap();
+        let trie = &dict.trie;
+        assert_eq!(trie.collation().id(), Collation::CASE_FOLDED.id());
+        assert!(trie.validate().is_ok());
+
+        // The words are given in the order of the collation, character by character
+        let words: Vec<_> = trie.iter_words().map(|(word, _)| word).collect();
+        assert_eq!(words, ["A", "a", "B", "b", "Test", "tent", "test"]);
+        assert_eq!(trie.frequency_of("Test").map(|f| f.get()), Some(2));
+        assert!(!trie.contains("TEST"));
+        drop(dict);
+
+        let error = DictionaryFile::read_file_with_collation(&dict_path, Collation::CODE_POINT);
+        assert!(error.is_err());
+
+        let _ = std::fs::remove_file(&words_path);
+        let _ = std::fs::remove_file(&dict_path);
+    }
+
+    #[test]
+    fn test_compile_empty() {
+        let dir = std::env::temp_dir();
+        let words_path = dir.join(format!("vague-search-empty-{}.txt", std::process::id()));
+        let dict_path = dir.join(format!("vague-search-empty-{}.bin", std::process::id()));
+        std::fs::write(&words_path, "\n").unwrap();
+
+        compile(
+            &words_path,
+            &dict_path,
+            IndexOptions::default(),
+            &mut Progress::disabled(),
+        )
+        .unwrap();
+
+        // A dictionary without any node, in which nothing is found
+        let dict = DictionaryFile::read_file(&dict_path).unwrap();
+        assert_eq!(dict.header().nb_nodes, 0);
+        assert!(dict.trie.validate().is_ok());
+        assert_eq!(dict.trie.word_count(), 0);
+        assert!(!dict.trie.contains("test"));
+        assert!(dict.quick_check().is_ok());
+        drop(dict);
+
+        let _ = std::fs::remove_file(&words_path);
+        let _ = std::fs::remove_file(&dict_path);
+    }
+
+    #[test]
+    fn test_compile_deterministic() {
+        let dir = std::env::temp_dir();
+        let path = |name: &str| dir.join(format!("vague-search-{}-{}", name, std::process::id()));
+        let (words_path, shuffled_path) = (path("same.txt"), path("same-shuffled.txt"));
+        let (dict_path, again_path) = (path("same.bin"), path("same-again.bin"));
+        let lines = [
+            "test 10",
+            "a 1",
+            "b 2",
+            "d 3",
+            "tent 3",
+            "best 5",
+            "te 1",
+            "日本 6",
+            "日本語 7",
+        ];
+        std::fs::write(&words_path, lines.join("\n")).unwrap();
+        let mut shuffled = lines.to_vec();
+        shuffled.reverse();
+        std::fs::write(&shuffled_path, shuffled.join("\n")).unwrap();
+
+        for &collation in &[Collation::CODE_POINT, Collation::CASE_FOLDED] {
+            for &bfs_layout in &[false, true] {
+                let compile_to = |words_path: &Path, dict_path: &Path| {
+                    compile(
+                        words_path,
+                        dict_path,
+                        IndexOptions {
+                            parse: ParseOptions {
+                                collation,
+                                ..ParseOptions::default()
+                            },
+                            bfs_layout,
+                            ..IndexOptions::default()
+                        },
+                        &mut Progress::disabled(),
+                    )
+                    .unwrap();
+                    std::fs::read(dict_path).unwrap()
+                };
+
+                let bytes = compile_to(&words_path, &dict_path);
+                assert_eq!(compile_to(&words_path, &again_path), bytes);
+
+                // The source lines are the only part depending on the order of the lines
+                #[cfg(not(feature = "source-lines"))]
+                assert_eq!(compile_to(&shuffled_path, &again_path), bytes);
+            }
+        }
+
+        for path in &[words_path, shuffled_path, dict_path, again_path] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn test_compile_jobs() {
+        let dir = std::env::temp_dir();
+        let path = |name: &str| dir.join(format!("vague-search-{}-{}", name, std::process::id()));
+        let (words_path, dict_path, jobs_path) =
+            (path("jobs.txt"), path("jobs.bin"), path("jobs-4.bin"));
+
+        // Duplicate words and many first characters, some of them in the same shard
+        let words: String = (0..2000u32)
+            .map(|i| {
+                let first = ['a', 'B', 'b', 'e', 'é', 'z', '日', '-'][i as usize % 8];
+                format!("{}{} {}\n", first, i % 700, i % 13 + 1)
+            })
+            .collect();
+        std::fs::write(&words_path, words).unwrap();
+
+        for &collation in &[Collation::CODE_POINT, Collation::CASE_FOLDED] {
+            for &accumulate in &[false, true] {
+                let compile_to = |dict_path: &Path, jobs| {
+                    compile(
+                        &words_path,
+                        dict_path,
+                        IndexOptions {
+                            parse: ParseOptions {
+                                accumulate,
+                                collation,
+                                jobs,
+                                ..ParseOptions::default()
+                            },
+                            ..IndexOptions::default()
+                        },
+                        &mut Progress::disabled(),
+                    )
+                    .unwrap();
+                    std::fs::read(dict_path).unwrap()
+                };
+                assert_eq!(compile_to(&jobs_path, 4), compile_to(&dict_path, 1));
+            }
+        }
+
+        for path in &[words_path, dict_path, jobs_path] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn test_compile_range_gap() {
+        let dir = std::env::temp_dir();
+        let path = |name: &str| dir.join(format!("vague-search-{}-{}", name, std::process::id()));
+        let (words_path, dict_path) = (path("range-gap.txt"), path("range-gap.bin"));
+        let words: String = "abcdefhkpxz"
+            .chars()
+            .map(|c| format!("{} 1\n", c))
+            .collect();
+        std::fs::write(&words_path, words).unwrap();
+
+        let compile_with = |range_gap| {
+            compile(
+                &words_path,
+                &dict_path,
+                IndexOptions {
+                    range_gap,
+                    ..IndexOptions::default()
+                },
+                &mut Progress::disabled(),
+            )
+            .unwrap();
+            let dict = DictionaryFile::read_file(&dict_path).unwrap();
+            assert!(dict.trie.validate().is_ok());
+            let words: Vec<_> = dict.trie.iter_words().collect();
+            (dict.header().nb_nodes, words)
+        };
+
+        // The larger the gap, the fewer nodes for the same words
+        let (no_range_nodes, words) = compile_with(0);
+        let (default_nodes, default_words) = compile_with(CompiledTrie::DEFAULT_RANGE_GAP);
+        let (large_nodes, large_words) = compile_with(100);
+        assert_eq!(default_words, words);
+        assert_eq!(large_words, words);
+        assert!(default_nodes < no_range_nodes);
+        assert!(large_nodes < default_nodes);
+
+        for path in &[words_path, dict_path] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn test_compile_dedup_chars() {
+        let dir = std::env::temp_dir();
+        let path = |name: &str| dir.join(format!("vague-search-{}-{}", name, std::process::id()));
+        let (words_path, dict_path) = (path("dedup-chars.txt"), path("dedup-chars.bin"));
+
+        // The same suffix before and after thousands of other characters
+        let mut words = String::from("ab 1\nabation 2\n");
+        for i in 0..300 {
+            let c = std::char::from_u32('一' as u32 + i).unwrap();
+            words.push_str(&format!("cd{} 3\n", c.to_string().repeat(10)));
+        }
+        words.push_str("ef 4\nefation 5\n");
+        std::fs::write(&words_path, words).unwrap();
+
+        let compile_with = |dedup_chars| {
+            compile(
+                &words_path,
+                &dict_path,
+                IndexOptions {
+                    dedup_chars,
+                    ..IndexOptions::default()
+                },
+                &mut Progress::disabled(),
+            )
+            .unwrap();
+            let dict = DictionaryFile::read_file(&dict_path).unwrap();
+            assert!(dict.trie.validate().is_ok());
+            let words: Vec<_> = dict.trie.iter_words().collect();
+            (dict.header().nb_chars_bytes, words)
+        };
+
+        let (nb_chars_bytes, words) = compile_with(false);
+        let (dedup_nb_chars_bytes, dedup_words) = compile_with(true);
+        assert_eq!(dedup_words, words);
+        assert_eq!(dedup_nb_chars_bytes + "ation".len(), nb_chars_bytes);
+
+        for path in &[words_path, dict_path] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn test_export_round_trip() {
+        let dir = std::env::temp_dir();
+        let path = |name: &str| dir.join(format!("vague-search-{}-{}", name, std::process::id()));
+        let (words_path, export_path) = (path("export.txt"), path("export-sorted.txt"));
+        let (dict_path, round_trip_path) = (path("export.bin"), path("export-round-trip.bin"));
+        std::fs::write(
+            &words_path,
+            "test 10\nnew_york 4\nbest 5\nsnake\\_case 2\ntent 3\nte 1\ntest 12\n日本 6\n",
+        )
+        .unwrap();
+
+        let compile_to = |words_path: &Path, dict_path: &Path, bfs_layout| {
+            compile(
+                words_path,
+                dict_path,
+                IndexOptions {
+                    parse: ParseOptions {
+                        phrase_separator: Some('_'),
+                        ..ParseOptions::default()
+                    },
+                    bfs_layout,
+                    ..IndexOptions::default()
+                },
+                &mut Progress::disabled(),
+            )
+            .unwrap()
+        };
+        for &bfs_layout in &[false, true] {
+            // The words are sorted, a word given several times keeping its last frequency
+            compile_to(&words_path, &dict_path, bfs_layout);
+            let mut exported = Vec::new();
+            export(&dict_path, Some('_'), &mut exported).unwrap();
+            assert_eq!(
+                String::from_utf8(exported.clone()).unwrap(),
+                "best 5\nnew_york 4\nsnake\\_case 2\nte 1\ntent 3\ntest 12\n日本 6\n"
+            );
+
+            // Compiling the exported words gives the same dictionary
+            std::fs::write(&export_path, &exported).unwrap();
+            compile_to(&export_path, &round_trip_path, bfs_layout);
+            let mut round_trip = Vec::new();
+            export(&round_trip_path, Some('_'), &mut round_trip).unwrap();
+            assert_eq!(round_trip, exported);
+
+            // The source lines are the ones of the sorted words
+            #[cfg(not(feature = "source-lines"))]
+            assert_eq!(
+                std::fs::read(&round_trip_path).unwrap(),
+                std::fs::read(&dict_path).unwrap()
+            );
+        }
+
+        for path in &[words_path, export_path, dict_path, round_trip_path] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[cfg(feature = "source-lines")]
+    #[test]
+    fn test_compile_source_lines() {
+        let dir = std::env::temp_dir();
+        let words_path = dir.join(format!("vague-search-lines-{}.txt", std::process::id()));
+        let dict_path = dir.join(format!("vague-search-lines-{}.bin", std::process::id()));
+        std::fs::write(&words_path, "test 10\ntent 3\nbest 5\nte 1\ntest 12\n").unwrap();
+
+        for &bfs_layout in &[false, true] {
+            compile(
+                &words_path,
+                &dict_path,
+                IndexOptions {
+                    bfs_layout,
+                    ..IndexOptions::default()
+                },
+                &mut Progress::disabled(),
+            )
+            .unwrap();
+            let dict = DictionaryFile::read_file(&dict_path).unwrap();
+
+            // A word given several times keeps its last line
+            assert_eq!(dict.trie.source_line("test"), Some(5));
+            assert_eq!(dict.trie.source_line("tent"), Some(2));
+            assert_eq!(dict.trie.source_line("best"), Some(3));
+            assert_eq!(dict.trie.source_line("te"), Some(4));
+            assert_eq!(dict.trie.source_line("t"), None);
+            assert_eq!(dict.trie.source_line("rest"), None);
+        }
+
+        let _ = std::fs::remove_file(&words_path);
+        let _ = std::fs::remove_file(&dict_path);
+    }
 }
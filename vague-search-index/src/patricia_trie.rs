@@ -1,9 +1,41 @@
 use crate::error::*;
-use crate::utils::read_lines;
+use crate::progress::{Progress, ProgressEvent, PARSE_EVENT_STEP};
+use crate::utils::open_file;
 use smartstring::alias::String;
 use snafu::*;
-use std::{cmp::Ordering, num::NonZeroU32, path::Path};
-use vague_search_core::TrieNodeDrainer;
+use std::{borrow::Cow, cmp::Ordering, io::BufRead, num::NonZeroU32, path::Path};
+use vague_search_core::{unescape_phrase, Collation, TrieNodeDrainer};
+
+/// The options of the creation of a trie from `<WORD> <FREQUENCY>` lines
+/// (see [create_from_reader](PatriciaNode::create_from_reader)).
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct ParseOptions {
+    /// The separator standing for a space in the words, to unescape them as phrases.
+    pub phrase_separator: Option<char>,
+    /// Whether a line may be a single `<WORD>` of frequency 1.
+    pub optional_frequency: bool,
+    /// Whether the frequencies of a word given several times are added together.
+    pub accumulate: bool,
+    /// Whether the malformed lines are skipped instead of failing.
+    pub lenient: bool,
+    /// The order of the children, by their first character.
+    pub collation: Collation,
+    /// The number of threads building the trie with the `parallel` feature.
+    pub jobs: usize,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            phrase_separator: None,
+            optional_frequency: false,
+            accumulate: false,
+            lenient: false,
+            collation: Collation::CODE_POINT,
+            jobs: 1,
+        }
+    }
+}
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct PatriciaNode {
@@ -12,8 +44,52 @@ pub struct PatriciaNode {
     freq: Option<NonZeroU32>,
 }
 
+/// Return the byte index of the first different character of the words
 pub fn index_difference(first: &str, second: &str) -> Option<usize> {
-    first.chars().zip(second.chars()).position(|(a, b)| a != b)
+    first
+        .char_indices()
+        .zip(second.chars())
+        .find(|((_, a), b)| a != b)
+        .map(|((i, _), _)| i)
+}
+
+/// Parse a `<WORD> <FREQUENCY>` line, whose `number` begins at 1.
+/// With `optional_frequency`, the line may also be a single `<WORD>` of frequency 1,
+/// but not be followed by anything else.
+fn parse_line<'a>(
+    wordfreq: &'a str,
+    path: &Path,
+    number: usize,
+    optional_frequency: bool,
+) -> Result<(&'a str, NonZeroU32)> {
+    let mut iter = wordfreq.split_whitespace();
+    // Parse word
+    let word = iter.next().context(ContentRead {
+        path,
+        line: wordfreq,
+        number,
+    })?;
+
+    // Parse frequency
+    let freq = match iter.next() {
+        Some(freqstr) => freqstr
+            .parse::<NonZeroU32>()
+            .context(Parsing { path, number })?,
+        None if optional_frequency => NonZeroU32::new(1).unwrap(),
+        None => None.context(ContentRead {
+            path,
+            line: wordfreq,
+            number,
+        })?,
+    };
+    if optional_frequency && iter.next().is_some() {
+        None.context(ExtraContent {
+            path,
+            line: wordfreq,
+            number,
+        })?;
+    }
+    Ok((word, freq))
 }
 
 impl PatriciaNode {
@@ -25,41 +101,155 @@ impl PatriciaNode {
         }
     }
 
-    pub(crate) fn create_from_file(filepath: impl AsRef<Path>) -> Result<Self> {
+    /// Create the trie from a file of `<WORD> <FREQUENCY>` lines,
+    /// like [create_from_reader](PatriciaNode::create_from_reader).
+    ///
+    /// With `gzip` or a `.gz` extension, the file is decompressed while it is read.
+    pub(crate) fn create_from_file(
+        filepath: impl AsRef<Path>,
+        gzip: bool,
+        options: ParseOptions,
+        progress: &mut Progress,
+        on_word: impl FnMut(&str, usize),
+    ) -> Result<(Self, usize)> {
         let path = filepath.as_ref();
+        let reader = open_file(path, gzip).context(FileOpen { path })?;
+        Self::create_from_reader(reader, path, options, progress, on_word)
+    }
+
+    /// Create the trie from a reader of `<WORD> <FREQUENCY>` lines,
+    /// the `path` naming where they are read from in the errors.
+    /// Return the trie and the number of skipped lines. The blank lines are ignored.
+    ///
+    /// With [lenient](ParseOptions::lenient), the malformed lines are reported in the standard error and skipped
+    /// instead of failing, the errors of the reader still failing.
+    /// With [optional_frequency](ParseOptions::optional_frequency), a line may also be a single `<WORD>` of frequency 1,
+    /// and the lines with more than two tokens are rejected instead of ignoring the rest.
+    /// With [accumulate](ParseOptions::accumulate), the frequencies of a word given on several lines are added together
+    /// instead of keeping the last one.
+    /// With a [phrase_separator](ParseOptions::phrase_separator), each word is unescaped
+    /// as a phrase (see [unescape_phrase](vague_search_core::unescape_phrase)).
+    /// The children are sorted by the collation of their first character.
+    /// Each inserted word is also given to `on_word` with its line number (beginning at 1).
+    ///
+    /// With the `parallel` feature and more than one job, the words are gathered
+    /// in `jobs` shards by their first character, whose tries are built on their own thread
+    /// (see [create_from_shards](PatriciaNode::create_from_shards)).
+    /// This gives the same trie as inserting the words one by one.
+    pub(crate) fn create_from_reader(
+        reader: impl BufRead,
+        path: &Path,
+        options: ParseOptions,
+        progress: &mut Progress,
+        mut on_word: impl FnMut(&str, usize),
+    ) -> Result<(Self, usize)> {
+        let ParseOptions {
+            phrase_separator,
+            optional_frequency,
+            accumulate,
+            lenient,
+            collation,
+            jobs,
+        } = options;
         let mut root = Self::create_empty();
-        let lines = read_lines(path).context(FileOpen { path })?;
-        for (number, line) in lines.enumerate() {
+        let nb_shards = if cfg!(feature = "parallel") && jobs > 1 {
+            jobs
+        } else {
+            0
+        };
+        let mut shards = vec![Vec::new(); nb_shards];
+        let mut nb_lines = 0;
+        let mut nb_skipped = 0;
+        for (number, line) in reader.lines().enumerate() {
+            if number != 0 && number % PARSE_EVENT_STEP == 0 {
+                progress.emit(ProgressEvent::Parse { lines: number });
+            }
+            nb_lines = number + 1;
+
             let wordfreq = line.context(FileRead { path })?;
-            let mut iter = wordfreq.split_whitespace();
-            // Parse word
-            let word = iter.next().context(ContentRead {
-                path,
-                line: &wordfreq,
-                number,
-            })?;
-
-            // Parse frequency
-            let freqstr = iter.next().context(ContentRead {
-                path,
-                line: &wordfreq,
-                number,
-            })?;
-            let freq = freqstr
-                .parse::<NonZeroU32>()
-                .context(Parsing { path, number })?;
-            root.insert(word, freq)
+            // The blank lines are ignored, e.g. at the end of the file
+            if wordfreq.trim().is_empty() {
+                continue;
+            }
+            // The errors give the line number beginning at 1, like the editors
+            let (word, freq) = match parse_line(&wordfreq, path, number + 1, optional_frequency) {
+                Ok(word_freq) => word_freq,
+                Err(error) if lenient => {
+                    eprintln!("Skipping a malformed line: {}", error);
+                    nb_skipped += 1;
+                    continue;
+                }
+                Err(error) => return Err(error),
+            };
+
+            let word = match phrase_separator {
+                Some(separator) => unescape_phrase(word, separator),
+                None => Cow::Borrowed(word),
+            };
+            match word.chars().next() {
+                Some(c) if nb_shards != 0 => {
+                    shards[c as usize % nb_shards].push((String::from(&*word), freq))
+                }
+                _ => root.insert_with_collation(&*word, freq, collation, accumulate),
+            }
+            on_word(&word, number + 1);
+        }
+
+        progress.emit(ProgressEvent::Parse { lines: nb_lines });
+        #[cfg(feature = "parallel")]
+        if nb_shards != 0 {
+            root = Self::create_from_shards(shards, collation, accumulate);
         }
-        Ok(root)
+        Ok((root, nb_skipped))
+    }
+
+    /// Create the trie of the shards of words, whose words of a same first character
+    /// are all in the same shard, by building the trie of each shard on its own thread.
+    #[cfg(feature = "parallel")]
+    fn create_from_shards(
+        shards: Vec<Vec<(String, NonZeroU32)>>,
+        collation: Collation,
+        accumulate: bool,
+    ) -> Self {
+        let tries: Vec<Self> = std::thread::scope(|scope| {
+            let handles: Vec<_> = shards
+                .into_iter()
+                .map(|words| {
+                    scope.spawn(move || {
+                        let mut trie = Self::create_empty();
+                        for (word, freq) in words {
+                            trie.insert_with_collation(word, freq, collation, accumulate);
+                        }
+                        trie
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+
+        // The children of the roots begin with different characters,
+        // so they only have to be sorted together
+        let mut root = Self::create_empty();
+        root.children = tries.into_iter().flat_map(|trie| trie.children).collect();
+        root.children.sort_unstable_by(|a, b| {
+            collation.cmp(
+                a.letters.chars().next().unwrap(),
+                b.letters.chars().next().unwrap(),
+            )
+        });
+        root
     }
 
     /// Divides a node by two in indicated index and creates the childs accordingly
-    fn divide_node(&mut self, word: &str, ind: usize, frequency: NonZeroU32) {
+    fn divide_node(&mut self, word: &str, ind: usize, frequency: NonZeroU32, collation: Collation) {
         // Divide the current node into the current and a new one
         let second_part = self.letters.split_off(ind);
         let second_part_node = PatriciaNode {
             letters: second_part,
-            children: std::mem::replace(&mut self.children, Vec::new()),
+            children: std::mem::take(&mut self.children),
             freq: self.freq.take(),
         };
 
@@ -83,7 +273,7 @@ impl PatriciaNode {
 
             let sec_first_char = second_part_node.letters.chars().next().unwrap();
             let new_first_char = new_word_node.letters.chars().next().unwrap();
-            match new_first_char.cmp(&sec_first_char) {
+            match collation.cmp(new_first_char, sec_first_char) {
                 Ordering::Less => self.children = vec![new_word_node, second_part_node],
                 Ordering::Equal => unreachable!(),
                 Ordering::Greater => self.children = vec![second_part_node, new_word_node],
@@ -100,20 +290,32 @@ impl PatriciaNode {
         self.children.insert(index, child);
     }
 
-    fn divide(&mut self, word: &str, frequency: NonZeroU32) -> bool {
-        let index_diff = index_difference(&self.letters, &word);
+    fn divide(
+        &mut self,
+        word: &str,
+        frequency: NonZeroU32,
+        collation: Collation,
+        accumulate: bool,
+    ) -> bool {
+        let index_diff = index_difference(&self.letters, word);
 
         match (index_diff, word.len().cmp(&self.letters.len())) {
             (Some(ind), _) => {
-                self.divide_node(word, ind, frequency);
+                self.divide_node(word, ind, frequency, collation);
                 true
             }
             (None, std::cmp::Ordering::Less) => {
-                self.divide_node(word, word.len(), frequency);
+                self.divide_node(word, word.len(), frequency, collation);
                 true
             }
             (None, std::cmp::Ordering::Equal) => {
-                self.freq = Some(frequency);
+                // The word already exists if the node has a frequency
+                self.freq = match self.freq {
+                    Some(freq) if accumulate => {
+                        NonZeroU32::new(freq.get().saturating_add(frequency.get()))
+                    }
+                    _ => Some(frequency),
+                };
                 true
             }
             (None, _) => false,
@@ -121,7 +323,22 @@ impl PatriciaNode {
     }
 
     /// Insert a word and its frequency in the patricia trie
+    #[cfg(test)]
     pub(crate) fn insert(&mut self, word: impl Into<String>, frequency: NonZeroU32) {
+        self.insert_with_collation(word, frequency, Collation::CODE_POINT, false)
+    }
+
+    /// Insert a word and its frequency in the patricia trie,
+    /// whose children are sorted by the collation of their first character.
+    /// With `accumulate`, the frequency of an existing word is added to its previous one
+    /// (saturating at `u32::MAX`), instead of replacing it
+    pub(crate) fn insert_with_collation(
+        &mut self,
+        word: impl Into<String>,
+        frequency: NonZeroU32,
+        collation: Collation,
+        accumulate: bool,
+    ) {
         // Clone to avoid destroying given data
         let mut word_cpy = word.into();
 
@@ -136,13 +353,13 @@ impl PatriciaNode {
         loop {
             let word_first_char = word_cpy.chars().next().unwrap();
             let res = parent.children.binary_search_by(|child| {
-                child.letters.chars().next().unwrap().cmp(&word_first_char)
+                collation.cmp(child.letters.chars().next().unwrap(), word_first_char)
             });
 
             let index_child = match res {
                 Ok(r) => {
                     let child = &mut parent.children[r];
-                    let insrt = child.divide(&word_cpy, frequency);
+                    let insrt = child.divide(&word_cpy, frequency, collation, accumulate);
                     if !insrt {
                         word_cpy = word_cpy.split_off(child.letters.len());
                         Some(r)
@@ -183,7 +400,7 @@ impl PatriciaNode {
 
         if child.letters.len() < word.len() {
             return !word.starts_with(child.letters.as_str()); // false to continue looping
-        } else if child.letters.len() > word.len() || child.freq == None {
+        } else if child.letters.len() > word.len() || child.freq.is_none() {
             return true;
         }
 
@@ -293,7 +510,7 @@ impl PatriciaNode {
 
 impl TrieNodeDrainer for PatriciaNode {
     fn drain_characters(&mut self) -> std::string::String {
-        std::mem::replace(&mut self.letters, String::new()).into()
+        std::mem::take(&mut self.letters).into()
     }
 
     fn frequency(&self) -> Option<NonZeroU32> {
@@ -301,7 +518,7 @@ impl TrieNodeDrainer for PatriciaNode {
     }
 
     fn drain_children(&mut self) -> Vec<Self> {
-        std::mem::replace(&mut self.children, Vec::new())
+        std::mem::take(&mut self.children)
     }
 }
 
@@ -336,13 +553,12 @@ mod tests {
             children: Vec::new(),
             freq: NonZeroU32::new(1),
         };
-        let mut expected = Vec::new();
-        expected.push(expected_node);
+        let expected = vec![expected_node];
 
         println!("{:?}", parent);
         // Compare
         assert!(parent.children.len() == 1);
-        assert!(parent.freq == None);
+        assert!(parent.freq.is_none());
         assert_eq!(parent.children, expected)
     }
 
@@ -373,7 +589,7 @@ mod tests {
         let expected = vec![expected_abc, expected_bac, expected_cab];
 
         assert!(parent.children.len() == 3);
-        assert!(parent.freq == None);
+        assert!(parent.freq.is_none());
         assert_eq!(parent.children, expected)
     }
 
@@ -405,6 +621,74 @@ mod tests {
         assert_eq!(only_child, expected_abc);
     }
 
+    #[test]
+    fn insert_multibyte_difference() {
+        let mut parent = empty_patricia();
+        parent.insert("日本語", NonZeroU32::new(1).unwrap());
+        parent.insert("日文", NonZeroU32::new(2).unwrap());
+        parent.insert("éa", NonZeroU32::new(3).unwrap());
+        parent.insert("éb", NonZeroU32::new(4).unwrap());
+
+        assert_eq!(parent.children.len(), 2);
+        assert_eq!(parent.children[0].letters.as_str(), "é");
+        assert_eq!(parent.children[1].letters.as_str(), "日");
+        assert_eq!(
+            parent.search(String::from("日文")).unwrap().freq,
+            NonZeroU32::new(2)
+        );
+        assert_eq!(
+            parent.search(String::from("日本語")).unwrap().freq,
+            NonZeroU32::new(1)
+        );
+        assert_eq!(
+            parent.search(String::from("éb")).unwrap().freq,
+            NonZeroU32::new(4)
+        );
+    }
+
+    #[test]
+    fn insert_accumulate() {
+        let nz = |freq| NonZeroU32::new(freq).unwrap();
+        let mut parent = empty_patricia();
+        parent.insert_with_collation("abc", nz(3), Collation::CODE_POINT, true);
+        parent.insert_with_collation("abc", nz(5), Collation::CODE_POINT, true);
+        assert_eq!(
+            parent.search(String::from("abc")).unwrap().freq,
+            NonZeroU32::new(8)
+        );
+
+        // A word inserted in the middle of a node is not a duplicate
+        parent.insert_with_collation("abcdef", nz(2), Collation::CODE_POINT, true);
+        parent.insert_with_collation("ab", nz(4), Collation::CODE_POINT, true);
+        parent.insert_with_collation("abcdef", nz(1), Collation::CODE_POINT, true);
+        assert_eq!(
+            parent.search(String::from("ab")).unwrap().freq,
+            NonZeroU32::new(4)
+        );
+        assert_eq!(
+            parent.search(String::from("abc")).unwrap().freq,
+            NonZeroU32::new(8)
+        );
+        assert_eq!(
+            parent.search(String::from("abcdef")).unwrap().freq,
+            NonZeroU32::new(3)
+        );
+
+        // The sum saturates
+        parent.insert_with_collation("abc", nz(u32::MAX), Collation::CODE_POINT, true);
+        assert_eq!(
+            parent.search(String::from("abc")).unwrap().freq,
+            NonZeroU32::new(u32::MAX)
+        );
+
+        // Without accumulating, the last frequency is kept
+        parent.insert("abc", nz(5));
+        assert_eq!(
+            parent.search(String::from("abc")).unwrap().freq,
+            NonZeroU32::new(5)
+        );
+    }
+
     #[test]
     fn insert_in_already_word() {
         let mut parent = empty_patricia();
@@ -479,7 +763,7 @@ mod tests {
         assert!(parent.children.len() == 1);
         let only_child = parent.children.pop().unwrap();
         assert!(only_child.children.len() == 2);
-        assert!(only_child.freq == None);
+        assert!(only_child.freq.is_none());
     }
 
     #[test]
@@ -560,4 +844,243 @@ mod tests {
         };
         assert_eq!(child.unwrap(), &expected_child);
     }
+
+    #[test]
+    fn create_from_file_phrases() {
+        let path =
+            std::env::temp_dir().join(format!("vague-search-phrases-{}.txt", std::process::id()));
+        std::fs::write(&path, "new_york 10\nsnake\\_case 3\nnew 5\n").unwrap();
+
+        let with_separator = PatriciaNode::create_from_file(
+            &path,
+            false,
+            ParseOptions {
+                phrase_separator: Some('_'),
+                ..ParseOptions::default()
+            },
+            &mut Progress::disabled(),
+            |_, _| {},
+        );
+        let without_separator = PatriciaNode::create_from_file(
+            &path,
+            false,
+            ParseOptions::default(),
+            &mut Progress::disabled(),
+            |_, _| {},
+        );
+        let _ = std::fs::remove_file(&path);
+
+        let trie = with_separator.unwrap().0;
+        let phrase = trie.search(String::from("new york")).unwrap();
+        assert_eq!(phrase.freq, NonZeroU32::new(10));
+        assert!(trie.search(String::from("snake_case")).is_some());
+        assert!(trie.search(String::from("new_york")).is_none());
+
+        let trie = without_separator.unwrap().0;
+        assert!(trie.search(String::from("new_york")).is_some());
+        assert!(trie.search(String::from("new york")).is_none());
+    }
+
+    #[test]
+    fn create_from_reader_cursor() {
+        let mut lines = Vec::new();
+        let reader = std::io::Cursor::new("apple 3\nbanana 5\napp 1\n");
+        let trie = PatriciaNode::create_from_reader(
+            reader,
+            Path::new("-"),
+            ParseOptions::default(),
+            &mut Progress::disabled(),
+            |word, line| lines.push((word.to_string(), line)),
+        )
+        .unwrap()
+        .0;
+
+        assert_eq!(
+            trie.search(String::from("apple")).unwrap().freq,
+            NonZeroU32::new(3)
+        );
+        assert_eq!(
+            trie.search(String::from("banana")).unwrap().freq,
+            NonZeroU32::new(5)
+        );
+        assert_eq!(
+            trie.search(String::from("app")).unwrap().freq,
+            NonZeroU32::new(1)
+        );
+        assert_eq!(
+            lines,
+            [
+                ("apple".to_string(), 1),
+                ("banana".to_string(), 2),
+                ("app".to_string(), 3)
+            ]
+        );
+
+        // The frequencies of the duplicate words are added together
+        let reader = std::io::Cursor::new("abc 3\nabc 5\n");
+        let trie = PatriciaNode::create_from_reader(
+            reader,
+            Path::new("-"),
+            ParseOptions {
+                accumulate: true,
+                ..ParseOptions::default()
+            },
+            &mut Progress::disabled(),
+            |_, _| {},
+        )
+        .unwrap()
+        .0;
+        assert_eq!(
+            trie.search(String::from("abc")).unwrap().freq,
+            NonZeroU32::new(8)
+        );
+
+        // The errors name the given path
+        let reader = std::io::Cursor::new("apple three\n");
+        let error = PatriciaNode::create_from_reader(
+            reader,
+            Path::new("-"),
+            ParseOptions::default(),
+            &mut Progress::disabled(),
+            |_, _| {},
+        )
+        .unwrap_err();
+        assert!(matches!(
+            error,
+            crate::error::Error::Parsing { ref path, number: 1, .. } if path == Path::new("-")
+        ));
+    }
+
+    #[test]
+    fn create_from_file_blank_lines() {
+        let path =
+            std::env::temp_dir().join(format!("vague-search-blank-{}.txt", std::process::id()));
+        std::fs::write(&path, "apple 3\n\nbanana 5\n \t \ncherry 2\n\n").unwrap();
+
+        let mut lines = Vec::new();
+        let result = PatriciaNode::create_from_file(
+            &path,
+            false,
+            ParseOptions::default(),
+            &mut Progress::disabled(),
+            |word, line| lines.push((word.to_string(), line)),
+        );
+        let _ = std::fs::remove_file(&path);
+
+        // The blank lines are neither words nor skipped lines
+        let (trie, nb_skipped) = result.unwrap();
+        assert_eq!(nb_skipped, 0);
+        assert_eq!(
+            trie.search(String::from("banana")).unwrap().freq,
+            NonZeroU32::new(5)
+        );
+        assert_eq!(
+            lines,
+            [
+                ("apple".to_string(), 1),
+                ("banana".to_string(), 3),
+                ("cherry".to_string(), 5)
+            ]
+        );
+    }
+
+    #[test]
+    fn create_from_reader_lenient() {
+        let create = |contents: &'static [u8], lenient| {
+            PatriciaNode::create_from_reader(
+                std::io::Cursor::new(contents),
+                Path::new("-"),
+                ParseOptions {
+                    lenient,
+                    ..ParseOptions::default()
+                },
+                &mut Progress::disabled(),
+                |_, _| {},
+            )
+        };
+        let contents = b"apple 3\nbanana\ncherry x\ndate 0\negg 2\n";
+
+        // The malformed lines are skipped and counted
+        let (trie, nb_skipped) = create(contents, true).unwrap();
+        assert_eq!(nb_skipped, 3);
+        assert_eq!(
+            trie.search(String::from("apple")).unwrap().freq,
+            NonZeroU32::new(3)
+        );
+        assert_eq!(
+            trie.search(String::from("egg")).unwrap().freq,
+            NonZeroU32::new(2)
+        );
+        for word in &["banana", "cherry", "date"] {
+            assert!(trie.search(String::from(*word)).is_none(), "{}", word);
+        }
+
+        // The first malformed line fails in strict mode, reported like the skipped ones
+        let error = create(contents, false).unwrap_err();
+        assert!(matches!(
+            error,
+            crate::error::Error::ContentRead { number: 2, .. }
+        ));
+        assert!(error.to_string().ends_with("at line 2: banana"));
+
+        // The lines which cannot be read still fail
+        assert!(create(b"apple 3\n\xFF 2\n", true).is_err());
+    }
+
+    #[test]
+    fn create_from_file_optional_frequency() {
+        let dir = std::env::temp_dir();
+        let create = |name: &str, contents: &str, optional_frequency: bool| {
+            let path = dir.join(format!("vague-search-{}-{}.txt", name, std::process::id()));
+            std::fs::write(&path, contents).unwrap();
+            let trie = PatriciaNode::create_from_file(
+                &path,
+                false,
+                ParseOptions {
+                    optional_frequency,
+                    ..ParseOptions::default()
+                },
+                &mut Progress::disabled(),
+                |_, _| {},
+            );
+            let _ = std::fs::remove_file(&path);
+            trie.map(|(trie, _)| trie)
+        };
+
+        // The words without a frequency have a frequency of 1
+        let trie = create("words-only", "apple\nbanana\n", true).unwrap();
+        assert_eq!(
+            trie.search(String::from("apple")).unwrap().freq,
+            NonZeroU32::new(1)
+        );
+        assert_eq!(
+            trie.search(String::from("banana")).unwrap().freq,
+            NonZeroU32::new(1)
+        );
+
+        let trie = create("words-mixed", "apple 7\nbanana\n", true).unwrap();
+        assert_eq!(
+            trie.search(String::from("apple")).unwrap().freq,
+            NonZeroU32::new(7)
+        );
+        assert_eq!(
+            trie.search(String::from("banana")).unwrap().freq,
+            NonZeroU32::new(1)
+        );
+
+        // The frequency is still required without the option
+        let error = create("words-required", "apple 7\nbanana\n", false).unwrap_err();
+        assert!(matches!(
+            error,
+            crate::error::Error::ContentRead { number: 2, .. }
+        ));
+
+        // More than a word and its frequency is rejected with the option
+        let error = create("words-extra", "apple 7\nbanana 3 yellow\n", true).unwrap_err();
+        assert!(matches!(
+            error,
+            crate::error::Error::ExtraContent { number: 2, .. }
+        ));
+        assert!(create("words-extra-ignored", "apple 7 x\n", false).is_ok());
+    }
 }
@@ -0,0 +1,95 @@
+//! Machine-readable progress of the compilation, enabled with `--progress-json`.
+//!
+//! Each event is written as a JSON object on its own line (NDJSON), e.g.:
+//! - `{"phase":"parse","lines":100000}`
+//! - `{"phase":"compress"}`
+//! - `{"phase":"write","bytes":123456}`
+//!
+//! With `--progress`, the same events are written as text lines for a person instead
+//! (e.g. `Parsed 100000 lines`).
+
+use std::io::Write;
+
+/// Number of parsed lines between two parse events.
+pub const PARSE_EVENT_STEP: usize = 100_000;
+
+/// An event of the compilation progress.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ProgressEvent {
+    /// Lines have been parsed from the words file.
+    Parse { lines: usize },
+
+    /// The trie is being compressed and compiled.
+    Compress,
+
+    /// The dictionary file has been written.
+    Write { bytes: usize },
+}
+
+/// How the events are written.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Format {
+    Json,
+    Text,
+}
+
+/// Write the compilation events to an optional output.
+pub struct Progress<'a> {
+    output: Option<&'a mut dyn Write>,
+    format: Format,
+}
+
+impl<'a> Progress<'a> {
+    /// Create a progress which does not write any event.
+    pub fn disabled() -> Self {
+        Self {
+            output: None,
+            format: Format::Json,
+        }
+    }
+
+    /// Create a progress which writes the events to the given output.
+    pub fn new(output: &'a mut dyn Write) -> Self {
+        Self {
+            output: Some(output),
+            format: Format::Json,
+        }
+    }
+
+    /// Create a progress which writes the events as text lines to the given output.
+    pub fn text(output: &'a mut dyn Write) -> Self {
+        Self {
+            output: Some(output),
+            format: Format::Text,
+        }
+    }
+
+    /// Write the event as a JSON or text line if the progress is enabled.
+    pub fn emit(&mut self, event: ProgressEvent) {
+        let output = match &mut self.output {
+            Some(output) => output,
+            None => return,
+        };
+
+        // The progress is informative, failing to write it must not stop the compilation
+        let r = match (self.format, event) {
+            (Format::Json, ProgressEvent::Parse { lines }) => {
+                writeln!(output, "{{\"phase\":\"parse\",\"lines\":{}}}", lines)
+            }
+            (Format::Json, ProgressEvent::Compress) => {
+                writeln!(output, "{{\"phase\":\"compress\"}}")
+            }
+            (Format::Json, ProgressEvent::Write { bytes }) => {
+                writeln!(output, "{{\"phase\":\"write\",\"bytes\":{}}}", bytes)
+            }
+            (Format::Text, ProgressEvent::Parse { lines }) => {
+                writeln!(output, "Parsed {} lines", lines)
+            }
+            (Format::Text, ProgressEvent::Compress) => writeln!(output, "Compressing the trie"),
+            (Format::Text, ProgressEvent::Write { bytes }) => {
+                writeln!(output, "Wrote {} bytes", bytes)
+            }
+        };
+        let _ = r.and_then(|_| output.flush());
+    }
+}
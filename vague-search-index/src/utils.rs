@@ -1,12 +1,96 @@
+#[cfg(feature = "gzip")]
+use flate2::bufread::MultiGzDecoder;
+use std::ffi::OsStr;
 use std::fs::File;
 use std::io::{self, BufRead};
 use std::path::Path;
 
-/// Returns an Iterator to the Reader of the lines of the file
-pub fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
-where
-    P: AsRef<Path>,
-{
-    let file = File::open(filename)?;
-    Ok(io::BufReader::new(file).lines())
+/// Returns a Reader of the file, decompressed if `gzip` is set or if its extension is `.gz`
+pub fn open_file(path: &Path, gzip: bool) -> io::Result<Box<dyn BufRead>> {
+    let file = io::BufReader::new(File::open(path)?);
+    if gzip || path.extension() == Some(OsStr::new("gz")) {
+        gzip_reader(file)
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+/// Returns a Reader of the decompressed gzip stream, whose members are decompressed
+/// one after the other
+#[cfg(feature = "gzip")]
+pub fn gzip_reader<'a>(reader: impl BufRead + 'a) -> io::Result<Box<dyn BufRead + 'a>> {
+    Ok(Box::new(io::BufReader::new(MultiGzDecoder::new(reader))))
+}
+
+/// Fails since the gzip streams can only be read with the `gzip` feature
+#[cfg(not(feature = "gzip"))]
+pub fn gzip_reader<'a>(_: impl BufRead + 'a) -> io::Result<Box<dyn BufRead + 'a>> {
+    Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "Reading a gzip file requires the `gzip` feature",
+    ))
+}
+
+#[cfg(all(test, feature = "gzip"))]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::{Read, Write};
+
+    fn decode(gzip: &[u8]) -> io::Result<Vec<u8>> {
+        let mut output = Vec::new();
+        gzip_reader(gzip)?.read_to_end(&mut output)?;
+        Ok(output)
+    }
+
+    fn encode(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_decode_members() {
+        assert_eq!(decode(&encode(b"")).unwrap(), b"");
+
+        let mut gzip = encode(b"apple 3\nbanana 5\n");
+        gzip.extend_from_slice(&encode(b"cherry 2\n"));
+        assert_eq!(decode(&gzip).unwrap(), b"apple 3\nbanana 5\ncherry 2\n");
+    }
+
+    #[test]
+    fn test_open_file() {
+        let dir = std::env::temp_dir();
+        let gz_path = dir.join(format!("vague-search-gzip-{}.txt.gz", std::process::id()));
+        let txt_path = dir.join(format!("vague-search-gzip-{}.txt", std::process::id()));
+        let gzip = encode(b"apple 3\nbanana 5\n");
+        std::fs::write(&gz_path, &gzip).unwrap();
+        std::fs::write(&txt_path, &gzip).unwrap();
+
+        let read = |path: &Path, gzip| -> io::Result<Vec<String>> {
+            open_file(path, gzip)?.lines().collect()
+        };
+        let by_extension = read(&gz_path, false);
+        let by_flag = read(&txt_path, true);
+        let not_decoded = read(&txt_path, false);
+        let _ = std::fs::remove_file(&gz_path);
+        let _ = std::fs::remove_file(&txt_path);
+
+        assert_eq!(by_extension.unwrap(), ["apple 3", "banana 5"]);
+        assert_eq!(by_flag.unwrap(), ["apple 3", "banana 5"]);
+        assert!(not_decoded.is_err());
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        let gzip = encode(b"apple 3\nbanana 5\n");
+        assert!(decode(b"apple 3\n").is_err());
+        assert!(decode(&gzip[..20]).is_err());
+
+        // A wrong checksum, stored before the size at the end of the member
+        let mut corrupted = gzip.clone();
+        let checksum_index = corrupted.len() - 8;
+        corrupted[checksum_index] ^= 1;
+        assert!(decode(&corrupted).is_err());
+    }
 }